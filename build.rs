@@ -0,0 +1,15 @@
+// Rebuild when migrations change so `sqlx::migrate!` embeds the latest set.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+
+    // The vendored protoc means the grpc feature builds without one installed.
+    #[cfg(feature = "grpc")]
+    {
+        let mut config = tonic_build::Config::new();
+        config
+            .protoc_executable(protoc_bin_vendored::protoc_bin_path().expect("No vendored protoc"));
+        tonic_build::configure()
+            .compile_protos_with_config(config, &["proto/polls.proto"], &["proto"])
+            .expect("Failed to compile proto/polls.proto");
+    }
+}
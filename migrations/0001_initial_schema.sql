@@ -0,0 +1,166 @@
+CREATE TABLE IF NOT EXISTS users (
+    id UUID PRIMARY KEY,
+    username VARCHAR(255) NOT NULL UNIQUE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS passkeys (
+    id SERIAL PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    passkey_data JSON NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE passkeys
+    ADD COLUMN IF NOT EXISTS nickname VARCHAR(64),
+    ADD COLUMN IF NOT EXISTS aaguid UUID,
+    ADD COLUMN IF NOT EXISTS last_used_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN IF NOT EXISTS cred_id TEXT;
+
+UPDATE passkeys SET cred_id = passkey_data->'cred'->>'cred_id'
+WHERE cred_id IS NULL;
+
+CREATE TABLE IF NOT EXISTS polls (
+    id UUID PRIMARY KEY,
+    creator_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    title VARCHAR(255) NOT NULL,
+    description TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    closed BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS external_id VARCHAR(255);
+
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS ballot_public_key TEXT;
+
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS tallied BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS expires_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE IF NOT EXISTS surveys (
+    id UUID PRIMARY KEY,
+    creator_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    title VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    closed BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS survey_id UUID REFERENCES surveys(id) ON DELETE CASCADE;
+
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS survey_position INT;
+
+CREATE TABLE IF NOT EXISTS poll_options (
+    id UUID PRIMARY KEY,
+    poll_id UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+    option_text VARCHAR(255) NOT NULL,
+    votes INT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS votes (
+    id UUID PRIMARY KEY,
+    poll_id UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+    option_id UUID NOT NULL REFERENCES poll_options(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(poll_id, user_id)
+);
+
+ALTER TABLE votes ALTER COLUMN option_id DROP NOT NULL;
+
+ALTER TABLE votes ADD COLUMN IF NOT EXISTS encrypted_ballot TEXT;
+
+ALTER TABLE poll_options ADD COLUMN IF NOT EXISTS allows_write_in BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE votes ADD COLUMN IF NOT EXISTS write_in_text VARCHAR(200);
+
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS allow_multiple BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE votes ADD COLUMN IF NOT EXISTS multi_choice BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE polls
+    ADD COLUMN IF NOT EXISTS vote_type VARCHAR(16) NOT NULL DEFAULT 'single',
+    ADD COLUMN IF NOT EXISTS max_choices INT;
+
+UPDATE polls SET vote_type = 'multiple'
+WHERE allow_multiple = TRUE AND vote_type = 'single';
+
+ALTER TABLE votes ADD COLUMN IF NOT EXISTS rank INT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_votes_ranked_ballot
+    ON votes(poll_id, user_id) WHERE rank = 1;
+
+ALTER TABLE polls
+    ADD COLUMN IF NOT EXISTS anonymous BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN IF NOT EXISTS voter_salt TEXT;
+
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS allow_vote_change BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE votes ALTER COLUMN user_id DROP NOT NULL;
+
+ALTER TABLE votes ADD COLUMN IF NOT EXISTS voter_hash TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_votes_anonymous_single_choice
+    ON votes(poll_id, voter_hash) WHERE multi_choice = FALSE AND voter_hash IS NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_votes_anonymous_multi_choice
+    ON votes(poll_id, option_id, voter_hash) WHERE multi_choice = TRUE AND voter_hash IS NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_votes_single_choice
+    ON votes(poll_id, user_id) WHERE multi_choice = FALSE;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_votes_multi_choice
+    ON votes(poll_id, option_id, user_id) WHERE multi_choice = TRUE;
+
+ALTER TABLE votes DROP CONSTRAINT IF EXISTS votes_poll_id_user_id_key;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS role VARCHAR(32) NOT NULL DEFAULT 'user';
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS can_create_polls BOOLEAN NOT NULL DEFAULT TRUE;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS banned BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS poll_reports (
+    id UUID PRIMARY KEY,
+    poll_id UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+    reporter_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason VARCHAR(500) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    resolved BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id UUID PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    family_id UUID NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    replaced_by UUID
+);
+
+CREATE INDEX IF NOT EXISTS idx_users_username ON users(username);
+
+CREATE INDEX IF NOT EXISTS idx_passkeys_user_id ON passkeys(user_id);
+
+CREATE INDEX IF NOT EXISTS idx_passkeys_user_cred ON passkeys(user_id, cred_id);
+
+CREATE INDEX IF NOT EXISTS idx_refresh_tokens_family_id ON refresh_tokens(family_id);
+
+CREATE INDEX IF NOT EXISTS idx_polls_title_search
+    ON polls USING GIN (to_tsvector('simple', title));
+
+CREATE INDEX IF NOT EXISTS idx_polls_creator_id ON polls(creator_id);
+
+CREATE INDEX IF NOT EXISTS idx_poll_options_poll_id ON poll_options(poll_id);
+
+CREATE INDEX IF NOT EXISTS idx_votes_poll_id ON votes(poll_id);
+
+CREATE INDEX IF NOT EXISTS idx_votes_user_id ON votes(user_id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_polls_external_id ON polls(external_id);
+
+CREATE INDEX IF NOT EXISTS idx_polls_survey_id ON polls(survey_id);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_poll_reports_open
+ON poll_reports(poll_id, reporter_user_id) WHERE resolved = FALSE;
@@ -0,0 +1,16 @@
+ALTER TABLE passkeys
+    ADD COLUMN IF NOT EXISTS sign_count BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS backup_eligible BOOLEAN,
+    ADD COLUMN IF NOT EXISTS backup_state BOOLEAN,
+    ADD COLUMN IF NOT EXISTS clone_suspected_at TIMESTAMP WITH TIME ZONE;
+
+CREATE TABLE IF NOT EXISTS security_events (
+    id UUID PRIMARY KEY,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    event_type VARCHAR(64) NOT NULL,
+    cred_id TEXT,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_security_events_user_id ON security_events(user_id, created_at);
@@ -0,0 +1,3 @@
+ALTER TABLE passkeys
+    ADD COLUMN IF NOT EXISTS attestation_format VARCHAR(32),
+    ADD COLUMN IF NOT EXISTS user_verified BOOLEAN;
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS tags (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(32) NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS poll_tags (
+    poll_id UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+    tag_id INT NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    PRIMARY KEY (poll_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_poll_tags_tag_id ON poll_tags(tag_id);
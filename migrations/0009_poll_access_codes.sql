@@ -0,0 +1 @@
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS access_code_hash TEXT;
@@ -0,0 +1,26 @@
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS email VARCHAR(254),
+    ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN IF NOT EXISTS email_notifications BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Several accounts may list an address, but only one may verify it.
+CREATE UNIQUE INDEX IF NOT EXISTS idx_users_verified_email
+    ON users(LOWER(email)) WHERE email_verified_at IS NOT NULL;
+
+CREATE TABLE IF NOT EXISTS email_verification_tokens (
+    token_hash TEXT PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(254) NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_verification_tokens_user_id
+    ON email_verification_tokens(user_id, created_at DESC);
+
+CREATE TABLE IF NOT EXISTS poll_notifications (
+    poll_id UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+    kind VARCHAR(32) NOT NULL,
+    sent_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (poll_id, kind)
+);
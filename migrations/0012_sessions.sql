@@ -0,0 +1,8 @@
+-- Matches the layout tower-sessions-sqlx-store expects for its Postgres store.
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY NOT NULL,
+    data BYTEA NOT NULL,
+    expiry_date TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_expiry_date ON sessions(expiry_date);
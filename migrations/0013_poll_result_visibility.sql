@@ -0,0 +1,3 @@
+ALTER TABLE polls
+    ADD COLUMN IF NOT EXISTS result_visibility VARCHAR(16) NOT NULL DEFAULT 'always'
+        CHECK (result_visibility IN ('always', 'after_vote', 'after_close'));
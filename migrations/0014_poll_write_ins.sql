@@ -0,0 +1,6 @@
+ALTER TABLE polls
+    ADD COLUMN IF NOT EXISTS allow_write_in BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Options voters added by writing them in, counted against the per-poll cap.
+ALTER TABLE poll_options
+    ADD COLUMN IF NOT EXISTS added_by_voter BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,15 @@
+-- Generated so they can't drift from the text; options get their own since a
+-- generated column can only see its own row.
+ALTER TABLE polls
+    ADD COLUMN IF NOT EXISTS search_vector tsvector GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', title), 'A')
+        || setweight(to_tsvector('simple', COALESCE(description, '')), 'B')
+    ) STORED;
+
+ALTER TABLE poll_options
+    ADD COLUMN IF NOT EXISTS search_vector tsvector GENERATED ALWAYS AS (
+        to_tsvector('simple', option_text)
+    ) STORED;
+
+CREATE INDEX IF NOT EXISTS idx_polls_search_vector ON polls USING GIN (search_vector);
+CREATE INDEX IF NOT EXISTS idx_poll_options_search_vector ON poll_options USING GIN (search_vector);
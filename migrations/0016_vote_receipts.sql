@@ -0,0 +1,5 @@
+ALTER TABLE votes ADD COLUMN IF NOT EXISTS receipt TEXT;
+ALTER TABLE votes ADD COLUMN IF NOT EXISTS receipt_issued_at TIMESTAMP WITH TIME ZONE;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_votes_receipt ON votes(poll_id, receipt)
+    WHERE receipt IS NOT NULL;
@@ -0,0 +1,98 @@
+-- Writes from other tools (scripts, admin consoles, another service) are
+-- announced on the poll_changes channel so the server can stream them like
+-- its own. The API's connections set polling_app.emits_events, since its
+-- handlers already broadcast what they write. Payloads name what changed; the
+-- server reads the rest back.
+
+CREATE OR REPLACE FUNCTION notify_vote_change() RETURNS trigger AS $$
+BEGIN
+    IF current_setting('polling_app.emits_events', true) = 'on' THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP <> 'INSERT' AND OLD.option_id IS NOT NULL THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'vote_update', 'poll_id', OLD.poll_id, 'option_id', OLD.option_id)::text);
+    END IF;
+
+    IF TG_OP <> 'DELETE' AND NEW.option_id IS NOT NULL THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'vote_update', 'poll_id', NEW.poll_id, 'option_id', NEW.option_id)::text);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER votes_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON votes
+    FOR EACH ROW EXECUTE FUNCTION notify_vote_change();
+
+CREATE OR REPLACE FUNCTION notify_poll_change() RETURNS trigger AS $$
+BEGIN
+    IF current_setting('polling_app.emits_events', true) = 'on' THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP = 'INSERT' THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_created', 'poll_id', NEW.id,
+            'title', NEW.title, 'creator_id', NEW.creator_id)::text);
+    ELSIF TG_OP = 'DELETE' THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_deleted', 'poll_id', OLD.id)::text);
+    ELSIF NEW.closed AND NOT OLD.closed THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_closed', 'poll_id', NEW.id)::text);
+    ELSIF NEW IS DISTINCT FROM OLD THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_edited', 'poll_id', NEW.id)::text);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER polls_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON polls
+    FOR EACH ROW EXECUTE FUNCTION notify_poll_change();
+
+-- Vote counter updates come with a vote and are announced by that.
+CREATE OR REPLACE FUNCTION notify_option_change() RETURNS trigger AS $$
+BEGIN
+    IF current_setting('polling_app.emits_events', true) = 'on' THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP = 'INSERT' THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'option_added', 'poll_id', NEW.poll_id, 'option_id', NEW.id)::text);
+    ELSE
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_edited', 'poll_id', COALESCE(NEW.poll_id, OLD.poll_id))::text);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER poll_options_notify_change
+    AFTER INSERT OR DELETE OR UPDATE OF option_text ON poll_options
+    FOR EACH ROW EXECUTE FUNCTION notify_option_change();
+
+CREATE OR REPLACE FUNCTION notify_comment_added() RETURNS trigger AS $$
+BEGIN
+    IF current_setting('polling_app.emits_events', true) = 'on' THEN
+        RETURN NULL;
+    END IF;
+
+    PERFORM pg_notify('poll_changes', json_build_object(
+        'type', 'comment_added', 'comment_id', NEW.id)::text);
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER comments_notify_added
+    AFTER INSERT ON comments
+    FOR EACH ROW EXECUTE FUNCTION notify_comment_added();
@@ -0,0 +1,37 @@
+-- Deleting a poll archives it: it drops out of listings, feeds and search but
+-- keeps its options and votes, so the creator can restore it.
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS archived_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_polls_archived ON polls(creator_id)
+    WHERE archived_at IS NOT NULL;
+
+-- Archiving and restoring from outside the API look like a delete and a
+-- create to streams, as they do when the API does it.
+CREATE OR REPLACE FUNCTION notify_poll_change() RETURNS trigger AS $$
+BEGIN
+    IF current_setting('polling_app.emits_events', true) = 'on' THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP = 'INSERT' THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_created', 'poll_id', NEW.id,
+            'title', NEW.title, 'creator_id', NEW.creator_id)::text);
+    ELSIF TG_OP = 'DELETE' OR (NEW.archived_at IS NOT NULL AND OLD.archived_at IS NULL) THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_deleted', 'poll_id', OLD.id)::text);
+    ELSIF NEW.archived_at IS NULL AND OLD.archived_at IS NOT NULL THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_created', 'poll_id', NEW.id,
+            'title', NEW.title, 'creator_id', NEW.creator_id)::text);
+    ELSIF NEW.closed AND NOT OLD.closed THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_closed', 'poll_id', NEW.id)::text);
+    ELSIF NEW IS DISTINCT FROM OLD THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_edited', 'poll_id', NEW.id)::text);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
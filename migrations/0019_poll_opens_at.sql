@@ -0,0 +1,40 @@
+-- Polls can be published ahead of time. Until opens_at they're listed as
+-- upcoming and refuse votes; `opened` records whether the opening has been
+-- announced, the way `closed` does for expires_at.
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS opens_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE polls ADD COLUMN IF NOT EXISTS opened BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE INDEX IF NOT EXISTS idx_polls_scheduled ON polls(opens_at)
+    WHERE NOT opened;
+
+CREATE OR REPLACE FUNCTION notify_poll_change() RETURNS trigger AS $$
+BEGIN
+    IF current_setting('polling_app.emits_events', true) = 'on' THEN
+        RETURN NULL;
+    END IF;
+
+    IF TG_OP = 'INSERT' THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_created', 'poll_id', NEW.id,
+            'title', NEW.title, 'creator_id', NEW.creator_id)::text);
+    ELSIF TG_OP = 'DELETE' OR (NEW.archived_at IS NOT NULL AND OLD.archived_at IS NULL) THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_deleted', 'poll_id', OLD.id)::text);
+    ELSIF NEW.archived_at IS NULL AND OLD.archived_at IS NOT NULL THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_created', 'poll_id', NEW.id,
+            'title', NEW.title, 'creator_id', NEW.creator_id)::text);
+    ELSIF NEW.opened AND NOT OLD.opened THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_opened', 'poll_id', NEW.id)::text);
+    ELSIF NEW.closed AND NOT OLD.closed THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_closed', 'poll_id', NEW.id)::text);
+    ELSIF NEW IS DISTINCT FROM OLD THEN
+        PERFORM pg_notify('poll_changes', json_build_object(
+            'type', 'poll_edited', 'poll_id', NEW.id)::text);
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
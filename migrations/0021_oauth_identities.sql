@@ -0,0 +1,14 @@
+-- Accounts signed in through an external OpenID Connect provider. The
+-- provider's issuer and subject identify the person; the email is what the
+-- provider reported at the last sign-in, kept for display only.
+CREATE TABLE IF NOT EXISTS oauth_identities (
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_login_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (issuer, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_oauth_identities_user_id ON oauth_identities(user_id);
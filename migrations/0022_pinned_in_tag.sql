@@ -0,0 +1,12 @@
+-- A creator can pin a poll to the top of the listing for one of its tags.
+-- Pins reference the poll's own tag, so a poll can only be pinned in a tag it
+-- carries and the pin goes if the tag is taken off the poll.
+CREATE TABLE IF NOT EXISTS pinned_in_tag (
+    poll_id UUID NOT NULL,
+    tag_id INT NOT NULL,
+    pinned_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (poll_id, tag_id),
+    FOREIGN KEY (poll_id, tag_id) REFERENCES poll_tags(poll_id, tag_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_pinned_in_tag_tag_id ON pinned_in_tag(tag_id);
@@ -0,0 +1,12 @@
+-- Vote links emailed to invited voters. The link carries a signed token bound
+-- to the poll and the address; its id is recorded here so it works only once.
+CREATE TABLE IF NOT EXISTS poll_vote_links (
+    id UUID PRIMARY KEY,
+    poll_id UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
+    email VARCHAR(254) NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_poll_vote_links_poll_id ON poll_vote_links(poll_id);
@@ -0,0 +1,6 @@
+-- External ids are the integrator's own: two creators may use the same one,
+-- and a create retried with it finds the creator's poll.
+DROP INDEX IF EXISTS idx_polls_external_id;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_polls_creator_external_id
+    ON polls(creator_id, external_id);
@@ -0,0 +1,564 @@
+use crate::auth::{BearerAuth, Claims, available_username};
+use crate::config::Config;
+use crate::db;
+use crate::db::models::Poll;
+use crate::email::normalize_email;
+use crate::error::{ErrorResponse, PollError};
+use crate::mailer::{Email, send_in_background};
+use crate::polls::{CastVoteRequest, VoteResponse, cast_vote};
+use crate::sse::SseSender;
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+// Invites are signed with the JWT secret; the audience keeps them from being
+// accepted as access tokens and vice versa.
+const INVITE_AUDIENCE: &str = "poll-invite";
+const DEFAULT_INVITE_TTL_HOURS: u32 = 24 * 7;
+const MAX_INVITE_TTL_HOURS: u32 = 24 * 30;
+
+// Emailed vote links are signed the same way under their own audience, and
+// carry the address they were sent to.
+const VOTE_LINK_AUDIENCE: &str = "poll-vote-link";
+const MAX_VOTE_LINK_EMAILS: usize = 50;
+
+// Access codes are short and human-chosen, so they're stored as bcrypt hashes
+// and attempts are rate limited per user.
+const MIN_ACCESS_CODE_LENGTH: usize = 4;
+// bcrypt ignores anything past 72 bytes.
+const MAX_ACCESS_CODE_BYTES: usize = 72;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct InviteClaims {
+    poll_id: Uuid,
+    aud: String,
+    exp: usize,
+    iat: usize,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateInviteRequest {
+    pub expires_in_hours: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UnlockPollRequest {
+    pub access_code: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InviteResponse {
+    pub poll_id: Uuid,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+fn create_invite_token(
+    poll_id: Uuid,
+    expires_at: DateTime<Utc>,
+    config: &Config,
+) -> Result<String, PollError> {
+    let claims = InviteClaims {
+        poll_id,
+        aud: INVITE_AUDIENCE.to_string(),
+        exp: expires_at.timestamp() as usize,
+        iat: Utc::now().timestamp() as usize,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+    )
+    .map_err(|e| {
+        error!("Error signing invite token: {:?}", e);
+        PollError::InviteCreationError
+    })
+}
+
+pub fn verify_invite_token(token: &str, poll_id: Uuid, config: &Config) -> bool {
+    let mut validation = Validation::default();
+    validation.set_audience(&[INVITE_AUDIENCE]);
+
+    decode::<InviteClaims>(
+        token,
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+        &validation,
+    )
+    .is_ok_and(|data| data.claims.poll_id == poll_id)
+}
+
+pub fn validate_access_code(code: &str) -> Result<(), PollError> {
+    if code.chars().count() < MIN_ACCESS_CODE_LENGTH
+        || code.len() > MAX_ACCESS_CODE_BYTES
+        || code.chars().any(char::is_control)
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    Ok(())
+}
+
+// bcrypt is slow on purpose, so it runs off the async workers.
+pub async fn hash_access_code(code: &str) -> Result<String, PollError> {
+    let code = code.to_string();
+
+    tokio::task::spawn_blocking(move || bcrypt::hash(code, bcrypt::DEFAULT_COST))
+        .await
+        .map_err(|e| {
+            error!("Access code hashing task failed: {:?}", e);
+            PollError::AccessCodeHashError
+        })?
+        .map_err(|e| {
+            error!("Error hashing access code: {:?}", e);
+            PollError::AccessCodeHashError
+        })
+}
+
+async fn verify_access_code(code: &str, stored: &str) -> bool {
+    let (code, stored) = (code.to_string(), stored.to_string());
+
+    tokio::task::spawn_blocking(move || bcrypt::verify(code, &stored).unwrap_or(false))
+        .await
+        .unwrap_or(false)
+}
+
+// Access that needs no further proof: the poll isn't gated, the caller owns
+// or moderates it, or was granted access earlier.
+pub async fn has_standing_access(
+    app_state: &AppState,
+    poll: &Poll,
+    auth: &BearerAuth,
+) -> Result<bool, PollError> {
+    let user_id = auth.0.sub;
+
+    if (!poll.is_private() && !poll.requires_access_code())
+        || poll.creator_id == user_id
+        || auth.is_admin()
+    {
+        return Ok(true);
+    }
+
+    db::has_poll_access(&app_state.db, poll.id, user_id)
+        .await
+        .map_err(PollError::from)
+}
+
+// Private polls answer as missing to anyone who hasn't been let in, so their
+// existence isn't disclosed. Redeeming an invite, and the access code where one
+// is set, records a grant so neither is needed again.
+pub async fn require_poll_access(
+    app_state: &AppState,
+    poll: &Poll,
+    auth: &BearerAuth,
+    invite_token: Option<&str>,
+) -> Result<(), PollError> {
+    // Archived polls are gone for everyone but those who can restore them.
+    if poll.is_archived() && poll.creator_id != auth.0.sub && !auth.is_admin() {
+        return Err(PollError::PollNotFound);
+    }
+
+    if has_standing_access(app_state, poll, auth).await? {
+        return Ok(());
+    }
+
+    if poll.is_private()
+        && !invite_token.is_some_and(|token| verify_invite_token(token, poll.id, &app_state.config))
+    {
+        return Err(PollError::PollNotFound);
+    }
+
+    if poll.requires_access_code() {
+        return Err(PollError::AccessCodeRequired);
+    }
+
+    db::grant_poll_access(&app_state.db, poll.id, auth.0.sub)
+        .await
+        .map_err(PollError::from)?;
+    app_state.read_cache.invalidate_listings();
+
+    Ok(())
+}
+
+pub async fn unlock_poll(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    Json(payload): Json<UnlockPollRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    match require_poll_access(&app_state, &poll, &auth, payload.token.as_deref()).await {
+        Err(PollError::AccessCodeRequired) => {}
+        result => return result.map(|_| StatusCode::NO_CONTENT),
+    }
+
+    app_state
+        .access_code_limiter
+        .check(user_id)
+        .map_err(|retry_after| PollError::RateLimited(retry_after.as_secs().max(1)))?;
+    app_state
+        .access_code_failure_limiter
+        .peek(&poll_id)
+        .map_err(|retry_after| PollError::RateLimited(retry_after.as_secs().max(1)))?;
+
+    let code = payload
+        .access_code
+        .as_deref()
+        .ok_or(PollError::AccessCodeRequired)?;
+
+    let stored = poll
+        .access_code_hash
+        .as_deref()
+        .ok_or(PollError::AccessCodeRequired)?;
+
+    if !verify_access_code(code, stored).await {
+        let _ = app_state.access_code_failure_limiter.check(poll_id);
+        return Err(PollError::InvalidAccessCode);
+    }
+
+    db::grant_poll_access(&app_state.db, poll_id, user_id)
+        .await
+        .map_err(PollError::from)?;
+    // The poll now shows up in their listings.
+    app_state.read_cache.invalidate_listings();
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn create_poll_invite(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    payload: Option<Json<CreateInviteRequest>>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    if poll.creator_id != user_id {
+        require_poll_access(&app_state, &poll, &auth, None).await?;
+        return Err(PollError::Unauthorized);
+    }
+
+    let ttl_hours = payload
+        .expires_in_hours
+        .unwrap_or(DEFAULT_INVITE_TTL_HOURS)
+        .clamp(1, MAX_INVITE_TTL_HOURS);
+    let expires_at = Utc::now() + ChronoDuration::hours(i64::from(ttl_hours));
+
+    let token = create_invite_token(poll_id, expires_at, &app_state.config)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(InviteResponse {
+            poll_id,
+            token,
+            expires_at,
+        }),
+    ))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VoteLinkClaims {
+    jti: Uuid,
+    poll_id: Uuid,
+    email: String,
+    aud: String,
+    exp: usize,
+    iat: usize,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SendVoteLinksRequest {
+    pub emails: Vec<String>,
+    pub expires_in_hours: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SendVoteLinksResponse {
+    pub poll_id: Uuid,
+    pub sent: usize,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MagicVoteRequest {
+    pub token: String,
+    #[serde(flatten)]
+    pub vote: CastVoteRequest,
+}
+
+fn create_vote_link_token(claims: &VoteLinkClaims, config: &Config) -> Result<String, PollError> {
+    encode(
+        &Header::default(),
+        claims,
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+    )
+    .map_err(|e| {
+        error!("Error signing vote link: {:?}", e);
+        PollError::InviteCreationError
+    })
+}
+
+fn decode_vote_link_token(token: &str, config: &Config) -> Result<VoteLinkClaims, PollError> {
+    let mut validation = Validation::default();
+    validation.set_audience(&[VOTE_LINK_AUDIENCE]);
+
+    decode::<VoteLinkClaims>(
+        token,
+        &DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+        &validation,
+    )
+    .map(|data| data.claims)
+    .map_err(|_| PollError::InvalidVoteLink)
+}
+
+// Emails each address its own link to vote on the poll without signing in.
+#[utoipa::path(
+    post,
+    path = "/polls/{poll_id}/invite",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    request_body = SendVoteLinksRequest,
+    responses(
+        (status = 202, description = "Vote links queued for sending", body = SendVoteLinksResponse),
+        (status = 400, description = "Invalid email address, too many addresses, or poll closed", body = ErrorResponse),
+        (status = 401, description = "Not the poll's creator", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn send_vote_links(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    Json(payload): Json<SendVoteLinksRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    if poll.creator_id != auth.0.sub {
+        require_poll_access(&app_state, &poll, &auth, None).await?;
+        return Err(PollError::Unauthorized);
+    }
+
+    if poll.is_closed() || poll.is_archived() {
+        return Err(PollError::PollClosed);
+    }
+
+    let mut emails: Vec<String> = Vec::with_capacity(payload.emails.len());
+    for email in &payload.emails {
+        let email = normalize_email(email)
+            .ok_or(PollError::InvalidEmail)?
+            .to_lowercase();
+        if !emails.contains(&email) {
+            emails.push(email);
+        }
+    }
+
+    if emails.is_empty() || emails.len() > MAX_VOTE_LINK_EMAILS {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let ttl_hours = payload
+        .expires_in_hours
+        .unwrap_or(DEFAULT_INVITE_TTL_HOURS)
+        .clamp(1, MAX_INVITE_TTL_HOURS);
+    let now = Utc::now();
+    let expires_at = now + ChronoDuration::hours(i64::from(ttl_hours));
+
+    let mut links = Vec::with_capacity(emails.len());
+    for email in emails {
+        let claims = VoteLinkClaims {
+            jti: Uuid::new_v4(),
+            poll_id,
+            email,
+            aud: VOTE_LINK_AUDIENCE.to_string(),
+            exp: expires_at.timestamp() as usize,
+            iat: now.timestamp() as usize,
+        };
+        let token = create_vote_link_token(&claims, &app_state.config)?;
+        links.push((claims, token));
+    }
+
+    let ids: Vec<(Uuid, String)> = links
+        .iter()
+        .map(|(claims, _)| (claims.jti, claims.email.clone()))
+        .collect();
+    db::create_vote_links(&app_state.db, poll_id, &ids, expires_at)
+        .await
+        .map_err(PollError::from)?;
+
+    let frontend_url = app_state.config.frontend_url.as_str().trim_end_matches('/');
+    for (claims, token) in &links {
+        send_in_background(
+            &app_state.mailer,
+            Email {
+                to: claims.email.clone(),
+                subject: format!("You're invited to vote: {}", poll.title),
+                body: format!(
+                    "Hi,\n\n{} invited you to vote on \"{}\". Cast your vote by opening:\n\n{}/polls/{}/vote?token={}\n\nThe link works once and expires in {} hours.\n",
+                    auth.0.username, poll.title, frontend_url, poll_id, token, ttl_hours
+                ),
+            },
+        );
+    }
+
+    info!(
+        "User {} sent {} vote links for poll {}",
+        auth.0.username,
+        links.len(),
+        poll_id
+    );
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(SendVoteLinksResponse {
+            poll_id,
+            sent: links.len(),
+            expires_at,
+        }),
+    ))
+}
+
+// The account with the address verified, or a new one for it: the link shows
+// its holder reads mail there.
+async fn vote_link_user(app_state: &AppState, email: &str) -> Result<Claims, PollError> {
+    let existing = db::get_verified_email_user(&app_state.db, email)
+        .await
+        .map_err(PollError::from)?;
+
+    let user_id = match existing {
+        Some(user_id) => user_id,
+        None => {
+            let user_id = Uuid::new_v4();
+            let username = available_username(&app_state.db, email.split('@').next())
+                .await
+                .map_err(PollError::from)?
+                .ok_or(PollError::InvalidRequest)?;
+
+            match db::create_email_user(&app_state.db, user_id, &username, email).await {
+                Ok(()) => {
+                    info!("Created user {} from a vote link", username);
+                    user_id
+                }
+                // Someone verified the address in the meantime.
+                Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+                    db::get_verified_email_user(&app_state.db, email)
+                        .await
+                        .map_err(PollError::from)?
+                        .ok_or(PollError::InvalidVoteLink)?
+                }
+                Err(e) => return Err(PollError::from(e)),
+            }
+        }
+    };
+
+    let (username, role, banned) = db::get_session_user(&app_state.db, user_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::UserNotFound)?;
+
+    if banned {
+        return Err(PollError::Unauthorized);
+    }
+
+    let now = Utc::now().timestamp() as usize;
+    Ok(Claims {
+        sub: user_id,
+        exp: now,
+        iat: now,
+        username,
+        role,
+        jti: Uuid::nil(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls/{poll_id}/vote/magic",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    request_body = MagicVoteRequest,
+    responses(
+        (status = 200, description = "Vote recorded for the link's address", body = VoteResponse),
+        (status = 400, description = "Vote link invalid, expired or used, or invalid ballot", body = ErrorResponse),
+        (status = 404, description = "Option not found", body = ErrorResponse),
+        (status = 409, description = "Already voted", body = ErrorResponse),
+        (status = 429, description = "Voting too fast", body = ErrorResponse),
+    )
+)]
+pub async fn vote_with_link(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    Path(poll_id): Path<Uuid>,
+    Json(payload): Json<MagicVoteRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let claims = decode_vote_link_token(&payload.token, &app_state.config)?;
+    if claims.poll_id != poll_id {
+        return Err(PollError::InvalidVoteLink);
+    }
+
+    if !db::consume_vote_link(&app_state.db, claims.jti, poll_id, &claims.email)
+        .await
+        .map_err(PollError::from)?
+    {
+        return Err(PollError::InvalidVoteLink);
+    }
+
+    let mut granted_to = None;
+    let voted = async {
+        let auth = BearerAuth(vote_link_user(&app_state, &claims.email).await?);
+
+        // The creator invited them, so they're let into a private or
+        // access-code poll like anyone holding an invite. The vote checks
+        // access, so the grant comes first and is taken back if it fails.
+        if db::grant_poll_access(&app_state.db, poll_id, auth.0.sub)
+            .await
+            .map_err(PollError::from)?
+        {
+            granted_to = Some(auth.0.sub);
+        }
+
+        cast_vote(&app_state, &sse_tx, &auth, poll_id, payload.vote).await
+    }
+    .await;
+
+    match voted {
+        Ok(response) => Ok((StatusCode::OK, Json(response))),
+        Err(e) => {
+            if let Some(user_id) = granted_to
+                && let Err(revoke_error) =
+                    db::revoke_poll_access(&app_state.db, poll_id, user_id).await
+            {
+                error!("Error revoking poll access: {:?}", revoke_error);
+            }
+            if let Err(release_error) = db::release_vote_link(&app_state.db, claims.jti).await {
+                error!("Error releasing vote link: {:?}", release_error);
+            }
+            Err(e)
+        }
+    }
+}
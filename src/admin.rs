@@ -0,0 +1,364 @@
+use crate::auth::BearerAuth;
+use crate::db;
+use crate::error::PollError;
+use crate::polls::broadcast_vote_updates;
+use crate::sse::{SseEvent, SseSender};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json, Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const OVERVIEW_CACHE_TTL: Duration = Duration::from_secs(10);
+const DEFAULT_USERS_PAGE_SIZE: u32 = 50;
+const MAX_USERS_PAGE_SIZE: u32 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct ListUsersParams {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BanUserRequest {
+    pub banned: bool,
+}
+
+impl Default for BanUserRequest {
+    fn default() -> Self {
+        BanUserRequest { banned: true }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListReportsParams {
+    pub resolved: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrphanCleanupParams {
+    #[serde(default)]
+    pub apply: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyPollParams {
+    #[serde(default)]
+    pub apply: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PollCreationPermissionRequest {
+    pub allowed: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAction {
+    #[default]
+    None,
+    Close,
+    Delete,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ResolveReportRequest {
+    #[serde(default)]
+    pub action: ReportAction,
+}
+
+pub async fn list_reports(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Query(params): Query<ListReportsParams>,
+) -> Result<impl IntoResponse, PollError> {
+    auth.require_admin()?;
+
+    let reports = db::get_reports(&app_state.db, params.resolved)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((StatusCode::OK, Json(reports)))
+}
+
+pub async fn resolve_report(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(report_id): Path<Uuid>,
+    payload: Option<Json<ResolveReportRequest>>,
+) -> Result<impl IntoResponse, PollError> {
+    auth.require_admin()?;
+
+    let Json(payload) = payload.unwrap_or_default();
+
+    let report = db::get_report(&app_state.db, report_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::ReportNotFound)?;
+
+    db::resolve_report(&app_state.db, report.id)
+        .await
+        .map_err(PollError::from)?;
+
+    match payload.action {
+        ReportAction::None => {}
+        ReportAction::Close => {
+            db::close_poll(&app_state.db, report.poll_id)
+                .await
+                .map_err(PollError::from)?;
+
+            let _ = sse_tx.send(SseEvent::PollClosed(report.poll_id));
+        }
+        ReportAction::Delete => {
+            db::delete_poll(&app_state.db, report.poll_id)
+                .await
+                .map_err(PollError::from)?;
+
+            let _ = sse_tx.send(SseEvent::PollDeleted(report.poll_id));
+        }
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Report resolved successfully"
+        })),
+    ))
+}
+
+pub async fn cleanup_orphans(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Query(params): Query<OrphanCleanupParams>,
+) -> Result<impl IntoResponse, PollError> {
+    auth.require_admin()?;
+
+    let report = if params.apply {
+        db::delete_orphans(&app_state.db).await
+    } else {
+        db::find_orphans(&app_state.db).await
+    }
+    .map_err(PollError::from)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "applied": params.apply,
+            "orphaned_options": report.orphaned_options,
+            "dangling_votes": report.dangling_votes,
+            "incomplete_polls": report.incomplete_polls,
+        })),
+    ))
+}
+
+pub async fn set_poll_creation_permission(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(user_id): Path<Uuid>,
+    Json(payload): Json<PollCreationPermissionRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    auth.require_admin()?;
+
+    let updated = db::set_can_create_polls(&app_state.db, user_id, payload.allowed)
+        .await
+        .map_err(PollError::from)?;
+
+    if !updated {
+        return Err(PollError::UserNotFound);
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "user_id": user_id,
+            "can_create_polls": payload.allowed
+        })),
+    ))
+}
+
+pub async fn list_users(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Query(params): Query<ListUsersParams>,
+) -> Result<impl IntoResponse, PollError> {
+    auth.require_admin()?;
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_USERS_PAGE_SIZE)
+        .clamp(1, MAX_USERS_PAGE_SIZE) as i64;
+    let offset = params.offset.unwrap_or(0) as i64;
+
+    let users = db::list_users(&app_state.db, limit, offset)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((StatusCode::OK, Json(users)))
+}
+
+pub async fn ban_user(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(user_id): Path<Uuid>,
+    payload: Option<Json<BanUserRequest>>,
+) -> Result<impl IntoResponse, PollError> {
+    auth.require_admin()?;
+
+    let Json(payload) = payload.unwrap_or_default();
+
+    if user_id == auth.0.sub {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let updated = db::set_user_banned(&app_state.db, user_id, payload.banned)
+        .await
+        .map_err(PollError::from)?;
+
+    if !updated {
+        return Err(PollError::UserNotFound);
+    }
+
+    if payload.banned {
+        db::revoke_user_refresh_tokens(&app_state.db, user_id)
+            .await
+            .map_err(PollError::from)?;
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "user_id": user_id,
+            "banned": payload.banned
+        })),
+    ))
+}
+
+pub async fn delete_any_poll(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    auth.require_admin()?;
+
+    let deleted = db::delete_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?;
+
+    if !deleted {
+        return Err(PollError::PollNotFound);
+    }
+
+    let _ = sse_tx.send(SseEvent::PollDeleted(poll_id));
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Poll deleted successfully"
+        })),
+    ))
+}
+
+pub async fn verify_poll_counts(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    Query(params): Query<VerifyPollParams>,
+) -> Result<impl IntoResponse, PollError> {
+    auth.require_admin()?;
+
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    // Encrypted ballots only reach the option counters through a tally, so
+    // there are no vote rows to check them against.
+    if poll.ballot_public_key.is_some() {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let drift = if params.apply {
+        db::repair_vote_count_drift(&app_state.db, poll_id).await
+    } else {
+        db::find_vote_count_drift(&app_state.db, poll_id).await
+    }
+    .map_err(PollError::from)?;
+
+    if params.apply && !drift.is_empty() {
+        let option_ids: Vec<Uuid> = drift.iter().map(|row| row.option_id).collect();
+        broadcast_vote_updates(&app_state, &sse_tx, poll_id, &option_ids).await?;
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "poll_id": poll_id,
+            "applied": params.apply,
+            "consistent": drift.is_empty(),
+            "drift": drift,
+        })),
+    ))
+}
+
+pub async fn admin_overview(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+) -> Result<impl IntoResponse, PollError> {
+    auth.require_admin()?;
+
+    let mut cache = app_state.overview_cache.lock().await;
+
+    if let Some((cached_at, overview)) = cache.as_ref()
+        && cached_at.elapsed() < OVERVIEW_CACHE_TTL
+    {
+        return Ok((StatusCode::OK, Json(overview.clone())));
+    }
+
+    let counts = db::get_activity_counts(&app_state.db)
+        .await
+        .map_err(PollError::from)?;
+
+    let size = app_state.db.size() as usize;
+    let idle = app_state.db.num_idle();
+
+    let overview = json!({
+        "db": {
+            "size": size,
+            "idle": idle,
+            "in_use": size.saturating_sub(idle),
+        },
+        "polls": {
+            "total": counts.total_polls,
+            "open": counts.open_polls,
+            "closed": counts.closed_polls,
+            "created_last_24h": counts.polls_last_24h,
+        },
+        "votes": {
+            "total": counts.total_votes,
+            "last_24h": counts.votes_last_24h,
+        },
+        "users": {
+            "total": counts.total_users,
+            "new_last_24h": counts.users_last_24h,
+        },
+        "sse": {
+            "active_connections": sse_tx.receiver_count(),
+        },
+        "cache": app_state.read_cache.stats(),
+    });
+
+    *cache = Some((Instant::now(), overview.clone()));
+
+    Ok((StatusCode::OK, Json(overview)))
+}
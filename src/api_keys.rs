@@ -0,0 +1,277 @@
+use crate::auth::{BearerAuth, Claims, hash_token};
+use crate::db;
+use crate::db::connection::DbPool;
+use crate::db::models::ApiKey;
+use crate::error::{ErrorResponse, PollError, WebauthnError};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json, Path},
+    http::{HeaderName, Method, StatusCode},
+    response::IntoResponse,
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{Duration as ChronoDuration, Utc};
+use openssl::rand::rand_bytes;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+pub const API_KEY_SCOPES: [&str; 3] = ["read", "vote", "manage"];
+
+const MAX_API_KEYS_PER_USER: i64 = 20;
+const MAX_API_KEY_NAME_LENGTH: usize = 100;
+
+// Keys look like `pak_1a2b3c4d_<secret>`. The part up to the second underscore
+// is stored as issued and identifies the key; the rest is only ever hashed.
+const KEY_MARKER: &str = "pak_";
+const PREFIX_BYTES: usize = 4;
+const PREFIX_LENGTH: usize = KEY_MARKER.len() + PREFIX_BYTES * 2;
+
+// Recording every use would turn each read into a write.
+const LAST_USED_RESOLUTION: ChronoDuration = ChronoDuration::minutes(1);
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreateApiKeyResponse {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+fn normalize_scopes(scopes: &[String]) -> Result<Vec<String>, PollError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(scopes.len());
+
+    for scope in scopes {
+        if !API_KEY_SCOPES.contains(&scope.as_str()) {
+            return Err(PollError::InvalidRequest);
+        }
+        if !normalized.contains(scope) {
+            normalized.push(scope.clone());
+        }
+    }
+
+    if normalized.is_empty() {
+        return Err(PollError::InvalidRequest);
+    }
+
+    Ok(normalized)
+}
+
+// Returns the key and its prefix.
+fn new_api_key() -> Result<(String, String), PollError> {
+    let mut id = [0u8; PREFIX_BYTES];
+    let mut secret = [0u8; 32];
+    rand_bytes(&mut id)
+        .and_then(|_| rand_bytes(&mut secret))
+        .map_err(|e| {
+            error!("Error generating API key: {:?}", e);
+            PollError::ApiKeyGenerationError
+        })?;
+
+    let prefix = format!(
+        "{}{}",
+        KEY_MARKER,
+        id.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+    );
+    let key = format!("{}_{}", prefix, URL_SAFE_NO_PAD.encode(secret));
+    Ok((key, prefix))
+}
+
+// Keys only reach poll, vote and read routes. Account, credential, key and
+// admin routes need the owner's session or token, so a leaked key can't be
+// used to take over the account or mint more keys.
+const API_KEY_ROUTES: [&str; 7] = [
+    "/polls",
+    "/search",
+    "/tags",
+    "/surveys",
+    "/comments",
+    "/me/polls",
+    "/me/votes",
+];
+
+fn api_key_route(path: &str) -> bool {
+    API_KEY_ROUTES.iter().any(|route| {
+        path.strip_prefix(route)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
+
+// On those routes `read` allows GET requests, `vote` casting and retracting
+// votes, and `manage` anything else.
+fn scope_allows(scopes: &[String], method: &Method, path: &str) -> bool {
+    if !api_key_route(path) {
+        return false;
+    }
+
+    let required = if matches!(*method, Method::GET | Method::HEAD) {
+        "read"
+    } else if path.ends_with("/vote") {
+        "vote"
+    } else {
+        "manage"
+    };
+
+    scopes
+        .iter()
+        .any(|scope| scope == required || scope == "manage")
+}
+
+// Requests made with a key act as its owner, with no token to revoke.
+pub async fn api_key_claims(
+    key: &str,
+    method: &Method,
+    path: &str,
+    db: &DbPool,
+) -> Result<Claims, WebauthnError> {
+    let prefix = key
+        .get(..PREFIX_LENGTH)
+        .filter(|prefix| prefix.starts_with(KEY_MARKER))
+        .ok_or(WebauthnError::InvalidToken)?;
+
+    let key_user = db::get_api_key_user(db, prefix, &hash_token(key))
+        .await
+        .map_err(|e| {
+            error!("Error loading API key: {:?}", e);
+            WebauthnError::Unknown
+        })?
+        .ok_or(WebauthnError::InvalidToken)?;
+
+    if key_user.banned {
+        return Err(WebauthnError::UserBanned);
+    }
+
+    if !scope_allows(&key_user.scopes, method, path) {
+        return Err(WebauthnError::InsufficientScope);
+    }
+
+    let now = Utc::now();
+    if key_user
+        .last_used_at
+        .is_none_or(|last_used_at| now - last_used_at >= LAST_USED_RESOLUTION)
+        && let Err(e) = db::touch_api_key(db, key_user.key_id).await
+    {
+        error!("Error recording API key use: {:?}", e);
+    }
+
+    Ok(Claims {
+        sub: key_user.user_id,
+        exp: now.timestamp() as usize,
+        iat: now.timestamp() as usize,
+        username: key_user.username,
+        role: key_user.role,
+        jti: Uuid::nil(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/api-keys",
+    tag = "auth",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "Key created; `key` is shown only this once", body = CreateApiKeyResponse),
+        (status = 400, description = "Invalid name or scopes", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 409, description = "Too many keys", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn create_api_key(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+    let name = payload.name.trim();
+
+    if name.is_empty() || name.chars().count() > MAX_API_KEY_NAME_LENGTH {
+        return Err(PollError::InvalidRequest);
+    }
+    let scopes = normalize_scopes(&payload.scopes)?;
+
+    let existing = db::count_user_api_keys(&app_state.db, user_id)
+        .await
+        .map_err(PollError::from)?;
+
+    if existing >= MAX_API_KEYS_PER_USER {
+        return Err(PollError::ApiKeyLimitReached);
+    }
+
+    let (key, prefix) = new_api_key()?;
+    let api_key = db::create_api_key(
+        &app_state.db,
+        user_id,
+        name,
+        &prefix,
+        &hash_token(&key),
+        &scopes,
+    )
+    .await
+    .map_err(PollError::from)?;
+
+    info!("User {} created API key {}", auth.0.username, prefix);
+
+    // Only the hash is stored, so this is the one chance to copy the key.
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateApiKeyResponse { api_key, key }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/api-keys",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The caller's API keys, without their secrets", body = Vec<ApiKey>),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn list_api_keys(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+) -> Result<impl IntoResponse, PollError> {
+    let api_keys = db::list_user_api_keys(&app_state.db, auth.0.sub)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((StatusCode::OK, Json(api_keys)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/me/api-keys/{key_id}",
+    tag = "auth",
+    params(("key_id" = Uuid, Path)),
+    responses(
+        (status = 204, description = "Key revoked"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+        (status = 404, description = "Key not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_api_key(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(key_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    let deleted = db::delete_api_key(&app_state.db, key_id, auth.0.sub)
+        .await
+        .map_err(PollError::from)?;
+
+    if !deleted {
+        return Err(PollError::ApiKeyNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,78 +1,137 @@
+use crate::api_keys::{self, X_API_KEY};
+use crate::authenticators;
+use crate::ceremony::{CeremonyState, CeremonyUser, PendingCeremony};
+use crate::config::Config;
+use crate::csrf;
 use crate::db;
-use crate::error::WebauthnError;
+use crate::db::models::PasskeyAttestation;
+use crate::email::{normalize_email, send_verification_email};
+use crate::error::{ErrorResponse, PollError, WebauthnError};
+use crate::session::{
+    access_token_cookie, access_token_from_cookie, clear_access_token_cookie, end_session,
+    session_claims, start_session,
+};
 use crate::startup::AppState;
 use axum::{
     async_trait,
-    extract::{Extension, FromRequestParts, Json, Path},
+    extract::{ConnectInfo, Extension, FromRequestParts, Json, Path},
     http::{
         StatusCode,
-        header::{AUTHORIZATION, HeaderMap},
+        header::{AUTHORIZATION, HeaderMap, SET_COOKIE},
         request::Parts,
     },
-    response::IntoResponse,
+    response::{IntoResponse, Response},
 };
-use chrono::{Duration as ChronoDuration, Utc};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
 use serde::{Deserialize, Serialize};
-use serde_json;
-use tracing::{error, info};
+use std::net::{IpAddr, SocketAddr};
+use tower_sessions::Session;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
 use uuid::Uuid;
+use webauthn_rs::prelude::WebauthnError as CoreWebauthnError;
 use webauthn_rs::prelude::*;
+use webauthn_rs_proto::AttestationConveyancePreference;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: Uuid,
     pub exp: usize,
     pub iat: usize,
     pub username: String,
+    #[serde(default)]
+    pub role: String,
+    #[serde(default)]
+    pub jti: Uuid,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct AuthRequest {
     pub username: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RegisterRequest {
+    pub username: String,
+    #[serde(default)]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AuthResponse {
     pub access_token: String,
     pub token_type: String,
     pub expires_in: i64,
+    pub refresh_token: String,
     pub user_id: Uuid,
     pub username: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct LogoutRequest {
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Debug, Clone)]
 pub struct BearerAuth(pub Claims);
 
 impl BearerAuth {
     pub async fn from_headers(
         headers: &HeaderMap,
-        jwt_secret: &str,
-    ) -> Result<Self, (StatusCode, String)> {
+        app_state: &AppState,
+    ) -> Result<Self, WebauthnError> {
         let auth_header = headers
             .get(AUTHORIZATION)
-            .ok_or((
-                StatusCode::UNAUTHORIZED,
-                "Missing Authorization header".to_string(),
-            ))?
+            .ok_or(WebauthnError::Unauthorized)?
             .to_str()
-            .map_err(|_| {
-                (
-                    StatusCode::UNAUTHORIZED,
-                    "Invalid Authorization header".to_string(),
-                )
+            .map_err(|_| WebauthnError::InvalidToken)?;
+
+        let token = auth_header
+            .strip_prefix("Bearer ")
+            .ok_or(WebauthnError::InvalidToken)?;
+
+        Self::from_token(token, app_state).await
+    }
+
+    // Used directly where the token can't travel in a header, such as
+    // EventSource connections.
+    pub async fn from_token(token: &str, app_state: &AppState) -> Result<Self, WebauthnError> {
+        let claims = decode_jwt(token, &app_state.config.jwt_secret)?;
+
+        let revoked = db::is_access_token_revoked(&app_state.db, claims.jti, claims.sub)
+            .await
+            .map_err(|e| {
+                error!("Error checking token revocation: {:?}", e);
+                WebauthnError::Unknown
             })?;
 
-        if !auth_header.starts_with("Bearer ") {
-            return Err((StatusCode::UNAUTHORIZED, "Invalid token format".to_string()));
+        if revoked {
+            return Err(WebauthnError::TokenRevoked);
         }
 
-        let token = &auth_header[7..];
-        let claims = decode_jwt(token, jwt_secret)
-            .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid token".to_string()))?;
-
         Ok(Self(claims))
     }
+
+    pub fn is_admin(&self) -> bool {
+        self.0.role == ADMIN_ROLE
+    }
+
+    pub fn require_admin(&self) -> Result<(), PollError> {
+        if self.is_admin() {
+            Ok(())
+        } else {
+            Err(PollError::Unauthorized)
+        }
+    }
 }
 
 #[async_trait]
@@ -80,37 +139,179 @@ impl<S> FromRequestParts<S> for BearerAuth
 where
     S: Send + Sync,
 {
-    type Rejection = (StatusCode, String);
+    type Rejection = WebauthnError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let app_state = parts.extensions.get::<AppState>().ok_or((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "AppState not found".to_string(),
-        ))?;
+        let app_state = parts.extensions.get::<AppState>().ok_or_else(|| {
+            error!("AppState extension missing");
+            WebauthnError::Unknown
+        })?;
+
+        if parts.headers.contains_key(AUTHORIZATION) {
+            return Self::from_headers(&parts.headers, app_state).await;
+        }
+
+        // Scripts and bots send an API key, limited to what its scopes allow.
+        if let Some(key) = parts.headers.get(&X_API_KEY) {
+            let key = key.to_str().map_err(|_| WebauthnError::InvalidToken)?;
+            return api_keys::api_key_claims(key, &parts.method, parts.uri.path(), &app_state.db)
+                .await
+                .map(Self);
+        }
+
+        // Browser clients authenticate with the access token cookie or,
+        // failing that, a session cookie.
+        let auth = match access_token_from_cookie(&parts.headers) {
+            Some(token) => Self::from_token(&token, app_state).await?,
+            None => {
+                let session = parts
+                    .extensions
+                    .get::<Session>()
+                    .ok_or(WebauthnError::Unauthorized)?;
+                session_claims(session, &app_state.db).await.map(Self)?
+            }
+        };
+
+        csrf::verify(&parts.method, &parts.headers)?;
 
-        Self::from_headers(&parts.headers, &app_state.jwt_secret).await
+        Ok(auth)
     }
 }
 
-pub fn create_jwt(user_id: Uuid, username: &str, secret: &str) -> Result<String, WebauthnError> {
+const MAX_USERNAME_LENGTH: usize = 255;
+const ADMIN_ROLE: &str = "admin";
+const DEFAULT_ROLE: &str = "user";
+
+pub fn normalize_username(username: &str) -> Result<String, WebauthnError> {
+    let username = username.trim();
+
+    if username.is_empty()
+        || username.chars().count() > MAX_USERNAME_LENGTH
+        || username.chars().any(char::is_control)
+    {
+        return Err(WebauthnError::InvalidUsername);
+    }
+
+    Ok(username.to_string())
+}
+
+const MAX_GENERATED_USERNAME_LENGTH: usize = 32;
+const USERNAME_ATTEMPTS: usize = 5;
+
+// Accounts created without a registration, by an OIDC sign-in or an emailed
+// vote link, are named after what's known of the person, with a suffix when
+// that's taken. None if no free name turned up.
+pub(crate) async fn available_username(
+    db: &db::DbPool,
+    name: Option<&str>,
+) -> Result<Option<String>, sqlx::Error> {
+    let base = name
+        .and_then(|name| normalize_username(name).ok())
+        .map(|name| name.chars().take(MAX_GENERATED_USERNAME_LENGTH).collect())
+        .unwrap_or_else(|| "user".to_string());
+
+    let mut candidate = base.clone();
+    for _ in 0..USERNAME_ATTEMPTS {
+        if db::get_user_id(db, &candidate).await?.is_none() {
+            return Ok(Some(candidate));
+        }
+        candidate = format!("{}-{}", base, &Uuid::new_v4().simple().to_string()[..6]);
+    }
+
+    Ok(None)
+}
+
+// Behind the hosting proxy the peer address is the proxy itself, so prefer the
+// address it appended to X-Forwarded-For.
+fn client_ip(headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+    headers
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or_else(|| peer.ip())
+}
+
+pub fn create_jwt(
+    user_id: Uuid,
+    username: &str,
+    role: &str,
+    config: &Config,
+) -> Result<String, WebauthnError> {
     let now = Utc::now();
-    let expiration = now + ChronoDuration::days(7);
+    let expiration = now + ChronoDuration::seconds(config.access_token_ttl_secs);
 
     let claims = Claims {
         sub: user_id,
         exp: expiration.timestamp() as usize,
         iat: now.timestamp() as usize,
         username: username.to_string(),
+        role: role.to_string(),
+        jti: Uuid::new_v4(),
     };
 
     encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
     )
     .map_err(|_| WebauthnError::TokenCreationError)
 }
 
+// Only the SHA-256 of refresh and verification tokens is stored, so a leaked
+// table can't be replayed.
+pub fn hash_token(token: &str) -> String {
+    sha256(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn new_refresh_token() -> Result<(String, String), WebauthnError> {
+    let mut bytes = [0u8; 32];
+    rand_bytes(&mut bytes).map_err(|_| WebauthnError::TokenCreationError)?;
+
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+    let hash = hash_token(&token);
+    Ok((token, hash))
+}
+
+async fn issue_refresh_token(app_state: &AppState, user_id: Uuid) -> Result<String, WebauthnError> {
+    let (token, hash) = new_refresh_token()?;
+
+    db::create_refresh_token(
+        &app_state.db,
+        user_id,
+        &hash,
+        Utc::now() + ChronoDuration::days(app_state.config.refresh_token_ttl_days),
+    )
+    .await
+    .map_err(|e| {
+        error!("Error storing refresh token: {:?}", e);
+        WebauthnError::TokenCreationError
+    })?;
+
+    Ok(token)
+}
+
+// Looked up on every sign-in and refresh so role changes and bans take effect
+// once the current access token expires.
+pub(crate) async fn active_user_role(
+    app_state: &AppState,
+    user_id: Uuid,
+) -> Result<String, WebauthnError> {
+    let (role, banned) = db::get_user_role(&app_state.db, user_id)
+        .await
+        .map_err(|_| WebauthnError::Unknown)?
+        .ok_or(WebauthnError::UserNotFound)?;
+
+    if banned {
+        return Err(WebauthnError::UserBanned);
+    }
+
+    Ok(role)
+}
+
 pub fn decode_jwt(token: &str, secret: &str) -> Result<Claims, WebauthnError> {
     let token_data = decode::<Claims>(
         token,
@@ -125,10 +326,49 @@ pub fn decode_jwt(token: &str, secret: &str) -> Result<Claims, WebauthnError> {
     Ok(token_data.claims)
 }
 
+fn registration_email(email: Option<&str>) -> Result<Option<String>, WebauthnError> {
+    email
+        .filter(|email| !email.trim().is_empty())
+        .map(|email| normalize_email(email).ok_or(WebauthnError::InvalidEmail))
+        .transpose()
+}
+
+// A failure here shouldn't undo the registration; the user can set the address
+// again from their profile.
+async fn start_email_verification(
+    app_state: &AppState,
+    user_id: Uuid,
+    username: &str,
+    email: &str,
+) {
+    match db::set_initial_user_email(&app_state.db, user_id, email).await {
+        Ok(true) => {
+            if let Err(e) = send_verification_email(app_state, user_id, username, email).await {
+                error!("Error sending verification email: {:?}", e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => error!("Error storing email address: {:?}", e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/register",
+    tag = "auth",
+    request_body = RegisterRequest,
+    responses(
+        (status = 201, description = "Account created", body = AuthResponse),
+        (status = 400, description = "Invalid username or email", body = ErrorResponse),
+        (status = 409, description = "Username taken", body = ErrorResponse),
+    )
+)]
 pub async fn register_user(
     Extension(app_state): Extension<AppState>,
-    Json(payload): Json<AuthRequest>,
+    Json(mut payload): Json<RegisterRequest>,
 ) -> Result<impl IntoResponse, WebauthnError> {
+    payload.username = normalize_username(&payload.username)?;
+    let email = registration_email(payload.email.as_deref())?;
     info!("Register user: {}", payload.username);
 
     let user_id = Uuid::new_v4();
@@ -141,12 +381,18 @@ pub async fn register_user(
         .await
         .map_err(|_| WebauthnError::Unknown)?;
 
-    let token = create_jwt(user_id, &payload.username, &app_state.jwt_secret)?;
+    if let Some(email) = &email {
+        start_email_verification(&app_state, user_id, &payload.username, email).await;
+    }
+
+    let token = create_jwt(user_id, &payload.username, DEFAULT_ROLE, &app_state.config)?;
+    let refresh_token = issue_refresh_token(&app_state, user_id).await?;
 
     let response = AuthResponse {
         access_token: token,
         token_type: "Bearer".to_string(),
-        expires_in: 7 * 24 * 60 * 60,
+        expires_in: app_state.config.access_token_ttl_secs,
+        refresh_token,
         user_id,
         username: payload.username,
     };
@@ -154,10 +400,28 @@ pub async fn register_user(
     Ok((StatusCode::CREATED, Json(response)))
 }
 
+// Signs in by username alone, with no proof of possession. Only for local
+// development; everywhere else sign-in goes through the passkey ceremonies.
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "auth",
+    request_body = AuthRequest,
+    responses(
+        (status = 200, description = "Signed in", body = AuthResponse),
+        (status = 401, description = "Dev login is disabled", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+    )
+)]
 pub async fn authenticate_user(
     Extension(app_state): Extension<AppState>,
-    Json(payload): Json<AuthRequest>,
+    Json(mut payload): Json<AuthRequest>,
 ) -> Result<impl IntoResponse, WebauthnError> {
+    if !app_state.config.dev_login_enabled {
+        return Err(WebauthnError::Unauthorized);
+    }
+
+    payload.username = normalize_username(&payload.username)?;
     info!("Authenticate user: {}", payload.username);
 
     let user_id = db::get_user_id(&app_state.db, &payload.username)
@@ -165,12 +429,15 @@ pub async fn authenticate_user(
         .map_err(|_| WebauthnError::Unknown)?
         .ok_or(WebauthnError::UserNotFound)?;
 
-    let token = create_jwt(user_id, &payload.username, &app_state.jwt_secret)?;
+    let role = active_user_role(&app_state, user_id).await?;
+    let token = create_jwt(user_id, &payload.username, &role, &app_state.config)?;
+    let refresh_token = issue_refresh_token(&app_state, user_id).await?;
 
     let response = AuthResponse {
         access_token: token,
         token_type: "Bearer".to_string(),
-        expires_in: 7 * 24 * 60 * 60,
+        expires_in: app_state.config.access_token_ttl_secs,
+        refresh_token,
         user_id,
         username: payload.username,
     };
@@ -178,14 +445,178 @@ pub async fn authenticate_user(
     Ok((StatusCode::OK, Json(response)))
 }
 
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New access and refresh tokens", body = AuthResponse),
+        (status = 401, description = "Refresh token invalid, expired or reused", body = ErrorResponse),
+    )
+)]
+pub async fn refresh_session(
+    Extension(app_state): Extension<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<impl IntoResponse, WebauthnError> {
+    let (refresh_token, refresh_hash) = new_refresh_token()?;
+
+    let rotation = db::rotate_refresh_token(
+        &app_state.db,
+        &hash_token(&payload.refresh_token),
+        &refresh_hash,
+        Utc::now() + ChronoDuration::days(app_state.config.refresh_token_ttl_days),
+    )
+    .await
+    .map_err(|e| {
+        error!("Error rotating refresh token: {:?}", e);
+        WebauthnError::Unknown
+    })?;
+
+    let (user_id, username) = match rotation {
+        db::RefreshRotation::Rotated { user_id, username } => (user_id, username),
+        db::RefreshRotation::Reused => {
+            warn!("Refresh token reuse detected, revoked its token family");
+            return Err(WebauthnError::InvalidToken);
+        }
+        db::RefreshRotation::Invalid => return Err(WebauthnError::InvalidToken),
+    };
+
+    let role = active_user_role(&app_state, user_id).await?;
+    let token = create_jwt(user_id, &username, &role, &app_state.config)?;
+
+    let response = AuthResponse {
+        access_token: token,
+        token_type: "Bearer".to_string(),
+        expires_in: app_state.config.access_token_ttl_secs,
+        refresh_token,
+        user_id,
+        username,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+// The access token stays on the denylist until it would have expired anyway;
+// passing the refresh token as well ends the whole session rather than just
+// this access token.
+#[utoipa::path(
+    post,
+    path = "/logout",
+    tag = "auth",
+    request_body(content = Option<LogoutRequest>, description = "Pass the refresh token to end the whole session"),
+    responses(
+        (status = 204, description = "Signed out"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn logout(
+    Extension(app_state): Extension<AppState>,
+    BearerAuth(claims): BearerAuth,
+    session: Session,
+    payload: Option<Json<LogoutRequest>>,
+) -> Result<impl IntoResponse, WebauthnError> {
+    end_session(&session).await?;
+
+    // Session sign-ins carry no access token to revoke.
+    if !claims.jti.is_nil() {
+        let expires_at =
+            DateTime::from_timestamp(claims.exp as i64, 0).ok_or(WebauthnError::InvalidToken)?;
+
+        db::revoke_access_token(&app_state.db, claims.jti, claims.sub, expires_at)
+            .await
+            .map_err(|e| {
+                error!("Error revoking access token: {:?}", e);
+                WebauthnError::Unknown
+            })?;
+    }
+
+    let Json(payload) = payload.unwrap_or_default();
+
+    if let Some(refresh_token) = payload.refresh_token {
+        db::revoke_refresh_token_family(&app_state.db, claims.sub, &hash_token(&refresh_token))
+            .await
+            .map_err(|e| {
+                error!("Error revoking refresh token: {:?}", e);
+                WebauthnError::Unknown
+            })?;
+    }
+
+    info!("User {} logged out", claims.username);
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(SET_COOKIE, clear_access_token_cookie())],
+    ))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UsernameAvailability {
+    pub available: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/username/available/{username}",
+    tag = "auth",
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, description = "Whether the username can be registered", body = UsernameAvailability),
+        (status = 400, description = "Invalid username", body = ErrorResponse),
+        (status = 429, description = "Too many checks", body = ErrorResponse),
+    )
+)]
+pub async fn check_username_available(
+    Extension(app_state): Extension<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(username): Path<String>,
+) -> Result<impl IntoResponse, WebauthnError> {
+    app_state
+        .username_check_limiter
+        .check(client_ip(&headers, peer))
+        .map_err(|retry_after| WebauthnError::RateLimited(retry_after.as_secs().max(1)))?;
+
+    let username = normalize_username(&username)?;
+
+    let available = db::get_user_id(&app_state.db, &username)
+        .await
+        .map_err(|_| WebauthnError::Unknown)?
+        .is_none();
+
+    Ok(Json(UsernameAvailability { available }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/register_start/{username}",
+    tag = "auth",
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, description = "Options for navigator.credentials.create(); pass state_id back to /register_finish", body = serde_json::Value,
+            example = json!({
+                "public_key": { "publicKey": {} },
+                "state_id": "5f0c7e64-0f5e-4a51-9a1e-2c4ad0f4a8b1",
+                "user_id": "0b9d2d2e-6f6b-4d53-8a53-43e5b3a4c7d1",
+                "username": "alice"
+            })),
+        (status = 400, description = "Invalid username", body = ErrorResponse),
+        (status = 409, description = "Username taken; only its owner, signed in, can add a passkey", body = ErrorResponse),
+    )
+)]
 pub async fn start_register(
     Extension(app_state): Extension<AppState>,
+    auth: Option<BearerAuth>,
     Path(username): Path<String>,
 ) -> Result<impl IntoResponse, WebauthnError> {
+    let username = normalize_username(&username)?;
     info!("Start WebAuthn register for: {}", username);
 
+    // An existing username is only open to its owner adding another passkey.
     let user_unique_id = match db::get_user_id(&app_state.db, &username).await {
-        Ok(Some(id)) => id,
+        Ok(Some(id)) if auth.is_some_and(|BearerAuth(claims)| claims.sub == id) => id,
+        Ok(Some(_)) => return Err(WebauthnError::UserAlreadyExists),
         Ok(None) => Uuid::new_v4(),
         Err(_) => return Err(WebauthnError::Unknown),
     };
@@ -199,7 +630,7 @@ pub async fn start_register(
         Err(_) => None,
     };
 
-    let (ccr, reg_state) = app_state
+    let (mut ccr, reg_state) = app_state
         .webauthn
         .start_passkey_registration(user_unique_id, &username, &username, exclude_credentials)
         .map_err(|e| {
@@ -207,11 +638,29 @@ pub async fn start_register(
             WebauthnError::Unknown
         })?;
 
+    // webauthn-rs asks for no attestation; request it when finish_register
+    // will be checking it.
+    let policy = &app_state.config.attestation_policy;
+    if policy.require_attestation || !policy.allowed_aaguids.is_empty() {
+        ccr.public_key.attestation = Some(AttestationConveyancePreference::Direct);
+    }
+
     info!("WebAuthn registration started for: {}", username);
 
+    let state_id = app_state
+        .ceremonies
+        .insert(
+            CeremonyState::Registration(reg_state),
+            Some(CeremonyUser {
+                user_id: user_unique_id,
+                username: username.clone(),
+            }),
+        )
+        .await;
+
     let state_response = serde_json::json!({
         "public_key": ccr,
-        "registration_state": serde_json::to_value(&reg_state).map_err(|_| WebauthnError::Unknown)?,
+        "state_id": state_id,
         "user_id": user_unique_id,
         "username": username
     });
@@ -219,49 +668,181 @@ pub async fn start_register(
     Ok(Json(state_response))
 }
 
+// Clients may still echo the user id and username from the start response; when
+// they do, they must agree with the stored ceremony.
+fn ceremony_matches(
+    ceremony: &PendingCeremony,
+    user_id: Option<Uuid>,
+    username: Option<&str>,
+) -> bool {
+    ceremony.user.as_ref().is_some_and(|user| {
+        user_id.is_none_or(|id| id == user.user_id)
+            && username.is_none_or(|name| name == user.username)
+    })
+}
+
+async fn reject_attestation(
+    app_state: &AppState,
+    user_id: Uuid,
+    attestation: Option<&PasskeyAttestation>,
+    reason: &str,
+) {
+    // First-time registrations have no users row yet.
+    let user_id = match db::get_username(&app_state.db, user_id).await {
+        Ok(Some(_)) => Some(user_id),
+        _ => None,
+    };
+
+    if let Err(e) = db::record_security_event(
+        &app_state.db,
+        user_id,
+        "attestation_rejected",
+        None,
+        serde_json::json!({
+            "reason": reason,
+            "format": attestation.map(|a| a.format.as_str()),
+            "aaguid": attestation.and_then(|a| a.aaguid),
+            "user_verified": attestation.map(|a| a.user_verified),
+        }),
+    )
+    .await
+    {
+        error!("Error recording security event: {:?}", e);
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/register_finish",
+    tag = "auth",
+    request_body = FinishRegisterRequest,
+    responses(
+        (status = 200, description = "Passkey registered and signed in", body = serde_json::Value,
+            example = json!({
+                "status": "success",
+                "message": "Registration successful",
+                "access_token": "eyJ...",
+                "token_type": "Bearer",
+                "expires_in": 900,
+                "refresh_token": "...",
+                "user_id": "0b9d2d2e-6f6b-4d53-8a53-43e5b3a4c7d1",
+                "username": "alice"
+            })),
+        (status = 400, description = "Ceremony expired or credential rejected", body = ErrorResponse),
+        (status = 403, description = "Authenticator not allowed by the attestation policy", body = ErrorResponse),
+        (status = 409, description = "Username taken", body = ErrorResponse),
+    )
+)]
 pub async fn finish_register(
     Extension(app_state): Extension<AppState>,
+    session: Session,
+    auth: Option<BearerAuth>,
     Json(payload): Json<FinishRegisterRequest>,
 ) -> Result<impl IntoResponse, WebauthnError> {
-    info!("Finish WebAuthn register for user_id: {}", payload.user_id);
+    let claimed_username = payload
+        .username
+        .as_deref()
+        .map(normalize_username)
+        .transpose()?;
+    let email = registration_email(payload.email.as_deref())?;
 
-    let reg_state: PasskeyRegistration = serde_json::from_value(payload.registration_state)
-        .map_err(|e| {
-            error!("Failed to deserialize registration state: {:?}", e);
-            WebauthnError::Unknown
-        })?;
+    let ceremony = app_state
+        .ceremonies
+        .take(payload.state_id)
+        .await
+        .filter(|c| ceremony_matches(c, payload.user_id, claimed_username.as_deref()))
+        .ok_or(WebauthnError::CorruptSession)?;
+
+    let (CeremonyState::Registration(reg_state), Some(user)) = (ceremony.state, ceremony.user)
+    else {
+        return Err(WebauthnError::CorruptSession);
+    };
+    let CeremonyUser { user_id, username } = user;
+    info!("Finish WebAuthn register for user_id: {}", user_id);
 
     let res = match app_state
         .webauthn
         .finish_passkey_registration(&payload.credential, &reg_state)
     {
         Ok(sk) => {
-            if let Err(e) = db::create_user(&app_state.db, payload.user_id, &payload.username).await
-            {
-                error!("Error creating user (may already exist): {:?}", e);
+            let attestation =
+                authenticators::parse_attestation(&payload.credential.response.attestation_object);
+
+            if let Some(reason) = authenticators::policy_violation(
+                &app_state.config.attestation_policy,
+                attestation.as_ref(),
+            ) {
+                warn!("Rejected passkey registration for {}: {}", username, reason);
+                reject_attestation(&app_state, user_id, attestation.as_ref(), reason).await;
+                return Err(WebauthnError::AuthenticatorNotAllowed);
             }
 
-            if let Err(e) = db::add_passkey(&app_state.db, payload.user_id, &sk).await {
-                error!("Error adding passkey to database: {:?}", e);
+            // Only the account's own token can add a passkey to it; anyone
+            // else gets a brand new account or a conflict.
+            let stored = if auth.is_some_and(|BearerAuth(claims)| claims.sub == user_id) {
+                db::add_user_passkey(
+                    &app_state.db,
+                    user_id,
+                    &sk,
+                    payload.nickname.as_deref(),
+                    attestation.as_ref(),
+                )
+                .await
+            } else {
+                db::register_user_with_passkey(
+                    &app_state.db,
+                    user_id,
+                    &username,
+                    &sk,
+                    payload.nickname.as_deref(),
+                    attestation.as_ref(),
+                )
+                .await
+            };
+
+            if let Err(e) = stored {
+                error!("Error registering passkey in database: {:?}", e);
+                if let sqlx::Error::Database(db_err) = &e
+                    && db_err.is_unique_violation()
+                {
+                    return Err(WebauthnError::UserAlreadyExists);
+                }
                 return Err(WebauthnError::Unknown);
             }
 
-            let token = create_jwt(payload.user_id, &payload.username, &app_state.jwt_secret)?;
+            if let Some(email) = &email {
+                start_email_verification(&app_state, user_id, &username, email).await;
+            }
 
-            info!("WebAuthn registration successful for: {}", payload.username);
+            // A passkey added to an existing account keeps that account's
+            // role, and a banned one gets no token.
+            let role = active_user_role(&app_state, user_id).await?;
+            let token = create_jwt(user_id, &username, &role, &app_state.config)?;
+            let refresh_token = issue_refresh_token(&app_state, user_id).await?;
+            start_session(&session, user_id).await?;
 
-            (
+            info!("WebAuthn registration successful for: {}", username);
+
+            let mut response = (
                 StatusCode::OK,
                 Json(serde_json::json!({
                     "status": "success",
                     "message": "Registration successful",
                     "access_token": token,
                     "token_type": "Bearer",
-                    "expires_in": 7 * 24 * 60 * 60,
-                    "user_id": payload.user_id,
-                    "username": payload.username
+                    "expires_in": app_state.config.access_token_ttl_secs,
+                    "refresh_token": refresh_token,
+                    "user_id": user_id,
+                    "username": username
                 })),
             )
+                .into_response();
+
+            if payload.cookie {
+                attach_access_token_cookie(&mut response, &token, &app_state.config);
+            }
+
+            response
         }
         Err(e) => {
             error!("finish_passkey_registration error: {:?}", e);
@@ -272,15 +853,162 @@ pub async fn finish_register(
                     "message": format!("Registration failed: {:?}", e)
                 })),
             )
+                .into_response()
         }
     };
     Ok(res)
 }
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CredentialDetails {
+    #[schema(value_type = String)]
+    pub cred_id: CredentialID,
+    pub nickname: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub aaguid: Option<Uuid>,
+    pub authenticator_name: Option<&'static str>,
+    pub sign_count: i64,
+    pub backup_eligible: Option<bool>,
+    pub backup_state: Option<bool>,
+    pub clone_suspected_at: Option<DateTime<Utc>>,
+    pub attestation_format: Option<String>,
+    pub user_verified: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/credentials/details",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The caller's passkeys", body = Vec<CredentialDetails>),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn list_credential_details(
+    Extension(app_state): Extension<AppState>,
+    BearerAuth(claims): BearerAuth,
+) -> Result<impl IntoResponse, WebauthnError> {
+    let records = db::get_user_passkey_records(&app_state.db, claims.sub)
+        .await
+        .map_err(|_| WebauthnError::Unknown)?;
+
+    let credentials: Vec<CredentialDetails> = records
+        .into_iter()
+        .map(|record| CredentialDetails {
+            cred_id: record.passkey.cred_id().clone(),
+            nickname: record.nickname,
+            created_at: record.created_at.map(|t| t.and_utc()),
+            last_used_at: record.last_used_at,
+            aaguid: record.aaguid,
+            authenticator_name: record
+                .aaguid
+                .as_ref()
+                .and_then(authenticators::authenticator_name),
+            sign_count: record.sign_count,
+            backup_eligible: record.backup_eligible,
+            backup_state: record.backup_state,
+            clone_suspected_at: record.clone_suspected_at,
+            attestation_format: record.attestation_format,
+            user_verified: record.user_verified,
+        })
+        .collect();
+
+    Ok(Json(credentials))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CredentialSummary {
+    pub id: i32,
+    #[schema(value_type = String)]
+    pub credential_id: CredentialID,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/credentials",
+    tag = "auth",
+    responses(
+        (status = 200, description = "The caller's passkeys", body = Vec<CredentialSummary>),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn list_credentials(
+    Extension(app_state): Extension<AppState>,
+    BearerAuth(claims): BearerAuth,
+) -> Result<impl IntoResponse, WebauthnError> {
+    let records = db::get_user_passkey_records(&app_state.db, claims.sub)
+        .await
+        .map_err(|_| WebauthnError::Unknown)?;
+
+    let credentials: Vec<CredentialSummary> = records
+        .into_iter()
+        .map(|record| CredentialSummary {
+            id: record.id,
+            credential_id: record.passkey.cred_id().clone(),
+            created_at: record.created_at.map(|t| t.and_utc()),
+        })
+        .collect();
+
+    Ok(Json(credentials))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/credentials/{cred_id}",
+    tag = "auth",
+    params(("cred_id" = String, Path, description = "Base64url credential id")),
+    responses(
+        (status = 204, description = "Passkey removed"),
+        (status = 404, description = "Credential not found", body = ErrorResponse),
+        (status = 400, description = "Can't remove the last passkey", body = ErrorResponse),
+    ),
+    security(("bearer" = []))
+)]
+pub async fn delete_credential(
+    Extension(app_state): Extension<AppState>,
+    BearerAuth(claims): BearerAuth,
+    Path(cred_id): Path<String>,
+) -> Result<impl IntoResponse, WebauthnError> {
+    let deletion = db::delete_passkey(&app_state.db, claims.sub, &cred_id)
+        .await
+        .map_err(|_| WebauthnError::Unknown)?;
+
+    match deletion {
+        db::PasskeyDeletion::Deleted => {
+            info!("Removed credential for user_id: {}", claims.sub);
+            Ok(StatusCode::NO_CONTENT)
+        }
+        db::PasskeyDeletion::NotFound => Err(WebauthnError::CredentialNotFound),
+        db::PasskeyDeletion::LastCredential => Err(WebauthnError::LastCredential),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/login_start/{username}",
+    tag = "auth",
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, description = "Options for navigator.credentials.get(); pass state_id back to /login_finish", body = serde_json::Value,
+            example = json!({
+                "public_key": { "publicKey": {} },
+                "state_id": "5f0c7e64-0f5e-4a51-9a1e-2c4ad0f4a8b1",
+                "user_id": "0b9d2d2e-6f6b-4d53-8a53-43e5b3a4c7d1",
+                "username": "alice"
+            })),
+        (status = 400, description = "User has no passkeys", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+    )
+)]
 pub async fn start_authentication(
     Extension(app_state): Extension<AppState>,
     Path(username): Path<String>,
 ) -> Result<impl IntoResponse, WebauthnError> {
+    let username = normalize_username(&username)?;
     info!("Start WebAuthn authentication for: {}", username);
 
     let user_unique_id = db::get_user_id(&app_state.db, &username)
@@ -306,9 +1034,20 @@ pub async fn start_authentication(
 
     info!("WebAuthn authentication started for: {}", username);
 
+    let state_id = app_state
+        .ceremonies
+        .insert(
+            CeremonyState::Authentication(auth_state),
+            Some(CeremonyUser {
+                user_id: user_unique_id,
+                username: username.clone(),
+            }),
+        )
+        .await;
+
     let state_response = serde_json::json!({
         "public_key": rcr,
-        "authentication_state": serde_json::to_value(&auth_state).map_err(|_| WebauthnError::Unknown)?,
+        "state_id": state_id,
         "user_id": user_unique_id,
         "username": username
     });
@@ -316,63 +1055,300 @@ pub async fn start_authentication(
     Ok(Json(state_response))
 }
 
+async fn complete_authentication(
+    app_state: &AppState,
+    session: &Session,
+    user_id: Uuid,
+    username: &str,
+    auth_result: &AuthenticationResult,
+    set_cookie: bool,
+) -> Result<Response, WebauthnError> {
+    let role = active_user_role(app_state, user_id).await?;
+
+    let mut passkeys = db::get_user_passkeys(&app_state.db, user_id)
+        .await
+        .map_err(|_| WebauthnError::Unknown)?;
+
+    if let Some(sk) = passkeys
+        .iter_mut()
+        .find(|sk| sk.cred_id() == auth_result.cred_id())
+    {
+        sk.update_credential(auth_result);
+
+        if let Err(e) = db::record_passkey_use(&app_state.db, user_id, sk, auth_result).await {
+            error!("Error updating passkey in database: {:?}", e);
+            return Err(WebauthnError::Unknown);
+        }
+    }
+
+    info!("WebAuthn authentication successful for: {}", username);
+
+    sign_in(app_state, session, user_id, username, &role, set_cookie).await
+}
+
+// The tokens and session every interactive sign-in ends with, whichever way
+// the user proved who they are.
+pub(crate) async fn sign_in(
+    app_state: &AppState,
+    session: &Session,
+    user_id: Uuid,
+    username: &str,
+    role: &str,
+    set_cookie: bool,
+) -> Result<Response, WebauthnError> {
+    let token = create_jwt(user_id, username, role, &app_state.config)?;
+    let refresh_token = issue_refresh_token(app_state, user_id).await?;
+    start_session(session, user_id).await?;
+
+    let mut response = (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "status": "success",
+            "message": "Authentication successful",
+            "access_token": token,
+            "token_type": "Bearer",
+            "expires_in": app_state.config.access_token_ttl_secs,
+            "refresh_token": refresh_token,
+            "user_id": user_id,
+            "username": username
+        })),
+    )
+        .into_response();
+
+    if set_cookie {
+        attach_access_token_cookie(&mut response, &token, &app_state.config);
+    }
+
+    Ok(response)
+}
+
+fn attach_access_token_cookie(response: &mut Response, token: &str, config: &Config) {
+    if let Some(cookie) = access_token_cookie(token, config) {
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+}
+
+// webauthn-rs refuses an assertion whose signature counter did not advance,
+// which means a second copy of the private key is likely in use. Mark the
+// credential so the owner can see it and keep a record of the attempt.
+async fn flag_possible_clone(
+    app_state: &AppState,
+    user_id: Uuid,
+    credential: &PublicKeyCredential,
+) {
+    let cred_id = db::credential_key(&CredentialID::from(credential.raw_id.to_vec()));
+    warn!(
+        "Signature counter regression for user_id {} on credential {:?}",
+        user_id, cred_id
+    );
+
+    if let Some(cred_id) = cred_id.as_deref()
+        && let Err(e) = db::flag_passkey_clone(&app_state.db, user_id, cred_id).await
+    {
+        error!("Error flagging cloned passkey: {:?}", e);
+    }
+
+    if let Err(e) = db::record_security_event(
+        &app_state.db,
+        Some(user_id),
+        "possible_cloned_authenticator",
+        cred_id.as_deref(),
+        serde_json::json!({ "reason": "signature counter did not increase" }),
+    )
+    .await
+    {
+        error!("Error recording security event: {:?}", e);
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/login_finish",
+    tag = "auth",
+    request_body = FinishAuthRequest,
+    responses(
+        (status = 200, description = "Signed in", body = serde_json::Value,
+            example = json!({
+                "status": "success",
+                "message": "Authentication successful",
+                "access_token": "eyJ...",
+                "token_type": "Bearer",
+                "expires_in": 900,
+                "refresh_token": "...",
+                "user_id": "0b9d2d2e-6f6b-4d53-8a53-43e5b3a4c7d1",
+                "username": "alice"
+            })),
+        (status = 400, description = "Ceremony expired or assertion rejected", body = ErrorResponse),
+        (status = 403, description = "Account banned, or the passkey looks cloned", body = ErrorResponse),
+    )
+)]
 pub async fn finish_authentication(
     Extension(app_state): Extension<AppState>,
+    session: Session,
     Json(payload): Json<FinishAuthRequest>,
 ) -> Result<impl IntoResponse, WebauthnError> {
-    info!(
-        "Finish WebAuthn authentication for user_id: {}",
-        payload.user_id
-    );
+    let ceremony = app_state
+        .ceremonies
+        .take(payload.state_id)
+        .await
+        .filter(|c| ceremony_matches(c, payload.user_id, payload.username.as_deref()))
+        .ok_or(WebauthnError::CorruptSession)?;
 
-    let auth_state: PasskeyAuthentication = serde_json::from_value(payload.authentication_state)
-        .map_err(|e| {
-            error!("Failed to deserialize authentication state: {:?}", e);
-            WebauthnError::Unknown
-        })?;
+    let (CeremonyState::Authentication(auth_state), Some(user)) = (ceremony.state, ceremony.user)
+    else {
+        return Err(WebauthnError::CorruptSession);
+    };
+    let CeremonyUser { user_id, username } = user;
+    info!("Finish WebAuthn authentication for user_id: {}", user_id);
 
     let res = match app_state
         .webauthn
         .finish_passkey_authentication(&payload.credential, &auth_state)
     {
         Ok(auth_result) => {
-            let mut passkeys = db::get_user_passkeys(&app_state.db, payload.user_id)
-                .await
-                .map_err(|_| WebauthnError::Unknown)?;
+            complete_authentication(
+                &app_state,
+                &session,
+                user_id,
+                &username,
+                &auth_result,
+                payload.cookie,
+            )
+            .await?
+        }
+        Err(CoreWebauthnError::CredentialPossibleCompromise) => {
+            flag_possible_clone(&app_state, user_id, &payload.credential).await;
+            return Err(WebauthnError::PossibleClonedCredential);
+        }
+        Err(e) => {
+            error!("finish_passkey_authentication error: {:?}", e);
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "status": "error",
+                    "message": format!("Authentication failed: {:?}", e)
+                })),
+            )
+                .into_response()
+        }
+    };
+    Ok(res)
+}
 
-            passkeys.iter_mut().for_each(|sk: &mut Passkey| {
-                sk.update_credential(&auth_result);
-            });
+#[utoipa::path(
+    post,
+    path = "/login_start_discoverable",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Options for a usernameless navigator.credentials.get()", body = serde_json::Value,
+            example = json!({
+                "public_key": { "publicKey": {} },
+                "state_id": "5f0c7e64-0f5e-4a51-9a1e-2c4ad0f4a8b1"
+            })),
+    )
+)]
+pub async fn start_discoverable_authentication(
+    Extension(app_state): Extension<AppState>,
+) -> Result<impl IntoResponse, WebauthnError> {
+    info!("Start discoverable WebAuthn authentication");
 
-            if let Err(e) =
-                db::update_user_passkeys(&app_state.db, payload.user_id, &passkeys).await
-            {
-                error!("Error updating passkeys in database: {:?}", e);
-                return Err(WebauthnError::Unknown);
-            }
+    let (rcr, auth_state) = app_state
+        .webauthn
+        .start_discoverable_authentication()
+        .map_err(|e| {
+            error!("start_discoverable_authentication error: {:?}", e);
+            WebauthnError::Unknown
+        })?;
 
-            let token = create_jwt(payload.user_id, &payload.username, &app_state.jwt_secret)?;
+    let state_id = app_state
+        .ceremonies
+        .insert(CeremonyState::DiscoverableAuthentication(auth_state), None)
+        .await;
 
-            info!(
-                "WebAuthn authentication successful for: {}",
-                payload.username
-            );
+    let state_response = serde_json::json!({
+        "public_key": rcr,
+        "state_id": state_id
+    });
 
-            (
-                StatusCode::OK,
-                Json(serde_json::json!({
-                    "status": "success",
-                    "message": "Authentication successful",
-                    "access_token": token,
-                    "token_type": "Bearer",
-                    "expires_in": 7 * 24 * 60 * 60,
-                    "user_id": payload.user_id,
-                    "username": payload.username
-                })),
+    Ok(Json(state_response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/login_finish_discoverable",
+    tag = "auth",
+    request_body = FinishDiscoverableAuthRequest,
+    responses(
+        (status = 200, description = "Signed in; same body as /login_finish", body = serde_json::Value),
+        (status = 400, description = "Ceremony expired or assertion rejected", body = ErrorResponse),
+        (status = 403, description = "Account banned, or the passkey looks cloned", body = ErrorResponse),
+        (status = 404, description = "No account for this passkey", body = ErrorResponse),
+    )
+)]
+pub async fn finish_discoverable_authentication(
+    Extension(app_state): Extension<AppState>,
+    session: Session,
+    Json(payload): Json<FinishDiscoverableAuthRequest>,
+) -> Result<impl IntoResponse, WebauthnError> {
+    let ceremony = app_state
+        .ceremonies
+        .take(payload.state_id)
+        .await
+        .ok_or(WebauthnError::CorruptSession)?;
+
+    let CeremonyState::DiscoverableAuthentication(auth_state) = ceremony.state else {
+        return Err(WebauthnError::CorruptSession);
+    };
+
+    let (user_id, _) = app_state
+        .webauthn
+        .identify_discoverable_authentication(&payload.credential)
+        .map_err(|e| {
+            error!("identify_discoverable_authentication error: {:?}", e);
+            WebauthnError::UserNotFound
+        })?;
+    info!(
+        "Finish discoverable WebAuthn authentication for user_id: {}",
+        user_id
+    );
+
+    let username = db::get_username(&app_state.db, user_id)
+        .await
+        .map_err(|_| WebauthnError::Unknown)?
+        .ok_or(WebauthnError::UserNotFound)?;
+
+    let passkeys = db::get_user_passkeys(&app_state.db, user_id)
+        .await
+        .map_err(|_| WebauthnError::Unknown)?;
+
+    if passkeys.is_empty() {
+        return Err(WebauthnError::UserHasNoCredentials);
+    }
+
+    let creds: Vec<DiscoverableKey> = passkeys.iter().map(DiscoverableKey::from).collect();
+
+    let res = match app_state.webauthn.finish_discoverable_authentication(
+        &payload.credential,
+        auth_state,
+        &creds,
+    ) {
+        Ok(auth_result) => {
+            complete_authentication(
+                &app_state,
+                &session,
+                user_id,
+                &username,
+                &auth_result,
+                payload.cookie,
             )
+            .await?
+        }
+        Err(CoreWebauthnError::CredentialPossibleCompromise) => {
+            flag_possible_clone(&app_state, user_id, &payload.credential).await;
+            return Err(WebauthnError::PossibleClonedCredential);
         }
         Err(e) => {
-            error!("finish_passkey_authentication error: {:?}", e);
+            error!("finish_discoverable_authentication error: {:?}", e);
             (
                 StatusCode::BAD_REQUEST,
                 Json(serde_json::json!({
@@ -380,23 +1356,48 @@ pub async fn finish_authentication(
                     "message": format!("Authentication failed: {:?}", e)
                 })),
             )
+                .into_response()
         }
     };
     Ok(res)
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct FinishRegisterRequest {
+    #[schema(value_type = Object)]
     pub credential: RegisterPublicKeyCredential,
-    pub registration_state: serde_json::Value,
-    pub user_id: Uuid,
-    pub username: String,
+    pub state_id: Uuid,
+    #[serde(default)]
+    pub user_id: Option<Uuid>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub nickname: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    // Also set the access token as an HttpOnly cookie.
+    #[serde(default)]
+    pub cookie: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct FinishAuthRequest {
+    #[schema(value_type = Object)]
     pub credential: PublicKeyCredential,
-    pub authentication_state: serde_json::Value,
-    pub user_id: Uuid,
-    pub username: String,
+    pub state_id: Uuid,
+    #[serde(default)]
+    pub user_id: Option<Uuid>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub cookie: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FinishDiscoverableAuthRequest {
+    #[schema(value_type = Object)]
+    pub credential: PublicKeyCredential,
+    pub state_id: Uuid,
+    #[serde(default)]
+    pub cookie: bool,
 }
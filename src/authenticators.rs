@@ -0,0 +1,111 @@
+use crate::config::AttestationPolicy;
+use crate::db::models::PasskeyAttestation;
+use serde::Deserialize;
+use uuid::Uuid;
+
+// Subset of the FIDO metadata service and the community passkey provider list.
+const KNOWN_AUTHENTICATORS: &[(&str, &str)] = &[
+    ("fbfc3007-154e-4ecc-8c0b-6e020557d7bd", "iCloud Keychain"),
+    (
+        "dd4ec289-e01d-41c9-bb89-70fa845d4bf2",
+        "iCloud Keychain (Managed)",
+    ),
+    (
+        "ea9b8d66-4d01-1d21-3ce4-b6b48cb575d4",
+        "Google Password Manager",
+    ),
+    ("adce0002-35bc-c60a-648b-0b25f1f05503", "Chrome on Mac"),
+    ("08987058-cadc-4b81-b6e1-30de50dcbe96", "Windows Hello"),
+    ("9ddd1817-af5a-4672-a2b9-3e3dd95000a9", "Windows Hello"),
+    ("6028b017-b1d4-4c02-b4b3-afcdafc96bb2", "Windows Hello"),
+    ("bada5566-a7aa-401f-bd96-45619a55120d", "1Password"),
+    ("d548826e-79b4-db40-a3d8-11116f7e8349", "Bitwarden"),
+    ("531126d6-e717-415c-9320-3d9aa6981239", "Dashlane"),
+    ("b84e4048-15dc-4dd0-8640-f4f60813c8af", "NordPass"),
+    ("0ea242b4-43c4-4a1b-8b17-dd6d0b6baec6", "Keeper"),
+    ("53414d53-554e-4700-0000-000000000000", "Samsung Pass"),
+    ("cb69481e-8ff7-4039-93ec-0a2729a154a8", "YubiKey 5 Series"),
+    ("ee882879-721c-4913-9775-3dfcce97072a", "YubiKey 5 Series"),
+    (
+        "fa2b99dc-9e39-4257-8f92-4a30d23c4118",
+        "YubiKey 5 Series with NFC",
+    ),
+    (
+        "2fc0579f-8113-47ea-b116-bb5a8db9202a",
+        "YubiKey 5 Series with NFC",
+    ),
+    ("c5ef55ff-ad9a-4b9f-b580-adebafe026d0", "YubiKey 5Ci"),
+];
+
+const AUTH_DATA_FLAGS_OFFSET: usize = 32;
+const AUTH_DATA_AAGUID_OFFSET: usize = 37;
+const USER_VERIFIED_FLAG: u8 = 0x04;
+const ATTESTED_CREDENTIAL_DATA_FLAG: u8 = 0x40;
+const NO_ATTESTATION_FORMAT: &str = "none";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawAttestationObject<'a> {
+    fmt: String,
+    auth_data: &'a [u8],
+}
+
+pub fn authenticator_name(aaguid: &Uuid) -> Option<&'static str> {
+    let aaguid = aaguid.to_string();
+    KNOWN_AUTHENTICATORS
+        .iter()
+        .find(|(known, _)| *known == aaguid)
+        .map(|(_, name)| *name)
+}
+
+pub fn parse_attestation(attestation_object: &[u8]) -> Option<PasskeyAttestation> {
+    let raw: RawAttestationObject = serde_cbor_2::from_slice(attestation_object).ok()?;
+    let flags = *raw.auth_data.get(AUTH_DATA_FLAGS_OFFSET)?;
+
+    Some(PasskeyAttestation {
+        aaguid: aaguid_from_auth_data(raw.auth_data, flags),
+        format: raw.fmt,
+        user_verified: flags & USER_VERIFIED_FLAG != 0,
+    })
+}
+
+fn aaguid_from_auth_data(auth_data: &[u8], flags: u8) -> Option<Uuid> {
+    if flags & ATTESTED_CREDENTIAL_DATA_FLAG == 0 {
+        return None;
+    }
+
+    let aaguid = auth_data.get(AUTH_DATA_AAGUID_OFFSET..AUTH_DATA_AAGUID_OFFSET + 16)?;
+    let aaguid = Uuid::from_slice(aaguid).ok()?;
+
+    if aaguid.is_nil() {
+        return None;
+    }
+
+    Some(aaguid)
+}
+
+// webauthn-rs has already checked the attestation signature by the time this
+// runs; this only decides whether the authenticator is acceptable.
+pub fn policy_violation(
+    policy: &AttestationPolicy,
+    attestation: Option<&PasskeyAttestation>,
+) -> Option<&'static str> {
+    let Some(attestation) = attestation else {
+        let unrestricted = !policy.require_attestation && policy.allowed_aaguids.is_empty();
+        return (!unrestricted).then_some("attestation object could not be read");
+    };
+
+    if policy.require_attestation && attestation.format == NO_ATTESTATION_FORMAT {
+        return Some("authenticator did not provide attestation");
+    }
+
+    if !policy.allowed_aaguids.is_empty()
+        && !attestation
+            .aaguid
+            .is_some_and(|aaguid| policy.allowed_aaguids.contains(&aaguid))
+    {
+        return Some("authenticator model is not on the allow list");
+    }
+
+    None
+}
@@ -0,0 +1,45 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use openssl::md::Md;
+use openssl::pkey::{Id, PKey, Private, Public};
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::rsa::Padding;
+use uuid::Uuid;
+
+const MIN_KEY_BITS: u32 = 2048;
+pub const MAX_BALLOT_LENGTH: usize = 2048;
+
+pub fn parse_public_key(pem: &str) -> Option<PKey<Public>> {
+    let key = PKey::public_key_from_pem(pem.as_bytes()).ok()?;
+    if key.id() != Id::RSA || key.bits() < MIN_KEY_BITS {
+        return None;
+    }
+    Some(key)
+}
+
+pub fn parse_private_key(pem: &str, public_key_pem: &str) -> Option<PKey<Private>> {
+    let public_key = parse_public_key(public_key_pem)?;
+    let key = PKey::private_key_from_pem(pem.as_bytes()).ok()?;
+    if !key.public_eq(&public_key) {
+        return None;
+    }
+    Some(key)
+}
+
+pub fn is_well_formed_ballot(ballot: &str) -> bool {
+    ballot.len() <= MAX_BALLOT_LENGTH && STANDARD.decode(ballot).is_ok()
+}
+
+pub fn decrypt_ballot(key: &PKey<Private>, ballot: &str) -> Option<Uuid> {
+    let ciphertext = STANDARD.decode(ballot).ok()?;
+
+    let mut ctx = PkeyCtx::new(key).ok()?;
+    ctx.decrypt_init().ok()?;
+    ctx.set_rsa_padding(Padding::PKCS1_OAEP).ok()?;
+    ctx.set_rsa_oaep_md(Md::sha256()).ok()?;
+
+    let mut plaintext = Vec::new();
+    ctx.decrypt_to_vec(&ciphertext, &mut plaintext).ok()?;
+
+    let option_id = std::str::from_utf8(&plaintext).ok()?;
+    Uuid::parse_str(option_id.trim()).ok()
+}
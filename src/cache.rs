@@ -0,0 +1,179 @@
+use crate::db;
+use crate::db::connection::DbPool;
+use crate::db::models::{Poll, PollOption};
+use crate::sse::{PollSnapshot, SseEvent};
+use moka::sync::Cache;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use uuid::Uuid;
+
+const MAX_CACHED_POLLS: u64 = 10_000;
+const MAX_CACHED_LISTINGS: u64 = 2_000;
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Counters {
+    fn record(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn to_json(&self, entries: u64) -> Value {
+        json!({
+            "hits": self.hits.load(Ordering::Relaxed),
+            "misses": self.misses.load(Ordering::Relaxed),
+            "entries": entries,
+        })
+    }
+}
+
+// Listings are cached per viewer, since which polls they include depends on
+// who's asking.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ListingKey {
+    creator_id: Option<Uuid>,
+    closed: Option<bool>,
+    search: Option<String>,
+    tag: Option<String>,
+    viewer_id: Option<Uuid>,
+    include_archived: bool,
+    limit: i64,
+    offset: i64,
+}
+
+pub struct PollPage {
+    pub polls: Vec<(Poll, Vec<PollOption>)>,
+    pub total: i64,
+}
+
+// Polls and poll listings as stored, before anything viewer specific (whether
+// they voted, whether they may see counts) is worked out. Entries are dropped
+// as the SSE broadcaster delivers events about them, local or relayed, and
+// otherwise expire after a few seconds so writes that announce nothing are
+// only briefly stale.
+pub struct ReadCache {
+    polls: Cache<Uuid, Arc<PollSnapshot>>,
+    listings: Cache<ListingKey, Arc<PollPage>>,
+    // Bumped by every invalidation. A read that started before one doesn't
+    // store its result, which may predate the change.
+    generation: AtomicU64,
+    poll_counters: Counters,
+    listing_counters: Counters,
+}
+
+impl ReadCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            polls: Cache::builder()
+                .max_capacity(MAX_CACHED_POLLS)
+                .time_to_live(ttl)
+                .build(),
+            listings: Cache::builder()
+                .max_capacity(MAX_CACHED_LISTINGS)
+                .time_to_live(ttl)
+                .build(),
+            generation: AtomicU64::new(0),
+            poll_counters: Counters::default(),
+            listing_counters: Counters::default(),
+        }
+    }
+
+    pub async fn poll(
+        &self,
+        db: &DbPool,
+        poll_id: Uuid,
+    ) -> Result<Option<Arc<PollSnapshot>>, sqlx::Error> {
+        if let Some(snapshot) = self.polls.get(&poll_id) {
+            self.poll_counters.record(true);
+            return Ok(Some(snapshot));
+        }
+        self.poll_counters.record(false);
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let Some((poll, options)) = db::get_poll_with_options(db, poll_id).await? else {
+            return Ok(None);
+        };
+
+        let snapshot = Arc::new(PollSnapshot { poll, options });
+        if self.generation.load(Ordering::Acquire) == generation {
+            self.polls.insert(poll_id, snapshot.clone());
+        }
+
+        Ok(Some(snapshot))
+    }
+
+    pub async fn listing(
+        &self,
+        db: &DbPool,
+        filter: &db::PollFilter<'_>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Arc<PollPage>, sqlx::Error> {
+        let key = ListingKey {
+            creator_id: filter.creator_id,
+            closed: filter.closed,
+            search: filter.search.map(str::to_string),
+            tag: filter.tag.map(str::to_string),
+            viewer_id: filter.viewer_id,
+            include_archived: filter.include_archived,
+            limit,
+            offset,
+        };
+
+        if let Some(page) = self.listings.get(&key) {
+            self.listing_counters.record(true);
+            return Ok(page);
+        }
+        self.listing_counters.record(false);
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let polls = db::get_polls_with_options(db, filter, Some(limit), offset).await?;
+        let total = db::count_polls(db, filter).await?;
+
+        let page = Arc::new(PollPage { polls, total });
+        if self.generation.load(Ordering::Acquire) == generation {
+            self.listings.insert(key, page.clone());
+        }
+
+        Ok(page)
+    }
+
+    // Any change can move a poll in or out of someone's listing, so listings
+    // are dropped wholesale.
+    pub fn invalidate_poll(&self, poll_id: Uuid) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.polls.invalidate(&poll_id);
+        self.listings.invalidate_all();
+    }
+
+    pub fn invalidate_listings(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.listings.invalidate_all();
+    }
+
+    // Comments and viewer counts aren't part of anything cached.
+    pub fn invalidate_for(&self, event: &SseEvent) {
+        match event {
+            SseEvent::CommentAdded(_) | SseEvent::ViewerCount(..) => {}
+            SseEvent::PollsCreated(_) => self.invalidate_listings(),
+            event => {
+                if let Some(poll_id) = event.poll_id() {
+                    self.invalidate_poll(poll_id);
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Value {
+        json!({
+            "polls": self.poll_counters.to_json(self.polls.entry_count()),
+            "listings": self.listing_counters.to_json(self.listings.entry_count()),
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use crate::oauth::OidcLogin;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use webauthn_rs::prelude::{
+    DiscoverableAuthentication, PasskeyAuthentication, PasskeyRegistration,
+};
+
+const CEREMONY_TTL: Duration = Duration::from_secs(5 * 60);
+
+pub enum CeremonyState {
+    Registration(PasskeyRegistration),
+    Authentication(PasskeyAuthentication),
+    DiscoverableAuthentication(DiscoverableAuthentication),
+    Oidc(OidcLogin),
+}
+
+pub struct CeremonyUser {
+    pub user_id: Uuid,
+    pub username: String,
+}
+
+// Discoverable logins don't know the user until the credential comes back,
+// so `user` is only set for ceremonies started against a username. OIDC
+// logins set it when a signed-in user starts one to link their account.
+pub struct PendingCeremony {
+    pub state: CeremonyState,
+    pub user: Option<CeremonyUser>,
+    started_at: Instant,
+}
+
+#[derive(Default)]
+pub struct CeremonyStore {
+    pending: Mutex<HashMap<Uuid, PendingCeremony>>,
+}
+
+impl CeremonyStore {
+    pub async fn insert(&self, state: CeremonyState, user: Option<CeremonyUser>) -> Uuid {
+        let state_id = Uuid::new_v4();
+        let mut pending = self.pending.lock().await;
+
+        pending.retain(|_, ceremony| ceremony.started_at.elapsed() < CEREMONY_TTL);
+        pending.insert(
+            state_id,
+            PendingCeremony {
+                state,
+                user,
+                started_at: Instant::now(),
+            },
+        );
+
+        state_id
+    }
+
+    pub async fn take(&self, state_id: Uuid) -> Option<PendingCeremony> {
+        let ceremony = self.pending.lock().await.remove(&state_id)?;
+
+        if ceremony.started_at.elapsed() >= CEREMONY_TTL {
+            return None;
+        }
+
+        Some(ceremony)
+    }
+}
@@ -0,0 +1,143 @@
+use crate::access::require_poll_access;
+use crate::auth::BearerAuth;
+use crate::db;
+use crate::db::models::Comment;
+use crate::error::PollError;
+use crate::sse::{SseEvent, SseSender};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json, Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+use uuid::Uuid;
+
+const MAX_COMMENT_LENGTH: usize = 2000;
+const DEFAULT_COMMENTS_PAGE_SIZE: u32 = 50;
+const MAX_COMMENTS_PAGE_SIZE: u32 = 200;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCommentRequest {
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListCommentsParams {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommentListResponse {
+    pub comments: Vec<Comment>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+pub async fn create_comment(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    Json(payload): Json<CreateCommentRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let body = payload.body.trim();
+    if body.is_empty() || body.chars().count() > MAX_COMMENT_LENGTH {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    require_poll_access(&app_state, &poll, &auth, None).await?;
+
+    let comment = db::create_comment(&app_state.db, poll_id, user_id, body)
+        .await
+        .map_err(PollError::from)?;
+
+    let _ = sse_tx.send(SseEvent::CommentAdded(Arc::new(comment.clone())));
+
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+pub async fn list_comments(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    Query(params): Query<ListCommentsParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    require_poll_access(&app_state, &poll, &auth, None).await?;
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_COMMENTS_PAGE_SIZE)
+        .clamp(1, MAX_COMMENTS_PAGE_SIZE) as i64;
+    let offset = params.offset.unwrap_or(0) as i64;
+
+    let comments = db::get_comments(&app_state.db, poll_id, limit, offset)
+        .await
+        .map_err(PollError::from)?;
+
+    let total = db::count_comments(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(CommentListResponse {
+            comments,
+            total,
+            limit,
+            offset,
+        }),
+    ))
+}
+
+pub async fn delete_comment(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(comment_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let comment = db::get_comment(&app_state.db, comment_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::CommentNotFound)?;
+
+    if comment.user_id != user_id {
+        let poll = db::get_poll(&app_state.db, comment.poll_id)
+            .await
+            .map_err(PollError::from)?
+            .ok_or(PollError::PollNotFound)?;
+
+        if poll.creator_id != user_id {
+            auth.require_admin()?;
+        }
+    }
+
+    db::delete_comment(&app_state.db, comment_id)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Comment deleted successfully"
+        })),
+    ))
+}
@@ -0,0 +1,299 @@
+use crate::cors::{self, OriginRule};
+use lettre::message::Mailbox;
+use std::env;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+use webauthn_rs::prelude::Url;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("{0} must be set")]
+    Missing(&'static str),
+    #[error("{name}={value:?} is invalid: {reason}")]
+    Invalid {
+        name: &'static str,
+        value: String,
+        reason: String,
+    },
+}
+
+// An empty `allowed_aaguids` accepts any authenticator. AAGUIDs are only
+// trustworthy when attestation is required as well. User verification needs
+// no switch here: webauthn-rs always requires it for passkey registration.
+#[derive(Debug, Clone, Default)]
+pub struct AttestationPolicy {
+    pub require_attestation: bool,
+    pub allowed_aaguids: Vec<Uuid>,
+}
+
+// The log backend writes messages to the server log instead of sending them,
+// which is enough for local development.
+#[derive(Debug, Clone)]
+pub enum MailBackend {
+    Log,
+    Smtp { url: String },
+}
+
+// How SSE events reach other instances. Local is enough for a single
+// instance; with several, every one needs the same relay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SseRelay {
+    Local,
+    Postgres,
+}
+
+// Sign-in through an external OpenID Connect provider, for people who can't
+// use a passkey. The provider sends users back to `redirect_url`, a frontend
+// page that hands the code to the API.
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: Url,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: Url,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub port: u16,
+    // Only used when built with the grpc feature.
+    pub grpc_port: u16,
+    pub database_url: String,
+    pub jwt_secret: String,
+    pub vote_receipt_secret: String,
+    pub voter_hash_secret: String,
+    pub frontend_url: Url,
+    pub cors_origins: Vec<OriginRule>,
+    pub db_max_connections: u32,
+    pub db_statement_timeout: Duration,
+    pub access_token_ttl_secs: i64,
+    pub refresh_token_ttl_days: i64,
+    pub session_ttl_days: i64,
+    pub cookie_secure: bool,
+    pub dev_login_enabled: bool,
+    pub attestation_policy: AttestationPolicy,
+    pub sse_channel_capacity: usize,
+    pub sse_poll_channel_capacity: usize,
+    pub sse_replay_buffer_size: usize,
+    pub sse_db_concurrency: usize,
+    pub sse_relay: SseRelay,
+    pub sse_database_changes: bool,
+    pub read_cache_ttl: Duration,
+    pub max_poll_options: usize,
+    pub max_write_in_options: i64,
+    pub username_checks_per_minute: u32,
+    pub poll_creates_per_minute: u32,
+    pub votes_per_minute: u32,
+    pub access_code_attempts_per_minute: u32,
+    pub access_code_failures_per_poll_per_hour: u32,
+    pub trending_window_hours: u32,
+    pub trending_half_life_hours: f64,
+    pub webhook_allow_insecure_targets: bool,
+    pub webhook_max_attempts: i32,
+    pub webhook_timeout: Duration,
+    pub mail_backend: MailBackend,
+    pub mail_from: String,
+    pub email_verification_ttl_hours: i64,
+    pub vote_milestones: Vec<i64>,
+    pub oidc: Option<OidcConfig>,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let frontend_url =
+            optional("FRONTEND_URL").unwrap_or_else(|| "http://localhost:3000".to_string());
+        let frontend_url = url("FRONTEND_URL", &frontend_url)?;
+
+        let cors_origins = cors::parse_origin_rules(
+            &optional("CORS_ALLOWED_ORIGINS")
+                .unwrap_or_else(|| cors::DEFAULT_ALLOWED_ORIGINS.to_string()),
+        );
+
+        let mail_backend = match optional("MAIL_BACKEND").map(|v| v.trim().to_lowercase()) {
+            None => MailBackend::Log,
+            Some(backend) if backend == "log" => MailBackend::Log,
+            Some(backend) if backend == "smtp" => MailBackend::Smtp {
+                url: required("SMTP_URL")?,
+            },
+            Some(backend) => {
+                return Err(invalid("MAIL_BACKEND", &backend, "expected log or smtp"));
+            }
+        };
+
+        let sse_relay = match optional("SSE_RELAY").map(|v| v.trim().to_lowercase()) {
+            None => SseRelay::Local,
+            Some(relay) if relay == "local" => SseRelay::Local,
+            Some(relay) if relay == "postgres" => SseRelay::Postgres,
+            Some(relay) => {
+                return Err(invalid("SSE_RELAY", &relay, "expected local or postgres"));
+            }
+        };
+
+        let mail_from =
+            optional("MAIL_FROM").unwrap_or_else(|| "Polling App <noreply@localhost>".to_string());
+        if let Err(e) = mail_from.parse::<Mailbox>() {
+            return Err(invalid("MAIL_FROM", &mail_from, e));
+        }
+
+        let mut vote_milestones: Vec<i64> = list("VOTE_MILESTONES", &[10, 100, 1000])?;
+        if vote_milestones.iter().any(|m| *m <= 0) {
+            return Err(invalid(
+                "VOTE_MILESTONES",
+                &env::var("VOTE_MILESTONES").unwrap_or_default(),
+                "milestones must be greater than zero",
+            ));
+        }
+        vote_milestones.sort_unstable();
+        vote_milestones.dedup();
+
+        let oidc = match optional("OIDC_ISSUER_URL") {
+            None => None,
+            Some(issuer) => Some(OidcConfig {
+                issuer: url("OIDC_ISSUER_URL", &issuer)?,
+                client_id: required("OIDC_CLIENT_ID")?,
+                client_secret: required("OIDC_CLIENT_SECRET")?,
+                redirect_url: match optional("OIDC_REDIRECT_URL") {
+                    Some(redirect_url) => url("OIDC_REDIRECT_URL", &redirect_url)?,
+                    None => frontend_url
+                        .join("oauth/callback")
+                        .map_err(|e| invalid("FRONTEND_URL", frontend_url.as_str(), e))?,
+                },
+            }),
+        };
+
+        // Defaults to the JWT secret; set separately so rotating that doesn't
+        // invalidate receipts voters are holding on to.
+        let jwt_secret = required("JWT_SECRET")?;
+        let vote_receipt_secret =
+            optional("VOTE_RECEIPT_SECRET").unwrap_or_else(|| jwt_secret.clone());
+        // Anonymous ballots are tied to their voter only through this secret,
+        // which never goes in the database. Changing it orphans existing
+        // anonymous ballots, so set it apart from the JWT secret.
+        let voter_hash_secret = optional("VOTER_HASH_SECRET").unwrap_or_else(|| jwt_secret.clone());
+
+        Ok(Config {
+            port: parsed("PORT", 8080)?,
+            grpc_port: parsed("GRPC_PORT", 50051)?,
+            database_url: required("DATABASE_URL")?,
+            jwt_secret,
+            vote_receipt_secret,
+            voter_hash_secret,
+            frontend_url,
+            cors_origins,
+            db_max_connections: positive("DB_MAX_CONNECTIONS", 20)?,
+            db_statement_timeout: Duration::from_millis(positive(
+                "DB_STATEMENT_TIMEOUT_MS",
+                10_000,
+            )?),
+            access_token_ttl_secs: positive("ACCESS_TOKEN_TTL_SECS", 15 * 60)?,
+            refresh_token_ttl_days: positive("REFRESH_TOKEN_TTL_DAYS", 30)?,
+            session_ttl_days: positive("SESSION_TTL_DAYS", 7)?,
+            cookie_secure: parsed("COOKIE_SECURE", true)?,
+            dev_login_enabled: parsed("DEV_LOGIN_ENABLED", false)?,
+            attestation_policy: AttestationPolicy {
+                require_attestation: parsed("WEBAUTHN_REQUIRE_ATTESTATION", false)?,
+                allowed_aaguids: list("WEBAUTHN_ALLOWED_AAGUIDS", &[])?,
+            },
+            sse_channel_capacity: positive("SSE_CHANNEL_CAPACITY", 100)?,
+            sse_poll_channel_capacity: positive("SSE_POLL_CHANNEL_CAPACITY", 32)?,
+            sse_replay_buffer_size: positive("SSE_REPLAY_BUFFER_SIZE", 500)?,
+            sse_db_concurrency: positive("SSE_DB_CONCURRENCY", 4)?,
+            sse_relay,
+            sse_database_changes: parsed("SSE_DATABASE_CHANGES", true)?,
+            read_cache_ttl: Duration::from_millis(positive("READ_CACHE_TTL_MS", 5_000)?),
+            max_poll_options: positive("MAX_OPTIONS", 20)?,
+            max_write_in_options: positive("MAX_WRITE_IN_OPTIONS", 20)?,
+            username_checks_per_minute: positive("USERNAME_CHECK_RATE_LIMIT", 30)?,
+            poll_creates_per_minute: positive("POLL_CREATE_PER_MIN", 10)?,
+            votes_per_minute: positive("VOTE_PER_MIN", 30)?,
+            access_code_attempts_per_minute: positive("ACCESS_CODE_PER_MIN", 5)?,
+            access_code_failures_per_poll_per_hour: positive(
+                "ACCESS_CODE_FAILURES_PER_POLL_PER_HOUR",
+                20,
+            )?,
+            trending_window_hours: positive("TRENDING_WINDOW_HOURS", 24)?,
+            trending_half_life_hours: positive("TRENDING_HALF_LIFE_HOURS", 6.0)?,
+            webhook_allow_insecure_targets: parsed("WEBHOOK_ALLOW_INSECURE_TARGETS", false)?,
+            webhook_max_attempts: positive("WEBHOOK_MAX_ATTEMPTS", 8)?,
+            webhook_timeout: Duration::from_millis(positive("WEBHOOK_TIMEOUT_MS", 10_000)?),
+            mail_backend,
+            mail_from,
+            email_verification_ttl_hours: positive("EMAIL_VERIFICATION_TTL_HOURS", 24)?,
+            vote_milestones,
+            oidc,
+        })
+    }
+}
+
+fn invalid(name: &'static str, value: &str, reason: impl Display) -> ConfigError {
+    ConfigError::Invalid {
+        name,
+        value: value.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn url(name: &'static str, value: &str) -> Result<Url, ConfigError> {
+    match Url::parse(value) {
+        Ok(url) if url.host_str().is_some() => Ok(url),
+        Ok(_) => Err(invalid(name, value, "URL has no host")),
+        Err(e) => Err(invalid(name, value, e)),
+    }
+}
+
+fn optional(name: &'static str) -> Option<String> {
+    env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn required(name: &'static str) -> Result<String, ConfigError> {
+    optional(name).ok_or(ConfigError::Missing(name))
+}
+
+fn parsed<T>(name: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match optional(name) {
+        Some(value) => value.trim().parse().map_err(|e| invalid(name, &value, e)),
+        None => Ok(default),
+    }
+}
+
+fn list<T>(name: &'static str, default: &[T]) -> Result<Vec<T>, ConfigError>
+where
+    T: FromStr + Clone,
+    T::Err: Display,
+{
+    let Some(value) = optional(name) else {
+        return Ok(default.to_vec());
+    };
+
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| entry.parse().map_err(|e| invalid(name, &value, e)))
+        .collect()
+}
+
+fn positive<T>(name: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: FromStr + PartialOrd + Default,
+    T::Err: Display,
+{
+    let value = parsed(name, default)?;
+
+    if value <= T::default() {
+        return Err(invalid(
+            name,
+            &env::var(name).unwrap_or_default(),
+            "must be greater than zero",
+        ));
+    }
+
+    Ok(value)
+}
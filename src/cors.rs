@@ -0,0 +1,124 @@
+use axum::http::HeaderValue;
+use tower_http::cors::AllowOrigin;
+use tracing::warn;
+
+pub const DEFAULT_ALLOWED_ORIGINS: &str = "https://polling-app-frontend-rho.vercel.app,https://*.vercel.app,http://localhost:3000,http://localhost:5173";
+
+// Configured through CORS_ALLOWED_ORIGINS, a comma-separated list of origins.
+#[derive(Debug, Clone)]
+pub enum OriginRule {
+    Exact(String),
+    // `https://*.example.com` matches any subdomain of example.com over https.
+    Suffix { scheme: String, suffix: String },
+}
+
+impl OriginRule {
+    fn parse(entry: &str) -> Option<Self> {
+        let entry = entry.trim().trim_end_matches('/');
+        let (scheme, host) = entry.split_once("://")?;
+
+        if scheme.is_empty() || host.is_empty() {
+            return None;
+        }
+
+        match host.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') && !suffix.contains('*') => {
+                Some(OriginRule::Suffix {
+                    scheme: scheme.to_ascii_lowercase(),
+                    suffix: suffix.to_ascii_lowercase(),
+                })
+            }
+            Some(_) => None,
+            None if host.contains('*') => None,
+            None => Some(OriginRule::Exact(entry.to_ascii_lowercase())),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            OriginRule::Exact(allowed) => origin.eq_ignore_ascii_case(allowed),
+            OriginRule::Suffix { scheme, suffix } => {
+                let origin = origin.to_ascii_lowercase();
+                let Some(host) = origin
+                    .strip_prefix(scheme.as_str())
+                    .and_then(|rest| rest.strip_prefix("://"))
+                else {
+                    return false;
+                };
+
+                host.strip_suffix(suffix.as_str())
+                    .is_some_and(|label| !label.is_empty() && !label.contains(['/', ':']))
+            }
+        }
+    }
+}
+
+pub fn parse_origin_rules(value: &str) -> Vec<OriginRule> {
+    value
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| {
+            let rule = OriginRule::parse(entry);
+            if rule.is_none() {
+                warn!("Ignoring invalid CORS origin: {}", entry.trim());
+            }
+            rule
+        })
+        .collect()
+}
+
+pub fn allow_origin(rules: Vec<OriginRule>) -> AllowOrigin {
+    AllowOrigin::predicate(move |origin: &HeaderValue, _| {
+        origin
+            .to_str()
+            .is_ok_and(|origin| rules.iter().any(|rule| rule.matches(origin)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed(origin: &str) -> bool {
+        parse_origin_rules(DEFAULT_ALLOWED_ORIGINS)
+            .iter()
+            .any(|rule| rule.matches(origin))
+    }
+
+    #[test]
+    fn exact_origins_match() {
+        assert!(allowed("http://localhost:3000"));
+        assert!(allowed("HTTP://LOCALHOST:5173"));
+        assert!(allowed("https://polling-app-frontend-rho.vercel.app"));
+
+        assert!(!allowed("http://localhost:3001"));
+        assert!(!allowed("https://localhost:3000"));
+        assert!(!allowed("http://localhost:3000.evil.com"));
+    }
+
+    #[test]
+    fn wildcards_match_subdomains_over_the_same_scheme() {
+        assert!(allowed("https://preview-123.vercel.app"));
+        assert!(allowed("https://a.b.vercel.app"));
+
+        assert!(!allowed("http://preview-123.vercel.app"));
+        assert!(!allowed("https://vercel.app"));
+        assert!(!allowed("https://.vercel.app"));
+        assert!(!allowed("https://preview.vercel.app:8443"));
+        assert!(!allowed("https://preview.vercel.app.evil.com"));
+    }
+
+    #[test]
+    fn lookalike_domains_are_rejected() {
+        assert!(!allowed("https://evil-vercel.app"));
+        assert!(!allowed("https://evilvercel.app"));
+    }
+
+    #[test]
+    fn invalid_entries_are_skipped() {
+        let rules =
+            parse_origin_rules("localhost, https://*, https://a.*.com, , https://*.ok.dev/");
+        assert_eq!(rules.len(), 1);
+        assert!(rules[0].matches("https://app.ok.dev"));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::error::WebauthnError;
+use crate::session::{api_cookie, cookie_value};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, header::SET_COOKIE},
+    response::IntoResponse,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use openssl::memcmp;
+use openssl::rand::rand_bytes;
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+
+pub const X_CSRF_TOKEN: HeaderName = HeaderName::from_static("x-csrf-token");
+const CSRF_COOKIE: &str = "csrf_token";
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CsrfTokenResponse {
+    pub csrf_token: String,
+}
+
+// Double-submit token: the cookie and the X-CSRF-Token header must match.
+// Another site can make the browser send the cookie but can't read it or the
+// response body, so it can't fill in the header.
+#[utoipa::path(
+    get,
+    path = "/csrf",
+    tag = "auth",
+    responses(
+        (status = 200, description = "A CSRF token, also set as the csrf_token cookie", body = CsrfTokenResponse),
+    )
+)]
+pub async fn issue_csrf_token(
+    Extension(app_state): Extension<AppState>,
+) -> Result<impl IntoResponse, WebauthnError> {
+    let mut bytes = [0u8; 32];
+    rand_bytes(&mut bytes).map_err(|e| {
+        error!("Error generating CSRF token: {:?}", e);
+        WebauthnError::TokenCreationError
+    })?;
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+
+    let cookie = api_cookie(CSRF_COOKIE, token.clone(), &app_state.config).build();
+    let cookie = HeaderValue::from_str(&cookie.to_string()).map_err(|_| WebauthnError::Unknown)?;
+
+    Ok((
+        [(SET_COOKIE, cookie)],
+        Json(CsrfTokenResponse { csrf_token: token }),
+    ))
+}
+
+// Only needed when the browser attached the credentials itself; a request
+// carrying a bearer token was built by a script that already held it.
+pub fn verify(method: &Method, headers: &HeaderMap) -> Result<(), WebauthnError> {
+    if !matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    ) {
+        return Ok(());
+    }
+
+    let cookie = cookie_value(headers, CSRF_COOKIE);
+    let header = headers.get(&X_CSRF_TOKEN).and_then(|v| v.to_str().ok());
+
+    match (cookie, header) {
+        (Some(cookie), Some(header))
+            if cookie.len() == header.len() && memcmp::eq(cookie.as_bytes(), header.as_bytes()) =>
+        {
+            Ok(())
+        }
+        _ => Err(WebauthnError::CsrfTokenInvalid),
+    }
+}
@@ -1,132 +1,31 @@
+use crate::config::Config;
 use sqlx::postgres::PgPoolOptions;
-use sqlx::{Pool, Postgres};
+use sqlx::{Executor, Pool, Postgres};
 use std::time::Duration;
 
 pub type DbPool = Pool<Postgres>;
 
-pub async fn init_db(database_url: &str) -> Result<DbPool, sqlx::Error> {
+pub async fn init_db(config: &Config) -> Result<DbPool, sqlx::Error> {
+    let statement_timeout_ms = config.db_statement_timeout.as_millis();
+
     let pool = PgPoolOptions::new()
-        .max_connections(20)
+        .max_connections(config.db_max_connections)
         .max_lifetime(Duration::from_secs(30 * 60))
         .idle_timeout(Duration::from_secs(10 * 60))
-        .connect(database_url)
+        .after_connect(move |conn, _meta| {
+            Box::pin(async move {
+                conn.execute(format!("SET statement_timeout = {}", statement_timeout_ms).as_str())
+                    .await?;
+                // Handlers broadcast their own writes, so the change triggers
+                // skip them.
+                conn.execute("SET polling_app.emits_events = 'on'").await?;
+                Ok(())
+            })
+        })
+        .connect(&config.database_url)
         .await?;
 
-    sqlx::query(
-        r#" 
-        CREATE TABLE IF NOT EXISTS users (
-            id UUID PRIMARY KEY,
-            username VARCHAR(255) NOT NULL UNIQUE,
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS passkeys (
-            id SERIAL PRIMARY KEY,
-            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-            passkey_data JSON NOT NULL,
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS polls (
-            id UUID PRIMARY KEY,
-            creator_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-            title VARCHAR(255) NOT NULL,
-            description TEXT,
-            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            closed BOOLEAN NOT NULL DEFAULT FALSE
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS poll_options (
-            id UUID PRIMARY KEY,
-            poll_id UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
-            option_text VARCHAR(255) NOT NULL,
-            votes INT NOT NULL DEFAULT 0
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS votes (
-            id UUID PRIMARY KEY,
-            poll_id UUID NOT NULL REFERENCES polls(id) ON DELETE CASCADE,
-            option_id UUID NOT NULL REFERENCES poll_options(id) ON DELETE CASCADE,
-            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(poll_id, user_id)
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_users_username ON users(username)
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_passkeys_user_id ON passkeys(user_id)
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_polls_creator_id ON polls(creator_id)
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_poll_options_poll_id ON poll_options(poll_id)
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_votes_poll_id ON votes(poll_id)
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE INDEX IF NOT EXISTS idx_votes_user_id ON votes(user_id)
-        "#,
-    )
-    .execute(&pool)
-    .await?;
+    sqlx::migrate!().run(&pool).await?;
 
     Ok(pool)
 }
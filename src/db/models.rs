@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
-use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::chrono::{DateTime, NaiveDateTime, Utc};
+use utoipa::ToSchema;
 use uuid::Uuid;
+use webauthn_rs::prelude::Passkey;
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct Poll {
@@ -11,6 +13,74 @@ pub struct Poll {
     #[sqlx(try_from = "DateTime<Utc>")]
     pub created_at: DateTime<Utc>,
     pub closed: bool,
+    pub ballot_public_key: Option<String>,
+    pub tallied: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub opens_at: Option<DateTime<Utc>>,
+    pub allow_multiple: bool,
+    pub vote_type: String,
+    pub max_choices: Option<i32>,
+    pub anonymous: bool,
+    pub allow_vote_change: bool,
+    pub visibility: String,
+    #[serde(skip)]
+    pub access_code_hash: Option<String>,
+    pub result_visibility: String,
+    pub allow_write_in: bool,
+    pub archived_at: Option<DateTime<Utc>>,
+    #[sqlx(default)]
+    pub tags: Vec<String>,
+}
+
+impl Poll {
+    pub fn is_ranked(&self) -> bool {
+        self.vote_type == "ranked"
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.visibility == "private"
+    }
+
+    pub fn requires_access_code(&self) -> bool {
+        self.access_code_hash.is_some()
+    }
+
+    // Only listed polls appear in listings, feeds and aggregates; everything
+    // else is reached by link, and archived polls not at all.
+    pub fn is_listed(&self) -> bool {
+        self.visibility == "public" && !self.requires_access_code() && !self.is_archived()
+    }
+
+    pub fn is_archived(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    // Scheduled polls open at opens_at even before the background task
+    // announces it.
+    pub fn is_upcoming(&self) -> bool {
+        self.opens_at.is_some_and(|opens_at| opens_at > Utc::now())
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+            || self
+                .expires_at
+                .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    // Whether anyone may see the counts, including unauthenticated streams.
+    pub fn results_public(&self) -> bool {
+        self.result_visibility == "always" || self.is_closed()
+    }
+
+    // The creator and admins always see the counts; after_vote polls show
+    // them to voters as well.
+    pub fn results_visible_to(&self, user_id: Uuid, is_admin: bool, user_voted: bool) -> bool {
+        self.results_public()
+            || self.creator_id == user_id
+            || is_admin
+            || (self.result_visibility == "after_vote" && user_voted)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,13 +89,250 @@ pub struct PollOption {
     pub poll_id: Uuid,
     pub option_text: String,
     pub votes: i32,
+    pub allows_write_in: bool,
 }
 #[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vote {
     pub id: Uuid,
     pub poll_id: Uuid,
+    pub option_id: Option<Uuid>,
+    pub user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PollReport {
+    pub id: Uuid,
+    pub poll_id: Uuid,
+    pub reporter_user_id: Uuid,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub resolved: bool,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct UserSummary {
+    pub id: Uuid,
+    pub username: String,
+    pub role: String,
+    pub banned: bool,
+    pub can_create_polls: bool,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TrendingPoll {
+    pub poll_id: Uuid,
+    pub recent_votes: i64,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct CreatorPollSummary {
+    pub id: Uuid,
+    pub title: String,
+    pub status: String,
+    pub visibility: String,
+    pub vote_type: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub archived_at: Option<DateTime<Utc>>,
+    pub total_votes: i64,
+    pub voter_count: i64,
+    pub option_count: i64,
+    pub comment_count: i64,
+    pub leading_option: Option<String>,
+    pub last_vote_at: Option<DateTime<Utc>>,
+}
+
+// Headlines mark matches with \u{2} and \u{3} so the text can be escaped
+// before the markers are turned into HTML.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PollSearchHit {
+    pub poll_id: Uuid,
+    pub rank: f32,
+    pub closed: bool,
+    pub created_at: DateTime<Utc>,
+    pub title_headline: String,
+    pub description_headline: Option<String>,
+    pub option_headlines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct TagCount {
+    pub name: String,
+    pub poll_count: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct UserProfile {
+    pub id: Uuid,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub email: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub email_notifications: bool,
+    pub created_at: Option<NaiveDateTime>,
+    pub passkey_count: i64,
+    pub polls_created: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct NotificationRecipient {
+    pub username: String,
+    pub email: String,
+    pub title: String,
+    pub total_votes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Comment {
+    pub id: Uuid,
+    pub poll_id: Uuid,
+    pub user_id: Uuid,
+    pub username: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Survey {
+    pub id: Uuid,
+    pub creator_id: Uuid,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    pub closed: bool,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ActivityCounts {
+    pub total_polls: i64,
+    pub open_polls: i64,
+    pub closed_polls: i64,
+    pub polls_last_24h: i64,
+    pub total_votes: i64,
+    pub votes_last_24h: i64,
+    pub total_users: i64,
+    pub users_last_24h: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct VoteExportRow {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub option_id: Option<Uuid>,
+    pub write_in_text: Option<String>,
+    pub rank: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct VoteHistoryEntry {
+    pub vote_id: Uuid,
+    pub poll_id: Uuid,
+    pub poll_title: String,
+    pub option_id: Option<Uuid>,
+    pub option_text: Option<String>,
+    pub write_in_text: Option<String>,
+    pub rank: Option<i32>,
+    pub voted_at: DateTime<Utc>,
+    pub poll_open: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OrphanReport {
+    pub orphaned_options: Vec<Uuid>,
+    pub dangling_votes: Vec<Uuid>,
+    pub incomplete_polls: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OptionCountDrift {
     pub option_id: Uuid,
+    pub stored_votes: i32,
+    pub actual_votes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub poll_id: Option<Uuid>,
+    pub url: String,
+    pub events: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PendingWebhookDelivery {
+    pub id: Uuid,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow, ToSchema)]
+pub struct ApiKey {
+    pub id: Uuid,
     pub user_id: Uuid,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
     pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+// The key's owner, as a request made with it acts.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ApiKeyUser {
+    pub key_id: Uuid,
+    pub user_id: Uuid,
+    pub username: String,
+    pub role: String,
+    pub banned: bool,
+    pub scopes: Vec<String>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PasskeyAttestation {
+    pub format: String,
+    pub aaguid: Option<Uuid>,
+    pub user_verified: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PasskeyRecord {
+    pub id: i32,
+    pub passkey: Passkey,
+    pub nickname: Option<String>,
+    pub aaguid: Option<Uuid>,
+    pub created_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub sign_count: i64,
+    pub backup_eligible: Option<bool>,
+    pub backup_state: Option<bool>,
+    pub clone_suspected_at: Option<DateTime<Utc>>,
+    pub attestation_format: Option<String>,
+    pub user_verified: Option<bool>,
 }
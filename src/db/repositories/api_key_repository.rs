@@ -0,0 +1,81 @@
+use crate::db::connection::DbPool;
+use crate::db::models::{ApiKey, ApiKeyUser};
+use sqlx::Error;
+use uuid::Uuid;
+
+const API_KEY_COLUMNS: &str = "id, user_id, name, prefix, scopes, created_at, last_used_at";
+
+pub async fn create_api_key(
+    pool: &DbPool,
+    user_id: Uuid,
+    name: &str,
+    prefix: &str,
+    key_hash: &str,
+    scopes: &[String],
+) -> Result<ApiKey, Error> {
+    sqlx::query_as::<_, ApiKey>(&format!(
+        "INSERT INTO api_keys (id, user_id, name, prefix, key_hash, scopes)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING {API_KEY_COLUMNS}"
+    ))
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(name)
+    .bind(prefix)
+    .bind(key_hash)
+    .bind(scopes)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn count_user_api_keys(pool: &DbPool, user_id: Uuid) -> Result<i64, Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM api_keys WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn list_user_api_keys(pool: &DbPool, user_id: Uuid) -> Result<Vec<ApiKey>, Error> {
+    sqlx::query_as::<_, ApiKey>(&format!(
+        "SELECT {API_KEY_COLUMNS} FROM api_keys WHERE user_id = $1 ORDER BY created_at DESC"
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn delete_api_key(pool: &DbPool, key_id: Uuid, user_id: Uuid) -> Result<bool, Error> {
+    let result = sqlx::query("DELETE FROM api_keys WHERE id = $1 AND user_id = $2")
+        .bind(key_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_api_key_user(
+    pool: &DbPool,
+    prefix: &str,
+    key_hash: &str,
+) -> Result<Option<ApiKeyUser>, Error> {
+    sqlx::query_as::<_, ApiKeyUser>(
+        "SELECT k.id AS key_id, u.id AS user_id, u.username, u.role, u.banned,
+                k.scopes, k.last_used_at
+         FROM api_keys k
+         JOIN users u ON u.id = k.user_id
+         WHERE k.prefix = $1 AND k.key_hash = $2",
+    )
+    .bind(prefix)
+    .bind(key_hash)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn touch_api_key(pool: &DbPool, key_id: Uuid) -> Result<(), Error> {
+    sqlx::query("UPDATE api_keys SET last_used_at = NOW() WHERE id = $1")
+        .bind(key_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
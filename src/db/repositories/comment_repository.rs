@@ -0,0 +1,82 @@
+use crate::db::connection::DbPool;
+use crate::db::models::Comment;
+use sqlx::Error;
+use uuid::Uuid;
+
+pub async fn create_comment(
+    pool: &DbPool,
+    poll_id: Uuid,
+    user_id: Uuid,
+    body: &str,
+) -> Result<Comment, Error> {
+    let comment = sqlx::query_as::<_, Comment>(
+        "WITH inserted AS (
+             INSERT INTO comments (id, poll_id, user_id, body) VALUES ($1, $2, $3, $4)
+             RETURNING id, poll_id, user_id, body, created_at
+         )
+         SELECT i.id, i.poll_id, i.user_id, u.username, i.body, i.created_at
+         FROM inserted i
+         JOIN users u ON u.id = i.user_id",
+    )
+    .bind(Uuid::new_v4())
+    .bind(poll_id)
+    .bind(user_id)
+    .bind(body)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(comment)
+}
+
+pub async fn get_comments(
+    pool: &DbPool,
+    poll_id: Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Comment>, Error> {
+    let comments = sqlx::query_as::<_, Comment>(
+        "SELECT c.id, c.poll_id, c.user_id, u.username, c.body, c.created_at
+         FROM comments c
+         JOIN users u ON u.id = c.user_id
+         WHERE c.poll_id = $1
+         ORDER BY c.created_at ASC, c.id ASC
+         LIMIT $2 OFFSET $3",
+    )
+    .bind(poll_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(comments)
+}
+
+pub async fn count_comments(pool: &DbPool, poll_id: Uuid) -> Result<i64, Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM comments WHERE poll_id = $1")
+        .bind(poll_id)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn get_comment(pool: &DbPool, comment_id: Uuid) -> Result<Option<Comment>, Error> {
+    let comment = sqlx::query_as::<_, Comment>(
+        "SELECT c.id, c.poll_id, c.user_id, u.username, c.body, c.created_at
+         FROM comments c
+         JOIN users u ON u.id = c.user_id
+         WHERE c.id = $1",
+    )
+    .bind(comment_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(comment)
+}
+
+pub async fn delete_comment(pool: &DbPool, comment_id: Uuid) -> Result<(), Error> {
+    sqlx::query("DELETE FROM comments WHERE id = $1")
+        .bind(comment_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use crate::db::connection::DbPool;
+use crate::db::models::NotificationRecipient;
+use chrono::{DateTime, Utc};
+use sqlx::Error;
+use uuid::Uuid;
+
+// Used at registration, where the address is only recorded if the account
+// doesn't already have one.
+pub async fn set_initial_user_email(
+    pool: &DbPool,
+    user_id: Uuid,
+    email: &str,
+) -> Result<bool, Error> {
+    let result = sqlx::query("UPDATE users SET email = $2 WHERE id = $1 AND email IS NULL")
+        .bind(user_id)
+        .bind(email)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Any change of address has to be verified again.
+pub async fn set_user_email(
+    pool: &DbPool,
+    user_id: Uuid,
+    email: Option<&str>,
+) -> Result<bool, Error> {
+    let result = sqlx::query("UPDATE users SET email = $2, email_verified_at = NULL WHERE id = $1")
+        .bind(user_id)
+        .bind(email)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_verified_email_user(pool: &DbPool, email: &str) -> Result<Option<Uuid>, Error> {
+    sqlx::query_scalar(
+        "SELECT id FROM users WHERE LOWER(email) = LOWER($1) AND email_verified_at IS NOT NULL",
+    )
+    .bind(email)
+    .fetch_optional(pool)
+    .await
+}
+
+// For people who first turn up through a link sent to their address, which
+// proves they can read mail there.
+pub async fn create_email_user(
+    pool: &DbPool,
+    user_id: Uuid,
+    username: &str,
+    email: &str,
+) -> Result<(), Error> {
+    sqlx::query(
+        "INSERT INTO users (id, username, email, email_verified_at)
+         VALUES ($1, $2, $3, CURRENT_TIMESTAMP)",
+    )
+    .bind(user_id)
+    .bind(username)
+    .bind(email)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn create_email_verification_token(
+    pool: &DbPool,
+    user_id: Uuid,
+    email: &str,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM email_verification_tokens WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO email_verification_tokens (token_hash, user_id, email, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(token_hash)
+    .bind(user_id)
+    .bind(email)
+    .bind(expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn last_verification_sent_at(
+    pool: &DbPool,
+    user_id: Uuid,
+) -> Result<Option<DateTime<Utc>>, Error> {
+    sqlx::query_scalar("SELECT MAX(created_at) FROM email_verification_tokens WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+}
+
+// Tokens are single use. A token only verifies the address it was issued for,
+// so one sent before the user changed their email does nothing.
+pub async fn consume_email_verification_token(
+    pool: &DbPool,
+    token_hash: &str,
+) -> Result<Option<(Uuid, String)>, Error> {
+    let mut tx = pool.begin().await?;
+
+    let token: Option<(Uuid, String)> = sqlx::query_as(
+        r#"
+        DELETE FROM email_verification_tokens
+        WHERE token_hash = $1 AND expires_at > CURRENT_TIMESTAMP
+        RETURNING user_id, email
+        "#,
+    )
+    .bind(token_hash)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((user_id, email)) = token else {
+        return Ok(None);
+    };
+
+    let result = sqlx::query(
+        r#"
+        UPDATE users SET email_verified_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND email = $2 AND email_verified_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(&email)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok((result.rows_affected() > 0).then_some((user_id, email)))
+}
+
+pub async fn purge_expired_email_verification_tokens(pool: &DbPool) -> Result<u64, Error> {
+    let result =
+        sqlx::query("DELETE FROM email_verification_tokens WHERE expires_at <= CURRENT_TIMESTAMP")
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Returns false when this notification has already gone out for the poll.
+pub async fn claim_poll_notification(
+    pool: &DbPool,
+    poll_id: Uuid,
+    kind: &str,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "INSERT INTO poll_notifications (poll_id, kind) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(poll_id)
+    .bind(kind)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_notification_recipient(
+    pool: &DbPool,
+    poll_id: Uuid,
+) -> Result<Option<NotificationRecipient>, Error> {
+    sqlx::query_as::<_, NotificationRecipient>(
+        r#"
+        SELECT u.username, u.email, p.title,
+               (SELECT COALESCE(SUM(o.votes), 0) FROM poll_options o WHERE o.poll_id = p.id)
+                   AS total_votes
+        FROM polls p
+        JOIN users u ON u.id = p.creator_id
+        WHERE p.id = $1
+          AND u.email IS NOT NULL
+          AND u.email_verified_at IS NOT NULL
+          AND u.email_notifications
+          AND NOT u.banned
+        "#,
+    )
+    .bind(poll_id)
+    .fetch_optional(pool)
+    .await
+}
@@ -0,0 +1,136 @@
+use crate::db::connection::DbPool;
+use crate::db::models::{OptionCountDrift, OrphanReport};
+use sqlx::{Error, PgConnection};
+use uuid::Uuid;
+
+// create_poll_with_options writes a poll and its options atomically; the grace
+// period is only a safety margin for rows written before that.
+const INCOMPLETE_POLL_GRACE_MINUTES: i32 = 5;
+
+async fn collect_orphans(conn: &mut PgConnection) -> Result<OrphanReport, Error> {
+    let orphaned_options: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT o.id FROM poll_options o
+         LEFT JOIN polls p ON p.id = o.poll_id
+         LEFT JOIN users u ON u.id = p.creator_id
+         WHERE p.id IS NULL OR u.id IS NULL
+         ORDER BY o.id",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let dangling_votes: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT v.id FROM votes v
+         LEFT JOIN poll_options o ON o.id = v.option_id
+         WHERE (v.option_id IS NOT NULL AND (o.id IS NULL OR o.poll_id <> v.poll_id))
+            OR (v.option_id IS NULL AND v.encrypted_ballot IS NULL)
+         ORDER BY v.id",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let incomplete_polls: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT p.id FROM polls p
+         LEFT JOIN poll_options o ON o.poll_id = p.id
+         WHERE p.created_at < NOW() - make_interval(mins => $1)
+         GROUP BY p.id
+         HAVING COUNT(o.id) < 2
+         ORDER BY p.id",
+    )
+    .bind(INCOMPLETE_POLL_GRACE_MINUTES)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(OrphanReport {
+        orphaned_options,
+        dangling_votes,
+        incomplete_polls,
+    })
+}
+
+pub async fn find_orphans(pool: &DbPool) -> Result<OrphanReport, Error> {
+    let mut conn = pool.acquire().await?;
+    collect_orphans(&mut conn).await
+}
+
+pub async fn delete_orphans(pool: &DbPool) -> Result<OrphanReport, Error> {
+    let mut tx = pool.begin().await?;
+
+    let report = collect_orphans(&mut tx).await?;
+
+    sqlx::query("DELETE FROM votes WHERE id = ANY($1)")
+        .bind(&report.dangling_votes)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM poll_options WHERE id = ANY($1)")
+        .bind(&report.orphaned_options)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM polls WHERE id = ANY($1)")
+        .bind(&report.incomplete_polls)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+// poll_options.votes is maintained incrementally by every vote path. Ranked
+// ballots count through their first preference, matching cast_ranked_ballot.
+async fn collect_count_drift(
+    conn: &mut PgConnection,
+    poll_id: Uuid,
+    lock: bool,
+) -> Result<Vec<OptionCountDrift>, Error> {
+    sqlx::query_as::<_, OptionCountDrift>(&format!(
+        "SELECT o.id AS option_id, o.votes AS stored_votes,
+                (SELECT COUNT(*) FROM votes v
+                 WHERE v.option_id = o.id AND (v.rank IS NULL OR v.rank = 1)) AS actual_votes
+         FROM poll_options o
+         WHERE o.poll_id = $1
+         ORDER BY o.id
+         {}",
+        if lock { "FOR UPDATE" } else { "" }
+    ))
+    .bind(poll_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map(|rows| {
+        rows.into_iter()
+            .filter(|row| i64::from(row.stored_votes) != row.actual_votes)
+            .collect()
+    })
+}
+
+pub async fn find_vote_count_drift(
+    pool: &DbPool,
+    poll_id: Uuid,
+) -> Result<Vec<OptionCountDrift>, Error> {
+    let mut conn = pool.acquire().await?;
+    collect_count_drift(&mut conn, poll_id, false).await
+}
+
+// The option rows stay locked until commit, so a vote that lands meanwhile
+// waits to apply its increment on top of the repaired count.
+pub async fn repair_vote_count_drift(
+    pool: &DbPool,
+    poll_id: Uuid,
+) -> Result<Vec<OptionCountDrift>, Error> {
+    let mut tx = pool.begin().await?;
+
+    let drift = collect_count_drift(&mut tx, poll_id, true).await?;
+
+    for row in &drift {
+        sqlx::query("UPDATE poll_options SET votes = $1 WHERE id = $2")
+            .bind(row.actual_votes as i32)
+            .bind(row.option_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(drift)
+}
@@ -1,9 +1,41 @@
+pub mod api_key_repository;
+pub mod comment_repository;
+pub mod email_repository;
+pub mod maintenance_repository;
+pub mod oauth_identity_repository;
 pub mod passkey_repository;
+pub mod poll_access_repository;
 pub mod poll_repository;
+pub mod refresh_token_repository;
+pub mod report_repository;
+pub mod revoked_token_repository;
+pub mod security_event_repository;
+pub mod session_repository;
+pub mod stats_repository;
+pub mod survey_repository;
+pub mod tag_repository;
 pub mod user_repository;
+pub mod vote_link_repository;
 pub mod vote_repository;
+pub mod webhook_repository;
 
+pub use api_key_repository::*;
+pub use comment_repository::*;
+pub use email_repository::*;
+pub use maintenance_repository::*;
+pub use oauth_identity_repository::*;
 pub use passkey_repository::*;
+pub use poll_access_repository::*;
 pub use poll_repository::*;
+pub use refresh_token_repository::*;
+pub use report_repository::*;
+pub use revoked_token_repository::*;
+pub use security_event_repository::*;
+pub use session_repository::*;
+pub use stats_repository::*;
+pub use survey_repository::*;
+pub use tag_repository::*;
 pub use user_repository::*;
+pub use vote_link_repository::*;
 pub use vote_repository::*;
+pub use webhook_repository::*;
@@ -0,0 +1,78 @@
+use crate::db::connection::DbPool;
+use sqlx::Error;
+use uuid::Uuid;
+
+// Returns the linked user, recording the sign-in.
+pub async fn sign_in_oauth_identity(
+    pool: &DbPool,
+    issuer: &str,
+    subject: &str,
+    email: Option<&str>,
+) -> Result<Option<Uuid>, Error> {
+    sqlx::query_scalar(
+        "UPDATE oauth_identities
+         SET last_login_at = NOW(), email = COALESCE($3, email)
+         WHERE issuer = $1 AND subject = $2
+         RETURNING user_id",
+    )
+    .bind(issuer)
+    .bind(subject)
+    .bind(email)
+    .fetch_optional(pool)
+    .await
+}
+
+// False when the identity was already linked, to this user or another.
+pub async fn link_oauth_identity(
+    pool: &DbPool,
+    user_id: Uuid,
+    issuer: &str,
+    subject: &str,
+    email: Option<&str>,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "INSERT INTO oauth_identities (issuer, subject, user_id, email, last_login_at)
+         VALUES ($1, $2, $3, $4, NOW())
+         ON CONFLICT (issuer, subject) DO NOTHING",
+    )
+    .bind(issuer)
+    .bind(subject)
+    .bind(user_id)
+    .bind(email)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Creates the account and its identity together, so a failed link doesn't
+// leave an account nobody can sign in to.
+pub async fn create_oauth_user(
+    pool: &DbPool,
+    user_id: Uuid,
+    username: &str,
+    issuer: &str,
+    subject: &str,
+    email: Option<&str>,
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("INSERT INTO users (id, username) VALUES ($1, $2)")
+        .bind(user_id)
+        .bind(username)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO oauth_identities (issuer, subject, user_id, email, last_login_at)
+         VALUES ($1, $2, $3, $4, NOW())",
+    )
+    .bind(issuer)
+    .bind(subject)
+    .bind(user_id)
+    .bind(email)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
@@ -1,19 +1,84 @@
 use crate::db::connection::DbPool;
+use crate::db::models::{PasskeyAttestation, PasskeyRecord};
 use sqlx::Error;
+use sqlx::PgConnection;
 use sqlx::Row;
 use sqlx::types::Json;
 use uuid::Uuid;
-use webauthn_rs::prelude::Passkey;
+use webauthn_rs::prelude::{AuthenticationResult, CredentialID, Passkey};
 
-pub async fn add_passkey(pool: &DbPool, user_id: Uuid, passkey: &Passkey) -> Result<(), Error> {
-    let passkey_json = serde_json::to_value(passkey).unwrap_or(serde_json::Value::Null);
+pub fn credential_key(cred_id: &CredentialID) -> Option<String> {
+    serde_json::to_value(cred_id)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+}
+
+fn cred_id_key(passkey: &Passkey) -> Option<String> {
+    credential_key(passkey.cred_id())
+}
+
+// Creates a new account with its first passkey. Inserting the user without
+// ON CONFLICT means an existing id or username fails the whole registration
+// instead of attaching a credential to someone else's account.
+pub async fn register_user_with_passkey(
+    pool: &DbPool,
+    user_id: Uuid,
+    username: &str,
+    passkey: &Passkey,
+    nickname: Option<&str>,
+    attestation: Option<&PasskeyAttestation>,
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
 
-    sqlx::query("INSERT INTO passkeys (user_id, passkey_data) VALUES ($1, $2)")
+    sqlx::query("INSERT INTO users (id, username) VALUES ($1, $2)")
         .bind(user_id)
-        .bind(passkey_json)
-        .execute(pool)
+        .bind(username)
+        .execute(&mut *tx)
         .await?;
 
+    insert_passkey(&mut tx, user_id, passkey, nickname, attestation).await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Adds a passkey to an account that already exists.
+pub async fn add_user_passkey(
+    pool: &DbPool,
+    user_id: Uuid,
+    passkey: &Passkey,
+    nickname: Option<&str>,
+    attestation: Option<&PasskeyAttestation>,
+) -> Result<(), Error> {
+    let mut conn = pool.acquire().await?;
+    insert_passkey(&mut conn, user_id, passkey, nickname, attestation).await
+}
+
+async fn insert_passkey(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    passkey: &Passkey,
+    nickname: Option<&str>,
+    attestation: Option<&PasskeyAttestation>,
+) -> Result<(), Error> {
+    let passkey_json = serde_json::to_value(passkey).unwrap_or(serde_json::Value::Null);
+
+    sqlx::query(
+        "INSERT INTO passkeys
+             (user_id, passkey_data, cred_id, nickname, aaguid, attestation_format, user_verified)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(user_id)
+    .bind(passkey_json)
+    .bind(cred_id_key(passkey))
+    .bind(nickname)
+    .bind(attestation.and_then(|a| a.aaguid))
+    .bind(attestation.map(|a| a.format.as_str()))
+    .bind(attestation.map(|a| a.user_verified))
+    .execute(conn)
+    .await?;
+
     Ok(())
 }
 
@@ -25,28 +90,128 @@ pub async fn get_user_passkeys(pool: &DbPool, user_id: Uuid) -> Result<Vec<Passk
 
     let passkeys: Vec<Passkey> = rows
         .into_iter()
-        .filter_map(|row| {
+        .map(|row| {
             let json_val: Json<Passkey> = row.get("passkey_data");
-            Some(json_val.0)
+            json_val.0
         })
         .collect();
 
     Ok(passkeys)
 }
 
-pub async fn update_user_passkeys(
+pub async fn get_user_passkey_records(
     pool: &DbPool,
     user_id: Uuid,
-    passkeys: &[Passkey],
+) -> Result<Vec<PasskeyRecord>, Error> {
+    let rows = sqlx::query(
+        "SELECT id, passkey_data, nickname, aaguid, created_at, last_used_at,
+                sign_count, backup_eligible, backup_state, clone_suspected_at,
+                attestation_format, user_verified
+         FROM passkeys
+         WHERE user_id = $1 ORDER BY id",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let json_val: Json<Passkey> = row.get("passkey_data");
+            PasskeyRecord {
+                id: row.get("id"),
+                passkey: json_val.0,
+                nickname: row.get("nickname"),
+                aaguid: row.get("aaguid"),
+                created_at: row.get("created_at"),
+                last_used_at: row.get("last_used_at"),
+                sign_count: row.get("sign_count"),
+                backup_eligible: row.get("backup_eligible"),
+                backup_state: row.get("backup_state"),
+                clone_suspected_at: row.get("clone_suspected_at"),
+                attestation_format: row.get("attestation_format"),
+                user_verified: row.get("user_verified"),
+            }
+        })
+        .collect())
+}
+
+pub async fn record_passkey_use(
+    pool: &DbPool,
+    user_id: Uuid,
+    passkey: &Passkey,
+    auth_result: &AuthenticationResult,
 ) -> Result<(), Error> {
-    sqlx::query("DELETE FROM passkeys WHERE user_id = $1")
-        .bind(user_id)
-        .execute(pool)
-        .await?;
+    let passkey_json = serde_json::to_value(passkey).unwrap_or(serde_json::Value::Null);
+
+    let result = sqlx::query(
+        "UPDATE passkeys SET passkey_data = $1, last_used_at = NOW(),
+             sign_count = $4, backup_eligible = $5, backup_state = $6
+         WHERE user_id = $2 AND cred_id = $3",
+    )
+    .bind(passkey_json)
+    .bind(user_id)
+    .bind(cred_id_key(passkey))
+    .bind(i64::from(auth_result.counter()))
+    .bind(auth_result.backup_eligible())
+    .bind(auth_result.backup_state())
+    .execute(pool)
+    .await?;
 
-    for passkey in passkeys {
-        add_passkey(pool, user_id, passkey).await?;
+    if result.rows_affected() == 0 {
+        return Err(Error::RowNotFound);
     }
 
     Ok(())
 }
+
+pub async fn flag_passkey_clone(pool: &DbPool, user_id: Uuid, cred_id: &str) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE passkeys SET clone_suspected_at = NOW()
+         WHERE user_id = $1 AND cred_id = $2",
+    )
+    .bind(user_id)
+    .bind(cred_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub enum PasskeyDeletion {
+    Deleted,
+    NotFound,
+    LastCredential,
+}
+
+pub async fn delete_passkey(
+    pool: &DbPool,
+    user_id: Uuid,
+    cred_id: &str,
+) -> Result<PasskeyDeletion, Error> {
+    let mut tx = pool.begin().await?;
+
+    let cred_ids: Vec<Option<String>> =
+        sqlx::query_scalar("SELECT cred_id FROM passkeys WHERE user_id = $1 FOR UPDATE")
+            .bind(user_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+    if !cred_ids.iter().any(|id| id.as_deref() == Some(cred_id)) {
+        return Ok(PasskeyDeletion::NotFound);
+    }
+
+    if cred_ids.len() <= 1 {
+        return Ok(PasskeyDeletion::LastCredential);
+    }
+
+    sqlx::query("DELETE FROM passkeys WHERE user_id = $1 AND cred_id = $2")
+        .bind(user_id)
+        .bind(cred_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(PasskeyDeletion::Deleted)
+}
@@ -0,0 +1,37 @@
+use crate::db::connection::DbPool;
+use sqlx::Error;
+use uuid::Uuid;
+
+// Returns whether the grant is new.
+pub async fn grant_poll_access(pool: &DbPool, poll_id: Uuid, user_id: Uuid) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "INSERT INTO poll_access (poll_id, user_id) VALUES ($1, $2)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(poll_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn revoke_poll_access(pool: &DbPool, poll_id: Uuid, user_id: Uuid) -> Result<(), Error> {
+    sqlx::query("DELETE FROM poll_access WHERE poll_id = $1 AND user_id = $2")
+        .bind(poll_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn has_poll_access(pool: &DbPool, poll_id: Uuid, user_id: Uuid) -> Result<bool, Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM poll_access WHERE poll_id = $1 AND user_id = $2)",
+    )
+    .bind(poll_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
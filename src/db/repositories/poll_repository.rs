@@ -1,49 +1,252 @@
 use crate::db::connection::DbPool;
-use crate::db::models::{Poll, PollOption};
-use sqlx::Error;
-use sqlx::Row;
+use crate::db::insert_poll_tags;
+use crate::db::models::{CreatorPollSummary, Poll, PollOption, PollSearchHit, TrendingPoll};
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{Error, PgConnection, Postgres, QueryBuilder};
+use sqlx::{FromRow, Row};
 use uuid::Uuid;
 
-pub async fn create_poll(
+const EXTERNAL_POLL_NAMESPACE: Uuid = Uuid::from_u128(0x6f1c_2a4e_8d3b_4f5a_9e7c_1b2d_3c4e_5f60);
+
+// Scoped to the creator, so one integrator's ids can't collide with, or reveal,
+// another's.
+pub fn external_poll_id(creator_id: Uuid, external_id: &str) -> Uuid {
+    let name = [creator_id.as_bytes().as_slice(), external_id.as_bytes()].concat();
+    Uuid::new_v5(&EXTERNAL_POLL_NAMESPACE, &name)
+}
+
+pub struct NewPoll<'a> {
+    pub title: &'a str,
+    pub description: Option<&'a str>,
+    pub external_id: Option<&'a str>,
+    pub ballot_public_key: Option<&'a str>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub opens_at: Option<DateTime<Utc>>,
+    pub allow_multiple: bool,
+    pub vote_type: &'a str,
+    pub max_choices: Option<i32>,
+    pub anonymous: bool,
+    pub allow_vote_change: bool,
+    pub visibility: &'a str,
+    pub access_code_hash: Option<&'a str>,
+    pub result_visibility: &'a str,
+    pub allow_write_in: bool,
+}
+
+pub struct NewPollWithOptions<'a> {
+    pub poll: NewPoll<'a>,
+    pub option_texts: &'a [String],
+    pub write_in_options: &'a [usize],
+    pub tags: Vec<String>,
+}
+
+// Returns the option ids when the poll was created, or None when a poll with
+// the same external id already existed and nothing was written.
+async fn insert_poll_with_options(
+    conn: &mut PgConnection,
+    creator_id: Uuid,
+    poll: &NewPoll<'_>,
+    option_texts: &[String],
+    write_in_options: &[usize],
+    tags: &[String],
+) -> Result<(Uuid, Option<Vec<Uuid>>), Error> {
+    let poll_id = poll.external_id.map_or_else(Uuid::new_v4, |external_id| {
+        external_poll_id(creator_id, external_id)
+    });
+
+    let inserted = sqlx::query(
+        "INSERT INTO polls (id, creator_id, title, description, external_id, ballot_public_key, expires_at, allow_multiple, vote_type, max_choices, anonymous, allow_vote_change, visibility, access_code_hash, result_visibility, allow_write_in, opens_at, opened)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, COALESCE($17 <= NOW(), TRUE))
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(poll_id)
+    .bind(creator_id)
+    .bind(poll.title)
+    .bind(poll.description)
+    .bind(poll.external_id)
+    .bind(poll.ballot_public_key)
+    .bind(poll.expires_at)
+    .bind(poll.allow_multiple)
+    .bind(poll.vote_type)
+    .bind(poll.max_choices)
+    .bind(poll.anonymous)
+    .bind(poll.allow_vote_change)
+    .bind(poll.visibility)
+    .bind(poll.access_code_hash)
+    .bind(poll.result_visibility)
+    .bind(poll.allow_write_in)
+    .bind(poll.opens_at)
+    .execute(&mut *conn)
+    .await?;
+
+    // Polls created before ids were scoped to their creator are found by the
+    // external id itself.
+    if inserted.rows_affected() == 0 {
+        let existing: Option<Uuid> =
+            sqlx::query_scalar("SELECT id FROM polls WHERE creator_id = $1 AND external_id = $2")
+                .bind(creator_id)
+                .bind(poll.external_id)
+                .fetch_optional(&mut *conn)
+                .await?;
+
+        return Ok((existing.unwrap_or(poll_id), None));
+    }
+
+    let option_ids: Vec<Uuid> = option_texts.iter().map(|_| Uuid::new_v4()).collect();
+
+    if !option_texts.is_empty() {
+        let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+            "INSERT INTO poll_options (id, poll_id, option_text, allows_write_in) ",
+        );
+        builder.push_values(
+            option_ids.iter().zip(option_texts).enumerate(),
+            |mut row, (index, (option_id, option_text))| {
+                row.push_bind(*option_id)
+                    .push_bind(poll_id)
+                    .push_bind(option_text)
+                    .push_bind(write_in_options.contains(&index));
+            },
+        );
+        builder.build().execute(&mut *conn).await?;
+    }
+
+    insert_poll_tags(conn, poll_id, tags).await?;
+
+    Ok((poll_id, Some(option_ids)))
+}
+
+pub async fn create_poll_with_options(
     pool: &DbPool,
     creator_id: Uuid,
-    title: &str,
-    description: Option<&str>,
-) -> Result<Uuid, Error> {
-    let poll_id = Uuid::new_v4();
+    poll: &NewPoll<'_>,
+    option_texts: &[String],
+    write_in_options: &[usize],
+    tags: &[String],
+) -> Result<(Uuid, Option<Vec<Uuid>>), Error> {
+    let mut tx = pool.begin().await?;
 
-    sqlx::query("INSERT INTO polls (id, creator_id, title, description) VALUES ($1, $2, $3, $4)")
-        .bind(poll_id)
-        .bind(creator_id)
-        .bind(title)
-        .bind(description)
-        .execute(pool)
+    let created = insert_poll_with_options(
+        &mut tx,
+        creator_id,
+        poll,
+        option_texts,
+        write_in_options,
+        tags,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(created)
+}
+
+// Created in one transaction, so a failure leaves none of the batch behind.
+pub async fn create_polls_with_options(
+    pool: &DbPool,
+    creator_id: Uuid,
+    polls: &[NewPollWithOptions<'_>],
+) -> Result<Vec<(Uuid, Option<Vec<Uuid>>)>, Error> {
+    let mut tx = pool.begin().await?;
+    let mut created = Vec::with_capacity(polls.len());
+
+    for new_poll in polls {
+        let created_poll = insert_poll_with_options(
+            &mut tx,
+            creator_id,
+            &new_poll.poll,
+            new_poll.option_texts,
+            new_poll.write_in_options,
+            &new_poll.tags,
+        )
         .await?;
 
-    Ok(poll_id)
+        created.push(created_poll);
+    }
+
+    tx.commit().await?;
+
+    Ok(created)
+}
+
+pub struct PollEdit<'a> {
+    pub title: Option<&'a str>,
+    pub description: Option<Option<&'a str>>,
+    pub add_options: &'a [String],
+    pub remove_options: &'a [Uuid],
 }
 
-pub async fn add_poll_option(
+pub enum PollEditOutcome {
+    Edited,
+    HasVotes,
+}
+
+pub async fn edit_poll(
     pool: &DbPool,
     poll_id: Uuid,
-    option_text: &str,
-) -> Result<Uuid, Error> {
-    let option_id = Uuid::new_v4();
+    edit: &PollEdit<'_>,
+) -> Result<PollEditOutcome, Error> {
+    let mut tx = pool.begin().await?;
 
-    sqlx::query("INSERT INTO poll_options (id, poll_id, option_text) VALUES ($1, $2, $3)")
-        .bind(option_id)
+    sqlx::query("SELECT id FROM polls WHERE id = $1 FOR UPDATE")
         .bind(poll_id)
-        .bind(option_text)
-        .execute(pool)
+        .fetch_one(&mut *tx)
         .await?;
 
-    Ok(option_id)
+    if !edit.remove_options.is_empty() {
+        let has_votes: bool =
+            sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM votes WHERE poll_id = $1)")
+                .bind(poll_id)
+                .fetch_one(&mut *tx)
+                .await?;
+
+        if has_votes {
+            tx.rollback().await?;
+            return Ok(PollEditOutcome::HasVotes);
+        }
+
+        sqlx::query("DELETE FROM poll_options WHERE poll_id = $1 AND id = ANY($2)")
+            .bind(poll_id)
+            .bind(edit.remove_options)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    if let Some(title) = edit.title {
+        sqlx::query("UPDATE polls SET title = $1 WHERE id = $2")
+            .bind(title)
+            .bind(poll_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    if let Some(description) = edit.description {
+        sqlx::query("UPDATE polls SET description = $1 WHERE id = $2")
+            .bind(description)
+            .bind(poll_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    if !edit.add_options.is_empty() {
+        let mut builder: QueryBuilder<Postgres> =
+            QueryBuilder::new("INSERT INTO poll_options (id, poll_id, option_text) ");
+        builder.push_values(edit.add_options, |mut row, option_text| {
+            row.push_bind(Uuid::new_v4())
+                .push_bind(poll_id)
+                .push_bind(option_text);
+        });
+        builder.build().execute(&mut *tx).await?;
+    }
+
+    tx.commit().await?;
+    Ok(PollEditOutcome::Edited)
 }
 
 pub async fn get_poll(pool: &DbPool, poll_id: Uuid) -> Result<Option<Poll>, Error> {
-    let row = sqlx::query_as::<_, Poll>(
-        "SELECT id, creator_id, title, description, created_at, closed FROM polls WHERE id = $1",
-    )
+    let row = sqlx::query_as::<_, Poll>(&format!(
+        "SELECT {POLL_COLUMNS} FROM polls p WHERE p.id = $1"
+    ))
     .bind(poll_id)
     .fetch_optional(pool)
     .await?;
@@ -51,19 +254,388 @@ pub async fn get_poll(pool: &DbPool, poll_id: Uuid) -> Result<Option<Poll>, Erro
     Ok(row)
 }
 
-pub async fn get_all_polls(pool: &DbPool) -> Result<Vec<Poll>, Error> {
-    let rows = sqlx::query_as::<_, Poll>(
-        "SELECT id, creator_id, title, description, created_at, closed FROM polls ORDER BY created_at DESC"
+const POLL_COLUMNS: &str = "p.id, p.creator_id, p.title, p.description, p.created_at, p.closed,
+    p.ballot_public_key, p.tallied, p.expires_at, p.opens_at, p.allow_multiple, p.vote_type, p.max_choices,
+    p.anonymous, p.allow_vote_change, p.visibility,
+    p.access_code_hash, p.result_visibility, p.allow_write_in, p.archived_at,
+    ARRAY(
+        SELECT t.name FROM poll_tags pt JOIN tags t ON t.id = pt.tag_id
+        WHERE pt.poll_id = p.id ORDER BY t.name
+    ) AS tags";
+
+const OPTION_COLUMNS: &str = "o.id AS option_id, o.option_text, o.votes, o.allows_write_in";
+
+fn assemble_polls_with_options(rows: Vec<PgRow>) -> Result<Vec<(Poll, Vec<PollOption>)>, Error> {
+    let mut polls: Vec<(Poll, Vec<PollOption>)> = Vec::new();
+
+    for row in rows {
+        let poll_id: Uuid = row.get("id");
+
+        if polls.last().is_none_or(|(poll, _)| poll.id != poll_id) {
+            polls.push((Poll::from_row(&row)?, Vec::new()));
+        }
+
+        if let Some(option_id) = row.get::<Option<Uuid>, _>("option_id")
+            && let Some((_, options)) = polls.last_mut()
+        {
+            options.push(PollOption {
+                id: option_id,
+                poll_id,
+                option_text: row.get("option_text"),
+                votes: row.get("votes"),
+                allows_write_in: row.get("allows_write_in"),
+            });
+        }
+    }
+
+    Ok(polls)
+}
+
+#[derive(Default)]
+pub struct PollFilter<'a> {
+    pub creator_id: Option<Uuid>,
+    pub closed: Option<bool>,
+    pub search: Option<&'a str>,
+    pub tag: Option<&'a str>,
+    pub viewer_id: Option<Uuid>,
+    pub include_archived: bool,
+}
+
+// Expired polls count as closed even before the background task flags them.
+// Unlisted, private and access-code polls are only listed for their creator
+// and for users who have been granted access. Archived polls are left out
+// unless asked for.
+const POLL_FILTER_CONDITIONS: &str = "($1::uuid IS NULL OR creator_id = $1)
+    AND ($2::boolean IS NULL OR (closed OR COALESCE(expires_at <= NOW(), FALSE)) = $2)
+    AND ($3::text IS NULL OR to_tsvector('simple', title) @@ plainto_tsquery('simple', $3))
+    AND ($4::text IS NULL OR EXISTS (
+        SELECT 1 FROM poll_tags pt JOIN tags t ON t.id = pt.tag_id
+        WHERE pt.poll_id = polls.id AND t.name = $4
+    ))
+    AND ((visibility = 'public' AND access_code_hash IS NULL) OR creator_id = $5 OR EXISTS (
+        SELECT 1 FROM poll_access pa WHERE pa.poll_id = polls.id AND pa.user_id = $5
+    ))
+    AND ($6::boolean OR archived_at IS NULL)";
+
+// Listings filtered by a tag start with the polls pinned in it; without a
+// tag nothing is pinned.
+const PINNED_IN_TAG: &str = "EXISTS (
+        SELECT 1 FROM pinned_in_tag pit JOIN tags t ON t.id = pit.tag_id
+        WHERE pit.poll_id = polls.id AND t.name = $4
+    )";
+
+pub async fn get_polls_with_options(
+    pool: &DbPool,
+    filter: &PollFilter<'_>,
+    limit: Option<i64>,
+    offset: i64,
+) -> Result<Vec<(Poll, Vec<PollOption>)>, Error> {
+    let rows = sqlx::query(&format!(
+        "WITH page AS (
+            SELECT *, {PINNED_IN_TAG} AS pinned_in_tag FROM polls
+            WHERE {POLL_FILTER_CONDITIONS}
+            ORDER BY pinned_in_tag DESC, created_at DESC, id DESC
+            LIMIT $7 OFFSET $8
+         )
+         SELECT {POLL_COLUMNS}, {OPTION_COLUMNS} FROM page p
+         LEFT JOIN poll_options o ON o.poll_id = p.id
+         ORDER BY p.pinned_in_tag DESC, p.created_at DESC, p.id DESC, o.option_text"
+    ))
+    .bind(filter.creator_id)
+    .bind(filter.closed)
+    .bind(filter.search)
+    .bind(filter.tag)
+    .bind(filter.viewer_id)
+    .bind(filter.include_archived)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    assemble_polls_with_options(rows)
+}
+
+pub async fn get_polls_with_options_by_ids(
+    pool: &DbPool,
+    poll_ids: &[Uuid],
+) -> Result<Vec<(Poll, Vec<PollOption>)>, Error> {
+    let rows = sqlx::query(&format!(
+        "SELECT {POLL_COLUMNS}, {OPTION_COLUMNS} FROM polls p
+         LEFT JOIN poll_options o ON o.poll_id = p.id
+         WHERE p.id = ANY($1)
+         ORDER BY p.id, o.option_text"
+    ))
+    .bind(poll_ids)
+    .fetch_all(pool)
+    .await?;
+
+    assemble_polls_with_options(rows)
+}
+
+// Each vote in the window contributes 2^(-age / half_life), so a burst of
+// recent activity outranks a larger but older pile of votes. Ranked ballots
+// count once, through their first preference.
+pub async fn get_trending_polls(
+    pool: &DbPool,
+    window_hours: u32,
+    half_life_hours: f64,
+    limit: i64,
+) -> Result<Vec<TrendingPoll>, Error> {
+    sqlx::query_as::<_, TrendingPoll>(
+        "SELECT v.poll_id,
+                COUNT(*) AS recent_votes,
+                SUM(POWER(0.5, EXTRACT(EPOCH FROM NOW() - v.created_at)::float8 / 3600 / $2)) AS score
+         FROM votes v
+         JOIN polls p ON p.id = v.poll_id
+         WHERE v.created_at > NOW() - make_interval(hours => $1)
+           AND (v.rank IS NULL OR v.rank = 1)
+           AND NOT (p.closed OR COALESCE(p.expires_at <= NOW(), FALSE))
+           AND p.visibility = 'public' AND p.access_code_hash IS NULL
+           AND p.archived_at IS NULL
+         GROUP BY v.poll_id
+         ORDER BY score DESC, recent_votes DESC, v.poll_id
+         LIMIT $3",
     )
+    .bind(window_hours as i32)
+    .bind(half_life_hours)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_poll_with_options(
+    pool: &DbPool,
+    poll_id: Uuid,
+) -> Result<Option<(Poll, Vec<PollOption>)>, Error> {
+    let rows = sqlx::query(&format!(
+        "SELECT {POLL_COLUMNS}, {OPTION_COLUMNS} FROM polls p
+         LEFT JOIN poll_options o ON o.poll_id = p.id
+         WHERE p.id = $1
+         ORDER BY o.option_text"
+    ))
+    .bind(poll_id)
     .fetch_all(pool)
     .await?;
 
-    Ok(rows)
+    Ok(assemble_polls_with_options(rows)?.pop())
+}
+
+pub async fn count_polls(pool: &DbPool, filter: &PollFilter<'_>) -> Result<i64, Error> {
+    sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM polls WHERE {POLL_FILTER_CONDITIONS}"
+    ))
+    .bind(filter.creator_id)
+    .bind(filter.closed)
+    .bind(filter.search)
+    .bind(filter.tag)
+    .bind(filter.viewer_id)
+    .bind(filter.include_archived)
+    .fetch_one(pool)
+    .await
+}
+
+// Every poll the user created, whatever its visibility. Voters are counted
+// once per poll however many options or ranks they picked; the leading option
+// is left empty until someone votes or when options tie for the lead.
+pub async fn get_creator_poll_summaries(
+    pool: &DbPool,
+    creator_id: Uuid,
+    closed: Option<bool>,
+    include_archived: bool,
+    order_by_votes: bool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<CreatorPollSummary>, Error> {
+    let order = if order_by_votes {
+        "total_votes DESC, created_at DESC, id"
+    } else {
+        "created_at DESC, id"
+    };
+
+    sqlx::query_as::<_, CreatorPollSummary>(&format!(
+        "SELECT p.id, p.title,
+                CASE WHEN p.closed OR COALESCE(p.expires_at <= NOW(), FALSE) THEN 'closed'
+                     WHEN p.opens_at > NOW() THEN 'upcoming'
+                     ELSE 'open' END AS status,
+                p.visibility, p.vote_type, p.created_at, p.expires_at, p.archived_at,
+                COALESCE(o.total_votes, 0) AS total_votes,
+                COALESCE(v.voter_count, 0) AS voter_count,
+                COALESCE(o.option_count, 0) AS option_count,
+                (SELECT COUNT(*) FROM comments c WHERE c.poll_id = p.id) AS comment_count,
+                o.leading_option,
+                v.last_vote_at
+         FROM polls p
+         LEFT JOIN LATERAL (
+            SELECT SUM(votes)::bigint AS total_votes,
+                   COUNT(*) AS option_count,
+                   (SELECT option_text FROM poll_options lo
+                    WHERE lo.poll_id = p.id AND lo.votes > 0
+                      AND lo.votes > ALL (SELECT votes FROM poll_options oo
+                                          WHERE oo.poll_id = p.id AND oo.id <> lo.id)) AS leading_option
+            FROM poll_options WHERE poll_id = p.id
+         ) o ON TRUE
+         LEFT JOIN LATERAL (
+            SELECT COUNT(DISTINCT COALESCE(user_id::text, voter_hash)) AS voter_count,
+                   MAX(created_at) AS last_vote_at
+            FROM votes WHERE poll_id = p.id
+         ) v ON TRUE
+         WHERE p.creator_id = $1
+           AND ($2::boolean IS NULL OR (p.closed OR COALESCE(p.expires_at <= NOW(), FALSE)) = $2)
+           AND ($5::boolean OR p.archived_at IS NULL)
+         ORDER BY {order}
+         LIMIT $3 OFFSET $4"
+    ))
+    .bind(creator_id)
+    .bind(closed)
+    .bind(limit)
+    .bind(offset)
+    .bind(include_archived)
+    .fetch_all(pool)
+    .await
+}
+
+// Polls match on their own text or any option's. Option matches rank at half
+// weight, below a title or description match.
+const POLL_SEARCH_CONDITIONS: &str = "(p.search_vector @@ q.query OR EXISTS (
+        SELECT 1 FROM poll_options o WHERE o.poll_id = p.id AND o.search_vector @@ q.query
+    ))
+    AND p.archived_at IS NULL
+    AND ((p.visibility = 'public' AND p.access_code_hash IS NULL) OR p.creator_id = $2 OR EXISTS (
+        SELECT 1 FROM poll_access pa WHERE pa.poll_id = p.id AND pa.user_id = $2
+    ))";
+
+pub async fn search_polls(
+    pool: &DbPool,
+    query: &str,
+    viewer_id: Uuid,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<PollSearchHit>, Error> {
+    sqlx::query_as::<_, PollSearchHit>(&format!(
+        "WITH q AS (
+            SELECT websearch_to_tsquery('simple', $1) AS query,
+                   format('StartSel=%s, StopSel=%s', chr(2), chr(3)) AS marks
+         )
+         SELECT p.id AS poll_id,
+                (ts_rank(p.search_vector, q.query) + COALESCE(opt.rank, 0) / 2)::real AS rank,
+                (p.closed OR COALESCE(p.expires_at <= NOW(), FALSE)) AS closed,
+                p.created_at,
+                ts_headline('simple', p.title, q.query, q.marks || ', HighlightAll=true') AS title_headline,
+                ts_headline('simple', p.description, q.query,
+                            q.marks || ', MaxFragments=2, MaxWords=25, MinWords=10') AS description_headline,
+                COALESCE(opt.headlines, ARRAY[]::text[]) AS option_headlines
+         FROM polls p
+         CROSS JOIN q
+         LEFT JOIN LATERAL (
+            SELECT MAX(ts_rank(o.search_vector, q.query)) AS rank,
+                   array_agg(ts_headline('simple', o.option_text, q.query, q.marks || ', HighlightAll=true')
+                             ORDER BY o.option_text) AS headlines
+            FROM poll_options o
+            WHERE o.poll_id = p.id AND o.search_vector @@ q.query
+         ) opt ON TRUE
+         WHERE {POLL_SEARCH_CONDITIONS}
+         ORDER BY rank DESC, p.created_at DESC, p.id
+         LIMIT $3 OFFSET $4"
+    ))
+    .bind(query)
+    .bind(viewer_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn count_search_results(
+    pool: &DbPool,
+    query: &str,
+    viewer_id: Uuid,
+) -> Result<i64, Error> {
+    sqlx::query_scalar(&format!(
+        "WITH q AS (SELECT websearch_to_tsquery('simple', $1) AS query)
+         SELECT COUNT(*) FROM polls p CROSS JOIN q
+         WHERE {POLL_SEARCH_CONDITIONS}"
+    ))
+    .bind(query)
+    .bind(viewer_id)
+    .fetch_one(pool)
+    .await
+}
+
+pub enum WriteInOption {
+    Existing(Uuid),
+    Added(PollOption),
+    LimitReached,
+}
+
+// Matches the text, already trimmed and with runs of whitespace collapsed,
+// against the poll's options ignoring case and spacing, and adds it as a new
+// option when nothing matches. The poll row
+// is locked so concurrent write-ins of the same text end up as one option.
+pub async fn add_write_in_option(
+    pool: &DbPool,
+    poll_id: Uuid,
+    option_text: &str,
+    max_write_ins: i64,
+) -> Result<WriteInOption, Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT id FROM polls WHERE id = $1 FOR UPDATE")
+        .bind(poll_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let existing: Option<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM poll_options
+         WHERE poll_id = $1
+           AND lower(regexp_replace(btrim(option_text), '[[:space:]]+', ' ', 'g')) = lower($2)
+         LIMIT 1",
+    )
+    .bind(poll_id)
+    .bind(option_text)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(option_id) = existing {
+        tx.rollback().await?;
+        return Ok(WriteInOption::Existing(option_id));
+    }
+
+    let added: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM poll_options WHERE poll_id = $1 AND added_by_voter",
+    )
+    .bind(poll_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if added >= max_write_ins {
+        tx.rollback().await?;
+        return Ok(WriteInOption::LimitReached);
+    }
+
+    let option_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO poll_options (id, poll_id, option_text, added_by_voter) VALUES ($1, $2, $3, TRUE)",
+    )
+    .bind(option_id)
+    .bind(poll_id)
+    .bind(option_text)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(WriteInOption::Added(PollOption {
+        id: option_id,
+        poll_id,
+        option_text: option_text.to_string(),
+        votes: 0,
+        allows_write_in: false,
+    }))
 }
 
 pub async fn get_poll_options(pool: &DbPool, poll_id: Uuid) -> Result<Vec<PollOption>, Error> {
     let rows = sqlx::query(
-        "SELECT id, poll_id, option_text, votes FROM poll_options WHERE poll_id = $1 ORDER BY option_text"
+        "SELECT id, poll_id, option_text, votes, allows_write_in FROM poll_options WHERE poll_id = $1 ORDER BY option_text"
     )
     .bind(poll_id)
     .fetch_all(pool)
@@ -76,6 +648,7 @@ pub async fn get_poll_options(pool: &DbPool, poll_id: Uuid) -> Result<Vec<PollOp
             poll_id: r.get("poll_id"),
             option_text: r.get("option_text"),
             votes: r.get("votes"),
+            allows_write_in: r.get("allows_write_in"),
         })
         .collect())
 }
@@ -89,11 +662,118 @@ pub async fn close_poll(pool: &DbPool, poll_id: Uuid) -> Result<(), Error> {
     Ok(())
 }
 
-pub async fn restart_poll(pool: &DbPool, poll_id: Uuid) -> Result<(), Error> {
-    sqlx::query("UPDATE polls SET closed = FALSE WHERE id = $1")
+// Archived polls stay as they are; returns whether the poll was restarted.
+pub async fn restart_poll(pool: &DbPool, poll_id: Uuid, reset_votes: bool) -> Result<bool, Error> {
+    let mut tx = pool.begin().await?;
+
+    let result = sqlx::query(
+        "UPDATE polls SET closed = FALSE,
+            expires_at = CASE WHEN expires_at <= NOW() THEN NULL ELSE expires_at END
+         WHERE id = $1 AND archived_at IS NULL",
+    )
+    .bind(poll_id)
+    .execute(&mut *tx)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if reset_votes {
+        sqlx::query("DELETE FROM votes WHERE poll_id = $1")
+            .bind(poll_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE poll_options SET votes = 0 WHERE poll_id = $1")
+            .bind(poll_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE polls SET tallied = FALSE WHERE id = $1")
+            .bind(poll_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+// Only announces: scheduled polls take votes from opens_at on regardless.
+pub async fn open_scheduled_polls(pool: &DbPool) -> Result<Vec<Uuid>, Error> {
+    sqlx::query_scalar(
+        "UPDATE polls SET opened = TRUE
+         WHERE NOT opened AND opens_at <= NOW() AND archived_at IS NULL
+         RETURNING id",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn close_expired_polls(pool: &DbPool) -> Result<Vec<Uuid>, Error> {
+    sqlx::query_scalar(
+        "UPDATE polls SET closed = TRUE
+         WHERE closed = FALSE AND expires_at <= NOW() AND archived_at IS NULL
+         RETURNING id",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// False when the poll was already archived, or restored, and nothing changed.
+pub async fn archive_poll(pool: &DbPool, poll_id: Uuid) -> Result<bool, Error> {
+    let result =
+        sqlx::query("UPDATE polls SET archived_at = NOW() WHERE id = $1 AND archived_at IS NULL")
+            .bind(poll_id)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn restore_poll(pool: &DbPool, poll_id: Uuid) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "UPDATE polls SET archived_at = NULL WHERE id = $1 AND archived_at IS NOT NULL",
+    )
+    .bind(poll_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_poll(pool: &DbPool, poll_id: Uuid) -> Result<bool, Error> {
+    let result = sqlx::query("DELETE FROM polls WHERE id = $1")
         .bind(poll_id)
         .execute(pool)
         .await?;
 
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn record_tally(
+    pool: &DbPool,
+    poll_id: Uuid,
+    counts: &[(Uuid, i32)],
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    for (option_id, votes) in counts {
+        sqlx::query("UPDATE poll_options SET votes = $1 WHERE id = $2 AND poll_id = $3")
+            .bind(votes)
+            .bind(option_id)
+            .bind(poll_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    sqlx::query("UPDATE polls SET tallied = TRUE WHERE id = $1")
+        .bind(poll_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
     Ok(())
 }
@@ -0,0 +1,152 @@
+use crate::db::connection::DbPool;
+use chrono::{DateTime, Utc};
+use sqlx::{Error, Row};
+use uuid::Uuid;
+
+pub enum RefreshRotation {
+    Rotated { user_id: Uuid, username: String },
+    Reused,
+    Invalid,
+}
+
+pub async fn create_refresh_token(
+    pool: &DbPool,
+    user_id: Uuid,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (id, user_id, family_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(Uuid::new_v4())
+    .bind(token_hash)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn revoke_user_refresh_tokens(pool: &DbPool, user_id: Uuid) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP
+         WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn revoke_refresh_token_family(
+    pool: &DbPool,
+    user_id: Uuid,
+    token_hash: &str,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP
+        WHERE family_id = (
+            SELECT family_id FROM refresh_tokens WHERE token_hash = $1 AND user_id = $2
+        )
+        AND revoked_at IS NULL
+        "#,
+    )
+    .bind(token_hash)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn rotate_refresh_token(
+    pool: &DbPool,
+    token_hash: &str,
+    new_token_hash: &str,
+    new_expires_at: DateTime<Utc>,
+) -> Result<RefreshRotation, Error> {
+    let mut tx = pool.begin().await?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT rt.id, rt.user_id, rt.family_id, rt.expires_at, rt.revoked_at, u.username
+        FROM refresh_tokens rt
+        JOIN users u ON u.id = rt.user_id
+        WHERE rt.token_hash = $1
+        FOR UPDATE OF rt
+        "#,
+    )
+    .bind(token_hash)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(RefreshRotation::Invalid);
+    };
+
+    let family_id: Uuid = row.get("family_id");
+
+    // A rotated token being presented again means it leaked; kill the whole
+    // family so neither party can keep refreshing.
+    if row.get::<Option<DateTime<Utc>>, _>("revoked_at").is_some() {
+        sqlx::query(
+            r#"
+            UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP
+            WHERE family_id = $1 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(family_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        return Ok(RefreshRotation::Reused);
+    }
+
+    if row.get::<DateTime<Utc>, _>("expires_at") <= Utc::now() {
+        return Ok(RefreshRotation::Invalid);
+    }
+
+    let old_id: Uuid = row.get("id");
+    let user_id: Uuid = row.get("user_id");
+    let new_id = Uuid::new_v4();
+
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (id, user_id, family_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(new_id)
+    .bind(user_id)
+    .bind(family_id)
+    .bind(new_token_hash)
+    .bind(new_expires_at)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP, replaced_by = $1
+        WHERE id = $2
+        "#,
+    )
+    .bind(new_id)
+    .bind(old_id)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(RefreshRotation::Rotated {
+        user_id,
+        username: row.get("username"),
+    })
+}
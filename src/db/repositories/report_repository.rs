@@ -0,0 +1,62 @@
+use crate::db::connection::DbPool;
+use crate::db::models::PollReport;
+use sqlx::Error;
+use uuid::Uuid;
+
+pub async fn create_report(
+    pool: &DbPool,
+    poll_id: Uuid,
+    reporter_user_id: Uuid,
+    reason: &str,
+) -> Result<Option<Uuid>, Error> {
+    let report_id = Uuid::new_v4();
+
+    let inserted = sqlx::query(
+        "INSERT INTO poll_reports (id, poll_id, reporter_user_id, reason) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (poll_id, reporter_user_id) WHERE resolved = FALSE DO NOTHING",
+    )
+    .bind(report_id)
+    .bind(poll_id)
+    .bind(reporter_user_id)
+    .bind(reason)
+    .execute(pool)
+    .await?;
+
+    if inserted.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(report_id))
+}
+
+pub async fn get_reports(pool: &DbPool, resolved: Option<bool>) -> Result<Vec<PollReport>, Error> {
+    let rows = sqlx::query_as::<_, PollReport>(
+        "SELECT id, poll_id, reporter_user_id, reason, created_at, resolved FROM poll_reports
+         WHERE ($1::BOOLEAN IS NULL OR resolved = $1) ORDER BY created_at ASC, id ASC",
+    )
+    .bind(resolved)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn get_report(pool: &DbPool, report_id: Uuid) -> Result<Option<PollReport>, Error> {
+    let row = sqlx::query_as::<_, PollReport>(
+        "SELECT id, poll_id, reporter_user_id, reason, created_at, resolved FROM poll_reports WHERE id = $1",
+    )
+    .bind(report_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn resolve_report(pool: &DbPool, report_id: Uuid) -> Result<(), Error> {
+    sqlx::query("UPDATE poll_reports SET resolved = TRUE WHERE id = $1")
+        .bind(report_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
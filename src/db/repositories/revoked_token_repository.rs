@@ -0,0 +1,55 @@
+use crate::db::connection::DbPool;
+use chrono::{DateTime, Utc};
+use sqlx::Error;
+use uuid::Uuid;
+
+pub async fn revoke_access_token(
+    pool: &DbPool,
+    jti: Uuid,
+    user_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO revoked_tokens (jti, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (jti) DO NOTHING
+        "#,
+    )
+    .bind(jti)
+    .bind(user_id)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Tokens issued to an account that has since been deleted count as revoked:
+// their denylist entries went with the user row, and nothing else lists them.
+// So do a banned user's, which lets a ban take effect on the next request.
+pub async fn is_access_token_revoked(
+    pool: &DbPool,
+    jti: Uuid,
+    user_id: Uuid,
+) -> Result<bool, Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM revoked_tokens WHERE jti = $1)
+             OR NOT EXISTS(SELECT 1 FROM users WHERE id = $2)
+             OR EXISTS(SELECT 1 FROM users WHERE id = $2 AND banned)",
+    )
+    .bind(jti)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+// Once a token's own expiry has passed it is rejected anyway, so its denylist
+// entry can go.
+pub async fn purge_expired_revoked_tokens(pool: &DbPool) -> Result<u64, Error> {
+    let result = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at <= CURRENT_TIMESTAMP")
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,27 @@
+use crate::db::connection::DbPool;
+use sqlx::Error;
+use uuid::Uuid;
+
+pub async fn record_security_event(
+    pool: &DbPool,
+    user_id: Option<Uuid>,
+    event_type: &str,
+    cred_id: Option<&str>,
+    details: serde_json::Value,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO security_events (id, user_id, event_type, cred_id, details)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(Uuid::new_v4())
+    .bind(user_id)
+    .bind(event_type)
+    .bind(cred_id)
+    .bind(details)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
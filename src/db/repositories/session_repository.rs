@@ -0,0 +1,11 @@
+use crate::db::connection::DbPool;
+use sqlx::Error;
+
+// The session layer ignores expired rows but never deletes them itself.
+pub async fn purge_expired_sessions(pool: &DbPool) -> Result<u64, Error> {
+    let result = sqlx::query("DELETE FROM sessions WHERE expiry_date <= CURRENT_TIMESTAMP")
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,22 @@
+use crate::db::connection::DbPool;
+use crate::db::models::ActivityCounts;
+use sqlx::Error;
+
+pub async fn get_activity_counts(pool: &DbPool) -> Result<ActivityCounts, Error> {
+    sqlx::query_as::<_, ActivityCounts>(
+        "SELECT
+            (SELECT COUNT(*) FROM polls) AS total_polls,
+            (SELECT COUNT(*) FROM polls WHERE closed = FALSE) AS open_polls,
+            (SELECT COUNT(*) FROM polls WHERE closed = TRUE) AS closed_polls,
+            (SELECT COUNT(*) FROM polls WHERE created_at > NOW() - INTERVAL '24 hours')
+                AS polls_last_24h,
+            (SELECT COUNT(*) FROM votes) AS total_votes,
+            (SELECT COUNT(*) FROM votes WHERE created_at > NOW() - INTERVAL '24 hours')
+                AS votes_last_24h,
+            (SELECT COUNT(*) FROM users) AS total_users,
+            (SELECT COUNT(*) FROM users WHERE created_at > LOCALTIMESTAMP - INTERVAL '24 hours')
+                AS users_last_24h",
+    )
+    .fetch_one(pool)
+    .await
+}
@@ -0,0 +1,130 @@
+use crate::db::connection::DbPool;
+use crate::db::models::{Poll, Survey};
+use sqlx::{Error, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+pub struct NewSurveyQuestion<'a> {
+    pub title: &'a str,
+    pub description: Option<&'a str>,
+    pub options: &'a [String],
+}
+
+pub struct CreatedSurveyQuestion {
+    pub poll_id: Uuid,
+    pub option_ids: Vec<Uuid>,
+}
+
+pub async fn create_survey(
+    pool: &DbPool,
+    creator_id: Uuid,
+    title: &str,
+    questions: &[NewSurveyQuestion<'_>],
+) -> Result<(Uuid, Vec<CreatedSurveyQuestion>), Error> {
+    let mut tx = pool.begin().await?;
+
+    let survey_id = Uuid::new_v4();
+    sqlx::query("INSERT INTO surveys (id, creator_id, title) VALUES ($1, $2, $3)")
+        .bind(survey_id)
+        .bind(creator_id)
+        .bind(title)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut created = Vec::with_capacity(questions.len());
+    for (position, question) in questions.iter().enumerate() {
+        let poll_id = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO polls (id, creator_id, title, description, survey_id, survey_position)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(poll_id)
+        .bind(creator_id)
+        .bind(question.title)
+        .bind(question.description)
+        .bind(survey_id)
+        .bind(position as i32)
+        .execute(&mut *tx)
+        .await?;
+
+        let option_ids: Vec<Uuid> = question.options.iter().map(|_| Uuid::new_v4()).collect();
+        let mut builder: QueryBuilder<Postgres> =
+            QueryBuilder::new("INSERT INTO poll_options (id, poll_id, option_text) ");
+        builder.push_values(
+            option_ids.iter().zip(question.options),
+            |mut row, (option_id, option_text)| {
+                row.push_bind(*option_id)
+                    .push_bind(poll_id)
+                    .push_bind(option_text);
+            },
+        );
+        builder.build().execute(&mut *tx).await?;
+
+        created.push(CreatedSurveyQuestion {
+            poll_id,
+            option_ids,
+        });
+    }
+
+    tx.commit().await?;
+    Ok((survey_id, created))
+}
+
+pub async fn get_survey(pool: &DbPool, survey_id: Uuid) -> Result<Option<Survey>, Error> {
+    let row = sqlx::query_as::<_, Survey>(
+        "SELECT id, creator_id, title, created_at, closed FROM surveys WHERE id = $1",
+    )
+    .bind(survey_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+pub async fn get_survey_polls(pool: &DbPool, survey_id: Uuid) -> Result<Vec<Poll>, Error> {
+    let rows = sqlx::query_as::<_, Poll>(
+        "SELECT id, creator_id, title, description, created_at, closed, ballot_public_key, tallied, expires_at, opens_at, allow_multiple, vote_type, max_choices, anonymous, allow_vote_change, visibility, access_code_hash, result_visibility, allow_write_in, archived_at FROM polls
+         WHERE survey_id = $1 ORDER BY survey_position ASC",
+    )
+    .bind(survey_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn cast_survey_votes(
+    pool: &DbPool,
+    user_id: Uuid,
+    answers: &[(Uuid, Uuid)],
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    for (poll_id, option_id) in answers {
+        let existing_vote = sqlx::query("SELECT id FROM votes WHERE poll_id = $1 AND user_id = $2")
+            .bind(poll_id)
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        if existing_vote.is_some() {
+            tx.rollback().await?;
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        sqlx::query("INSERT INTO votes (id, poll_id, option_id, user_id) VALUES ($1, $2, $3, $4)")
+            .bind(Uuid::new_v4())
+            .bind(poll_id)
+            .bind(option_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE poll_options SET votes = votes + 1 WHERE id = $1")
+            .bind(option_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use crate::db::connection::DbPool;
+use crate::db::models::TagCount;
+use sqlx::{Error, PgConnection};
+use uuid::Uuid;
+
+pub async fn insert_poll_tags(
+    conn: &mut PgConnection,
+    poll_id: Uuid,
+    tags: &[String],
+) -> Result<(), Error> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query("INSERT INTO tags (name) SELECT UNNEST($1::text[]) ON CONFLICT (name) DO NOTHING")
+        .bind(tags)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO poll_tags (poll_id, tag_id)
+         SELECT $1, id FROM tags WHERE name = ANY($2)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(poll_id)
+    .bind(tags)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+// False when the poll doesn't carry the tag. Pinning again keeps the pin.
+pub async fn pin_poll_in_tag(pool: &DbPool, poll_id: Uuid, tag: &str) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "INSERT INTO pinned_in_tag (poll_id, tag_id)
+         SELECT pt.poll_id, pt.tag_id
+         FROM poll_tags pt JOIN tags t ON t.id = pt.tag_id
+         WHERE pt.poll_id = $1 AND t.name = $2
+         ON CONFLICT (poll_id, tag_id) DO UPDATE SET pinned_at = pinned_in_tag.pinned_at",
+    )
+    .bind(poll_id)
+    .bind(tag)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn unpin_poll_in_tag(pool: &DbPool, poll_id: Uuid, tag: &str) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "DELETE FROM pinned_in_tag pit
+         USING tags t
+         WHERE t.id = pit.tag_id AND pit.poll_id = $1 AND t.name = $2",
+    )
+    .bind(poll_id)
+    .bind(tag)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn list_tags(pool: &DbPool, limit: i64) -> Result<Vec<TagCount>, Error> {
+    sqlx::query_as::<_, TagCount>(
+        "SELECT t.name, COUNT(pt.poll_id) AS poll_count
+         FROM tags t
+         JOIN poll_tags pt ON pt.tag_id = t.id
+         JOIN polls p ON p.id = pt.poll_id
+         WHERE p.visibility = 'public' AND p.access_code_hash IS NULL
+         GROUP BY t.name
+         ORDER BY poll_count DESC, t.name
+         LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
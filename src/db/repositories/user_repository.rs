@@ -1,4 +1,5 @@
 use crate::db::connection::DbPool;
+use crate::db::models::{UserProfile, UserSummary};
 use sqlx::{Error, Row};
 use uuid::Uuid;
 
@@ -11,6 +12,15 @@ pub async fn get_user_id(pool: &DbPool, username: &str) -> Result<Option<Uuid>,
     Ok(row.map(|r| r.get::<Uuid, _>("id")))
 }
 
+pub async fn get_username(pool: &DbPool, user_id: Uuid) -> Result<Option<String>, Error> {
+    let row = sqlx::query("SELECT username FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|r| r.get::<String, _>("username")))
+}
+
 pub async fn create_user(pool: &DbPool, user_id: Uuid, username: &str) -> Result<(), Error> {
     sqlx::query("INSERT INTO users (id, username) VALUES ($1, $2)")
         .bind(user_id)
@@ -20,3 +30,223 @@ pub async fn create_user(pool: &DbPool, user_id: Uuid, username: &str) -> Result
 
     Ok(())
 }
+
+pub async fn get_user_profile(pool: &DbPool, user_id: Uuid) -> Result<Option<UserProfile>, Error> {
+    sqlx::query_as::<_, UserProfile>(
+        "SELECT u.id, u.username, u.display_name, u.avatar_url, u.email, u.email_verified_at,
+                u.email_notifications, u.created_at,
+                (SELECT COUNT(*) FROM passkeys WHERE user_id = u.id) AS passkey_count,
+                (SELECT COUNT(*) FROM polls WHERE creator_id = u.id) AS polls_created
+         FROM users u
+         WHERE u.id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+// `None` leaves a field untouched, `Some(None)` clears it.
+pub async fn update_user_profile(
+    pool: &DbPool,
+    user_id: Uuid,
+    display_name: Option<Option<&str>>,
+    avatar_url: Option<Option<&str>>,
+    email_notifications: Option<bool>,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "UPDATE users SET
+             display_name = CASE WHEN $2 THEN $3 ELSE display_name END,
+             avatar_url = CASE WHEN $4 THEN $5 ELSE avatar_url END,
+             email_notifications = COALESCE($6, email_notifications)
+         WHERE id = $1",
+    )
+    .bind(user_id)
+    .bind(display_name.is_some())
+    .bind(display_name.flatten())
+    .bind(avatar_url.is_some())
+    .bind(avatar_url.flatten())
+    .bind(email_notifications)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_user_role(pool: &DbPool, user_id: Uuid) -> Result<Option<(String, bool)>, Error> {
+    let row = sqlx::query("SELECT role, banned FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|r| (r.get::<String, _>("role"), r.get::<bool, _>("banned"))))
+}
+
+pub async fn get_session_user(
+    pool: &DbPool,
+    user_id: Uuid,
+) -> Result<Option<(String, String, bool)>, Error> {
+    let row = sqlx::query("SELECT username, role, banned FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|r| {
+        (
+            r.get::<String, _>("username"),
+            r.get::<String, _>("role"),
+            r.get::<bool, _>("banned"),
+        )
+    }))
+}
+
+pub async fn list_users(pool: &DbPool, limit: i64, offset: i64) -> Result<Vec<UserSummary>, Error> {
+    let rows = sqlx::query_as::<_, UserSummary>(
+        "SELECT id, username, role, banned, can_create_polls, created_at FROM users
+         ORDER BY created_at ASC, id ASC
+         LIMIT $1 OFFSET $2",
+    )
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn set_user_banned(pool: &DbPool, user_id: Uuid, banned: bool) -> Result<bool, Error> {
+    let result = sqlx::query("UPDATE users SET banned = $1 WHERE id = $2")
+        .bind(banned)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn user_can_create_polls(pool: &DbPool, user_id: Uuid) -> Result<bool, Error> {
+    let row = sqlx::query("SELECT role, can_create_polls FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.is_some_and(|r| {
+        r.get::<String, _>("role") == "admin" || r.get::<bool, _>("can_create_polls")
+    }))
+}
+
+pub async fn set_can_create_polls(
+    pool: &DbPool,
+    user_id: Uuid,
+    allowed: bool,
+) -> Result<bool, Error> {
+    let result = sqlx::query("UPDATE users SET can_create_polls = $1 WHERE id = $2")
+        .bind(allowed)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub struct AccountDeletion {
+    pub deleted_polls: Vec<Uuid>,
+    pub affected_options: Vec<(Uuid, Uuid)>,
+}
+
+// Polls the user created go away entirely. Their votes on other people's open
+// polls are removed and the counts adjusted; votes on closed polls are kept so
+// published results don't change, but are detached from the account.
+// Anonymous votes are found through the user's voter key.
+pub async fn delete_user_account(
+    pool: &DbPool,
+    user_id: Uuid,
+    voter_key: &str,
+) -> Result<Option<AccountDeletion>, Error> {
+    let mut tx = pool.begin().await?;
+
+    let exists = sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+    if exists.is_none() {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+
+    let deleted_polls: Vec<Uuid> =
+        sqlx::query_scalar("DELETE FROM polls WHERE creator_id = $1 RETURNING id")
+            .bind(user_id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+    let removed = sqlx::query(
+        "DELETE FROM votes v
+         USING polls p
+         WHERE p.id = v.poll_id
+           AND NOT (p.closed OR COALESCE(p.expires_at <= CURRENT_TIMESTAMP, FALSE))
+           AND (v.user_id = $1
+                OR (p.anonymous
+                    AND v.voter_hash = encode(sha256(convert_to($2 || ':' || p.id::text, 'UTF8')), 'hex')))
+         RETURNING v.poll_id, v.option_id, v.rank",
+    )
+    .bind(user_id)
+    .bind(voter_key)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let affected_options: Vec<(Uuid, Uuid)> = removed
+        .iter()
+        .filter(|row| {
+            row.get::<Option<i32>, _>("rank")
+                .is_none_or(|rank| rank == 1)
+        })
+        .filter_map(|row| {
+            row.get::<Option<Uuid>, _>("option_id")
+                .map(|option_id| (row.get("poll_id"), option_id))
+        })
+        .collect();
+
+    let option_ids: Vec<Uuid> = affected_options.iter().map(|(_, id)| *id).collect();
+
+    sqlx::query("UPDATE poll_options SET votes = votes - 1 WHERE id = ANY($1)")
+        .bind(&option_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    // A fresh secret per deletion keeps one voter's rows on a poll grouped
+    // (ranked ballots need that) without being derivable from the user id.
+    let anonymizer = Uuid::new_v4().to_string();
+
+    sqlx::query(
+        "UPDATE votes v
+         SET user_id = NULL,
+             voter_hash = encode(sha256(convert_to($2 || ':' || v.poll_id::text, 'UTF8')), 'hex')
+         FROM polls p
+         WHERE p.id = v.poll_id
+           AND (v.user_id = $1
+                OR (p.anonymous
+                    AND v.voter_hash = encode(sha256(convert_to($3 || ':' || p.id::text, 'UTF8')), 'hex')))",
+    )
+    .bind(user_id)
+    .bind(&anonymizer)
+    .bind(voter_key)
+    .execute(&mut *tx)
+    .await?;
+
+    // Passkeys, refresh tokens, comments and reports cascade with the user
+    // row. Sessions and access tokens don't record the user in a way SQL can
+    // match, so they stay until they expire; both are checked against the
+    // users table on every request and are rejected from here on.
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(AccountDeletion {
+        deleted_polls,
+        affected_options,
+    }))
+}
@@ -0,0 +1,66 @@
+use crate::db::connection::DbPool;
+use chrono::{DateTime, Utc};
+use sqlx::Error;
+use uuid::Uuid;
+
+pub async fn create_vote_links(
+    pool: &DbPool,
+    poll_id: Uuid,
+    links: &[(Uuid, String)],
+    expires_at: DateTime<Utc>,
+) -> Result<(), Error> {
+    let (ids, emails): (Vec<Uuid>, Vec<String>) = links.iter().cloned().unzip();
+
+    sqlx::query(
+        "INSERT INTO poll_vote_links (id, poll_id, email, expires_at)
+         SELECT id, $2, email, $4 FROM UNNEST($1::uuid[], $3::text[]) AS l(id, email)",
+    )
+    .bind(&ids)
+    .bind(poll_id)
+    .bind(&emails)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Marks the link used. False if it was already used, has expired, or wasn't
+// issued for this poll and address.
+pub async fn consume_vote_link(
+    pool: &DbPool,
+    link_id: Uuid,
+    poll_id: Uuid,
+    email: &str,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "UPDATE poll_vote_links SET used_at = NOW()
+         WHERE id = $1 AND poll_id = $2 AND email = $3
+           AND used_at IS NULL AND expires_at > NOW()",
+    )
+    .bind(link_id)
+    .bind(poll_id)
+    .bind(email)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Gives the link back when the vote it was used for didn't go through.
+pub async fn release_vote_link(pool: &DbPool, link_id: Uuid) -> Result<(), Error> {
+    sqlx::query("UPDATE poll_vote_links SET used_at = NULL WHERE id = $1")
+        .bind(link_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn purge_expired_vote_links(pool: &DbPool) -> Result<u64, Error> {
+    let result = sqlx::query("DELETE FROM poll_vote_links WHERE expires_at <= CURRENT_TIMESTAMP")
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
@@ -1,50 +1,680 @@
 use crate::db::connection::DbPool;
-use sqlx::Error;
+use crate::db::models::{VoteExportRow, VoteHistoryEntry};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::stream::BoxStream;
+use sqlx::{Error, Row};
+use std::collections::HashSet;
 use uuid::Uuid;
 
-pub async fn cast_vote(
+// Votes on anonymous polls are keyed by a salted hash of the user id and never
+// store the user id itself.
+#[derive(Clone, Copy)]
+pub enum Voter<'a> {
+    User(Uuid),
+    Anonymous(&'a str),
+}
+
+impl Voter<'_> {
+    fn user_id(&self) -> Option<Uuid> {
+        match self {
+            Voter::User(user_id) => Some(*user_id),
+            Voter::Anonymous(_) => None,
+        }
+    }
+
+    fn voter_hash(&self) -> Option<&str> {
+        match self {
+            Voter::User(_) => None,
+            Voter::Anonymous(voter_hash) => Some(voter_hash),
+        }
+    }
+}
+
+pub enum VoteChange {
+    Cast,
+    Unchanged,
+    Changed { previous_option_id: Option<Uuid> },
+}
+
+pub async fn change_vote(
     pool: &DbPool,
     poll_id: Uuid,
     option_id: Uuid,
-    user_id: Uuid,
-) -> Result<(), Error> {
+    voter: Voter<'_>,
+    write_in_text: Option<&str>,
+    allow_change: bool,
+) -> Result<VoteChange, Error> {
     let mut tx = pool.begin().await?;
 
-    let existing_vote = sqlx::query("SELECT id FROM votes WHERE poll_id = $1 AND user_id = $2")
+    let existing_query = "SELECT id, option_id FROM votes
+         WHERE poll_id = $1 AND user_id IS NOT DISTINCT FROM $2 AND voter_hash IS NOT DISTINCT FROM $3
+         FOR UPDATE";
+
+    let mut existing_vote = sqlx::query(existing_query)
         .bind(poll_id)
-        .bind(user_id)
+        .bind(voter.user_id())
+        .bind(voter.voter_hash())
         .fetch_optional(&mut *tx)
         .await?;
 
-    if existing_vote.is_some() {
+    if existing_vote.is_none() {
+        let inserted = sqlx::query(
+            "INSERT INTO votes (id, poll_id, option_id, user_id, voter_hash, write_in_text)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(Uuid::new_v4())
+        .bind(poll_id)
+        .bind(option_id)
+        .bind(voter.user_id())
+        .bind(voter.voter_hash())
+        .bind(write_in_text)
+        .execute(&mut *tx)
+        .await?;
+
+        if inserted.rows_affected() > 0 {
+            sqlx::query("UPDATE poll_options SET votes = votes + 1 WHERE id = $1")
+                .bind(option_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+            return Ok(VoteChange::Cast);
+        }
+
+        // A concurrent request inserted the vote first; change it instead.
+        existing_vote = sqlx::query(existing_query)
+            .bind(poll_id)
+            .bind(voter.user_id())
+            .bind(voter.voter_hash())
+            .fetch_optional(&mut *tx)
+            .await?;
+    }
+
+    let existing_vote = existing_vote.ok_or(Error::RowNotFound)?;
+    let vote_id: Uuid = existing_vote.get("id");
+    let previous_option_id: Option<Uuid> = existing_vote.get("option_id");
+
+    if previous_option_id == Some(option_id) {
         tx.rollback().await?;
-        return Err(sqlx::Error::RowNotFound);
+        return Ok(VoteChange::Unchanged);
+    }
+
+    if !allow_change {
+        tx.rollback().await?;
+        return Err(Error::RowNotFound);
+    }
+
+    if let Some(previous_option_id) = previous_option_id {
+        sqlx::query("UPDATE poll_options SET votes = votes - 1 WHERE id = $1")
+            .bind(previous_option_id)
+            .execute(&mut *tx)
+            .await?;
     }
 
-    let vote_id = Uuid::new_v4();
-    sqlx::query("INSERT INTO votes (id, poll_id, option_id, user_id) VALUES ($1, $2, $3, $4)")
+    sqlx::query("UPDATE votes SET option_id = $1, write_in_text = $2 WHERE id = $3")
+        .bind(option_id)
+        .bind(write_in_text)
         .bind(vote_id)
-        .bind(poll_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("UPDATE poll_options SET votes = votes + 1 WHERE id = $1")
         .bind(option_id)
-        .bind(user_id)
         .execute(&mut *tx)
         .await?;
 
+    tx.commit().await?;
+    Ok(VoteChange::Changed { previous_option_id })
+}
+
+// Removes every vote row the voter holds on the poll and returns the options
+// whose counters were decremented. Ranked ballots only count their first choice.
+pub async fn retract_vote(
+    pool: &DbPool,
+    poll_id: Uuid,
+    voter: Voter<'_>,
+) -> Result<Option<Vec<Uuid>>, Error> {
+    let mut tx = pool.begin().await?;
+
+    let deleted = sqlx::query(
+        "DELETE FROM votes
+         WHERE poll_id = $1 AND user_id IS NOT DISTINCT FROM $2 AND voter_hash IS NOT DISTINCT FROM $3
+         RETURNING option_id, rank",
+    )
+    .bind(poll_id)
+    .bind(voter.user_id())
+    .bind(voter.voter_hash())
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if deleted.is_empty() {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+
+    let option_ids: Vec<Uuid> = deleted
+        .iter()
+        .filter(|row| {
+            row.get::<Option<i32>, _>("rank")
+                .is_none_or(|rank| rank == 1)
+        })
+        .filter_map(|row| row.get::<Option<Uuid>, _>("option_id"))
+        .collect();
+
+    sqlx::query("UPDATE poll_options SET votes = votes - 1 WHERE id = ANY($1)")
+        .bind(&option_ids)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Some(option_ids))
+}
+
+pub enum MultiChoiceVote {
+    Cast,
+    AlreadyChosen,
+    LimitReached,
+}
+
+pub async fn cast_multi_choice_vote(
+    pool: &DbPool,
+    poll_id: Uuid,
+    option_id: Uuid,
+    voter: Voter<'_>,
+    write_in_text: Option<&str>,
+    max_choices: Option<i32>,
+) -> Result<MultiChoiceVote, Error> {
+    let mut tx = pool.begin().await?;
+
+    if let Some(max_choices) = max_choices {
+        // Serialise this voter's selections on the poll so concurrent requests
+        // can't both pass the limit check.
+        sqlx::query(
+            "SELECT pg_advisory_xact_lock(hashtextextended($1::text || COALESCE($2::text, $3), 0))",
+        )
+        .bind(poll_id)
+        .bind(voter.user_id())
+        .bind(voter.voter_hash())
+        .execute(&mut *tx)
+        .await?;
+
+        let chosen: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM votes
+             WHERE poll_id = $1 AND user_id IS NOT DISTINCT FROM $2 AND voter_hash IS NOT DISTINCT FROM $3",
+        )
+        .bind(poll_id)
+        .bind(voter.user_id())
+        .bind(voter.voter_hash())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if chosen >= max_choices as i64 {
+            tx.rollback().await?;
+            return Ok(MultiChoiceVote::LimitReached);
+        }
+    }
+
+    let inserted = sqlx::query(
+        "INSERT INTO votes (id, poll_id, option_id, user_id, voter_hash, write_in_text, multi_choice)
+         VALUES ($1, $2, $3, $4, $5, $6, TRUE)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(Uuid::new_v4())
+    .bind(poll_id)
+    .bind(option_id)
+    .bind(voter.user_id())
+    .bind(voter.voter_hash())
+    .bind(write_in_text)
+    .execute(&mut *tx)
+    .await?;
+
+    if inserted.rows_affected() == 0 {
+        tx.rollback().await?;
+        return Ok(MultiChoiceVote::AlreadyChosen);
+    }
+
     sqlx::query("UPDATE poll_options SET votes = votes + 1 WHERE id = $1")
         .bind(option_id)
         .execute(&mut *tx)
         .await?;
 
+    tx.commit().await?;
+    Ok(MultiChoiceVote::Cast)
+}
+
+// Ranked ballots are stored as one row per ranked option. Only the first
+// preference counts towards poll_options.votes; the full ranking is used for
+// instant-runoff tabulation.
+pub async fn cast_ranked_ballot(
+    pool: &DbPool,
+    poll_id: Uuid,
+    user_id: Uuid,
+    rankings: &[Uuid],
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    for (index, option_id) in rankings.iter().enumerate() {
+        let inserted = sqlx::query(
+            "INSERT INTO votes (id, poll_id, option_id, user_id, multi_choice, rank)
+             VALUES ($1, $2, $3, $4, TRUE, $5)
+             ON CONFLICT DO NOTHING",
+        )
+        .bind(Uuid::new_v4())
+        .bind(poll_id)
+        .bind(option_id)
+        .bind(user_id)
+        .bind(index as i32 + 1)
+        .execute(&mut *tx)
+        .await?;
+
+        if inserted.rows_affected() == 0 {
+            tx.rollback().await?;
+            return Err(Error::RowNotFound);
+        }
+    }
+
+    if let Some(first_choice) = rankings.first() {
+        sqlx::query("UPDATE poll_options SET votes = votes + 1 WHERE id = $1")
+            .bind(first_choice)
+            .execute(&mut *tx)
+            .await?;
+    }
+
     tx.commit().await?;
     Ok(())
 }
 
-pub async fn user_has_voted(pool: &DbPool, poll_id: Uuid, user_id: Uuid) -> Result<bool, Error> {
-    let row = sqlx::query("SELECT id FROM votes WHERE poll_id = $1 AND user_id = $2")
+pub async fn get_ranked_ballots(pool: &DbPool, poll_id: Uuid) -> Result<Vec<Vec<Uuid>>, Error> {
+    // Anonymous and anonymised ballots have no user_id; their rows are tied
+    // together by the voter hash instead.
+    let rows = sqlx::query(
+        "SELECT COALESCE(user_id::text, voter_hash) AS voter, option_id FROM votes
+         WHERE poll_id = $1 AND rank IS NOT NULL
+         ORDER BY voter, rank",
+    )
+    .bind(poll_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut ballots: Vec<Vec<Uuid>> = Vec::new();
+    let mut current_voter: Option<String> = None;
+
+    for row in rows {
+        let voter: String = row.get("voter");
+        if current_voter.as_ref() != Some(&voter) {
+            ballots.push(Vec::new());
+            current_voter = Some(voter);
+        }
+        if let Some(ballot) = ballots.last_mut() {
+            ballot.push(row.get("option_id"));
+        }
+    }
+
+    Ok(ballots)
+}
+
+pub async fn get_write_ins(pool: &DbPool, option_id: Uuid) -> Result<Vec<(String, i64)>, Error> {
+    sqlx::query_as(
+        "SELECT write_in_text, COUNT(*) FROM votes
+         WHERE option_id = $1 AND write_in_text IS NOT NULL
+         GROUP BY write_in_text
+         ORDER BY COUNT(*) DESC, write_in_text",
+    )
+    .bind(option_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub fn stream_poll_votes(
+    pool: &DbPool,
+    poll_id: Uuid,
+) -> BoxStream<'_, Result<VoteExportRow, Error>> {
+    sqlx::query_as::<_, VoteExportRow>(
+        "SELECT v.id, v.user_id, u.username, v.option_id, v.write_in_text, v.rank, v.created_at
+         FROM votes v
+         LEFT JOIN users u ON u.id = v.user_id
+         WHERE v.poll_id = $1
+         ORDER BY v.created_at, v.id",
+    )
+    .bind(poll_id)
+    .fetch(pool)
+}
+
+pub async fn cast_encrypted_vote(
+    pool: &DbPool,
+    poll_id: Uuid,
+    encrypted_ballot: &str,
+    user_id: Uuid,
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    let existing_vote = sqlx::query("SELECT id FROM votes WHERE poll_id = $1 AND user_id = $2")
         .bind(poll_id)
         .bind(user_id)
-        .fetch_optional(pool)
+        .fetch_optional(&mut *tx)
         .await?;
 
+    if existing_vote.is_some() {
+        tx.rollback().await?;
+        return Err(sqlx::Error::RowNotFound);
+    }
+
+    sqlx::query(
+        "INSERT INTO votes (id, poll_id, user_id, encrypted_ballot) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(Uuid::new_v4())
+    .bind(poll_id)
+    .bind(user_id)
+    .bind(encrypted_ballot)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn get_encrypted_ballots(pool: &DbPool, poll_id: Uuid) -> Result<Vec<String>, Error> {
+    let rows = sqlx::query(
+        "SELECT encrypted_ballot FROM votes WHERE poll_id = $1 AND encrypted_ballot IS NOT NULL",
+    )
+    .bind(poll_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| r.get::<String, _>("encrypted_ballot"))
+        .collect())
+}
+
+pub async fn get_voted_poll_ids(
+    pool: &DbPool,
+    user_id: Uuid,
+    poll_ids: &[Uuid],
+    voter_hashes: &[String],
+) -> Result<HashSet<Uuid>, Error> {
+    let voted: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT DISTINCT poll_id FROM votes
+         WHERE poll_id = ANY($2) AND (user_id = $1 OR voter_hash = ANY($3))",
+    )
+    .bind(user_id)
+    .bind(poll_ids)
+    .bind(voter_hashes)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(voted.into_iter().collect())
+}
+
+// Anonymous votes only carry the voter hash, so they are matched by
+// recomputing it from the voter key ($2) per anonymous poll the same way the
+// vote handler does.
+const USER_VOTES_CTE: &str = "WITH user_votes AS (
+         SELECT v.* FROM votes v WHERE v.user_id = $1
+         UNION ALL
+         SELECT v.* FROM polls p
+         JOIN votes v ON v.poll_id = p.id
+             AND v.voter_hash = encode(sha256(convert_to($2 || ':' || p.id::text, 'UTF8')), 'hex')
+         WHERE p.anonymous
+     )";
+
+pub async fn get_votes_for_user(
+    pool: &DbPool,
+    user_id: Uuid,
+    voter_key: &str,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<VoteHistoryEntry>, Error> {
+    let query = format!(
+        "{USER_VOTES_CTE}
+         SELECT v.id AS vote_id, p.id AS poll_id, p.title AS poll_title, v.option_id,
+                o.option_text, v.write_in_text, v.rank, v.created_at AS voted_at,
+                NOT (p.closed OR COALESCE(p.expires_at <= CURRENT_TIMESTAMP, FALSE)) AS poll_open
+         FROM user_votes v
+         JOIN polls p ON p.id = v.poll_id
+         LEFT JOIN poll_options o ON o.id = v.option_id
+         ORDER BY v.created_at DESC, v.id
+         LIMIT $3 OFFSET $4"
+    );
+
+    sqlx::query_as::<_, VoteHistoryEntry>(&query)
+        .bind(user_id)
+        .bind(voter_key)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn count_votes_for_user(
+    pool: &DbPool,
+    user_id: Uuid,
+    voter_key: &str,
+) -> Result<i64, Error> {
+    let query = format!("{USER_VOTES_CTE} SELECT COUNT(*) FROM user_votes");
+
+    sqlx::query_scalar(&query)
+        .bind(user_id)
+        .bind(voter_key)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn user_has_voted(
+    pool: &DbPool,
+    poll_id: Uuid,
+    voter: Voter<'_>,
+    option_id: Option<Uuid>,
+) -> Result<bool, Error> {
+    let row = sqlx::query(
+        "SELECT id FROM votes
+         WHERE poll_id = $1 AND user_id IS NOT DISTINCT FROM $2 AND voter_hash IS NOT DISTINCT FROM $3
+         AND ($4::uuid IS NULL OR option_id = $4)
+         LIMIT 1",
+    )
+    .bind(poll_id)
+    .bind(voter.user_id())
+    .bind(voter.voter_hash())
+    .bind(option_id)
+    .fetch_optional(pool)
+    .await?;
+
     Ok(row.is_some())
 }
+
+pub enum HistoryBucket {
+    Minute,
+    Hour,
+}
+
+impl HistoryBucket {
+    fn precision(&self) -> &'static str {
+        match self {
+            HistoryBucket::Minute => "minute",
+            HistoryBucket::Hour => "hour",
+        }
+    }
+}
+
+// Derived from the timestamps of the ballots currently stored, so retracted
+// votes drop out of the history along with the vote itself.
+pub async fn get_vote_counts_by_bucket(
+    pool: &DbPool,
+    poll_id: Uuid,
+    bucket: &HistoryBucket,
+) -> Result<Vec<(DateTime<Utc>, Uuid, i64)>, Error> {
+    let rows = sqlx::query(
+        "SELECT date_trunc($2, created_at) AS bucket, option_id, COUNT(*) AS votes
+         FROM votes
+         WHERE poll_id = $1 AND option_id IS NOT NULL AND (rank IS NULL OR rank = 1)
+         GROUP BY bucket, option_id
+         ORDER BY bucket",
+    )
+    .bind(poll_id)
+    .bind(bucket.precision())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.get("bucket"), r.get("option_id"), r.get("votes")))
+        .collect())
+}
+
+pub enum VoteCohort {
+    RegistrationDate(NaiveDate),
+    FirstVote,
+}
+
+pub async fn get_vote_breakdown(
+    pool: &DbPool,
+    poll_id: Uuid,
+    cohort: &VoteCohort,
+) -> Result<Vec<(Uuid, String, i64)>, Error> {
+    let rows = match cohort {
+        VoteCohort::RegistrationDate(boundary) => {
+            sqlx::query(
+                "SELECT v.option_id,
+                        CASE WHEN u.created_at < $2 THEN 'registered_before' ELSE 'registered_after' END AS cohort,
+                        COUNT(*) AS votes
+                 FROM votes v
+                 JOIN users u ON u.id = v.user_id
+                 WHERE v.poll_id = $1 AND (v.rank IS NULL OR v.rank = 1)
+                 GROUP BY v.option_id, cohort",
+            )
+            .bind(poll_id)
+            .bind(boundary)
+            .fetch_all(pool)
+            .await?
+        }
+        VoteCohort::FirstVote => {
+            sqlx::query(
+                "SELECT v.option_id,
+                        CASE WHEN EXISTS (
+                            SELECT 1 FROM votes prev
+                            WHERE prev.user_id = v.user_id AND prev.created_at < v.created_at
+                        ) THEN 'returning_voter' ELSE 'first_vote' END AS cohort,
+                        COUNT(*) AS votes
+                 FROM votes v
+                 WHERE v.poll_id = $1 AND (v.rank IS NULL OR v.rank = 1)
+                 GROUP BY v.option_id, cohort",
+            )
+            .bind(poll_id)
+            .fetch_all(pool)
+            .await?
+        }
+    };
+
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.get("option_id"), r.get("cohort"), r.get("votes")))
+        .collect())
+}
+
+// A ranked ballot's receipt goes on its first preference; multi-choice votes
+// get one per option.
+pub async fn store_vote_receipt(
+    pool: &DbPool,
+    poll_id: Uuid,
+    option_id: Option<Uuid>,
+    voter: Voter<'_>,
+    receipt: &str,
+    issued_at: DateTime<Utc>,
+) -> Result<bool, Error> {
+    let result = sqlx::query(
+        "UPDATE votes SET receipt = $5, receipt_issued_at = $6
+         WHERE poll_id = $1
+           AND option_id IS NOT DISTINCT FROM $2
+           AND user_id IS NOT DISTINCT FROM $3
+           AND voter_hash IS NOT DISTINCT FROM $4
+           AND (rank IS NULL OR rank = 1)",
+    )
+    .bind(poll_id)
+    .bind(option_id)
+    .bind(voter.user_id())
+    .bind(voter.voter_hash())
+    .bind(receipt)
+    .bind(issued_at)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub struct ReceiptedVote {
+    pub option_id: Option<Uuid>,
+    pub user_id: Option<Uuid>,
+    pub voter_hash: Option<String>,
+    pub issued_at: DateTime<Utc>,
+}
+
+pub async fn get_receipted_vote(
+    pool: &DbPool,
+    poll_id: Uuid,
+    receipt: &str,
+) -> Result<Option<ReceiptedVote>, Error> {
+    let row = sqlx::query(
+        "SELECT option_id, user_id, voter_hash, receipt_issued_at FROM votes
+         WHERE poll_id = $1 AND receipt = $2",
+    )
+    .bind(poll_id)
+    .bind(receipt)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|r| ReceiptedVote {
+        option_id: r.get("option_id"),
+        user_id: r.get("user_id"),
+        voter_hash: r.get("voter_hash"),
+        issued_at: r.get("receipt_issued_at"),
+    }))
+}
+
+// Polls created before voter keys hashed anonymous ballots with a salt kept
+// on the poll row. A non-null salt marks a poll whose ballots still need
+// moving over.
+pub async fn has_legacy_voter_hashes(pool: &DbPool) -> Result<bool, Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM polls WHERE voter_salt IS NOT NULL)")
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn get_user_ids(pool: &DbPool) -> Result<Vec<Uuid>, Error> {
+    sqlx::query_scalar("SELECT id FROM users")
+        .fetch_all(pool)
+        .await
+}
+
+// Rehashes every legacy anonymous ballot whose salted hash matches one of the
+// given users, then drops the salts. Ballots matching nobody, like those of
+// deleted accounts, keep the hash they have.
+pub async fn rekey_legacy_voter_hashes(
+    pool: &DbPool,
+    user_ids: &[Uuid],
+    voter_keys: &[String],
+) -> Result<u64, Error> {
+    let mut tx = pool.begin().await?;
+
+    let rekeyed = sqlx::query(
+        "UPDATE votes v
+         SET voter_hash = encode(sha256(convert_to(k.voter_key || ':' || p.id::text, 'UTF8')), 'hex')
+         FROM polls p, UNNEST($1::uuid[], $2::text[]) AS k(user_id, voter_key)
+         WHERE p.id = v.poll_id
+           AND p.voter_salt IS NOT NULL
+           AND v.voter_hash = encode(sha256(convert_to(p.voter_salt || ':' || k.user_id::text, 'UTF8')), 'hex')",
+    )
+    .bind(user_ids)
+    .bind(voter_keys)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query("UPDATE polls SET voter_salt = NULL WHERE voter_salt IS NOT NULL")
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(rekeyed)
+}
@@ -0,0 +1,199 @@
+use crate::db::connection::DbPool;
+use crate::db::models::{PendingWebhookDelivery, Webhook, WebhookDelivery};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::Error;
+use uuid::Uuid;
+
+const WEBHOOK_COLUMNS: &str = "id, owner_id, poll_id, url, events, created_at";
+
+pub async fn create_webhook(
+    pool: &DbPool,
+    owner_id: Uuid,
+    poll_id: Option<Uuid>,
+    url: &str,
+    secret: &str,
+    events: &[String],
+) -> Result<Webhook, Error> {
+    sqlx::query_as::<_, Webhook>(&format!(
+        "INSERT INTO webhooks (id, owner_id, poll_id, url, secret, events)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING {WEBHOOK_COLUMNS}"
+    ))
+    .bind(Uuid::new_v4())
+    .bind(owner_id)
+    .bind(poll_id)
+    .bind(url)
+    .bind(secret)
+    .bind(events)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn count_user_webhooks(pool: &DbPool, owner_id: Uuid) -> Result<i64, Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM webhooks WHERE owner_id = $1")
+        .bind(owner_id)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn list_user_webhooks(pool: &DbPool, owner_id: Uuid) -> Result<Vec<Webhook>, Error> {
+    sqlx::query_as::<_, Webhook>(&format!(
+        "SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE owner_id = $1 ORDER BY created_at DESC"
+    ))
+    .bind(owner_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_webhook(pool: &DbPool, webhook_id: Uuid) -> Result<Option<Webhook>, Error> {
+    sqlx::query_as::<_, Webhook>(&format!(
+        "SELECT {WEBHOOK_COLUMNS} FROM webhooks WHERE id = $1"
+    ))
+    .bind(webhook_id)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn delete_webhook(
+    pool: &DbPool,
+    webhook_id: Uuid,
+    owner_id: Uuid,
+) -> Result<bool, Error> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1 AND owner_id = $2")
+        .bind(webhook_id)
+        .bind(owner_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Fans an event out to every webhook the poll's creator registered for it,
+// either for this poll or for all of their polls.
+pub async fn enqueue_webhook_deliveries(
+    pool: &DbPool,
+    poll_id: Uuid,
+    event_type: &str,
+    payload: &Value,
+) -> Result<u64, Error> {
+    let result = sqlx::query(
+        "INSERT INTO webhook_deliveries (id, webhook_id, event_type, payload)
+         SELECT gen_random_uuid(), w.id, $2, $3
+         FROM webhooks w
+         JOIN polls p ON p.creator_id = w.owner_id
+         WHERE p.id = $1
+           AND (w.poll_id IS NULL OR w.poll_id = $1)
+           AND $2 = ANY(w.events)",
+    )
+    .bind(poll_id)
+    .bind(event_type)
+    .bind(payload)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Claiming counts as an attempt and pushes the delivery past the lease, so a
+// worker that dies mid-request leaves it to be retried rather than stuck.
+pub async fn claim_due_webhook_deliveries(
+    pool: &DbPool,
+    limit: i64,
+    lease_secs: f64,
+) -> Result<Vec<PendingWebhookDelivery>, Error> {
+    sqlx::query_as::<_, PendingWebhookDelivery>(
+        "UPDATE webhook_deliveries d
+         SET attempts = d.attempts + 1,
+             next_attempt_at = NOW() + make_interval(secs => $2)
+         FROM webhooks w
+         WHERE w.id = d.webhook_id
+           AND d.id IN (
+               SELECT id FROM webhook_deliveries
+               WHERE status = 'pending' AND next_attempt_at <= NOW()
+               ORDER BY next_attempt_at
+               LIMIT $1
+               FOR UPDATE SKIP LOCKED
+           )
+         RETURNING d.id, d.event_type, d.payload, d.attempts, w.url, w.secret",
+    )
+    .bind(limit)
+    .bind(lease_secs)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn mark_webhook_delivered(
+    pool: &DbPool,
+    delivery_id: Uuid,
+    status_code: i32,
+) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = 'delivered', delivered_at = NOW(), last_status_code = $2, last_error = NULL
+         WHERE id = $1",
+    )
+    .bind(delivery_id)
+    .bind(status_code)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Without a retry time the delivery is given up on.
+pub async fn mark_webhook_attempt_failed(
+    pool: &DbPool,
+    delivery_id: Uuid,
+    status_code: Option<i32>,
+    error: &str,
+    retry_at: Option<DateTime<Utc>>,
+) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = CASE WHEN $4::timestamptz IS NULL THEN 'failed' ELSE 'pending' END,
+             next_attempt_at = COALESCE($4, next_attempt_at),
+             last_status_code = $2,
+             last_error = $3
+         WHERE id = $1",
+    )
+    .bind(delivery_id)
+    .bind(status_code)
+    .bind(error)
+    .bind(retry_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn list_webhook_deliveries(
+    pool: &DbPool,
+    webhook_id: Uuid,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, Error> {
+    sqlx::query_as::<_, WebhookDelivery>(
+        "SELECT id, webhook_id, event_type, payload, status, attempts, next_attempt_at,
+                last_status_code, last_error, created_at, delivered_at
+         FROM webhook_deliveries
+         WHERE webhook_id = $1
+         ORDER BY created_at DESC
+         LIMIT $2",
+    )
+    .bind(webhook_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn purge_finished_webhook_deliveries(pool: &DbPool, days: i32) -> Result<u64, Error> {
+    let result = sqlx::query(
+        "DELETE FROM webhook_deliveries
+         WHERE status <> 'pending' AND created_at < NOW() - make_interval(days => $1)",
+    )
+    .bind(days)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,149 @@
+use crate::auth::{BearerAuth, hash_token};
+use crate::db;
+use crate::error::PollError;
+use crate::mailer::{Email, send_in_background};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{Duration as ChronoDuration, Utc};
+use lettre::Address;
+use openssl::rand::rand_bytes;
+use serde::Deserialize;
+use serde_json::json;
+use tracing::{error, info};
+use uuid::Uuid;
+
+const MAX_EMAIL_LENGTH: usize = 254;
+const VERIFICATION_RESEND_COOLDOWN_SECS: i64 = 60;
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+pub fn normalize_email(email: &str) -> Option<String> {
+    let email = email.trim();
+
+    if email.len() > MAX_EMAIL_LENGTH || email.parse::<Address>().is_err() {
+        return None;
+    }
+
+    Some(email.to_string())
+}
+
+// Each send is a message to an address the caller chose, so sends are spaced
+// out per account.
+pub async fn check_verification_cooldown(
+    app_state: &AppState,
+    user_id: Uuid,
+) -> Result<(), PollError> {
+    let last_sent = db::last_verification_sent_at(&app_state.db, user_id)
+        .await
+        .map_err(PollError::from)?;
+
+    if let Some(last_sent) = last_sent {
+        let elapsed = (Utc::now() - last_sent).num_seconds();
+        if elapsed < VERIFICATION_RESEND_COOLDOWN_SECS {
+            return Err(PollError::RateLimited(
+                (VERIFICATION_RESEND_COOLDOWN_SECS - elapsed).max(1) as u64,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn send_verification_email(
+    app_state: &AppState,
+    user_id: Uuid,
+    username: &str,
+    email: &str,
+) -> Result<(), PollError> {
+    let mut bytes = [0u8; 32];
+    rand_bytes(&mut bytes).map_err(|e| {
+        error!("Error generating verification token: {:?}", e);
+        PollError::VerificationTokenError
+    })?;
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+
+    let ttl_hours = app_state.config.email_verification_ttl_hours;
+    db::create_email_verification_token(
+        &app_state.db,
+        user_id,
+        email,
+        &hash_token(&token),
+        Utc::now() + ChronoDuration::hours(ttl_hours),
+    )
+    .await
+    .map_err(PollError::from)?;
+
+    let link = format!(
+        "{}/verify-email?token={}",
+        app_state.config.frontend_url.as_str().trim_end_matches('/'),
+        token
+    );
+
+    send_in_background(
+        &app_state.mailer,
+        Email {
+            to: email.to_string(),
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Hi {},\n\nConfirm this address for your Polling App account by opening:\n\n{}\n\nThe link expires in {} hours. If you didn't ask for this, you can ignore this email.\n",
+                username, link, ttl_hours
+            ),
+        },
+    );
+
+    Ok(())
+}
+
+pub async fn verify_email(
+    Extension(app_state): Extension<AppState>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let verified = db::consume_email_verification_token(&app_state.db, &hash_token(&payload.token))
+        .await
+        .map_err(|e| match &e {
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => PollError::EmailInUse,
+            _ => PollError::from(e),
+        })?;
+
+    let (user_id, email) = verified.ok_or(PollError::InvalidVerificationToken)?;
+
+    info!("User {} verified their email address", user_id);
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "verified": true,
+            "email": email,
+        })),
+    ))
+}
+
+pub async fn resend_verification_email(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let profile = db::get_user_profile(&app_state.db, user_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::UserNotFound)?;
+
+    let email = profile.email.ok_or(PollError::EmailNotSet)?;
+    if profile.email_verified_at.is_some() {
+        return Err(PollError::EmailAlreadyVerified);
+    }
+
+    check_verification_cooldown(&app_state, user_id).await?;
+    send_verification_email(&app_state, user_id, &profile.username, &email).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
@@ -1,10 +1,14 @@
+use crate::request_id;
+use crate::validation::FieldError;
 use axum::{
     Json,
-    http::StatusCode,
+    http::{StatusCode, header::RETRY_AFTER},
     response::{IntoResponse, Response},
 };
-use serde_json::json;
+use serde::Serialize;
+use serde_json::{Value, json};
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Error, Debug)]
 pub enum WebauthnError {
@@ -20,10 +24,40 @@ pub enum WebauthnError {
     Unauthorized,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Token has been revoked")]
+    TokenRevoked,
     #[error("Token creation error")]
     TokenCreationError,
     #[error("User already exists")]
     UserAlreadyExists,
+    #[error("Invalid username")]
+    InvalidUsername,
+    #[error("Invalid email address")]
+    InvalidEmail,
+    #[error("Credential not found")]
+    CredentialNotFound,
+    #[error("Cannot remove the last remaining credential")]
+    LastCredential,
+    #[error("User is banned")]
+    UserBanned,
+    #[error("Credential may have been cloned")]
+    PossibleClonedCredential,
+    #[error("Authenticator does not meet the attestation policy")]
+    AuthenticatorNotAllowed,
+    #[error("Missing or invalid CSRF token")]
+    CsrfTokenInvalid,
+    #[error("Too many requests, retry in {0} seconds")]
+    RateLimited(u64),
+    #[error("API key does not allow this request")]
+    InsufficientScope,
+    #[error("OpenID Connect sign-in is not configured")]
+    OidcNotConfigured,
+    #[error("Identity provider request failed")]
+    OidcProviderError,
+    #[error("Identity provider returned an invalid ID token")]
+    OidcTokenInvalid,
+    #[error("External identity is linked to another account")]
+    IdentityInUse,
 }
 
 #[derive(Error, Debug)]
@@ -36,65 +70,578 @@ pub enum PollError {
     PollNotFound,
     #[error("Poll option not found")]
     OptionNotFound,
+    #[error("Poll is not tagged with this tag")]
+    TagNotOnPoll,
+    #[error("Survey not found")]
+    SurveyNotFound,
     #[error("Poll is closed")]
     PollClosed,
+    #[error("Poll is not open for voting yet")]
+    PollNotOpen,
     #[error("User already voted on this poll")]
     AlreadyVoted,
+    #[error("User has not voted on this poll")]
+    VoteNotFound,
+    #[error("Maximum number of choices already selected")]
+    ChoiceLimitReached,
+    #[error("Poll has reached its limit of written-in options")]
+    WriteInLimitReached,
+    #[error("Options cannot be removed once voting has started")]
+    PollHasVotes,
+    #[error("Poll must be closed before it can be tallied")]
+    PollStillOpen,
+    #[error("Results are hidden until you vote or the poll closes")]
+    ResultsHidden,
+    #[error("Poll has already been tallied")]
+    AlreadyTallied,
+    #[error("User already reported this poll")]
+    AlreadyReported,
+    #[error("Report not found")]
+    ReportNotFound,
+    #[error("Comment not found")]
+    CommentNotFound,
+    #[error("User is not permitted to create polls")]
+    CreationNotPermitted,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Webhook not found")]
+    WebhookNotFound,
+    #[error("Webhook limit reached")]
+    WebhookLimitReached,
+    #[error("Failed to generate webhook secret")]
+    WebhookSecretError,
+    #[error("API key not found")]
+    ApiKeyNotFound,
+    #[error("API key limit reached")]
+    ApiKeyLimitReached,
+    #[error("Failed to generate API key")]
+    ApiKeyGenerationError,
+    #[error("Invalid email address")]
+    InvalidEmail,
+    #[error("No email address is set")]
+    EmailNotSet,
+    #[error("Email address is already verified")]
+    EmailAlreadyVerified,
+    #[error("Email address is verified by another account")]
+    EmailInUse,
+    #[error("Verification token is invalid or expired")]
+    InvalidVerificationToken,
+    #[error("Failed to create verification token")]
+    VerificationTokenError,
+    #[error("Failed to generate QR code")]
+    QrCodeError,
+    #[error("No counted vote matches this receipt")]
+    ReceiptNotFound,
+    #[error("Failed to create vote receipt")]
+    ReceiptCreationError,
+    #[error("Failed to create invite token")]
+    InviteCreationError,
+    #[error("Vote link is invalid, expired or already used")]
+    InvalidVoteLink,
+    #[error("Poll requires an access code")]
+    AccessCodeRequired,
+    #[error("Access code is incorrect")]
+    InvalidAccessCode,
+    #[error("Failed to secure access code")]
+    AccessCodeHashError,
+    #[error("{} field(s) are invalid", .0.len())]
+    Validation(Vec<FieldError>),
+    #[error("{} poll(s) in the batch are invalid", .0.len())]
+    InvalidBatch(Vec<(usize, PollError)>),
+    #[error("Rate limited, retry after {0} seconds")]
+    RateLimited(u64),
     #[error("Database error: {0}")]
     DatabaseError(String),
+    #[error("Database query timed out")]
+    DatabaseTimeout,
 }
 
-impl IntoResponse for WebauthnError {
+// Shared response shape for both error enums. `code` is stable for clients
+// to branch on; `error` and `message` are for people.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub code: &'static str,
+    pub error: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    error: &'static str,
+    message: String,
+    details: Option<Value>,
+    retry_after: Option<u64>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, code: &'static str, error: &'static str, message: String) -> Self {
+        Self {
+            status,
+            code,
+            error,
+            message,
+            details: None,
+            retry_after: None,
+        }
+    }
+
+    fn details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    fn retry_after(mut self, seconds: u64) -> Self {
+        self.details = Some(json!({ "retry_after_secs": seconds }));
+        self.retry_after = Some(seconds);
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match &self {
-            WebauthnError::Unknown => (StatusCode::INTERNAL_SERVER_ERROR, "Unknown error"),
-            WebauthnError::CorruptSession => (StatusCode::BAD_REQUEST, "Corrupt session"),
-            WebauthnError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+        let body = ErrorResponse {
+            code: self.code,
+            error: self.error,
+            message: self.message,
+            details: self.details,
+            request_id: request_id::current(),
+        };
+
+        if let Some(retry_after) = self.retry_after {
+            return (
+                self.status,
+                [(RETRY_AFTER, retry_after.to_string())],
+                Json(body),
+            )
+                .into_response();
+        }
+
+        (self.status, Json(body)).into_response()
+    }
+}
+
+// gRPC callers get the nearest status code, with the same error code and
+// details the HTTP API would have sent carried in metadata.
+#[cfg(feature = "grpc")]
+impl From<ApiError> for tonic::Status {
+    fn from(error: ApiError) -> Self {
+        let code = match error.status {
+            StatusCode::BAD_REQUEST => tonic::Code::InvalidArgument,
+            StatusCode::UNAUTHORIZED => tonic::Code::Unauthenticated,
+            StatusCode::FORBIDDEN => tonic::Code::PermissionDenied,
+            StatusCode::NOT_FOUND => tonic::Code::NotFound,
+            StatusCode::CONFLICT => tonic::Code::AlreadyExists,
+            StatusCode::TOO_MANY_REQUESTS => tonic::Code::ResourceExhausted,
+            StatusCode::SERVICE_UNAVAILABLE => tonic::Code::Unavailable,
+            _ => tonic::Code::Internal,
+        };
+
+        let mut status = tonic::Status::new(code, error.message);
+        let metadata = status.metadata_mut();
+        metadata.insert(
+            "x-error-code",
+            tonic::metadata::MetadataValue::from_static(error.code),
+        );
+        if let Some(details) = error.details
+            && let Ok(value) = details.to_string().parse()
+        {
+            metadata.insert("x-error-details", value);
+        }
+
+        status
+    }
+}
+
+impl From<WebauthnError> for ApiError {
+    fn from(error: WebauthnError) -> Self {
+        let (status, code, error_message) = match &error {
+            WebauthnError::Unknown => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "INTERNAL_ERROR",
+                "Unknown error",
+            ),
+            WebauthnError::CorruptSession => (
+                StatusCode::BAD_REQUEST,
+                "CORRUPT_SESSION",
+                "Corrupt session",
+            ),
+            WebauthnError::UserNotFound => {
+                (StatusCode::NOT_FOUND, "USER_NOT_FOUND", "User not found")
+            }
             WebauthnError::UserHasNoCredentials => (
                 StatusCode::BAD_REQUEST,
+                "NO_CREDENTIALS",
                 "User has no registered credentials",
             ),
-            WebauthnError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
-            WebauthnError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
-            WebauthnError::TokenCreationError => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create token")
+            WebauthnError::Unauthorized => {
+                (StatusCode::UNAUTHORIZED, "UNAUTHORIZED", "Unauthorized")
             }
-            WebauthnError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
+            WebauthnError::InvalidToken => {
+                (StatusCode::UNAUTHORIZED, "INVALID_TOKEN", "Invalid token")
+            }
+            WebauthnError::TokenRevoked => (
+                StatusCode::UNAUTHORIZED,
+                "TOKEN_REVOKED",
+                "Token has been revoked",
+            ),
+            WebauthnError::TokenCreationError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "TOKEN_CREATION_FAILED",
+                "Failed to create token",
+            ),
+            WebauthnError::UserAlreadyExists => {
+                (StatusCode::CONFLICT, "USER_EXISTS", "User already exists")
+            }
+            WebauthnError::InvalidUsername => (
+                StatusCode::BAD_REQUEST,
+                "INVALID_USERNAME",
+                "Invalid username",
+            ),
+            WebauthnError::InvalidEmail => (
+                StatusCode::BAD_REQUEST,
+                "INVALID_EMAIL",
+                "Invalid email address",
+            ),
+            WebauthnError::CredentialNotFound => (
+                StatusCode::NOT_FOUND,
+                "CREDENTIAL_NOT_FOUND",
+                "Credential not found",
+            ),
+            WebauthnError::LastCredential => (
+                StatusCode::BAD_REQUEST,
+                "LAST_CREDENTIAL",
+                "Cannot remove the last remaining credential",
+            ),
+            WebauthnError::UserBanned => (StatusCode::FORBIDDEN, "USER_BANNED", "User is banned"),
+            WebauthnError::PossibleClonedCredential => (
+                StatusCode::FORBIDDEN,
+                "CREDENTIAL_CLONED",
+                "Credential may have been cloned",
+            ),
+            WebauthnError::AuthenticatorNotAllowed => (
+                StatusCode::FORBIDDEN,
+                "AUTHENTICATOR_NOT_ALLOWED",
+                "Authenticator does not meet the attestation policy",
+            ),
+            WebauthnError::CsrfTokenInvalid => (
+                StatusCode::FORBIDDEN,
+                "CSRF_TOKEN_INVALID",
+                "Missing or invalid CSRF token",
+            ),
+            WebauthnError::RateLimited(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "RATE_LIMITED",
+                "Too many requests",
+            ),
+            WebauthnError::InsufficientScope => (
+                StatusCode::FORBIDDEN,
+                "INSUFFICIENT_SCOPE",
+                "API key does not allow this request",
+            ),
+            WebauthnError::OidcNotConfigured => (
+                StatusCode::NOT_FOUND,
+                "OIDC_NOT_CONFIGURED",
+                "OpenID Connect sign-in is not configured",
+            ),
+            WebauthnError::OidcProviderError => (
+                StatusCode::BAD_GATEWAY,
+                "OIDC_PROVIDER_ERROR",
+                "Identity provider request failed",
+            ),
+            WebauthnError::OidcTokenInvalid => (
+                StatusCode::UNAUTHORIZED,
+                "OIDC_TOKEN_INVALID",
+                "Identity provider returned an invalid ID token",
+            ),
+            WebauthnError::IdentityInUse => (
+                StatusCode::CONFLICT,
+                "IDENTITY_IN_USE",
+                "External identity is linked to another account",
+            ),
         };
 
-        let body = Json(json!({
-            "error": error_message,
-            "details": self.to_string()
-        }));
+        let api_error = ApiError::new(status, code, error_message, error.to_string());
 
-        (status, body).into_response()
+        match error {
+            WebauthnError::RateLimited(retry_after) => api_error.retry_after(retry_after),
+            _ => api_error,
+        }
     }
 }
 
-impl IntoResponse for PollError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match &self {
-            PollError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
-            PollError::InvalidRequest => (StatusCode::BAD_REQUEST, "Invalid request"),
-            PollError::PollNotFound => (StatusCode::NOT_FOUND, "Poll not found"),
-            PollError::OptionNotFound => (StatusCode::NOT_FOUND, "Poll option not found"),
-            PollError::PollClosed => (StatusCode::BAD_REQUEST, "Poll is closed"),
-            PollError::AlreadyVoted => (StatusCode::CONFLICT, "User already voted on this poll"),
-            PollError::DatabaseError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg.as_str()),
+impl From<PollError> for ApiError {
+    fn from(error: PollError) -> Self {
+        let (status, code, error_message) = match &error {
+            PollError::Unauthorized => (StatusCode::UNAUTHORIZED, "UNAUTHORIZED", "Unauthorized"),
+            PollError::InvalidRequest => (
+                StatusCode::BAD_REQUEST,
+                "INVALID_REQUEST",
+                "Invalid request",
+            ),
+            PollError::PollNotFound => (StatusCode::NOT_FOUND, "POLL_NOT_FOUND", "Poll not found"),
+            PollError::OptionNotFound => (
+                StatusCode::NOT_FOUND,
+                "OPTION_NOT_FOUND",
+                "Poll option not found",
+            ),
+            PollError::TagNotOnPoll => (
+                StatusCode::BAD_REQUEST,
+                "TAG_NOT_ON_POLL",
+                "Poll is not tagged with this tag",
+            ),
+            PollError::SurveyNotFound => (
+                StatusCode::NOT_FOUND,
+                "SURVEY_NOT_FOUND",
+                "Survey not found",
+            ),
+            PollError::PollClosed => (StatusCode::BAD_REQUEST, "POLL_CLOSED", "Poll is closed"),
+            PollError::PollNotOpen => (
+                StatusCode::BAD_REQUEST,
+                "POLL_NOT_OPEN",
+                "Poll is not open for voting yet",
+            ),
+            PollError::AlreadyVoted => (
+                StatusCode::CONFLICT,
+                "ALREADY_VOTED",
+                "User already voted on this poll",
+            ),
+            PollError::VoteNotFound => (
+                StatusCode::NOT_FOUND,
+                "VOTE_NOT_FOUND",
+                "User has not voted on this poll",
+            ),
+            PollError::ChoiceLimitReached => (
+                StatusCode::CONFLICT,
+                "CHOICE_LIMIT_REACHED",
+                "Maximum number of choices already selected",
+            ),
+            PollError::WriteInLimitReached => (
+                StatusCode::CONFLICT,
+                "WRITE_IN_LIMIT_REACHED",
+                "Write-in limit reached",
+            ),
+            PollError::PollHasVotes => (
+                StatusCode::CONFLICT,
+                "POLL_HAS_VOTES",
+                "Options cannot be removed once voting has started",
+            ),
+            PollError::ResultsHidden => (
+                StatusCode::FORBIDDEN,
+                "RESULTS_HIDDEN",
+                "Results are hidden",
+            ),
+            PollError::PollStillOpen => (
+                StatusCode::BAD_REQUEST,
+                "POLL_STILL_OPEN",
+                "Poll must be closed before it can be tallied",
+            ),
+            PollError::AlreadyTallied => (
+                StatusCode::CONFLICT,
+                "ALREADY_TALLIED",
+                "Poll has already been tallied",
+            ),
+            PollError::AlreadyReported => (
+                StatusCode::CONFLICT,
+                "ALREADY_REPORTED",
+                "User already reported this poll",
+            ),
+            PollError::ReportNotFound => (
+                StatusCode::NOT_FOUND,
+                "REPORT_NOT_FOUND",
+                "Report not found",
+            ),
+            PollError::CommentNotFound => (
+                StatusCode::NOT_FOUND,
+                "COMMENT_NOT_FOUND",
+                "Comment not found",
+            ),
+            PollError::CreationNotPermitted => (
+                StatusCode::FORBIDDEN,
+                "CREATION_NOT_PERMITTED",
+                "User is not permitted to create polls",
+            ),
+            PollError::UserNotFound => (StatusCode::NOT_FOUND, "USER_NOT_FOUND", "User not found"),
+            PollError::WebhookNotFound => (
+                StatusCode::NOT_FOUND,
+                "WEBHOOK_NOT_FOUND",
+                "Webhook not found",
+            ),
+            PollError::WebhookLimitReached => (
+                StatusCode::CONFLICT,
+                "WEBHOOK_LIMIT_REACHED",
+                "Webhook limit reached",
+            ),
+            PollError::AccessCodeRequired => (
+                StatusCode::FORBIDDEN,
+                "ACCESS_CODE_REQUIRED",
+                "Poll requires an access code",
+            ),
+            PollError::InvalidAccessCode => (
+                StatusCode::FORBIDDEN,
+                "INVALID_ACCESS_CODE",
+                "Access code is incorrect",
+            ),
+            PollError::AccessCodeHashError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "ACCESS_CODE_HASH_FAILED",
+                "Failed to secure access code",
+            ),
+            PollError::WebhookSecretError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "WEBHOOK_SECRET_FAILED",
+                "Failed to generate webhook secret",
+            ),
+            PollError::ApiKeyNotFound => (
+                StatusCode::NOT_FOUND,
+                "API_KEY_NOT_FOUND",
+                "API key not found",
+            ),
+            PollError::ApiKeyLimitReached => (
+                StatusCode::CONFLICT,
+                "API_KEY_LIMIT_REACHED",
+                "API key limit reached",
+            ),
+            PollError::ApiKeyGenerationError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "API_KEY_GENERATION_FAILED",
+                "Failed to generate API key",
+            ),
+            PollError::InvalidEmail => (
+                StatusCode::BAD_REQUEST,
+                "INVALID_EMAIL",
+                "Invalid email address",
+            ),
+            PollError::EmailNotSet => (
+                StatusCode::CONFLICT,
+                "EMAIL_NOT_SET",
+                "No email address is set",
+            ),
+            PollError::EmailAlreadyVerified => (
+                StatusCode::CONFLICT,
+                "EMAIL_ALREADY_VERIFIED",
+                "Email address is already verified",
+            ),
+            PollError::EmailInUse => (
+                StatusCode::CONFLICT,
+                "EMAIL_IN_USE",
+                "Email address is verified by another account",
+            ),
+            PollError::InvalidVerificationToken => (
+                StatusCode::BAD_REQUEST,
+                "INVALID_VERIFICATION_TOKEN",
+                "Verification token is invalid or expired",
+            ),
+            PollError::VerificationTokenError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "VERIFICATION_TOKEN_FAILED",
+                "Failed to create verification token",
+            ),
+            PollError::QrCodeError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "QR_CODE_FAILED",
+                "Failed to generate QR code",
+            ),
+            PollError::ReceiptNotFound => (
+                StatusCode::NOT_FOUND,
+                "RECEIPT_NOT_FOUND",
+                "Receipt not found",
+            ),
+            PollError::ReceiptCreationError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "RECEIPT_CREATION_FAILED",
+                "Failed to create vote receipt",
+            ),
+            PollError::InviteCreationError => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "INVITE_CREATION_FAILED",
+                "Failed to create invite token",
+            ),
+            PollError::InvalidVoteLink => (
+                StatusCode::BAD_REQUEST,
+                "INVALID_VOTE_LINK",
+                "Vote link is invalid, expired or already used",
+            ),
+            PollError::Validation(_) => (
+                StatusCode::BAD_REQUEST,
+                "VALIDATION_FAILED",
+                "Invalid fields",
+            ),
+            PollError::InvalidBatch(_) => {
+                (StatusCode::BAD_REQUEST, "INVALID_BATCH", "Invalid batch")
+            }
+            PollError::RateLimited(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "RATE_LIMITED",
+                "Too many requests",
+            ),
+            PollError::DatabaseError(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "DATABASE_ERROR",
+                "Database error",
+            ),
+            PollError::DatabaseTimeout => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "DATABASE_TIMEOUT",
+                "Database query timed out",
+            ),
         };
 
-        let body = Json(json!({
-            "error": error_message,
-            "details": self.to_string()
-        }));
+        let api_error = ApiError::new(status, code, error_message, error.to_string());
+
+        match error {
+            PollError::RateLimited(retry_after) => api_error.retry_after(retry_after),
+            PollError::Validation(errors) => api_error.details(json!({ "fields": errors })),
+            PollError::InvalidBatch(errors) => {
+                let errors: Vec<Value> = errors
+                    .into_iter()
+                    .map(|(index, error)| {
+                        let item = ApiError::from(error);
+                        let mut entry = json!({
+                            "index": index,
+                            "code": item.code,
+                            "message": item.message,
+                        });
+                        if let Some(fields) = item.details.and_then(|d| d.get("fields").cloned()) {
+                            entry["fields"] = fields;
+                        }
+                        entry
+                    })
+                    .collect();
+                api_error.details(json!({ "errors": errors }))
+            }
+            _ => api_error,
+        }
+    }
+}
+
+impl IntoResponse for WebauthnError {
+    fn into_response(self) -> Response {
+        ApiError::from(self).into_response()
+    }
+}
 
-        (status, body).into_response()
+impl IntoResponse for PollError {
+    fn into_response(self) -> Response {
+        ApiError::from(self).into_response()
     }
 }
 
+const QUERY_CANCELED: &str = "57014";
+
 impl From<sqlx::Error> for PollError {
     fn from(error: sqlx::Error) -> Self {
-        PollError::DatabaseError(error.to_string())
+        match &error {
+            sqlx::Error::PoolTimedOut => PollError::DatabaseTimeout,
+            sqlx::Error::Database(e) if e.code().as_deref() == Some(QUERY_CANCELED) => {
+                PollError::DatabaseTimeout
+            }
+            _ => PollError::DatabaseError(error.to_string()),
+        }
     }
 }
 
@@ -0,0 +1,73 @@
+use axum::{
+    Json,
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+    },
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use openssl::sha::sha256;
+use serde::Serialize;
+use serde_json::Value;
+
+// Responses are per viewer, so shared caches must not keep them, and clients
+// have to revalidate before reusing one.
+const CACHE_POLICY: &str = "private, no-cache";
+
+// `server_time` is stamped on every response and says nothing about the poll,
+// so it's left out of the hash; otherwise no two responses would ever match.
+fn strip_server_time(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.remove("server_time");
+            map.values_mut().for_each(strip_server_time);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_server_time),
+        _ => {}
+    }
+}
+
+fn etag_for(value: &Value) -> String {
+    let mut hashed = value.clone();
+    strip_server_time(&mut hashed);
+    let digest = sha256(hashed.to_string().as_bytes());
+
+    format!("\"{}\"", URL_SAFE_NO_PAD.encode(&digest[..16]))
+}
+
+// Weak comparison, as RFC 9110 asks for If-None-Match.
+fn matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|candidate| {
+            candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+        })
+}
+
+// Answers with 304 and no body when the client already holds this exact
+// response.
+pub fn json_with_etag<T: Serialize>(headers: &HeaderMap, body: T) -> Response {
+    let Ok(value) = serde_json::to_value(&body) else {
+        return Json(body).into_response();
+    };
+
+    let etag = etag_for(&value);
+    let Ok(etag_header) = HeaderValue::from_str(&etag) else {
+        return Json(value).into_response();
+    };
+    let cache_headers = [
+        (ETAG, etag_header),
+        (CACHE_CONTROL, HeaderValue::from_static(CACHE_POLICY)),
+    ];
+
+    if matches(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+
+    (StatusCode::OK, cache_headers, Json(value)).into_response()
+}
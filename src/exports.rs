@@ -0,0 +1,162 @@
+use crate::auth::BearerAuth;
+use crate::db;
+use crate::db::models::{Poll, PollOption, VoteExportRow};
+use crate::error::PollError;
+use crate::startup::AppState;
+use axum::{
+    body::Body,
+    extract::{Extension, Path, Query},
+    http::{
+        StatusCode,
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    },
+    response::IntoResponse,
+};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    #[default]
+    Json,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_header(options: &[PollOption], anonymous: bool) -> String {
+    let mut out = String::from("option_id,option_text,votes\n");
+    for option in options {
+        out.push_str(&format!(
+            "{},{},{}\n",
+            option.id,
+            csv_field(&option.option_text),
+            option.votes
+        ));
+    }
+
+    if !anonymous {
+        out.push_str("\nvote_id,user_id,username,option_id,write_in_text,rank,created_at\n");
+    }
+    out
+}
+
+fn csv_vote_row(vote: &VoteExportRow) -> String {
+    format!(
+        "{},{},{},{},{},{},{}\n",
+        vote.id,
+        vote.user_id.map(|id| id.to_string()).unwrap_or_default(),
+        csv_field(vote.username.as_deref().unwrap_or_default()),
+        vote.option_id.map(|id| id.to_string()).unwrap_or_default(),
+        csv_field(vote.write_in_text.as_deref().unwrap_or_default()),
+        vote.rank.map(|rank| rank.to_string()).unwrap_or_default(),
+        vote.created_at.to_rfc3339(),
+    )
+}
+
+// The JSON document is written by hand so vote rows can be streamed one at a
+// time instead of collected into a single Value.
+fn json_header(poll: &Poll, options: &[PollOption]) -> String {
+    let totals: Vec<_> = options
+        .iter()
+        .map(|opt| json!({"id": opt.id, "text": opt.option_text, "votes": opt.votes}))
+        .collect();
+
+    let head = json!({
+        "poll_id": poll.id,
+        "title": poll.title,
+        "anonymous": poll.anonymous,
+        "options": totals,
+    })
+    .to_string();
+
+    let mut out = head[..head.len() - 1].to_string();
+    if !poll.anonymous {
+        out.push_str(",\"votes\":[");
+    }
+    out
+}
+
+pub async fn export_poll(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    Query(params): Query<ExportParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let (poll, options) = db::get_poll_with_options(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    if poll.creator_id != user_id {
+        return Err(PollError::Unauthorized);
+    }
+
+    let pool = app_state.db.clone();
+    let format = params.format;
+
+    let (content_type, extension) = match format {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "csv"),
+        ExportFormat::Json => ("application/json", "json"),
+    };
+
+    let stream = async_stream::stream! {
+        let anonymous = poll.anonymous;
+
+        yield Ok::<_, sqlx::Error>(match format {
+            ExportFormat::Csv => csv_header(&options, anonymous),
+            ExportFormat::Json => json_header(&poll, &options),
+        });
+
+        // Individual ballots would reveal who voted for what.
+        if !anonymous {
+            let mut votes = db::stream_poll_votes(&pool, poll_id);
+            let mut first = true;
+
+            while let Some(vote) = votes.next().await {
+                let vote = vote?;
+                yield Ok(match format {
+                    ExportFormat::Csv => csv_vote_row(&vote),
+                    ExportFormat::Json => {
+                        let row = serde_json::to_string(&vote).unwrap_or_default();
+                        if first { row } else { format!(",{}", row) }
+                    }
+                });
+                first = false;
+            }
+        }
+
+        if let ExportFormat::Json = format {
+            yield Ok(if anonymous { "}".to_string() } else { "]}".to_string() });
+        }
+    };
+
+    Ok((
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, content_type.to_string()),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"poll-{}.{}\"", poll_id, extension),
+            ),
+        ],
+        Body::from_stream(stream),
+    ))
+}
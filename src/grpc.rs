@@ -0,0 +1,459 @@
+use crate::auth::BearerAuth;
+use crate::error::{ApiError, PollError};
+use crate::polls::{self, PollResponse, PollVisibility, ResultVisibility, VoteType};
+use crate::sse::poll_updates_sse::CountsViewer;
+use crate::sse::{SseEvent, SseSender, StreamWake};
+use crate::startup::AppState;
+use crate::validation::FieldError;
+use axum::http::StatusCode;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use std::pin::Pin;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::transport::Server;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+use tracing::warn;
+use uuid::Uuid;
+
+// Generated code; snapshots are the one large event and are sent rarely.
+#[allow(clippy::large_enum_variant)]
+pub mod proto {
+    tonic::include_proto!("polls.v1");
+}
+
+use proto::poll_event::Event;
+use proto::poll_service_server::{PollService, PollServiceServer};
+
+// The same operations as the HTTP API, for internal services that speak gRPC.
+// Each call goes through the shared poll functions, so validation, access
+// checks, rate limits and broadcasts behave exactly as they do over HTTP.
+#[derive(Clone)]
+pub struct GrpcPolls {
+    app_state: AppState,
+    sse_tx: SseSender,
+}
+
+pub fn service(app_state: AppState, sse_tx: SseSender) -> PollServiceServer<GrpcPolls> {
+    PollServiceServer::new(GrpcPolls { app_state, sse_tx })
+}
+
+// Runs until the broadcaster is shut down, which also ends open WatchPoll
+// streams so the server can stop.
+pub async fn serve(
+    listener: TcpListener,
+    app_state: AppState,
+    sse_tx: SseSender,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut shutdown = sse_tx.shutdown_receiver();
+    let incoming = TcpIncoming::from_listener(listener, true, None)?;
+
+    Server::builder()
+        .add_service(service(app_state, sse_tx))
+        .serve_with_incoming_shutdown(incoming, async move {
+            let _ = shutdown.wait_for(|closed| *closed).await;
+        })
+        .await?;
+
+    Ok(())
+}
+
+fn status(error: impl Into<ApiError>) -> Status {
+    Status::from(error.into())
+}
+
+fn invalid_field(field: impl Into<String>, message: &str) -> PollError {
+    PollError::Validation(vec![FieldError::new(field, message)])
+}
+
+fn parse_id(field: impl Into<String>, value: &str) -> Result<Uuid, PollError> {
+    value
+        .parse()
+        .map_err(|_| invalid_field(field, "must be a valid id"))
+}
+
+fn vote_type(value: &str, max_choices: Option<u32>) -> Result<Option<VoteType>, PollError> {
+    match value {
+        "" => Ok(None),
+        "single" => Ok(Some(VoteType::Single)),
+        "multiple" => Ok(Some(VoteType::Multiple { max_choices })),
+        "ranked" => Ok(Some(VoteType::Ranked)),
+        _ => Err(invalid_field(
+            "vote_type",
+            "must be single, multiple or ranked",
+        )),
+    }
+}
+
+fn visibility(value: &str) -> Result<PollVisibility, PollError> {
+    match value {
+        "" | "public" => Ok(PollVisibility::Public),
+        "unlisted" => Ok(PollVisibility::Unlisted),
+        "private" => Ok(PollVisibility::Private),
+        _ => Err(invalid_field(
+            "visibility",
+            "must be public, unlisted or private",
+        )),
+    }
+}
+
+fn result_visibility(value: &str) -> Result<ResultVisibility, PollError> {
+    match value {
+        "" | "always" => Ok(ResultVisibility::Always),
+        "after_vote" => Ok(ResultVisibility::AfterVote),
+        "after_close" => Ok(ResultVisibility::AfterClose),
+        _ => Err(invalid_field(
+            "result_visibility",
+            "must be always, after_vote or after_close",
+        )),
+    }
+}
+
+fn parse_time(field: &'static str, value: &str) -> Result<DateTime<Utc>, PollError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| invalid_field(field, "must be an RFC 3339 time"))
+}
+
+impl TryFrom<proto::CreatePollRequest> for polls::CreatePollRequest {
+    type Error = PollError;
+
+    fn try_from(request: proto::CreatePollRequest) -> Result<Self, PollError> {
+        let expires_at = request
+            .expires_at
+            .as_deref()
+            .map(|value| parse_time("expires_at", value))
+            .transpose()?;
+        let opens_at = request
+            .opens_at
+            .as_deref()
+            .map(|value| parse_time("opens_at", value))
+            .transpose()?;
+
+        Ok(polls::CreatePollRequest {
+            title: request.title,
+            description: request.description,
+            options: request.options,
+            external_id: request.external_id,
+            ballot_public_key: None,
+            write_in_options: Vec::new(),
+            expires_at,
+            opens_at,
+            allow_multiple: false,
+            vote_type: vote_type(&request.vote_type, request.max_choices)?,
+            anonymous: request.anonymous,
+            allow_vote_change: request.allow_vote_change,
+            tags: request.tags,
+            visibility: visibility(&request.visibility)?,
+            access_code: request.access_code,
+            result_visibility: result_visibility(&request.result_visibility)?,
+            allow_write_in: request.allow_write_in,
+        })
+    }
+}
+
+impl TryFrom<proto::CastVoteRequest> for polls::CastVoteRequest {
+    type Error = PollError;
+
+    fn try_from(request: proto::CastVoteRequest) -> Result<Self, PollError> {
+        let rankings = request
+            .rankings
+            .iter()
+            .enumerate()
+            .map(|(index, id)| parse_id(format!("rankings[{}]", index), id))
+            .collect::<Result<_, _>>()?;
+
+        Ok(polls::CastVoteRequest {
+            option_id: request
+                .option_id
+                .as_deref()
+                .map(|id| parse_id("option_id", id))
+                .transpose()?,
+            encrypted_ballot: request.encrypted_ballot,
+            write_in_text: request.write_in_text,
+            write_in: request.write_in,
+            rankings,
+        })
+    }
+}
+
+impl From<PollResponse> for proto::Poll {
+    fn from(poll: PollResponse) -> Self {
+        let total_votes = poll.options.iter().map(|option| option.votes).sum();
+
+        proto::Poll {
+            id: poll.id.to_string(),
+            title: poll.title,
+            description: poll.description,
+            creator_id: poll.creator_id.to_string(),
+            created_at: poll.created_at,
+            expires_at: poll.expires_at,
+            opens_at: poll.opens_at,
+            upcoming: poll.upcoming,
+            closed: poll.closed,
+            vote_type: poll.vote_type,
+            max_choices: poll.max_choices,
+            anonymous: poll.anonymous,
+            allow_vote_change: poll.allow_vote_change,
+            allow_write_in: poll.allow_write_in,
+            tags: poll.tags,
+            visibility: poll.visibility,
+            result_visibility: poll.result_visibility,
+            results_visible: poll.results_visible,
+            user_voted: poll.user_voted,
+            options: poll
+                .options
+                .into_iter()
+                .map(|option| proto::PollOption {
+                    id: option.id.to_string(),
+                    text: option.text,
+                    votes: option.votes,
+                })
+                .collect(),
+            total_votes,
+            viewer_count: poll.viewer_count.unwrap_or_default() as u64,
+        }
+    }
+}
+
+fn poll_event(id: u64, event: Event) -> proto::PollEvent {
+    proto::PollEvent {
+        id,
+        event: Some(event),
+    }
+}
+
+// What the poll's SSE stream would send for this event, or None when it isn't
+// about this poll.
+async fn render_event(
+    app_state: &AppState,
+    poll_id: Uuid,
+    event: &SseEvent,
+    viewer: &mut CountsViewer,
+) -> Option<Event> {
+    match event {
+        SseEvent::VoteUpdate(update) if update.poll_id == poll_id => {
+            let show_counts = viewer.show_counts(app_state, &update.snapshot.poll).await;
+            let options = &update.snapshot.options;
+
+            Some(Event::VoteUpdate(proto::VoteUpdate {
+                options: options
+                    .iter()
+                    .map(|option| proto::PollOption {
+                        id: option.id.to_string(),
+                        text: option.option_text.clone(),
+                        votes: show_counts.then_some(option.votes as i64),
+                    })
+                    .collect(),
+                total_votes: show_counts
+                    .then(|| options.iter().map(|option| option.votes as i64).sum()),
+                updated_option_id: update.option_id.to_string(),
+            }))
+        }
+        SseEvent::OptionAdded(option) if option.poll_id == poll_id => {
+            Some(Event::OptionAdded(proto::PollOption {
+                id: option.id.to_string(),
+                text: option.option_text.clone(),
+                votes: Some(option.votes as i64),
+            }))
+        }
+        SseEvent::CommentAdded(comment) if comment.poll_id == poll_id => {
+            Some(Event::CommentAdded(proto::CommentAdded {
+                comment_id: comment.id.to_string(),
+                user_id: comment.user_id.to_string(),
+                username: comment.username.clone(),
+                body: comment.body.clone(),
+                created_at: comment.created_at.to_rfc3339(),
+            }))
+        }
+        SseEvent::ViewerCount(viewer_poll_id, count) if *viewer_poll_id == poll_id => {
+            Some(Event::ViewerCount(*count as u64))
+        }
+        SseEvent::PollOpened(opened_poll_id) if *opened_poll_id == poll_id => {
+            Some(Event::PollOpened(true))
+        }
+        SseEvent::PollClosed(closed_poll_id) if *closed_poll_id == poll_id => {
+            Some(Event::PollClosed(true))
+        }
+        SseEvent::PollDeleted(deleted_poll_id) if *deleted_poll_id == poll_id => {
+            Some(Event::PollDeleted(true))
+        }
+        _ => None,
+    }
+}
+
+impl GrpcPolls {
+    async fn authenticate<T>(&self, request: &Request<T>) -> Result<BearerAuth, Status> {
+        let headers = request.metadata().clone().into_headers();
+        BearerAuth::from_headers(&headers, &self.app_state)
+            .await
+            .map_err(status)
+    }
+
+    // Access is checked again each time, so a stream stops if the caller
+    // loses access to the poll while watching.
+    async fn snapshot(
+        &self,
+        auth: &BearerAuth,
+        poll_id: Uuid,
+        token: Option<&str>,
+    ) -> Result<proto::PollEvent, Status> {
+        let poll = polls::load_poll(&self.app_state, &self.sse_tx, auth, poll_id, token)
+            .await
+            .map_err(status)?;
+
+        Ok(poll_event(0, Event::Snapshot(poll.into())))
+    }
+}
+
+type WatchPollStream = Pin<Box<dyn Stream<Item = Result<proto::PollEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl PollService for GrpcPolls {
+    async fn create_poll(
+        &self,
+        request: Request<proto::CreatePollRequest>,
+    ) -> Result<Response<proto::CreatePollResponse>, Status> {
+        let auth = self.authenticate(&request).await?;
+        let payload = polls::CreatePollRequest::try_from(request.into_inner()).map_err(status)?;
+
+        let (status_code, poll) = polls::submit_poll(&self.app_state, &self.sse_tx, &auth, payload)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(proto::CreatePollResponse {
+            poll_id: poll.poll_id.to_string(),
+            title: poll.title,
+            description: poll.description,
+            options: poll
+                .options
+                .into_iter()
+                .map(|option| proto::PollOption {
+                    id: option.id.to_string(),
+                    text: option.text,
+                    votes: None,
+                })
+                .collect(),
+            created: status_code == StatusCode::CREATED,
+        }))
+    }
+
+    async fn get_poll(
+        &self,
+        request: Request<proto::GetPollRequest>,
+    ) -> Result<Response<proto::Poll>, Status> {
+        let auth = self.authenticate(&request).await?;
+        let request = request.into_inner();
+        let poll_id = parse_id("poll_id", &request.poll_id).map_err(status)?;
+
+        let poll = polls::load_poll(
+            &self.app_state,
+            &self.sse_tx,
+            &auth,
+            poll_id,
+            request.token.as_deref(),
+        )
+        .await
+        .map_err(status)?;
+
+        Ok(Response::new(poll.into()))
+    }
+
+    async fn cast_vote(
+        &self,
+        request: Request<proto::CastVoteRequest>,
+    ) -> Result<Response<proto::CastVoteResponse>, Status> {
+        let auth = self.authenticate(&request).await?;
+        let request = request.into_inner();
+        let poll_id = parse_id("poll_id", &request.poll_id).map_err(status)?;
+        let payload = polls::CastVoteRequest::try_from(request).map_err(status)?;
+
+        let vote = polls::cast_vote(&self.app_state, &self.sse_tx, &auth, poll_id, payload)
+            .await
+            .map_err(status)?;
+
+        Ok(Response::new(proto::CastVoteResponse {
+            message: vote.message,
+            receipt: vote.receipt.map(|receipt| proto::VoteReceipt {
+                receipt: receipt.receipt,
+                issued_at: receipt.issued_at.to_rfc3339(),
+            }),
+        }))
+    }
+
+    type WatchPollStream = WatchPollStream;
+
+    async fn watch_poll(
+        &self,
+        request: Request<proto::WatchPollRequest>,
+    ) -> Result<Response<Self::WatchPollStream>, Status> {
+        let auth = self.authenticate(&request).await?;
+        let request = request.into_inner();
+        let poll_id = parse_id("poll_id", &request.poll_id).map_err(status)?;
+
+        // Subscribed before the snapshot is read so nothing in between is lost.
+        let mut rx = self.sse_tx.subscribe_poll(poll_id);
+        let mut shutdown = self.sse_tx.shutdown_receiver();
+        let initial = self
+            .snapshot(&auth, poll_id, request.token.as_deref())
+            .await?;
+
+        let service = self.clone();
+        let token = request.token;
+        let mut viewer = CountsViewer::new(Some(auth.clone()));
+
+        let stream = async_stream::stream! {
+            let _watching = service.sse_tx.watch_poll(poll_id);
+            yield Ok(initial);
+
+            loop {
+                let wake = tokio::select! {
+                    result = rx.recv() => StreamWake::Received(result),
+                    _ = shutdown.wait_for(|closed| *closed) => StreamWake::Shutdown,
+                };
+
+                let message = match wake {
+                    StreamWake::Received(Ok(message)) => message,
+                    StreamWake::Received(Err(RecvError::Lagged(skipped))) => {
+                        warn!("gRPC watcher for poll {} lagged by {} events, resyncing", poll_id, skipped);
+                        match service.snapshot(&auth, poll_id, token.as_deref()).await {
+                            Ok(snapshot) => yield Ok(snapshot),
+                            Err(e) => {
+                                yield Err(e);
+                                break;
+                            }
+                        }
+                        continue;
+                    }
+                    StreamWake::Received(Err(RecvError::Closed)) => break,
+                    StreamWake::Heartbeat => continue,
+                    StreamWake::Shutdown => {
+                        yield Err(Status::unavailable("Server is shutting down"));
+                        break;
+                    }
+                };
+
+                if matches!(&message.event, SseEvent::PollEdited(snapshot) if snapshot.poll.id == poll_id) {
+                    match service.snapshot(&auth, poll_id, token.as_deref()).await {
+                        Ok(snapshot) => yield Ok(proto::PollEvent { id: message.id, ..snapshot }),
+                        Err(e) => {
+                            yield Err(e);
+                            break;
+                        }
+                    }
+                    continue;
+                }
+
+                if let Some(event) = render_event(&service.app_state, poll_id, &message.event, &mut viewer).await {
+                    yield Ok(poll_event(message.id, event));
+                }
+                if matches!(message.event, SseEvent::PollDeleted(id) if id == poll_id) {
+                    break;
+                }
+            }
+        };
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
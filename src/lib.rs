@@ -0,0 +1,43 @@
+pub mod access;
+pub mod admin;
+pub mod api_keys;
+pub mod auth;
+pub mod authenticators;
+pub mod ballots;
+pub mod cache;
+pub mod ceremony;
+pub mod comments;
+pub mod config;
+pub mod cors;
+pub mod csrf;
+pub mod email;
+pub mod error;
+pub mod etag;
+pub mod exports;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod mailer;
+pub mod notifications;
+pub mod oauth;
+pub mod openapi;
+pub mod polls;
+pub mod profile;
+pub mod qr;
+pub mod rate_limit;
+pub mod receipts;
+pub mod request_id;
+pub mod routes;
+pub mod session;
+pub mod sse;
+pub mod startup;
+pub mod surveys;
+pub mod validation;
+pub mod webhooks;
+pub mod db {
+    pub mod connection;
+    pub mod models;
+    pub mod repositories;
+
+    pub use connection::*;
+    pub use repositories::*;
+}
@@ -1,40 +1,12 @@
-use crate::auth::{
-    authenticate_user, finish_authentication, finish_register, register_user, start_authentication,
-    start_register,
-};
-use crate::polls::{close_poll, create_poll, get_poll, list_polls, restart_poll, vote_on_poll};
-use crate::sse::{all_polls_sse, create_sse_broadcaster, poll_updates_sse};
-use crate::startup::AppState;
-use axum::{
-    Router,
-    extract::Extension,
-    http::{
-        StatusCode,
-        header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
-    },
-    response::IntoResponse,
-    routing::options,
-};
-use std::env;
+use rust_backend::config::{Config, SseRelay};
+use rust_backend::db;
+use rust_backend::polls::rekey_legacy_voter_hashes;
+use rust_backend::routes::build_router;
+use rust_backend::sse::{PostgresBroadcaster, SseSender, create_sse_broadcaster, start_relay};
+use rust_backend::startup::AppState;
 use std::net::SocketAddr;
-use std::time::Duration;
-use tower_http::cors::{AllowOrigin, CorsLayer};
-use tower_http::timeout::TimeoutLayer;
-use tracing::{error, info};
-
-mod auth;
-mod error;
-mod polls;
-mod sse;
-mod startup;
-mod db {
-    pub mod connection;
-    pub mod models;
-    pub mod repositories;
-
-    pub use connection::*;
-    pub use repositories::*;
-}
+use std::sync::Arc;
+use tracing::{error, info, warn};
 
 #[tokio::main]
 async fn main() {
@@ -47,12 +19,15 @@ async fn main() {
     }
     tracing_subscriber::fmt::init();
 
-    let jwt_secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set in env");
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set in env");
-
-    let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
+    let config = match Config::from_env() {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            error!("Invalid configuration: {}", e);
+            panic!("Configuration error");
+        }
+    };
 
-    let db_pool = match db::init_db(&db_url).await {
+    let db_pool = match db::init_db(&config).await {
         Ok(pool) => {
             info!("Database initialized successfully");
             pool
@@ -63,122 +38,101 @@ async fn main() {
         }
     };
 
-    let app_state = AppState::new(db_pool.clone(), jwt_secret).await;
-    let sse_tx = create_sse_broadcaster();
-    let app = Router::new()
-        .route(
-            "/register_start/:username",
-            options(|| async { (StatusCode::OK, "") }).post(start_register),
-        )
-        .route(
-            "/register_finish",
-            options(|| async { (StatusCode::OK, "") }).post(finish_register),
-        )
-        .route(
-            "/login_start/:username",
-            options(|| async { (StatusCode::OK, "") }).post(start_authentication),
-        )
-        .route(
-            "/login_finish",
-            options(|| async { (StatusCode::OK, "") }).post(finish_authentication),
-        )
-        .route(
-            "/register",
-            options(|| async { (StatusCode::OK, "") }).post(register_user),
-        )
-        .route(
-            "/login",
-            options(|| async { (StatusCode::OK, "") }).post(authenticate_user),
-        )
-        .route(
-            "/polls",
-            options(|| async { (StatusCode::OK, "") })
-                .post(create_poll)
-                .get(list_polls),
-        )
-        .route(
-            "/polls/:poll_id",
-            options(|| async { (StatusCode::OK, "") }).get(get_poll),
-        )
-        .route(
-            "/polls/:poll_id/vote",
-            options(|| async { (StatusCode::OK, "") }).post(vote_on_poll),
-        )
-        .route(
-            "/polls/:poll_id/close",
-            options(|| async { (StatusCode::OK, "") }).post(close_poll),
-        )
-        .route(
-            "/polls/:poll_id/restart",
-            options(|| async { (StatusCode::OK, "") }).post(restart_poll),
-        )
-        .route(
-            "/polls/:poll_id/sse",
-            options(|| async { (StatusCode::OK, "") }).get(poll_updates_sse),
-        )
-        .route(
-            "/polls/sse",
-            options(|| async { (StatusCode::OK, "") }).get(all_polls_sse),
-        )
-        .layer(
-            CorsLayer::new()
-                .allow_origin(AllowOrigin::list([
-                    "https://polling-app-frontend-rho.vercel.app"
-                        .parse()
-                        .unwrap(),
-                    "https://*.vercel.app".parse().unwrap(),
-                    "http://localhost:3000".parse().unwrap(),
-                    "http://localhost:5173".parse().unwrap(),
-                ]))
-                .allow_credentials(true)
-                .allow_methods([
-                    axum::http::Method::GET,
-                    axum::http::Method::POST,
-                    axum::http::Method::PUT,
-                    axum::http::Method::DELETE,
-                    axum::http::Method::OPTIONS,
-                    axum::http::Method::PATCH,
-                    axum::http::Method::HEAD,
-                ])
-                .allow_headers([
-                    CONTENT_TYPE,
-                    ACCEPT,
-                    AUTHORIZATION,
-                    axum::http::header::ORIGIN,
-                    axum::http::header::COOKIE,
-                ])
-                .expose_headers([
-                    axum::http::header::CONTENT_TYPE,
-                    AUTHORIZATION,
-                    axum::http::header::SET_COOKIE,
-                ])
-                .max_age(Duration::from_secs(86400)),
-        )
-        .layer(TimeoutLayer::with_status_code(
-            StatusCode::REQUEST_TIMEOUT,
-            Duration::from_hours(24 * 30),
-        ))
-        .layer(Extension(app_state))
-        .layer(Extension(sse_tx));
+    match rekey_legacy_voter_hashes(&db_pool, &config).await {
+        Ok(0) => {}
+        Ok(rekeyed) => info!("Rekeyed {} legacy anonymous ballots", rekeyed),
+        Err(e) => {
+            error!("Failed to rekey legacy anonymous ballots: {:?}", e);
+            panic!("Database initialization failed");
+        }
+    }
+
+    info!("CORS allowed origins: {:?}", config.cors_origins);
+
+    if config.dev_login_enabled {
+        warn!("DEV_LOGIN_ENABLED is set: POST /login issues tokens without a passkey");
+    }
+
+    let sse_tx = create_sse_broadcaster(&config);
+
+    if config.sse_relay == SseRelay::Postgres {
+        let broadcaster = Arc::new(PostgresBroadcaster::new(db_pool.clone()));
+        if let Err(e) = start_relay(&sse_tx, broadcaster, db_pool.clone()).await {
+            error!("Failed to start the SSE relay: {}", e);
+            panic!("SSE relay failed");
+        }
+    }
+    let app_state = AppState::new(config.clone(), db_pool.clone(), sse_tx.clone()).await;
+    let app = build_router(app_state.clone(), sse_tx.clone());
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], port.parse().unwrap()));
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     info!("🚀 Server listening on {addr}");
 
     let listener = tokio::net::TcpListener::bind(addr)
         .await
         .expect("Unable to spawn tcp listener");
 
-    axum::serve(listener, app).await.unwrap();
-}
+    #[cfg(feature = "grpc")]
+    let grpc_server = {
+        let grpc_addr = SocketAddr::from(([0, 0, 0, 0], config.grpc_port));
+        let grpc_listener = tokio::net::TcpListener::bind(grpc_addr)
+            .await
+            .expect("Unable to spawn gRPC listener");
+        info!("gRPC server listening on {grpc_addr}");
+
+        tokio::spawn(rust_backend::grpc::serve(
+            grpc_listener,
+            app_state.clone(),
+            sse_tx.clone(),
+        ))
+    };
 
-#[allow(dead_code)]
-async fn handler_404() -> impl IntoResponse {
-    (StatusCode::NOT_FOUND, "nothing to see here")
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(sse_tx))
+    .await
+    .unwrap();
+
+    // The shutdown signal stopped both servers; wait for the gRPC streams to
+    // finish too.
+    #[cfg(feature = "grpc")]
+    match grpc_server.await {
+        Ok(Err(e)) => error!("gRPC server failed: {}", e),
+        Err(e) => error!("gRPC server task failed: {}", e),
+        Ok(Ok(())) => {}
+    }
+
+    // Every request has finished by now; wait for their connections to be
+    // returned so in-flight transactions commit or roll back before exiting.
+    db_pool.close().await;
+    info!("Server stopped");
 }
 
-async fn debug_db_stats(Extension(app_state): Extension<AppState>) -> impl IntoResponse {
-    match db::get_pool_stats(&app_state.db).await {
-        Ok(stats) => (StatusCode::OK, stats),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", e)),
+async fn shutdown_signal(sse_tx: SseSender) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
+
+    info!("Shutdown signal received, closing SSE streams");
+    sse_tx.shutdown();
 }
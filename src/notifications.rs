@@ -0,0 +1,135 @@
+use crate::config::Config;
+use crate::db;
+use crate::db::connection::DbPool;
+use crate::mailer::{Email, Mailer, send_in_background};
+use crate::sse::{SseEvent, SseSender};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+enum PollNotification {
+    Closed,
+    Milestone(i64),
+}
+
+impl PollNotification {
+    fn kind(&self) -> String {
+        match self {
+            PollNotification::Closed => "closed".to_string(),
+            PollNotification::Milestone(votes) => format!("votes:{}", votes),
+        }
+    }
+}
+
+fn poll_notification(event: &SseEvent, milestones: &[i64]) -> Option<(Uuid, PollNotification)> {
+    match event {
+        SseEvent::PollClosed(poll_id) => Some((*poll_id, PollNotification::Closed)),
+        SseEvent::VoteUpdate(update) => {
+            let total: i64 = update.snapshot.options.iter().map(|o| o.votes as i64).sum();
+
+            // Only the highest milestone passed is announced; smaller ones
+            // skipped over by a jump in the count aren't sent late.
+            milestones
+                .iter()
+                .rev()
+                .find(|m| **m <= total)
+                .map(|m| (update.poll_id, PollNotification::Milestone(*m)))
+        }
+        _ => None,
+    }
+}
+
+async fn notify(
+    db: &DbPool,
+    mailer: &Arc<dyn Mailer>,
+    config: &Config,
+    poll_id: Uuid,
+    notification: PollNotification,
+) -> Result<(), sqlx::Error> {
+    // Claiming first means each notification is considered once per poll,
+    // whether or not the creator can be emailed at that moment.
+    if !db::claim_poll_notification(db, poll_id, &notification.kind()).await? {
+        return Ok(());
+    }
+
+    let Some(recipient) = db::get_notification_recipient(db, poll_id).await? else {
+        return Ok(());
+    };
+
+    let link = format!(
+        "{}/polls/{}",
+        config.frontend_url.as_str().trim_end_matches('/'),
+        poll_id
+    );
+
+    let (subject, summary) = match notification {
+        PollNotification::Closed => (
+            format!("Your poll \"{}\" has closed", recipient.title),
+            format!(
+                "Your poll \"{}\" has closed with {} votes.",
+                recipient.title, recipient.total_votes
+            ),
+        ),
+        PollNotification::Milestone(votes) => (
+            format!("Your poll \"{}\" reached {} votes", recipient.title, votes),
+            format!(
+                "Your poll \"{}\" has reached {} votes.",
+                recipient.title, votes
+            ),
+        ),
+    };
+
+    send_in_background(
+        mailer,
+        Email {
+            to: recipient.email,
+            subject,
+            body: format!(
+                "Hi {},\n\n{}\n\nSee the results: {}\n\nYou can turn these emails off in your profile settings.\n",
+                recipient.username, summary, link
+            ),
+        },
+    );
+
+    Ok(())
+}
+
+pub fn spawn_notification_worker(
+    db: DbPool,
+    sse_tx: SseSender,
+    config: Arc<Config>,
+    mailer: Arc<dyn Mailer>,
+) {
+    let mut rx = sse_tx.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let message = match rx.recv().await {
+                Ok(message) => message,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Notification worker lagged, {} events were skipped",
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            // Sent by the instance where the change happened.
+            if message.remote {
+                continue;
+            }
+
+            let Some((poll_id, notification)) =
+                poll_notification(&message.event, &config.vote_milestones)
+            else {
+                continue;
+            };
+
+            if let Err(e) = notify(&db, &mailer, &config, poll_id, notification).await {
+                error!("Failed to send notification for poll {}: {}", poll_id, e);
+            }
+        }
+    });
+}
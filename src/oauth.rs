@@ -0,0 +1,465 @@
+use crate::api_keys::X_API_KEY;
+use crate::auth::{BearerAuth, active_user_role, available_username, hash_token, sign_in};
+use crate::ceremony::{CeremonyState, CeremonyUser};
+use crate::config::{Config, OidcConfig};
+use crate::db;
+use crate::error::{ErrorResponse, WebauthnError};
+use crate::session::{api_cookie, cookie_value};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json},
+    http::{HeaderMap, HeaderValue, header::SET_COOKIE},
+    response::IntoResponse,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{DecodingKey, Validation, decode, decode_header};
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use serde::{Deserialize, de::DeserializeOwned};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tower_sessions::Session;
+use tower_sessions::cookie::Cookie;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use webauthn_rs::prelude::Url;
+
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(10);
+const SCOPES: &str = "openid email profile";
+
+// Ties a login to the browser that started it, so a state and code captured
+// elsewhere can't be finished by someone else. Lives as long as the ceremony.
+const LOGIN_COOKIE: &str = "oidc_login";
+const LOGIN_COOKIE_PATH: &str = "/auth/oidc";
+const LOGIN_COOKIE_TTL: time::Duration = time::Duration::minutes(5);
+
+// What the login needs to remember between sending the user to the provider
+// and the code coming back.
+pub struct OidcLogin {
+    nonce: String,
+    code_verifier: String,
+    binding_hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: Url,
+    token_endpoint: Url,
+    jwks_uri: Url,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    preferred_username: Option<String>,
+    name: Option<String>,
+}
+
+// Talks to the configured provider. Its discovery document is fetched once,
+// on first use; signing keys are fetched for every login so key rotation
+// needs no restart.
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    discovery: OnceCell<Discovery>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(PROVIDER_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            config,
+            http,
+            discovery: OnceCell::new(),
+        }
+    }
+
+    async fn fetch<T: DeserializeOwned>(&self, url: Url) -> Result<T, WebauthnError> {
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                error!("Error contacting identity provider: {:?}", e);
+                WebauthnError::OidcProviderError
+            })?;
+
+        response.json().await.map_err(|e| {
+            error!("Unexpected response from identity provider: {:?}", e);
+            WebauthnError::OidcProviderError
+        })
+    }
+
+    async fn discovery(&self) -> Result<&Discovery, WebauthnError> {
+        self.discovery
+            .get_or_try_init(|| async {
+                let url = Url::parse(&format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.as_str().trim_end_matches('/')
+                ))
+                .map_err(|_| WebauthnError::OidcProviderError)?;
+
+                let discovery: Discovery = self.fetch(url).await?;
+
+                // ID tokens are checked against the discovered issuer, so it
+                // has to be the one configured.
+                if discovery.issuer.trim_end_matches('/')
+                    != self.config.issuer.as_str().trim_end_matches('/')
+                {
+                    error!(
+                        "Identity provider reports issuer {}, expected {}",
+                        discovery.issuer, self.config.issuer
+                    );
+                    return Err(WebauthnError::OidcProviderError);
+                }
+
+                Ok(discovery)
+            })
+            .await
+    }
+
+    async fn authorization_url(
+        &self,
+        state_id: Uuid,
+        nonce: &str,
+        code_challenge: &str,
+    ) -> Result<Url, WebauthnError> {
+        let mut url = self.discovery().await?.authorization_endpoint.clone();
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", self.config.redirect_url.as_str())
+            .append_pair("scope", SCOPES)
+            .append_pair("state", &state_id.to_string())
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url)
+    }
+
+    // Exchanges the code and verifies the ID token that comes back: signed by
+    // one of the provider's keys, issued by it to us, unexpired and bound to
+    // this login's nonce.
+    async fn exchange_code(
+        &self,
+        code: &str,
+        login: &OidcLogin,
+    ) -> Result<(String, IdTokenClaims), WebauthnError> {
+        let discovery = self.discovery().await?;
+
+        let response = self
+            .http
+            .post(discovery.token_endpoint.clone())
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+                ("code_verifier", &login.code_verifier),
+            ])
+            .send()
+            .await
+            .map_err(|e| {
+                error!("Error exchanging authorization code: {:?}", e);
+                WebauthnError::OidcProviderError
+            })?;
+
+        // The provider refuses codes that are expired, reused or not ours.
+        if response.status().is_client_error() {
+            warn!(
+                "Identity provider rejected authorization code: {}",
+                response.status()
+            );
+            return Err(WebauthnError::OidcTokenInvalid);
+        }
+
+        let tokens: TokenResponse = response
+            .error_for_status()
+            .map_err(|e| {
+                error!("Error exchanging authorization code: {:?}", e);
+                WebauthnError::OidcProviderError
+            })?
+            .json()
+            .await
+            .map_err(|e| {
+                error!("Unexpected token response from identity provider: {:?}", e);
+                WebauthnError::OidcProviderError
+            })?;
+
+        let header =
+            decode_header(&tokens.id_token).map_err(|_| WebauthnError::OidcTokenInvalid)?;
+        let keys: JwkSet = self.fetch(discovery.jwks_uri.clone()).await?;
+        let jwk = match header.kid.as_deref() {
+            Some(kid) => keys.find(kid),
+            None => keys.keys.first(),
+        }
+        .ok_or(WebauthnError::OidcTokenInvalid)?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|_| WebauthnError::OidcTokenInvalid)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&discovery.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+
+        let claims = decode::<IdTokenClaims>(&tokens.id_token, &key, &validation)
+            .map_err(|e| {
+                warn!("Rejected ID token: {:?}", e);
+                WebauthnError::OidcTokenInvalid
+            })?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(login.nonce.as_str()) {
+            warn!("ID token nonce does not match the login");
+            return Err(WebauthnError::OidcTokenInvalid);
+        }
+
+        Ok((discovery.issuer.clone(), claims))
+    }
+}
+
+fn random_token(len: usize) -> Result<String, WebauthnError> {
+    let mut bytes = vec![0u8; len];
+    rand_bytes(&mut bytes).map_err(|_| WebauthnError::Unknown)?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(sha256(code_verifier.as_bytes()))
+}
+
+fn login_cookie(binding: String, config: &Config) -> Option<HeaderValue> {
+    let cookie = api_cookie(LOGIN_COOKIE, binding, config)
+        .path(LOGIN_COOKIE_PATH)
+        .http_only(true)
+        .max_age(LOGIN_COOKIE_TTL)
+        .build();
+
+    HeaderValue::from_str(&cookie.to_string()).ok()
+}
+
+fn clear_login_cookie() -> HeaderValue {
+    let cookie = Cookie::build((LOGIN_COOKIE, ""))
+        .path(LOGIN_COOKIE_PATH)
+        .removal()
+        .build();
+
+    HeaderValue::from_str(&cookie.to_string()).expect("Removal cookie is a valid header")
+}
+
+fn oidc_client(app_state: &AppState) -> Result<&OidcClient, WebauthnError> {
+    app_state
+        .oidc
+        .as_deref()
+        .ok_or(WebauthnError::OidcNotConfigured)
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FinishOidcLoginRequest {
+    pub state: Uuid,
+    pub code: String,
+    #[serde(default)]
+    pub cookie: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/oidc/start",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Send the user to authorization_url; the provider returns them to the redirect URL with code and state for /auth/oidc/finish, which must carry the oidc_login cookie set here. When signed in with a session or access token, the identity is linked to the current account", body = serde_json::Value,
+            example = json!({
+                "authorization_url": "https://accounts.example.com/authorize?response_type=code&...",
+                "state_id": "5f0c7e64-0f5e-4a51-9a1e-2c4ad0f4a8b1"
+            })),
+        (status = 401, description = "Credentials sent but invalid", body = ErrorResponse),
+        (status = 403, description = "API keys can't link identities", body = ErrorResponse),
+        (status = 404, description = "OIDC sign-in is not configured", body = ErrorResponse),
+        (status = 502, description = "Identity provider unreachable", body = ErrorResponse),
+    )
+)]
+pub async fn start_oidc_login(
+    Extension(app_state): Extension<AppState>,
+    headers: HeaderMap,
+    auth: Result<BearerAuth, WebauthnError>,
+) -> Result<impl IntoResponse, WebauthnError> {
+    let client = oidc_client(&app_state)?;
+
+    // Linking needs the account's own session or token. Credentials that
+    // don't check out are an error rather than a fresh, unlinked login.
+    if headers.contains_key(&X_API_KEY) {
+        return Err(WebauthnError::InsufficientScope);
+    }
+    let user = match auth {
+        Ok(BearerAuth(claims)) => Some(CeremonyUser {
+            user_id: claims.sub,
+            username: claims.username,
+        }),
+        Err(WebauthnError::Unauthorized) => None,
+        Err(e) => return Err(e),
+    };
+
+    let binding = random_token(32)?;
+    let login = OidcLogin {
+        nonce: random_token(16)?,
+        code_verifier: random_token(32)?,
+        binding_hash: hash_token(&binding),
+    };
+
+    let nonce = login.nonce.clone();
+    let code_challenge = code_challenge(&login.code_verifier);
+    let state_id = app_state
+        .ceremonies
+        .insert(CeremonyState::Oidc(login), user)
+        .await;
+    let authorization_url = client
+        .authorization_url(state_id, &nonce, &code_challenge)
+        .await?;
+
+    let mut response = Json(serde_json::json!({
+        "authorization_url": authorization_url,
+        "state_id": state_id,
+    }))
+    .into_response();
+    if let Some(cookie) = login_cookie(binding, &app_state.config) {
+        response.headers_mut().append(SET_COOKIE, cookie);
+    }
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/oidc/finish",
+    tag = "auth",
+    request_body = FinishOidcLoginRequest,
+    responses(
+        (status = 200, description = "Signed in; same body as /login_finish. Identities not seen before get a new account, unless the login was started signed in", body = serde_json::Value),
+        (status = 400, description = "Login expired, unknown or started by another browser", body = ErrorResponse),
+        (status = 401, description = "Code or ID token rejected", body = ErrorResponse),
+        (status = 403, description = "Account banned", body = ErrorResponse),
+        (status = 409, description = "Identity already linked to another account", body = ErrorResponse),
+        (status = 502, description = "Identity provider unreachable", body = ErrorResponse),
+    )
+)]
+pub async fn finish_oidc_login(
+    Extension(app_state): Extension<AppState>,
+    session: Session,
+    headers: HeaderMap,
+    Json(payload): Json<FinishOidcLoginRequest>,
+) -> Result<impl IntoResponse, WebauthnError> {
+    let client = oidc_client(&app_state)?;
+
+    let ceremony = app_state
+        .ceremonies
+        .take(payload.state)
+        .await
+        .ok_or(WebauthnError::CorruptSession)?;
+    let CeremonyState::Oidc(login) = ceremony.state else {
+        return Err(WebauthnError::CorruptSession);
+    };
+
+    let binding = cookie_value(&headers, LOGIN_COOKIE).map(|binding| hash_token(&binding));
+    if binding.as_deref() != Some(login.binding_hash.as_str()) {
+        warn!("OIDC login finished by a client that didn't start it");
+        return Err(WebauthnError::CorruptSession);
+    }
+
+    let (issuer, claims) = client.exchange_code(&payload.code, &login).await?;
+    let email = claims.email.as_deref();
+
+    let linked = db::sign_in_oauth_identity(&app_state.db, &issuer, &claims.sub, email)
+        .await
+        .map_err(|e| {
+            error!("Error loading external identity: {:?}", e);
+            WebauthnError::Unknown
+        })?;
+
+    let (user_id, username) = match (linked, ceremony.user) {
+        (Some(user_id), Some(user)) if user_id != user.user_id => {
+            return Err(WebauthnError::IdentityInUse);
+        }
+        (Some(user_id), _) => {
+            let username = db::get_username(&app_state.db, user_id)
+                .await
+                .map_err(|_| WebauthnError::Unknown)?
+                .ok_or(WebauthnError::UserNotFound)?;
+            (user_id, username)
+        }
+        (None, Some(user)) => {
+            let inserted =
+                db::link_oauth_identity(&app_state.db, user.user_id, &issuer, &claims.sub, email)
+                    .await
+                    .map_err(|e| {
+                        error!("Error linking external identity: {:?}", e);
+                        WebauthnError::Unknown
+                    })?;
+            if !inserted {
+                return Err(WebauthnError::IdentityInUse);
+            }
+            info!("Linked {} identity to user {}", issuer, user.username);
+            (user.user_id, user.username)
+        }
+        (None, None) => {
+            let user_id = Uuid::new_v4();
+            // Named after what the provider knows them by.
+            let name = claims
+                .preferred_username
+                .as_deref()
+                .or_else(|| claims.email.as_deref().and_then(|e| e.split('@').next()))
+                .or(claims.name.as_deref());
+            let username = available_username(&app_state.db, name)
+                .await
+                .map_err(|_| WebauthnError::Unknown)?
+                .ok_or(WebauthnError::UserAlreadyExists)?;
+            db::create_oauth_user(
+                &app_state.db,
+                user_id,
+                &username,
+                &issuer,
+                &claims.sub,
+                email,
+            )
+            .await
+            .map_err(|e| {
+                error!("Error creating account for external identity: {:?}", e);
+                WebauthnError::Unknown
+            })?;
+            info!("Created user {} from {} identity", username, issuer);
+            (user_id, username)
+        }
+    };
+
+    let role = active_user_role(&app_state, user_id).await?;
+    info!("OIDC authentication successful for: {}", username);
+
+    let mut response = sign_in(
+        &app_state,
+        &session,
+        user_id,
+        &username,
+        &role,
+        payload.cookie,
+    )
+    .await?;
+    response
+        .headers_mut()
+        .append(SET_COOKIE, clear_login_cookie());
+
+    Ok(response)
+}
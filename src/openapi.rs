@@ -0,0 +1,141 @@
+use crate::api_keys::{self, X_API_KEY};
+use crate::error::ErrorResponse;
+use crate::{access, auth, csrf, oauth, polls, receipts, sse};
+use axum::{
+    http::header::CONTENT_TYPE,
+    response::{Html, IntoResponse},
+};
+use std::sync::LazyLock;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Polling App API"),
+    paths(
+        auth::start_register,
+        auth::finish_register,
+        auth::start_authentication,
+        auth::finish_authentication,
+        auth::start_discoverable_authentication,
+        auth::finish_discoverable_authentication,
+        auth::register_user,
+        auth::authenticate_user,
+        auth::refresh_session,
+        oauth::start_oidc_login,
+        oauth::finish_oidc_login,
+        auth::logout,
+        auth::check_username_available,
+        auth::list_credentials,
+        auth::delete_credential,
+        auth::list_credential_details,
+        api_keys::create_api_key,
+        api_keys::list_api_keys,
+        api_keys::delete_api_key,
+        csrf::issue_csrf_token,
+        polls::create_poll,
+        polls::list_polls,
+        polls::bulk_create_polls,
+        polls::trending_polls,
+        polls::search_polls,
+        polls::import_poll,
+        polls::get_poll,
+        polls::edit_poll,
+        polls::delete_poll,
+        polls::archive_poll,
+        polls::restore_poll,
+        polls::pin_poll_in_tag,
+        polls::unpin_poll_in_tag,
+        polls::get_poll_definition,
+        polls::vote_on_poll,
+        access::send_vote_links,
+        access::vote_with_link,
+        receipts::verify_receipt,
+        polls::retract_vote,
+        polls::get_my_polls,
+        polls::get_my_votes,
+        polls::close_poll,
+        polls::restart_poll,
+        polls::report_poll,
+        polls::get_poll_breakdown,
+        polls::get_poll_history,
+        polls::tally_poll,
+        polls::get_option_write_ins,
+        polls::list_tags,
+        sse::all_polls_sse::all_polls_sse,
+        sse::poll_updates_sse::poll_updates_sse,
+    ),
+    components(schemas(
+        ErrorResponse,
+        polls::PollStatus,
+        polls::MyPollsSort,
+        polls::HistoryInterval,
+        polls::BreakdownBy,
+        polls::HistoryOptionResponse,
+        polls::HistoryPointResponse,
+        polls::OptionBreakdownResponse,
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "auth", description = "Passkey and OIDC sign-in, registration, sessions and credentials"),
+        (name = "polls", description = "Creating, voting on and managing polls"),
+        (name = "sse", description = "Server-sent event streams"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+// Poll, vote and read routes also take an API key in place of the token.
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(X_API_KEY.as_str()))),
+        );
+    }
+}
+
+static OPENAPI_JSON: LazyLock<String> = LazyLock::new(|| {
+    ApiDoc::openapi()
+        .to_json()
+        .expect("OpenAPI document should serialize")
+});
+
+pub async fn openapi_json() -> impl IntoResponse {
+    ([(CONTENT_TYPE, "application/json")], OPENAPI_JSON.as_str())
+}
+
+// Swagger UI's assets are loaded from a CDN so they don't have to be bundled
+// into the binary.
+pub async fn swagger_ui() -> impl IntoResponse {
+    Html(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>Polling App API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>"##,
+    )
+}
@@ -1,26 +1,126 @@
+use crate::access::{hash_access_code, require_poll_access, validate_access_code};
+use crate::ballots;
+use crate::config::Config;
 use crate::db;
-use crate::error::PollError;
-use crate::sse::{SseEvent, SseSender};
+use crate::db::models::{CreatorPollSummary, Poll, PollOption, TagCount, VoteHistoryEntry};
+use crate::error::{ErrorResponse, PollError};
+use crate::etag::json_with_etag;
+use crate::receipts::{VoteReceipt, issue_receipt};
+use crate::sse::{PollSnapshot, PollUpdate, SseEvent, SseSender};
 use crate::startup::AppState;
+use crate::validation;
 use axum::{
-    extract::{Extension, Json, Path},
-    http::StatusCode,
+    extract::{Extension, Json, Path, Query},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
 };
+use chrono::{DateTime, NaiveDate, Utc};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sha::sha256;
+use openssl::sign::Signer;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 
 use crate::auth::BearerAuth;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct CreatePollRequest {
     pub title: String,
     pub description: Option<String>,
     pub options: Vec<String>,
+    pub external_id: Option<String>,
+    pub ballot_public_key: Option<String>,
+    #[serde(default)]
+    pub write_in_options: Vec<usize>,
+    #[serde(alias = "closes_at")]
+    pub expires_at: Option<DateTime<Utc>>,
+    // Publishes the poll as upcoming; votes are refused until this time.
+    pub opens_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub allow_multiple: bool,
+    pub vote_type: Option<VoteType>,
+    #[serde(default)]
+    pub anonymous: bool,
+    #[serde(default)]
+    pub allow_vote_change: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub visibility: PollVisibility,
+    pub access_code: Option<String>,
+    #[serde(default)]
+    pub result_visibility: ResultVisibility,
+    // Lets voters add their own option by voting with `write_in`.
+    #[serde(default)]
+    pub allow_write_in: bool,
 }
 
-#[derive(Debug, Serialize)]
+// Unlisted polls are reachable by anyone with the link but left out of
+// listings; private polls additionally need an invite.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PollVisibility {
+    #[default]
+    Public,
+    Unlisted,
+    Private,
+}
+
+impl PollVisibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PollVisibility::Public => "public",
+            PollVisibility::Unlisted => "unlisted",
+            PollVisibility::Private => "private",
+        }
+    }
+}
+
+// When voters get to see the counts. The creator and admins always can.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultVisibility {
+    #[default]
+    Always,
+    AfterVote,
+    AfterClose,
+}
+
+impl ResultVisibility {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResultVisibility::Always => "always",
+            ResultVisibility::AfterVote => "after_vote",
+            ResultVisibility::AfterClose => "after_close",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteType {
+    Single,
+    Multiple { max_choices: Option<u32> },
+    Ranked,
+}
+
+impl CreatePollRequest {
+    // `allow_multiple` predates `vote_type` and is still accepted on its own.
+    pub fn effective_vote_type(&self) -> VoteType {
+        match self.vote_type {
+            Some(vote_type) => vote_type,
+            None if self.allow_multiple => VoteType::Multiple { max_choices: None },
+            None => VoteType::Single,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct CreatePollResponse {
     pub poll_id: Uuid,
     pub title: String,
@@ -28,305 +128,2744 @@ pub struct CreatePollResponse {
     pub options: Vec<PollOptionResponse>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkCreatePollResult {
+    pub index: usize,
+    pub created: bool,
+    #[serde(flatten)]
+    pub poll: CreatePollResponse,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkCreatePollsResponse {
+    pub created: usize,
+    pub existing: usize,
+    pub results: Vec<BulkCreatePollResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct PollOptionResponse {
     pub id: Uuid,
     pub text: String,
 }
 
-#[derive(Debug, Serialize)]
-pub struct PollResponse {
-    pub id: Uuid,
-    pub title: String,
-    pub description: Option<String>,
-    pub creator_id: Uuid,
-    pub created_at: String,
-    pub closed: bool,
-    pub options: Vec<PollOptionWithVotesResponse>,
-    pub user_voted: bool,
-    pub current_user_id: Option<Uuid>,
-}
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PollResponse {
+    pub id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub creator_id: Uuid,
+    pub created_at: String,
+    pub closed: bool,
+    pub options: Vec<PollOptionWithVotesResponse>,
+    pub user_voted: bool,
+    pub current_user_id: Option<Uuid>,
+    pub ballot_public_key: Option<String>,
+    pub tallied: bool,
+    pub expires_at: Option<String>,
+    pub opens_at: Option<String>,
+    // Scheduled to open later; votes are refused until then.
+    pub upcoming: bool,
+    pub archived_at: Option<String>,
+    pub allow_multiple: bool,
+    pub vote_type: String,
+    pub max_choices: Option<i32>,
+    pub anonymous: bool,
+    pub allow_vote_change: bool,
+    pub tags: Vec<String>,
+    pub visibility: String,
+    pub access_code_required: bool,
+    pub result_visibility: String,
+    pub results_visible: bool,
+    pub allow_write_in: bool,
+    // Clients currently watching the poll's event stream; only on GET /polls/:id.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub viewer_count: Option<usize>,
+    pub ranked_results: Option<RankedResults>,
+    pub server_time: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RankedResults {
+    pub rounds: Vec<RankedRound>,
+    pub winner: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RankedRound {
+    pub counts: Vec<RankedCount>,
+    pub eliminated: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RankedCount {
+    pub option_id: Uuid,
+    pub votes: i64,
+}
+
+const DEFAULT_POLLS_PAGE_SIZE: u32 = 20;
+const MAX_BULK_POLLS: usize = 100;
+const MAX_POLLS_PAGE_SIZE: u32 = 100;
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PollStatus {
+    Open,
+    Closed,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VoteHistoryParams {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VoteHistoryResponse {
+    pub votes: Vec<VoteHistoryEntry>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MyPollsSort {
+    #[default]
+    CreatedAt,
+    TotalVotes,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MyPollsParams {
+    #[serde(default)]
+    pub sort: MyPollsSort,
+    pub status: Option<PollStatus>,
+    // Archived polls are left out unless asked for, so they can be restored.
+    #[serde(default)]
+    pub include_archived: bool,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MyPollsResponse {
+    pub polls: Vec<CreatorPollSummary>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetPollParams {
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EditPollRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub add_options: Vec<String>,
+    #[serde(default)]
+    pub remove_options: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListPollsParams {
+    #[serde(alias = "per_page")]
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    pub page: Option<u32>,
+    pub creator_id: Option<Uuid>,
+    pub closed: Option<bool>,
+    pub status: Option<PollStatus>,
+    pub q: Option<String>,
+    pub tag: Option<String>,
+}
+
+const MAX_TAGS_PER_POLL: usize = 10;
+const MAX_TAG_LENGTH: usize = 32;
+const DEFAULT_TAG_LIST_SIZE: u32 = 50;
+const MAX_TAG_LIST_SIZE: u32 = 200;
+
+const DEFAULT_TRENDING_LIMIT: u32 = 10;
+const MAX_TRENDING_LIMIT: u32 = 50;
+const MAX_TRENDING_WINDOW_HOURS: u32 = 24 * 7;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TrendingParams {
+    pub hours: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrendingPollResponse {
+    #[serde(flatten)]
+    pub poll: PollResponse,
+    pub recent_votes: i64,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrendingPollsResponse {
+    pub polls: Vec<TrendingPollResponse>,
+    pub window_hours: u32,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+    // Web search syntax: "quoted phrases", -excluded, or.
+    pub q: String,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+// Text fields are HTML-escaped with matches wrapped in <mark>.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResult {
+    pub poll_id: Uuid,
+    pub title: String,
+    pub snippet: Option<String>,
+    pub matched_options: Vec<String>,
+    pub rank: f32,
+    pub closed: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+const MAX_SEARCH_QUERY_LENGTH: usize = 200;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListTagsParams {
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PollListResponse {
+    pub polls: Vec<PollResponse>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub page: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PollOptionWithVotesResponse {
+    pub id: Uuid,
+    pub text: String,
+    // Null while the poll's results are hidden from the caller.
+    pub votes: Option<i64>,
+    pub allows_write_in: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CastVoteRequest {
+    pub option_id: Option<Uuid>,
+    pub encrypted_ballot: Option<String>,
+    pub write_in_text: Option<String>,
+    // Votes for a new option with this text on polls with allow_write_in.
+    pub write_in: Option<String>,
+    #[serde(default)]
+    pub rankings: Vec<Uuid>,
+}
+
+const MAX_WRITE_IN_LENGTH: usize = 200;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WriteInResponse {
+    pub text: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RestartPollParams {
+    #[serde(default)]
+    pub reset_votes: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TallyPollRequest {
+    pub private_key: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VoteResponse {
+    pub success: bool,
+    pub message: String,
+    // Keep this to check later that the ballot was counted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<VoteReceipt>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReportPollRequest {
+    pub reason: String,
+}
+
+const MAX_REPORT_REASON_LENGTH: usize = 500;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum HistoryInterval {
+    #[serde(rename = "1m")]
+    Minute,
+    #[default]
+    #[serde(rename = "1h")]
+    Hour,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryParams {
+    #[serde(default)]
+    pub interval: HistoryInterval,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HistoryOptionResponse {
+    pub id: Uuid,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HistoryPointResponse {
+    pub at: DateTime<Utc>,
+    pub votes: BTreeMap<Uuid, i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakdownBy {
+    RegistrationDate,
+    IsFirstVote,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BreakdownParams {
+    pub by: BreakdownBy,
+    pub boundary: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OptionBreakdownResponse {
+    pub id: Uuid,
+    pub text: String,
+    pub cohorts: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct PollSettings {}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PollDefinition {
+    pub title: String,
+    pub description: Option<String>,
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub settings: PollSettings,
+}
+
+impl From<PollDefinition> for CreatePollRequest {
+    fn from(definition: PollDefinition) -> Self {
+        CreatePollRequest {
+            title: definition.title,
+            description: definition.description,
+            options: definition.options,
+            external_id: None,
+            ballot_public_key: None,
+            write_in_options: Vec::new(),
+            expires_at: None,
+            opens_at: None,
+            allow_multiple: false,
+            vote_type: None,
+            anonymous: false,
+            allow_vote_change: false,
+            tags: definition.tags,
+            visibility: PollVisibility::default(),
+            access_code: None,
+            result_visibility: ResultVisibility::default(),
+            allow_write_in: false,
+        }
+    }
+}
+
+// Tags are matched case-insensitively, so they're stored lowercased.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_lowercase();
+
+    let valid = !tag.is_empty()
+        && tag.chars().count() <= MAX_TAG_LENGTH
+        && tag
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_'));
+
+    valid.then_some(tag)
+}
+
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, PollError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+
+    for tag in tags {
+        let tag = normalize_tag(tag).ok_or(PollError::InvalidRequest)?;
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    if normalized.len() > MAX_TAGS_PER_POLL {
+        return Err(PollError::InvalidRequest);
+    }
+
+    Ok(normalized)
+}
+
+// Trims the text fields in place before checking them.
+pub fn validate_create_poll_request(
+    payload: &mut CreatePollRequest,
+    max_options: usize,
+) -> Result<(), PollError> {
+    validation::trim_poll_fields(payload);
+
+    let errors = validation::check_poll_fields(payload, max_options);
+    if !errors.is_empty() {
+        return Err(PollError::Validation(errors));
+    }
+
+    if let Some(external_id) = &payload.external_id
+        && (external_id.is_empty() || external_id.len() > 255)
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    if let Some(public_key) = &payload.ballot_public_key
+        && ballots::parse_public_key(public_key).is_none()
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    match payload.effective_vote_type() {
+        VoteType::Single => {
+            if payload.allow_multiple {
+                return Err(PollError::InvalidRequest);
+            }
+        }
+        VoteType::Multiple { max_choices } => {
+            if payload.ballot_public_key.is_some()
+                || max_choices.is_some_and(|max| max == 0 || max as usize > payload.options.len())
+            {
+                return Err(PollError::InvalidRequest);
+            }
+        }
+        VoteType::Ranked => {
+            if payload.allow_multiple
+                || payload.ballot_public_key.is_some()
+                || !payload.write_in_options.is_empty()
+                || payload.allow_write_in
+            {
+                return Err(PollError::InvalidRequest);
+            }
+        }
+    }
+
+    if payload.allow_write_in && payload.ballot_public_key.is_some() {
+        return Err(PollError::InvalidRequest);
+    }
+
+    if payload.anonymous
+        && (payload.ballot_public_key.is_some()
+            || payload.effective_vote_type() == VoteType::Ranked)
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    if let Some(expires_at) = payload.expires_at
+        && expires_at <= Utc::now()
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    if let (Some(opens_at), Some(expires_at)) = (payload.opens_at, payload.expires_at)
+        && opens_at >= expires_at
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    if payload
+        .write_in_options
+        .iter()
+        .any(|&index| index >= payload.options.len())
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    normalize_tags(&payload.tags)?;
+
+    if let Some(code) = &payload.access_code {
+        validate_access_code(code)?;
+    }
+
+    Ok(())
+}
+
+// Anonymous ballots are stored under sha256("{voter_key}:{poll_id}"), where
+// the voter key is an HMAC of the user id under a server-side secret. Nothing
+// in the database ties a ballot to an account, yet given the key the
+// repository can still find one user's ballots across polls.
+pub(crate) fn voter_key(config: &Config, user_id: Uuid) -> String {
+    let key = PKey::hmac(config.voter_hash_secret.as_bytes()).expect("HMAC key is valid");
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).expect("HMAC-SHA256 is available");
+    signer
+        .update(user_id.to_string().as_bytes())
+        .expect("HMAC-SHA256 is available");
+
+    hex(&signer.sign_to_vec().expect("HMAC-SHA256 is available"))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn anonymous_voter_hash(config: &Config, poll: &Poll, user_id: Uuid) -> Option<String> {
+    poll.anonymous.then(|| {
+        hex(&sha256(
+            format!("{}:{}", voter_key(config, user_id), poll.id).as_bytes(),
+        ))
+    })
+}
+
+// Runs once at startup; after that no poll has a salt left.
+pub async fn rekey_legacy_voter_hashes(
+    pool: &db::DbPool,
+    config: &Config,
+) -> Result<u64, sqlx::Error> {
+    if !db::has_legacy_voter_hashes(pool).await? {
+        return Ok(0);
+    }
+
+    let user_ids = db::get_user_ids(pool).await?;
+    let voter_keys: Vec<String> = user_ids
+        .iter()
+        .map(|user_id| voter_key(config, *user_id))
+        .collect();
+
+    db::rekey_legacy_voter_hashes(pool, &user_ids, &voter_keys).await
+}
+
+fn voter_for(user_id: Uuid, voter_hash: Option<&str>) -> db::Voter<'_> {
+    voter_hash.map_or(db::Voter::User(user_id), db::Voter::Anonymous)
+}
+
+pub async fn broadcast_vote_updates(
+    app_state: &AppState,
+    sse_tx: &SseSender,
+    poll_id: Uuid,
+    option_ids: &[Uuid],
+) -> Result<(), PollError> {
+    let Some((poll, options)) = db::get_poll_with_options(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+    else {
+        return Ok(());
+    };
+
+    let snapshot = Arc::new(PollSnapshot { poll, options });
+
+    for option in snapshot
+        .options
+        .iter()
+        .filter(|opt| option_ids.contains(&opt.id))
+    {
+        let _ = sse_tx.send(SseEvent::VoteUpdate(PollUpdate {
+            poll_id,
+            option_id: option.id,
+            new_vote_count: option.votes as i64,
+            snapshot: snapshot.clone(),
+        }));
+
+        println!(
+            "✅ Broadcasted vote update for poll {} (option {} has {} votes)",
+            poll_id, option.id, option.votes
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn viewer_has_voted(
+    app_state: &AppState,
+    poll: &Poll,
+    user_id: Uuid,
+) -> Result<bool, PollError> {
+    let voter_hash = anonymous_voter_hash(&app_state.config, poll, user_id);
+
+    db::user_has_voted(
+        &app_state.db,
+        poll.id,
+        voter_for(user_id, voter_hash.as_deref()),
+        None,
+    )
+    .await
+    .map_err(PollError::from)
+}
+
+async fn require_visible_results(
+    app_state: &AppState,
+    poll: &Poll,
+    auth: &BearerAuth,
+) -> Result<(), PollError> {
+    let user_id = auth.0.sub;
+    if poll.results_visible_to(user_id, auth.is_admin(), false) {
+        return Ok(());
+    }
+
+    let user_voted = viewer_has_voted(app_state, poll, user_id).await?;
+
+    if poll.results_visible_to(user_id, auth.is_admin(), user_voted) {
+        Ok(())
+    } else {
+        Err(PollError::ResultsHidden)
+    }
+}
+
+pub async fn require_poll_creation(app_state: &AppState, user_id: Uuid) -> Result<(), PollError> {
+    let allowed = db::user_can_create_polls(&app_state.db, user_id)
+        .await
+        .map_err(PollError::from)?;
+
+    if !allowed {
+        return Err(PollError::CreationNotPermitted);
+    }
+
+    Ok(())
+}
+
+fn new_poll<'a>(
+    payload: &'a CreatePollRequest,
+    access_code_hash: Option<&'a str>,
+) -> db::NewPoll<'a> {
+    let (vote_type, max_choices) = match payload.effective_vote_type() {
+        VoteType::Single => ("single", None),
+        VoteType::Multiple { max_choices } => ("multiple", max_choices.map(|max| max as i32)),
+        VoteType::Ranked => ("ranked", None),
+    };
+
+    db::NewPoll {
+        title: &payload.title,
+        description: payload.description.as_deref(),
+        external_id: payload.external_id.as_deref(),
+        ballot_public_key: payload.ballot_public_key.as_deref(),
+        expires_at: payload.expires_at,
+        opens_at: payload.opens_at,
+        allow_multiple: vote_type == "multiple",
+        vote_type,
+        max_choices,
+        anonymous: payload.anonymous,
+        allow_vote_change: payload.allow_vote_change,
+        visibility: payload.visibility.as_str(),
+        access_code_hash,
+        result_visibility: payload.result_visibility.as_str(),
+        allow_write_in: payload.allow_write_in,
+    }
+}
+
+// A create that named one of the creator's existing external ids returns that
+// poll as it is now.
+async fn existing_poll_response(
+    app_state: &AppState,
+    poll_id: Uuid,
+) -> Result<CreatePollResponse, PollError> {
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    let options = db::get_poll_options(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok(CreatePollResponse {
+        poll_id,
+        title: poll.title,
+        description: poll.description,
+        options: options
+            .into_iter()
+            .map(|opt| PollOptionResponse {
+                id: opt.id,
+                text: opt.option_text,
+            })
+            .collect(),
+    })
+}
+
+fn created_poll_response(
+    poll_id: Uuid,
+    option_ids: Vec<Uuid>,
+    payload: CreatePollRequest,
+) -> CreatePollResponse {
+    CreatePollResponse {
+        poll_id,
+        title: payload.title,
+        description: payload.description,
+        options: option_ids
+            .into_iter()
+            .zip(payload.options)
+            .map(|(id, text)| PollOptionResponse { id, text })
+            .collect(),
+    }
+}
+
+async fn insert_poll(
+    app_state: &AppState,
+    sse_tx: &SseSender,
+    user_id: Uuid,
+    payload: CreatePollRequest,
+) -> Result<(StatusCode, CreatePollResponse), PollError> {
+    let access_code_hash = match payload.access_code.as_deref() {
+        Some(code) => Some(hash_access_code(code).await?),
+        None => None,
+    };
+
+    let (poll_id, option_ids) = db::create_poll_with_options(
+        &app_state.db,
+        user_id,
+        &new_poll(&payload, access_code_hash.as_deref()),
+        &payload.options,
+        &payload.write_in_options,
+        &normalize_tags(&payload.tags)?,
+    )
+    .await
+    .map_err(PollError::from)?;
+
+    let Some(option_ids) = option_ids else {
+        let response = existing_poll_response(app_state, poll_id).await?;
+        return Ok((StatusCode::OK, response));
+    };
+
+    let _ = sse_tx.send(SseEvent::PollCreated(crate::sse::PollCreated {
+        poll_id,
+        title: payload.title.clone(),
+        creator_id: user_id,
+    }));
+
+    Ok((
+        StatusCode::CREATED,
+        created_poll_response(poll_id, option_ids, payload),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls",
+    tag = "polls",
+    request_body = CreatePollRequest,
+    responses(
+        (status = 201, description = "Poll created", body = CreatePollResponse),
+        (status = 200, description = "A poll with this external_id already exists", body = CreatePollResponse),
+        (status = 400, description = "Invalid poll; details.fields lists bad fields", body = ErrorResponse),
+        (status = 403, description = "Not allowed to create polls", body = ErrorResponse),
+        (status = 429, description = "Too many polls created", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn create_poll(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Json(payload): Json<CreatePollRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let (status, response) = submit_poll(&app_state, &sse_tx, &auth, payload).await?;
+
+    Ok((status, Json(response)))
+}
+
+// Shared by the HTTP handler and the gRPC service.
+pub async fn submit_poll(
+    app_state: &AppState,
+    sse_tx: &SseSender,
+    auth: &BearerAuth,
+    mut payload: CreatePollRequest,
+) -> Result<(StatusCode, CreatePollResponse), PollError> {
+    let user_id = auth.0.sub;
+
+    app_state
+        .poll_create_limiter
+        .check(user_id)
+        .map_err(|retry_after| PollError::RateLimited(retry_after.as_secs().max(1)))?;
+
+    validate_create_poll_request(&mut payload, app_state.config.max_poll_options)?;
+    require_poll_creation(app_state, user_id).await?;
+
+    insert_poll(app_state, sse_tx, user_id, payload).await
+}
+
+fn validate_bulk_create_request(
+    payloads: &mut [CreatePollRequest],
+    max_options: usize,
+) -> Result<(), PollError> {
+    if payloads.is_empty() || payloads.len() > MAX_BULK_POLLS {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let mut external_ids = HashSet::new();
+    let errors: Vec<(usize, PollError)> = payloads
+        .iter_mut()
+        .enumerate()
+        .filter_map(|(index, payload)| {
+            let duplicate = payload
+                .external_id
+                .clone()
+                .is_some_and(|external_id| !external_ids.insert(external_id));
+
+            if duplicate {
+                return Some((index, PollError::InvalidRequest));
+            }
+
+            validate_create_poll_request(payload, max_options)
+                .err()
+                .map(|e| (index, e))
+        })
+        .collect();
+
+    if !errors.is_empty() {
+        return Err(PollError::InvalidBatch(errors));
+    }
+
+    Ok(())
+}
+
+// Counts against the creation rate limit once per request, not per poll.
+#[utoipa::path(
+    post,
+    path = "/polls/bulk",
+    tag = "polls",
+    request_body = Vec<CreatePollRequest>,
+    responses(
+        (status = 201, description = "At least one poll was created", body = BulkCreatePollsResponse),
+        (status = 200, description = "Every poll already existed", body = BulkCreatePollsResponse),
+        (status = 400, description = "One or more polls are invalid; details.errors lists them by index, with fields where known", body = ErrorResponse),
+        (status = 403, description = "Not allowed to create polls", body = ErrorResponse),
+        (status = 429, description = "Too many polls created", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn bulk_create_polls(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Json(mut payloads): Json<Vec<CreatePollRequest>>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    app_state
+        .poll_create_limiter
+        .check(user_id)
+        .map_err(|retry_after| PollError::RateLimited(retry_after.as_secs().max(1)))?;
+
+    validate_bulk_create_request(&mut payloads, app_state.config.max_poll_options)?;
+    require_poll_creation(&app_state, user_id).await?;
+
+    let mut access_code_hashes = Vec::with_capacity(payloads.len());
+    for payload in &payloads {
+        access_code_hashes.push(match payload.access_code.as_deref() {
+            Some(code) => Some(hash_access_code(code).await?),
+            None => None,
+        });
+    }
+
+    let new_polls = payloads
+        .iter()
+        .zip(&access_code_hashes)
+        .map(|(payload, hash)| {
+            Ok(db::NewPollWithOptions {
+                poll: new_poll(payload, hash.as_deref()),
+                option_texts: &payload.options,
+                write_in_options: &payload.write_in_options,
+                tags: normalize_tags(&payload.tags)?,
+            })
+        })
+        .collect::<Result<Vec<_>, PollError>>()?;
+
+    let inserted = db::create_polls_with_options(&app_state.db, user_id, &new_polls)
+        .await
+        .map_err(PollError::from)?;
+
+    let mut results = Vec::with_capacity(inserted.len());
+    let mut created_events = Vec::new();
+
+    for (index, ((poll_id, option_ids), payload)) in inserted.into_iter().zip(payloads).enumerate()
+    {
+        let (created, poll) = match option_ids {
+            Some(option_ids) => {
+                created_events.push(crate::sse::PollCreated {
+                    poll_id,
+                    title: payload.title.clone(),
+                    creator_id: user_id,
+                });
+                (true, created_poll_response(poll_id, option_ids, payload))
+            }
+            None => (false, existing_poll_response(&app_state, poll_id).await?),
+        };
+
+        results.push(BulkCreatePollResult {
+            index,
+            created,
+            poll,
+        });
+    }
+
+    let created_count = created_events.len();
+    if !created_events.is_empty() {
+        let _ = sse_tx.send(SseEvent::PollsCreated(Arc::new(created_events)));
+    }
+
+    let status = if created_count > 0 {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+
+    Ok((
+        status,
+        Json(BulkCreatePollsResponse {
+            created: created_count,
+            existing: results.len() - created_count,
+            results,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/polls/{poll_id}/definition",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Portable definition of the poll", body = PollDefinition),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn get_poll_definition(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    require_poll_access(&app_state, &poll, &auth, None).await?;
+
+    let options = db::get_poll_options(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?;
+
+    let definition = PollDefinition {
+        title: poll.title,
+        description: poll.description,
+        options: options.into_iter().map(|opt| opt.option_text).collect(),
+        tags: poll.tags,
+        settings: PollSettings::default(),
+    };
+
+    Ok((StatusCode::OK, Json(definition)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls/import",
+    tag = "polls",
+    request_body = PollDefinition,
+    responses(
+        (status = 201, description = "Poll created", body = CreatePollResponse),
+        (status = 400, description = "Invalid poll; details.fields lists bad fields", body = ErrorResponse),
+        (status = 403, description = "Not allowed to create polls", body = ErrorResponse),
+        (status = 429, description = "Too many polls created", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn import_poll(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Json(definition): Json<PollDefinition>,
+) -> Result<impl IntoResponse, PollError> {
+    let payload = CreatePollRequest::from(definition);
+    let (status, response) = submit_poll(&app_state, &sse_tx, &auth, payload).await?;
+
+    Ok((status, Json(response)))
+}
+
+pub async fn build_poll_response(
+    app_state: &AppState,
+    poll: Poll,
+    viewer: &BearerAuth,
+    now: DateTime<Utc>,
+) -> Result<PollResponse, PollError> {
+    let options = db::get_poll_options(&app_state.db, poll.id)
+        .await
+        .map_err(PollError::from)?;
+
+    poll_response_with_options(app_state, poll, options, viewer, now).await
+}
+
+async fn poll_response_with_options(
+    app_state: &AppState,
+    poll: Poll,
+    options: Vec<PollOption>,
+    viewer: &BearerAuth,
+    now: DateTime<Utc>,
+) -> Result<PollResponse, PollError> {
+    let user_id = viewer.0.sub;
+    let voter_hash = anonymous_voter_hash(&app_state.config, &poll, user_id);
+    let user_voted = db::user_has_voted(
+        &app_state.db,
+        poll.id,
+        voter_for(user_id, voter_hash.as_deref()),
+        None,
+    )
+    .await
+    .unwrap_or(false);
+
+    let results_visible = poll.results_visible_to(user_id, viewer.is_admin(), user_voted);
+    let ranked_results = if poll.is_ranked() && results_visible {
+        let ballots = db::get_ranked_ballots(&app_state.db, poll.id)
+            .await
+            .map_err(PollError::from)?;
+        let option_ids: Vec<Uuid> = options.iter().map(|opt| opt.id).collect();
+        Some(tabulate_ranked(&option_ids, &ballots))
+    } else {
+        None
+    };
+
+    let mut response = poll_response_from_parts(poll, options, user_voted, viewer, now);
+    response.ranked_results = ranked_results;
+    Ok(response)
+}
+
+// Instant-runoff: each round counts every ballot for its highest-ranked option
+// still in the running, then drops the weakest option until one has a majority.
+fn tabulate_ranked(option_ids: &[Uuid], ballots: &[Vec<Uuid>]) -> RankedResults {
+    let mut remaining: Vec<Uuid> = option_ids.to_vec();
+    let mut rounds = Vec::new();
+
+    loop {
+        let counts: Vec<RankedCount> = remaining
+            .iter()
+            .map(|&option_id| RankedCount {
+                option_id,
+                votes: ballots
+                    .iter()
+                    .filter(|ballot| {
+                        ballot.iter().find(|id| remaining.contains(id)) == Some(&option_id)
+                    })
+                    .count() as i64,
+            })
+            .collect();
+
+        let total: i64 = counts.iter().map(|c| c.votes).sum();
+        let leader = counts.iter().max_by_key(|c| c.votes);
+        let trailer = counts.iter().min_by_key(|c| c.votes);
+
+        let (Some(leader), Some(trailer)) = (leader, trailer) else {
+            return RankedResults {
+                rounds,
+                winner: None,
+            };
+        };
+
+        if total > 0 && (leader.votes * 2 > total || counts.len() == 1) {
+            let winner = Some(leader.option_id);
+            rounds.push(RankedRound {
+                counts,
+                eliminated: None,
+            });
+            return RankedResults { rounds, winner };
+        }
+
+        // Nothing left to separate the options: no ballots, or an exact tie.
+        if total == 0 || leader.votes == trailer.votes {
+            rounds.push(RankedRound {
+                counts,
+                eliminated: None,
+            });
+            return RankedResults {
+                rounds,
+                winner: None,
+            };
+        }
+
+        let eliminated = trailer.option_id;
+        remaining.retain(|&id| id != eliminated);
+        rounds.push(RankedRound {
+            counts,
+            eliminated: Some(eliminated),
+        });
+    }
+}
+
+fn poll_response_from_parts(
+    poll: Poll,
+    options: Vec<PollOption>,
+    user_voted: bool,
+    viewer: &BearerAuth,
+    now: DateTime<Utc>,
+) -> PollResponse {
+    let user_id = viewer.0.sub;
+    let results_visible = poll.results_visible_to(user_id, viewer.is_admin(), user_voted);
+
+    let option_responses = options
+        .into_iter()
+        .map(|opt| PollOptionWithVotesResponse {
+            id: opt.id,
+            text: opt.option_text,
+            votes: results_visible.then_some(opt.votes as i64),
+            allows_write_in: opt.allows_write_in,
+        })
+        .collect();
+
+    let closed = poll.is_closed();
+    let upcoming = poll.is_upcoming();
+    let access_code_required = poll.requires_access_code();
+
+    PollResponse {
+        id: poll.id,
+        title: poll.title,
+        description: poll.description,
+        creator_id: poll.creator_id,
+        created_at: poll.created_at.to_rfc3339(),
+        closed,
+        options: option_responses,
+        user_voted,
+        current_user_id: Some(user_id),
+        ballot_public_key: poll.ballot_public_key,
+        tallied: poll.tallied,
+        expires_at: poll.expires_at.map(|t| t.to_rfc3339()),
+        opens_at: poll.opens_at.map(|t| t.to_rfc3339()),
+        upcoming,
+        archived_at: poll.archived_at.map(|t| t.to_rfc3339()),
+        allow_multiple: poll.allow_multiple,
+        vote_type: poll.vote_type,
+        max_choices: poll.max_choices,
+        anonymous: poll.anonymous,
+        allow_vote_change: poll.allow_vote_change,
+        tags: poll.tags,
+        visibility: poll.visibility,
+        access_code_required,
+        result_visibility: poll.result_visibility,
+        results_visible,
+        allow_write_in: poll.allow_write_in,
+        viewer_count: None,
+        ranked_results: None,
+        server_time: now.to_rfc3339(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/polls",
+    tag = "polls",
+    params(ListPollsParams),
+    responses(
+        (status = 200, description = "Listed polls, newest first", body = PollListResponse),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn list_polls(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    headers: HeaderMap,
+    Query(params): Query<ListPollsParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_POLLS_PAGE_SIZE)
+        .clamp(1, MAX_POLLS_PAGE_SIZE) as i64;
+    let offset = match params.page {
+        Some(page) => (page.max(1) as i64 - 1) * limit,
+        None => params.offset.unwrap_or(0) as i64,
+    };
+
+    let tag = params
+        .tag
+        .as_deref()
+        .map(|tag| normalize_tag(tag).ok_or(PollError::InvalidRequest))
+        .transpose()?;
+
+    let filter = db::PollFilter {
+        creator_id: params.creator_id,
+        closed: params
+            .status
+            .map(|status| matches!(status, PollStatus::Closed))
+            .or(params.closed),
+        search: params.q.as_deref().map(str::trim).filter(|q| !q.is_empty()),
+        tag: tag.as_deref(),
+        viewer_id: Some(user_id),
+        ..Default::default()
+    };
+
+    let page = app_state
+        .read_cache
+        .listing(&app_state.db, &filter, limit, offset)
+        .await
+        .map_err(PollError::from)?;
+
+    let poll_ids: Vec<Uuid> = page.polls.iter().map(|(poll, _)| poll.id).collect();
+    let voter_hashes: Vec<String> = page
+        .polls
+        .iter()
+        .filter_map(|(poll, _)| anonymous_voter_hash(&app_state.config, poll, user_id))
+        .collect();
+    let voted_poll_ids = db::get_voted_poll_ids(&app_state.db, user_id, &poll_ids, &voter_hashes)
+        .await
+        .map_err(PollError::from)?;
+
+    let now = Utc::now();
+    let poll_responses = page
+        .polls
+        .iter()
+        .map(|(poll, options)| {
+            let user_voted = voted_poll_ids.contains(&poll.id);
+            poll_response_from_parts(poll.clone(), options.clone(), user_voted, &auth, now)
+        })
+        .collect();
+
+    Ok(json_with_etag(
+        &headers,
+        PollListResponse {
+            polls: poll_responses,
+            total: page.total,
+            limit,
+            offset,
+            page: offset / limit + 1,
+        },
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/polls/trending",
+    tag = "polls",
+    params(TrendingParams),
+    responses(
+        (status = 200, description = "Polls with the most recent votes", body = TrendingPollsResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn trending_polls(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Query(params): Query<TrendingParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let window_hours = params
+        .hours
+        .unwrap_or(app_state.config.trending_window_hours)
+        .clamp(1, MAX_TRENDING_WINDOW_HOURS);
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_TRENDING_LIMIT)
+        .clamp(1, MAX_TRENDING_LIMIT) as i64;
+
+    let trending = db::get_trending_polls(
+        &app_state.db,
+        window_hours,
+        app_state.config.trending_half_life_hours,
+        limit,
+    )
+    .await
+    .map_err(PollError::from)?;
+
+    let poll_ids: Vec<Uuid> = trending.iter().map(|t| t.poll_id).collect();
+    let mut polls: HashMap<Uuid, (Poll, Vec<PollOption>)> =
+        db::get_polls_with_options_by_ids(&app_state.db, &poll_ids)
+            .await
+            .map_err(PollError::from)?
+            .into_iter()
+            .map(|(poll, options)| (poll.id, (poll, options)))
+            .collect();
+
+    let voter_hashes: Vec<String> = polls
+        .values()
+        .filter_map(|(poll, _)| anonymous_voter_hash(&app_state.config, poll, user_id))
+        .collect();
+    let voted_poll_ids = db::get_voted_poll_ids(&app_state.db, user_id, &poll_ids, &voter_hashes)
+        .await
+        .map_err(PollError::from)?;
+
+    let now = Utc::now();
+    let responses = trending
+        .into_iter()
+        .filter_map(|entry| {
+            let (poll, options) = polls.remove(&entry.poll_id)?;
+            let user_voted = voted_poll_ids.contains(&poll.id);
+
+            Some(TrendingPollResponse {
+                poll: poll_response_from_parts(poll, options, user_voted, &auth, now),
+                recent_votes: entry.recent_votes,
+                score: entry.score,
+            })
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(TrendingPollsResponse {
+            polls: responses,
+            window_hours,
+        }),
+    ))
+}
+
+fn highlight(headline: &str) -> String {
+    let mut html = String::with_capacity(headline.len());
+
+    for c in headline.chars() {
+        match c {
+            '\u{2}' => html.push_str("<mark>"),
+            '\u{3}' => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+
+    html
+}
+
+#[utoipa::path(
+    get,
+    path = "/search",
+    tag = "polls",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Matching polls, best match first", body = SearchResponse),
+        (status = 400, description = "Empty or overlong query", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn search_polls(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Query(params): Query<SearchParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let query = params.q.trim();
+    if query.is_empty() || query.chars().count() > MAX_SEARCH_QUERY_LENGTH {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_POLLS_PAGE_SIZE)
+        .clamp(1, MAX_POLLS_PAGE_SIZE) as i64;
+    let offset = params.offset.unwrap_or(0) as i64;
+
+    let hits = db::search_polls(&app_state.db, query, user_id, limit, offset)
+        .await
+        .map_err(PollError::from)?;
+
+    let total = db::count_search_results(&app_state.db, query, user_id)
+        .await
+        .map_err(PollError::from)?;
+
+    let results = hits
+        .into_iter()
+        .map(|hit| SearchResult {
+            poll_id: hit.poll_id,
+            title: highlight(&hit.title_headline),
+            snippet: hit.description_headline.as_deref().map(highlight),
+            matched_options: hit.option_headlines.iter().map(|o| highlight(o)).collect(),
+            rank: hit.rank,
+            closed: hit.closed,
+            created_at: hit.created_at,
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(SearchResponse {
+            results,
+            total,
+            limit,
+            offset,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/tags",
+    tag = "polls",
+    params(ListTagsParams),
+    responses(
+        (status = 200, description = "Tags by number of polls", body = Vec<TagCount>),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn list_tags(
+    Extension(app_state): Extension<AppState>,
+    _auth: BearerAuth,
+    Query(params): Query<ListTagsParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_TAG_LIST_SIZE)
+        .clamp(1, MAX_TAG_LIST_SIZE) as i64;
+
+    let tags = db::list_tags(&app_state.db, limit)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((StatusCode::OK, Json(tags)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/polls/{poll_id}",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path), GetPollParams),
+    responses(
+        (status = 200, description = "The poll with its current results", body = PollResponse),
+        (status = 304, description = "Unchanged since the ETag sent in If-None-Match"),
+        (status = 403, description = "Access code required", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn get_poll(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    headers: HeaderMap,
+    Path(poll_id): Path<Uuid>,
+    Query(params): Query<GetPollParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let response = load_poll(&app_state, &sse_tx, &auth, poll_id, params.token.as_deref()).await?;
+
+    Ok(json_with_etag(&headers, response))
+}
+
+pub async fn load_poll(
+    app_state: &AppState,
+    sse_tx: &SseSender,
+    auth: &BearerAuth,
+    poll_id: Uuid,
+    token: Option<&str>,
+) -> Result<PollResponse, PollError> {
+    let snapshot = app_state
+        .read_cache
+        .poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    require_poll_access(app_state, &snapshot.poll, auth, token).await?;
+
+    let mut response = poll_response_with_options(
+        app_state,
+        snapshot.poll.clone(),
+        snapshot.options.clone(),
+        auth,
+        Utc::now(),
+    )
+    .await?;
+    response.viewer_count = Some(sse_tx.viewer_count(poll_id));
+
+    Ok(response)
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls/{poll_id}/vote",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    request_body = CastVoteRequest,
+    responses(
+        (status = 200, description = "Vote recorded", body = VoteResponse),
+        (status = 400, description = "Invalid ballot for this poll, or poll closed or not yet open", body = ErrorResponse),
+        (status = 403, description = "Access code required", body = ErrorResponse),
+        (status = 404, description = "Poll or option not found", body = ErrorResponse),
+        (status = 409, description = "Already voted, choice limit reached, or write-in limit reached", body = ErrorResponse),
+        (status = 429, description = "Voting too fast", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn vote_on_poll(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    Json(payload): Json<CastVoteRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let response = cast_vote(&app_state, &sse_tx, &auth, poll_id, payload).await?;
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+pub async fn cast_vote(
+    app_state: &AppState,
+    sse_tx: &SseSender,
+    auth: &BearerAuth,
+    poll_id: Uuid,
+    payload: CastVoteRequest,
+) -> Result<VoteResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    app_state
+        .vote_limiter
+        .check(user_id)
+        .map_err(|retry_after| PollError::RateLimited(retry_after.as_secs().max(1)))?;
+
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    require_poll_access(app_state, &poll, auth, None).await?;
+
+    if poll.is_closed() || poll.is_archived() {
+        return Err(PollError::PollClosed);
+    }
+
+    if poll.is_upcoming() {
+        return Err(PollError::PollNotOpen);
+    }
+
+    if poll.ballot_public_key.is_some() {
+        let ballot = payload
+            .encrypted_ballot
+            .as_deref()
+            .filter(|b| ballots::is_well_formed_ballot(b))
+            .ok_or(PollError::InvalidRequest)?;
+
+        return match db::cast_encrypted_vote(&app_state.db, poll_id, ballot, user_id).await {
+            Ok(_) => {
+                let receipt =
+                    issue_receipt(app_state, poll_id, None, db::Voter::User(user_id)).await?;
+
+                Ok(VoteResponse {
+                    success: true,
+                    message: "Encrypted ballot recorded successfully".to_string(),
+                    receipt,
+                })
+            }
+            Err(sqlx::Error::RowNotFound) => Err(PollError::AlreadyVoted),
+            Err(e) => Err(PollError::from(e)),
+        };
+    }
+
+    let mut options = db::get_poll_options(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?;
+
+    if poll.is_ranked() {
+        let rankings = &payload.rankings;
+        let unique: HashSet<&Uuid> = rankings.iter().collect();
+
+        if rankings.is_empty()
+            || unique.len() != rankings.len()
+            || payload.write_in_text.is_some()
+            || payload.write_in.is_some()
+        {
+            return Err(PollError::InvalidRequest);
+        }
+
+        if !rankings
+            .iter()
+            .all(|id| options.iter().any(|opt| opt.id == *id))
+        {
+            return Err(PollError::OptionNotFound);
+        }
+
+        return match db::cast_ranked_ballot(&app_state.db, poll_id, user_id, rankings).await {
+            Ok(_) => {
+                broadcast_vote_updates(app_state, sse_tx, poll_id, &rankings[..1]).await?;
+                let receipt = issue_receipt(
+                    app_state,
+                    poll_id,
+                    Some(rankings[0]),
+                    db::Voter::User(user_id),
+                )
+                .await?;
+
+                Ok(VoteResponse {
+                    success: true,
+                    message: "Ranked ballot recorded successfully".to_string(),
+                    receipt,
+                })
+            }
+            Err(sqlx::Error::RowNotFound) => Err(PollError::AlreadyVoted),
+            Err(e) => Err(PollError::from(e)),
+        };
+    }
+
+    let voter_hash = anonymous_voter_hash(&app_state.config, &poll, user_id);
+    let voter = voter_for(user_id, voter_hash.as_deref());
+
+    let option_id = match (payload.option_id, payload.write_in.as_deref()) {
+        (Some(option_id), None) => option_id,
+        (None, Some(text)) => {
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+            if !poll.allow_write_in
+                || text.is_empty()
+                || text.chars().count() > MAX_WRITE_IN_LENGTH
+                || payload.write_in_text.is_some()
+            {
+                return Err(PollError::InvalidRequest);
+            }
+
+            // Checked up front so a refused vote doesn't leave a new option behind.
+            if !poll.allow_multiple
+                && !poll.allow_vote_change
+                && db::user_has_voted(&app_state.db, poll_id, voter, None)
+                    .await
+                    .map_err(PollError::from)?
+            {
+                return Err(PollError::AlreadyVoted);
+            }
+
+            match db::add_write_in_option(
+                &app_state.db,
+                poll_id,
+                &text,
+                app_state.config.max_write_in_options,
+            )
+            .await
+            .map_err(PollError::from)?
+            {
+                db::WriteInOption::Existing(option_id) => option_id,
+                db::WriteInOption::Added(option) => {
+                    let option_id = option.id;
+                    let _ = sse_tx.send(SseEvent::OptionAdded(Arc::new(option.clone())));
+                    options.push(option);
+                    option_id
+                }
+                db::WriteInOption::LimitReached => return Err(PollError::WriteInLimitReached),
+            }
+        }
+        _ => return Err(PollError::InvalidRequest),
+    };
+
+    let option = options
+        .iter()
+        .find(|opt| opt.id == option_id)
+        .ok_or(PollError::OptionNotFound)?;
+
+    let write_in_text = payload
+        .write_in_text
+        .as_deref()
+        .map(str::trim)
+        .filter(|text| !text.is_empty());
+
+    if let Some(text) = write_in_text
+        && (!option.allows_write_in || text.chars().count() > MAX_WRITE_IN_LENGTH)
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    if poll.allow_multiple {
+        if db::user_has_voted(&app_state.db, poll_id, voter, Some(option_id))
+            .await
+            .map_err(PollError::from)?
+        {
+            return Err(PollError::AlreadyVoted);
+        }
+
+        return match db::cast_multi_choice_vote(
+            &app_state.db,
+            poll_id,
+            option_id,
+            voter,
+            write_in_text,
+            poll.max_choices,
+        )
+        .await
+        .map_err(PollError::from)?
+        {
+            db::MultiChoiceVote::Cast => {
+                broadcast_vote_updates(app_state, sse_tx, poll_id, &[option_id]).await?;
+                let receipt = issue_receipt(app_state, poll_id, Some(option_id), voter).await?;
+
+                Ok(VoteResponse {
+                    success: true,
+                    message: "Vote recorded successfully".to_string(),
+                    receipt,
+                })
+            }
+            db::MultiChoiceVote::AlreadyChosen => Err(PollError::AlreadyVoted),
+            db::MultiChoiceVote::LimitReached => Err(PollError::ChoiceLimitReached),
+        };
+    }
+
+    let change = match db::change_vote(
+        &app_state.db,
+        poll_id,
+        option_id,
+        voter,
+        write_in_text,
+        poll.allow_vote_change,
+    )
+    .await
+    {
+        Ok(change) => change,
+        Err(sqlx::Error::RowNotFound) => return Err(PollError::AlreadyVoted),
+        Err(e) => return Err(PollError::from(e)),
+    };
+
+    let (affected_options, message) = match change {
+        db::VoteChange::Unchanged => (vec![], "Vote unchanged"),
+        db::VoteChange::Cast => (vec![option_id], "Vote recorded successfully"),
+        db::VoteChange::Changed { previous_option_id } => (
+            previous_option_id.into_iter().chain([option_id]).collect(),
+            "Vote changed successfully",
+        ),
+    };
+
+    // An unchanged vote keeps the receipt it was first given.
+    let mut receipt = None;
+    if !affected_options.is_empty() {
+        broadcast_vote_updates(app_state, sse_tx, poll_id, &affected_options).await?;
+        receipt = issue_receipt(app_state, poll_id, Some(option_id), voter).await?;
+    }
+
+    Ok(VoteResponse {
+        success: true,
+        message: message.to_string(),
+        receipt,
+    })
+}
+
+// The creator's dashboard: every poll they made, including unlisted and
+// private ones, with counts they can always see.
+#[utoipa::path(
+    get,
+    path = "/me/polls",
+    tag = "polls",
+    params(MyPollsParams),
+    responses(
+        (status = 200, description = "The caller's polls with vote totals and quick stats", body = MyPollsResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn get_my_polls(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Query(params): Query<MyPollsParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_POLLS_PAGE_SIZE)
+        .clamp(1, MAX_POLLS_PAGE_SIZE) as i64;
+    let offset = params.offset.unwrap_or(0) as i64;
+    let closed = params
+        .status
+        .map(|status| matches!(status, PollStatus::Closed));
+
+    let polls = db::get_creator_poll_summaries(
+        &app_state.db,
+        user_id,
+        closed,
+        params.include_archived,
+        matches!(params.sort, MyPollsSort::TotalVotes),
+        limit,
+        offset,
+    )
+    .await
+    .map_err(PollError::from)?;
+
+    let filter = db::PollFilter {
+        creator_id: Some(user_id),
+        closed,
+        viewer_id: Some(user_id),
+        include_archived: params.include_archived,
+        ..Default::default()
+    };
+    let total = db::count_polls(&app_state.db, &filter)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(MyPollsResponse {
+            polls,
+            total,
+            limit,
+            offset,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/votes",
+    tag = "polls",
+    params(VoteHistoryParams),
+    responses(
+        (status = 200, description = "The caller's votes, newest first", body = VoteHistoryResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn get_my_votes(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Query(params): Query<VoteHistoryParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_POLLS_PAGE_SIZE)
+        .clamp(1, MAX_POLLS_PAGE_SIZE) as i64;
+    let offset = params.offset.unwrap_or(0) as i64;
+
+    let voter_key = voter_key(&app_state.config, user_id);
+    let votes = db::get_votes_for_user(&app_state.db, user_id, &voter_key, limit, offset)
+        .await
+        .map_err(PollError::from)?;
+
+    let total = db::count_votes_for_user(&app_state.db, user_id, &voter_key)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(VoteHistoryResponse {
+            votes,
+            total,
+            limit,
+            offset,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/polls/{poll_id}/vote",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Vote retracted", body = VoteResponse),
+        (status = 400, description = "Poll closed or doesn't allow vote changes", body = ErrorResponse),
+        (status = 404, description = "Poll or vote not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn retract_vote(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    require_poll_access(&app_state, &poll, &auth, None).await?;
+
+    if poll.is_closed() || poll.is_archived() {
+        return Err(PollError::PollClosed);
+    }
+
+    // Retracting and recasting would otherwise sidestep the change restriction.
+    if !poll.allow_vote_change {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let voter_hash = anonymous_voter_hash(&app_state.config, &poll, user_id);
+    let voter = voter_for(user_id, voter_hash.as_deref());
+
+    let affected_options = db::retract_vote(&app_state.db, poll_id, voter)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::VoteNotFound)?;
+
+    if !affected_options.is_empty() {
+        broadcast_vote_updates(&app_state, &sse_tx, poll_id, &affected_options).await?;
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(VoteResponse {
+            success: true,
+            message: "Vote retracted successfully".to_string(),
+            receipt: None,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls/{poll_id}/close",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Poll closed", body = serde_json::Value,
+            example = json!({ "success": true, "message": "Poll closed successfully" })),
+        (status = 401, description = "Not the poll's creator", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn close_poll(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    if poll.creator_id != user_id {
+        return Err(PollError::Unauthorized);
+    }
+
+    db::close_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?;
+
+    let _ = sse_tx.send(SseEvent::PollClosed(poll_id));
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Poll closed successfully"
+        })),
+    ))
+}
+
+// Deleting archives the poll rather than removing it; the creator finds it
+// again with GET /me/polls?include_archived=true and can restore it.
+#[utoipa::path(
+    delete,
+    path = "/polls/{poll_id}",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Poll deleted", body = serde_json::Value,
+            example = json!({ "success": true, "message": "Poll deleted successfully" })),
+        (status = 401, description = "Neither the poll's creator nor an admin", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn delete_poll(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    archive(&app_state, &sse_tx, &auth, poll_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Poll deleted successfully"
+        })),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls/{poll_id}/archive",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Poll archived", body = serde_json::Value,
+            example = json!({ "success": true, "message": "Poll archived successfully" })),
+        (status = 401, description = "Neither the poll's creator nor an admin", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn archive_poll(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    archive(&app_state, &sse_tx, &auth, poll_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Poll archived successfully"
+        })),
+    ))
+}
+
+// Archiving an archived poll changes nothing and announces nothing. To
+// everyone watching, an archived poll is a deleted one.
+async fn archive(
+    app_state: &AppState,
+    sse_tx: &SseSender,
+    auth: &BearerAuth,
+    poll_id: Uuid,
+) -> Result<(), PollError> {
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    if poll.creator_id != auth.0.sub {
+        auth.require_admin()?;
+    }
+
+    if db::archive_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+    {
+        let _ = sse_tx.send(SseEvent::PollDeleted(poll_id));
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/polls/{poll_id}/archive",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Poll restored", body = serde_json::Value,
+            example = json!({ "success": true, "message": "Poll restored successfully" })),
+        (status = 401, description = "Neither the poll's creator nor an admin", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn restore_poll(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    if poll.creator_id != auth.0.sub {
+        auth.require_admin()?;
+    }
+
+    // Comes back the way a restarted poll does, not as a new poll.
+    if db::restore_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+    {
+        let _ = sse_tx.send(SseEvent::PollOpened(poll_id));
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Poll restored successfully"
+        })),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/polls/{poll_id}/tags/{tag}/pin",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path), ("tag" = String, Path)),
+    responses(
+        (status = 200, description = "Poll pinned to the top of the tag's listing", body = serde_json::Value,
+            example = json!({ "success": true, "message": "Poll pinned in tag" })),
+        (status = 400, description = "Invalid tag, or the poll doesn't carry it", body = ErrorResponse),
+        (status = 401, description = "Not the poll's creator", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn pin_poll_in_tag(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path((poll_id, tag)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse, PollError> {
+    let tag = creator_tag(&app_state, &auth, poll_id, &tag).await?;
+
+    if !db::pin_poll_in_tag(&app_state.db, poll_id, &tag)
+        .await
+        .map_err(PollError::from)?
+    {
+        return Err(PollError::TagNotOnPoll);
+    }
+    app_state.read_cache.invalidate_listings();
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Poll pinned in tag"
+        })),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/polls/{poll_id}/tags/{tag}/pin",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path), ("tag" = String, Path)),
+    responses(
+        (status = 200, description = "Poll no longer pinned in the tag", body = serde_json::Value,
+            example = json!({ "success": true, "message": "Poll unpinned from tag" })),
+        (status = 400, description = "Invalid tag", body = ErrorResponse),
+        (status = 401, description = "Not the poll's creator", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn unpin_poll_in_tag(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path((poll_id, tag)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse, PollError> {
+    let tag = creator_tag(&app_state, &auth, poll_id, &tag).await?;
 
-#[derive(Debug, Serialize)]
-pub struct PollOptionWithVotesResponse {
-    pub id: Uuid,
-    pub text: String,
-    pub votes: i64,
-}
+    if db::unpin_poll_in_tag(&app_state.db, poll_id, &tag)
+        .await
+        .map_err(PollError::from)?
+    {
+        app_state.read_cache.invalidate_listings();
+    }
 
-#[derive(Debug, Deserialize)]
-pub struct CastVoteRequest {
-    pub option_id: Uuid,
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Poll unpinned from tag"
+        })),
+    ))
 }
 
-#[derive(Debug, Serialize)]
-pub struct VoteResponse {
-    pub success: bool,
-    pub message: String,
+// Pins order a tag's listing for everyone, so only the poll's creator may
+// set them.
+async fn creator_tag(
+    app_state: &AppState,
+    auth: &BearerAuth,
+    poll_id: Uuid,
+    tag: &str,
+) -> Result<String, PollError> {
+    let tag = normalize_tag(tag).ok_or(PollError::InvalidRequest)?;
+
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    if poll.creator_id != auth.0.sub {
+        return Err(PollError::Unauthorized);
+    }
+
+    Ok(tag)
 }
 
-pub async fn create_poll(
+#[utoipa::path(
+    patch,
+    path = "/polls/{poll_id}",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    request_body = EditPollRequest,
+    responses(
+        (status = 200, description = "The updated poll", body = PollResponse),
+        (status = 400, description = "Invalid edit, or poll closed; details.fields lists bad fields", body = ErrorResponse),
+        (status = 401, description = "Not the poll's creator", body = ErrorResponse),
+        (status = 404, description = "Poll or option not found", body = ErrorResponse),
+        (status = 409, description = "Options can't be removed once voted on", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn edit_poll(
     Extension(app_state): Extension<AppState>,
     Extension(sse_tx): Extension<SseSender>,
     auth: BearerAuth,
-    Json(payload): Json<CreatePollRequest>,
+    Path(poll_id): Path<Uuid>,
+    Json(payload): Json<EditPollRequest>,
 ) -> Result<impl IntoResponse, PollError> {
     let user_id = auth.0.sub;
 
-    if payload.title.is_empty() || payload.options.is_empty() {
-        return Err(PollError::InvalidRequest);
+    let (poll, options) = db::get_poll_with_options(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    if poll.is_archived() {
+        return Err(PollError::PollNotFound);
+    }
+
+    if poll.creator_id != user_id {
+        return Err(PollError::Unauthorized);
+    }
+
+    if poll.is_closed() {
+        return Err(PollError::PollClosed);
     }
 
-    if payload.options.len() < 2 {
+    let title = payload.title.as_deref().map(str::trim);
+    let description = payload.description.as_deref().map(str::trim);
+    let add_options: Vec<String> = payload
+        .add_options
+        .iter()
+        .map(|opt| opt.trim().to_string())
+        .collect();
+    let removed: HashSet<&Uuid> = payload.remove_options.iter().collect();
+
+    if removed.len() != payload.remove_options.len() {
         return Err(PollError::InvalidRequest);
     }
 
-    let poll_id = db::create_poll(
-        &app_state.db,
-        user_id,
-        &payload.title,
-        payload.description.as_deref(),
-    )
-    .await
-    .map_err(|e| PollError::DatabaseError(e.to_string()))?;
+    if !removed
+        .iter()
+        .all(|id| options.iter().any(|opt| opt.id == **id))
+    {
+        return Err(PollError::OptionNotFound);
+    }
 
-    let mut option_responses = Vec::new();
-    for option_text in payload.options {
-        let option_id = db::add_poll_option(&app_state.db, poll_id, &option_text)
-            .await
-            .map_err(|e| PollError::DatabaseError(e.to_string()))?;
+    // Encrypted ballots are tallied against the option list they were cast on.
+    if poll.ballot_public_key.is_some() && !payload.add_options.is_empty() {
+        return Err(PollError::InvalidRequest);
+    }
 
-        option_responses.push(PollOptionResponse {
-            id: option_id,
-            text: option_text,
-        });
+    let kept: Vec<&str> = options
+        .iter()
+        .filter(|opt| !removed.contains(&opt.id))
+        .map(|opt| opt.option_text.as_str())
+        .collect();
+    let option_count = kept.len() + add_options.len();
+
+    let mut errors = Vec::new();
+    if let Some(title) = title {
+        validation::check_title(title, &mut errors);
+    }
+    validation::check_description(description, &mut errors);
+    validation::check_option_count(
+        "add_options",
+        option_count,
+        app_state.config.max_poll_options,
+        &mut errors,
+    );
+    validation::check_options("add_options", &add_options, &kept, &mut errors);
+    if !errors.is_empty() {
+        return Err(PollError::Validation(errors));
     }
 
-    let _ = sse_tx.send(SseEvent::PollCreated(crate::sse::PollCreated {
-        poll_id,
-        title: payload.title.clone(),
-        creator_id: user_id,
-    }));
+    if poll
+        .max_choices
+        .is_some_and(|max| max as usize > option_count)
+    {
+        return Err(PollError::InvalidRequest);
+    }
 
-    let response = CreatePollResponse {
-        poll_id,
-        title: payload.title,
-        description: payload.description,
-        options: option_responses,
+    let edit = db::PollEdit {
+        title,
+        description: description.map(|d| Some(d).filter(|d| !d.is_empty())),
+        add_options: &add_options,
+        remove_options: &payload.remove_options,
     };
 
-    Ok((StatusCode::CREATED, Json(response)))
+    match db::edit_poll(&app_state.db, poll_id, &edit)
+        .await
+        .map_err(PollError::from)?
+    {
+        db::PollEditOutcome::Edited => {}
+        db::PollEditOutcome::HasVotes => return Err(PollError::PollHasVotes),
+    }
+
+    let (poll, options) = db::get_poll_with_options(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    let _ = sse_tx.send(SseEvent::PollEdited(Arc::new(PollSnapshot {
+        poll: poll.clone(),
+        options,
+    })));
+
+    let response = build_poll_response(&app_state, poll, &auth, Utc::now()).await?;
+    Ok((StatusCode::OK, Json(response)))
 }
 
-pub async fn list_polls(
+#[utoipa::path(
+    post,
+    path = "/polls/{poll_id}/restart",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path), RestartPollParams),
+    responses(
+        (status = 200, description = "Poll reopened", body = serde_json::Value,
+            example = json!({ "success": true, "message": "Poll restarted successfully" })),
+        (status = 401, description = "Not the poll's creator", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn restart_poll(
     Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
     auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    Query(params): Query<RestartPollParams>,
 ) -> Result<impl IntoResponse, PollError> {
     let user_id = auth.0.sub;
-    let polls = db::get_all_polls(&app_state.db)
+
+    let poll = db::get_poll(&app_state.db, poll_id)
         .await
-        .map_err(|e| PollError::DatabaseError(e.to_string()))?;
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
 
-    let mut poll_responses = Vec::new();
+    // Archived polls look deleted until they're restored.
+    if poll.is_archived() {
+        return Err(PollError::PollNotFound);
+    }
 
-    for poll in polls {
-        let options = db::get_poll_options(&app_state.db, poll.id)
-            .await
-            .map_err(|e| PollError::DatabaseError(e.to_string()))?;
+    if poll.creator_id != user_id {
+        return Err(PollError::Unauthorized);
+    }
+
+    if !db::restart_poll(&app_state.db, poll_id, params.reset_votes)
+        .await
+        .map_err(PollError::from)?
+    {
+        return Err(PollError::PollNotFound);
+    }
 
-        let user_voted = db::user_has_voted(&app_state.db, poll.id, user_id)
+    if params.reset_votes {
+        let options = db::get_poll_options(&app_state.db, poll_id)
             .await
-            .unwrap_or(false);
-        let option_responses = options
-            .into_iter()
-            .map(|opt| PollOptionWithVotesResponse {
-                id: opt.id,
-                text: opt.option_text,
-                votes: opt.votes as i64,
-            })
-            .collect();
+            .map_err(PollError::from)?;
+        let option_ids: Vec<Uuid> = options.iter().map(|opt| opt.id).collect();
 
-        poll_responses.push(PollResponse {
-            id: poll.id,
-            title: poll.title,
-            description: poll.description,
-            creator_id: poll.creator_id,
-            created_at: poll.created_at.to_rfc3339(),
-            closed: poll.closed,
-            options: option_responses,
-            user_voted,
-            current_user_id: Some(user_id),
-        });
+        broadcast_vote_updates(&app_state, &sse_tx, poll_id, &option_ids).await?;
     }
 
-    Ok((StatusCode::OK, Json(poll_responses)))
+    let _ = sse_tx.send(SseEvent::PollOpened(poll_id));
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Poll restarted successfully"
+        })),
+    ))
 }
 
-pub async fn get_poll(
+#[utoipa::path(
+    post,
+    path = "/polls/{poll_id}/report",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    request_body = ReportPollRequest,
+    responses(
+        (status = 201, description = "Report filed", body = serde_json::Value,
+            example = json!({
+                "success": true,
+                "report_id": "7c1f4d8a-3b7e-4f5a-9d2c-6e8b1a0f3c45",
+                "message": "Poll reported successfully"
+            })),
+        (status = 400, description = "Invalid reason", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+        (status = 409, description = "Already reported by the caller", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn report_poll(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(poll_id): Path<Uuid>,
+    Json(payload): Json<ReportPollRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let reason = payload.reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REPORT_REASON_LENGTH {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    require_poll_access(&app_state, &poll, &auth, None).await?;
+
+    let report_id = db::create_report(&app_state.db, poll_id, user_id, reason)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::AlreadyReported)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "success": true,
+            "report_id": report_id,
+            "message": "Poll reported successfully"
+        })),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/polls/{poll_id}/breakdown",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path), BreakdownParams),
+    responses(
+        (status = 200, description = "Votes per option split into voter cohorts", body = serde_json::Value,
+            example = json!({
+                "poll_id": "0b9d2d2e-6f6b-4d53-8a53-43e5b3a4c7d1",
+                "by": "registration_date",
+                "boundary": "2024-01-01",
+                "options": [{ "id": "5f0c7e64-0f5e-4a51-9a1e-2c4ad0f4a8b1", "text": "Yes", "cohorts": { "before": 3, "after": 5 } }]
+            })),
+        (status = 401, description = "Not the poll's creator", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn get_poll_breakdown(
     Extension(app_state): Extension<AppState>,
     auth: BearerAuth,
     Path(poll_id): Path<Uuid>,
+    Query(params): Query<BreakdownParams>,
 ) -> Result<impl IntoResponse, PollError> {
     let user_id = auth.0.sub;
+
     let poll = db::get_poll(&app_state.db, poll_id)
         .await
-        .map_err(|e| PollError::DatabaseError(e.to_string()))?
+        .map_err(PollError::from)?
         .ok_or(PollError::PollNotFound)?;
 
+    if poll.creator_id != user_id {
+        return Err(PollError::Unauthorized);
+    }
+
+    // Cohorts are derived from who voted, which anonymous polls must not reveal.
+    if poll.anonymous {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let (cohort, cohort_names) = match params.by {
+        BreakdownBy::RegistrationDate => {
+            let boundary = params.boundary.ok_or(PollError::InvalidRequest)?;
+            (
+                db::VoteCohort::RegistrationDate(boundary),
+                ["registered_before", "registered_after"],
+            )
+        }
+        BreakdownBy::IsFirstVote => (db::VoteCohort::FirstVote, ["first_vote", "returning_voter"]),
+    };
+
     let options = db::get_poll_options(&app_state.db, poll_id)
         .await
-        .map_err(|e| PollError::DatabaseError(e.to_string()))?;
+        .map_err(PollError::from)?;
 
-    let user_voted = db::user_has_voted(&app_state.db, poll_id, user_id)
+    let counts = db::get_vote_breakdown(&app_state.db, poll_id, &cohort)
         .await
-        .unwrap_or(false);
+        .map_err(PollError::from)?;
 
-    let option_responses = options
+    let option_responses: Vec<OptionBreakdownResponse> = options
         .into_iter()
-        .map(|opt| PollOptionWithVotesResponse {
-            id: opt.id,
-            text: opt.option_text,
-            votes: opt.votes as i64,
+        .map(|opt| {
+            let mut cohorts: BTreeMap<String, i64> = cohort_names
+                .iter()
+                .map(|name| (name.to_string(), 0))
+                .collect();
+            for (option_id, cohort, votes) in &counts {
+                if *option_id == opt.id {
+                    cohorts.insert(cohort.clone(), *votes);
+                }
+            }
+            OptionBreakdownResponse {
+                id: opt.id,
+                text: opt.option_text,
+                cohorts,
+            }
         })
         .collect();
 
-    let response = PollResponse {
-        id: poll.id,
-        title: poll.title,
-        description: poll.description,
-        creator_id: poll.creator_id,
-        created_at: poll.created_at.to_rfc3339(),
-        closed: poll.closed,
-        options: option_responses,
-        user_voted,
-        current_user_id: Some(user_id),
-    };
-
-    Ok((StatusCode::OK, Json(response)))
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "poll_id": poll_id,
+            "by": params.by,
+            "boundary": params.boundary,
+            "options": option_responses,
+        })),
+    ))
 }
 
-pub async fn vote_on_poll(
+#[utoipa::path(
+    get,
+    path = "/polls/{poll_id}/history",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path), HistoryParams),
+    responses(
+        (status = 200, description = "Cumulative votes per option over time", body = serde_json::Value,
+            example = json!({
+                "poll_id": "0b9d2d2e-6f6b-4d53-8a53-43e5b3a4c7d1",
+                "interval": "1h",
+                "options": [{ "id": "5f0c7e64-0f5e-4a51-9a1e-2c4ad0f4a8b1", "text": "Yes" }],
+                "points": [{ "at": "2024-05-01T12:00:00Z", "votes": { "5f0c7e64-0f5e-4a51-9a1e-2c4ad0f4a8b1": 4 } }]
+            })),
+        (status = 403, description = "Results are hidden from the caller", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn get_poll_history(
     Extension(app_state): Extension<AppState>,
-    Extension(sse_tx): Extension<SseSender>,
     auth: BearerAuth,
     Path(poll_id): Path<Uuid>,
-    Json(payload): Json<CastVoteRequest>,
+    Query(params): Query<HistoryParams>,
 ) -> Result<impl IntoResponse, PollError> {
-    let user_id = auth.0.sub;
-
     let poll = db::get_poll(&app_state.db, poll_id)
         .await
-        .map_err(|e| PollError::DatabaseError(e.to_string()))?
+        .map_err(PollError::from)?
         .ok_or(PollError::PollNotFound)?;
 
-    if poll.closed {
-        return Err(PollError::PollClosed);
+    require_poll_access(&app_state, &poll, &auth, None).await?;
+    require_visible_results(&app_state, &poll, &auth).await?;
+
+    // Minute buckets are fine-grained enough to line a ballot up with when
+    // someone was seen voting, which anonymous polls must not allow.
+    if poll.anonymous && params.interval == HistoryInterval::Minute {
+        return Err(PollError::InvalidRequest);
     }
 
+    let bucket = match params.interval {
+        HistoryInterval::Minute => db::HistoryBucket::Minute,
+        HistoryInterval::Hour => db::HistoryBucket::Hour,
+    };
+
     let options = db::get_poll_options(&app_state.db, poll_id)
         .await
-        .map_err(|e| PollError::DatabaseError(e.to_string()))?;
-
-    let option_exists = options.iter().any(|opt| opt.id == payload.option_id);
-    if !option_exists {
-        return Err(PollError::OptionNotFound);
-    }
-
-    match db::cast_vote(&app_state.db, poll_id, payload.option_id, user_id).await {
-        Ok(_) => {
-            let updated_options = db::get_poll_options(&app_state.db, poll_id)
-                .await
-                .map_err(|e| PollError::DatabaseError(e.to_string()))?;
+        .map_err(PollError::from)?;
 
-            if let Some(updated_option) = updated_options.iter().find(|o| o.id == payload.option_id)
-            {
-                let _ = sse_tx.send(crate::sse::SseEvent::VoteUpdate(crate::sse::PollUpdate {
-                    poll_id,
-                    option_id: payload.option_id,
-                    new_vote_count: updated_option.votes as i64,
-                }));
-
-                println!(
-                    "✅ Broadcasted vote update for poll {} (option {} has {} votes)",
-                    poll_id, payload.option_id, updated_option.votes
-                );
-            }
+    let counts = db::get_vote_counts_by_bucket(&app_state.db, poll_id, &bucket)
+        .await
+        .map_err(PollError::from)?;
 
-            let response = VoteResponse {
-                success: true,
-                message: "Vote recorded successfully".to_string(),
-            };
-            Ok((StatusCode::OK, Json(response)))
+    // Each point carries the running total for every option, so charts can
+    // plot the series directly without filling gaps.
+    let mut totals: BTreeMap<Uuid, i64> = options.iter().map(|opt| (opt.id, 0)).collect();
+    let mut points: Vec<HistoryPointResponse> = Vec::new();
+    for (at, option_id, votes) in counts {
+        *totals.entry(option_id).or_insert(0) += votes;
+        match points.last_mut() {
+            Some(point) if point.at == at => point.votes = totals.clone(),
+            _ => points.push(HistoryPointResponse {
+                at,
+                votes: totals.clone(),
+            }),
         }
-        Err(sqlx::Error::RowNotFound) => Err(PollError::AlreadyVoted),
-        Err(e) => Err(PollError::DatabaseError(e.to_string())),
     }
+
+    let option_responses: Vec<HistoryOptionResponse> = options
+        .into_iter()
+        .map(|opt| HistoryOptionResponse {
+            id: opt.id,
+            text: opt.option_text,
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "poll_id": poll_id,
+            "interval": params.interval,
+            "options": option_responses,
+            "points": points,
+        })),
+    ))
 }
 
-pub async fn close_poll(
+#[utoipa::path(
+    post,
+    path = "/polls/{poll_id}/tally",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path)),
+    request_body = TallyPollRequest,
+    responses(
+        (status = 200, description = "Decrypted totals for an encrypted-ballot poll", body = serde_json::Value,
+            example = json!({
+                "poll_id": "0b9d2d2e-6f6b-4d53-8a53-43e5b3a4c7d1",
+                "options": [{ "id": "5f0c7e64-0f5e-4a51-9a1e-2c4ad0f4a8b1", "text": "Yes", "votes": 4 }],
+                "total_ballots": 5,
+                "spoiled_ballots": 1
+            })),
+        (status = 400, description = "Invalid key, or poll still open", body = ErrorResponse),
+        (status = 401, description = "Not the poll's creator", body = ErrorResponse),
+        (status = 404, description = "Poll not found", body = ErrorResponse),
+        (status = 409, description = "Already tallied", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn tally_poll(
     Extension(app_state): Extension<AppState>,
-    Extension(sse_tx): Extension<SseSender>,
     auth: BearerAuth,
     Path(poll_id): Path<Uuid>,
+    Json(payload): Json<TallyPollRequest>,
 ) -> Result<impl IntoResponse, PollError> {
     let user_id = auth.0.sub;
 
     let poll = db::get_poll(&app_state.db, poll_id)
         .await
-        .map_err(|e| PollError::DatabaseError(e.to_string()))?
+        .map_err(PollError::from)?
         .ok_or(PollError::PollNotFound)?;
 
     if poll.creator_id != user_id {
         return Err(PollError::Unauthorized);
     }
 
-    db::close_poll(&app_state.db, poll_id)
+    let public_key = poll
+        .ballot_public_key
+        .as_deref()
+        .ok_or(PollError::InvalidRequest)?;
+
+    if !poll.is_closed() {
+        return Err(PollError::PollStillOpen);
+    }
+
+    if poll.tallied {
+        return Err(PollError::AlreadyTallied);
+    }
+
+    let private_key = ballots::parse_private_key(&payload.private_key, public_key)
+        .ok_or(PollError::InvalidRequest)?;
+
+    let options = db::get_poll_options(&app_state.db, poll_id)
         .await
-        .map_err(|e| PollError::DatabaseError(e.to_string()))?;
+        .map_err(PollError::from)?;
 
-    let _ = sse_tx.send(SseEvent::PollClosed(poll_id));
+    let encrypted_ballots = db::get_encrypted_ballots(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?;
+
+    let mut counts: Vec<(Uuid, i32)> = options.iter().map(|opt| (opt.id, 0)).collect();
+    let mut spoiled = 0;
+    for ballot in &encrypted_ballots {
+        let choice = ballots::decrypt_ballot(&private_key, ballot)
+            .and_then(|option_id| counts.iter_mut().find(|(id, _)| *id == option_id));
+        match choice {
+            Some((_, votes)) => *votes += 1,
+            None => spoiled += 1,
+        }
+    }
+
+    db::record_tally(&app_state.db, poll_id, &counts)
+        .await
+        .map_err(PollError::from)?;
+    app_state.read_cache.invalidate_poll(poll_id);
+
+    let option_responses: Vec<PollOptionWithVotesResponse> = options
+        .into_iter()
+        .zip(counts)
+        .map(|(opt, (_, votes))| PollOptionWithVotesResponse {
+            id: opt.id,
+            text: opt.option_text,
+            votes: Some(votes as i64),
+            allows_write_in: opt.allows_write_in,
+        })
+        .collect();
 
     Ok((
         StatusCode::OK,
         Json(json!({
-            "success": true,
-            "message": "Poll closed successfully"
+            "poll_id": poll_id,
+            "options": option_responses,
+            "total_ballots": encrypted_ballots.len(),
+            "spoiled_ballots": spoiled,
         })),
     ))
 }
 
-pub async fn restart_poll(
+#[utoipa::path(
+    get,
+    path = "/polls/{poll_id}/options/{option_id}/write-ins",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path), ("option_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "Write-in answers with how often each was given", body = Vec<WriteInResponse>),
+        (status = 401, description = "Not the poll's creator", body = ErrorResponse),
+        (status = 404, description = "Poll or write-in option not found", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn get_option_write_ins(
     Extension(app_state): Extension<AppState>,
-    Extension(sse_tx): Extension<SseSender>,
     auth: BearerAuth,
-    Path(poll_id): Path<Uuid>,
+    Path((poll_id, option_id)): Path<(Uuid, Uuid)>,
 ) -> Result<impl IntoResponse, PollError> {
     let user_id = auth.0.sub;
 
     let poll = db::get_poll(&app_state.db, poll_id)
         .await
-        .map_err(|e| PollError::DatabaseError(e.to_string()))?
+        .map_err(PollError::from)?
         .ok_or(PollError::PollNotFound)?;
 
     if poll.creator_id != user_id {
         return Err(PollError::Unauthorized);
     }
 
-    db::restart_poll(&app_state.db, poll_id)
+    let options = db::get_poll_options(&app_state.db, poll_id)
         .await
-        .map_err(|e| PollError::DatabaseError(e.to_string()))?;
+        .map_err(PollError::from)?;
 
-    let _ = sse_tx.send(SseEvent::PollCreated(crate::sse::PollCreated {
-        poll_id,
-        title: poll.title,
-        creator_id: poll.creator_id,
-    }));
+    if !options
+        .iter()
+        .any(|opt| opt.id == option_id && opt.allows_write_in)
+    {
+        return Err(PollError::OptionNotFound);
+    }
 
-    Ok((
-        StatusCode::OK,
-        Json(json!({
-            "success": true,
-            "message": "Poll restarted successfully"
-        })),
-    ))
+    let write_ins: Vec<WriteInResponse> = db::get_write_ins(&app_state.db, option_id)
+        .await
+        .map_err(PollError::from)?
+        .into_iter()
+        .map(|(text, count)| WriteInResponse { text, count })
+        .collect();
+
+    Ok((StatusCode::OK, Json(write_ins)))
 }
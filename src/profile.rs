@@ -0,0 +1,225 @@
+use crate::auth::BearerAuth;
+use crate::db;
+use crate::email::{check_verification_cooldown, normalize_email, send_verification_email};
+use crate::error::PollError;
+use crate::polls::{broadcast_vote_updates, voter_key};
+use crate::sse::{SseEvent, SseSender};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::info;
+use uuid::Uuid;
+use webauthn_rs::prelude::Url;
+
+const MAX_DISPLAY_NAME_LENGTH: usize = 64;
+const MAX_AVATAR_URL_LENGTH: usize = 2048;
+
+#[derive(Debug, Serialize)]
+pub struct ProfileResponse {
+    pub id: Uuid,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub email_notifications: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub passkey_count: i64,
+    pub polls_created: i64,
+    pub votes_cast: i64,
+}
+
+// An empty string clears the field; an omitted field is left as it is.
+#[derive(Debug, Deserialize)]
+pub struct UpdateProfileRequest {
+    pub display_name: Option<String>,
+    pub avatar_url: Option<String>,
+    pub email: Option<String>,
+    pub email_notifications: Option<bool>,
+}
+
+fn validate_display_name(display_name: &str) -> Result<Option<&str>, PollError> {
+    let display_name = display_name.trim();
+
+    if display_name.chars().count() > MAX_DISPLAY_NAME_LENGTH
+        || display_name.chars().any(char::is_control)
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    Ok(Some(display_name).filter(|name| !name.is_empty()))
+}
+
+fn validate_avatar_url(avatar_url: &str) -> Result<Option<&str>, PollError> {
+    let avatar_url = avatar_url.trim();
+
+    if avatar_url.is_empty() {
+        return Ok(None);
+    }
+
+    let valid = avatar_url.len() <= MAX_AVATAR_URL_LENGTH
+        && Url::parse(avatar_url)
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host());
+
+    if !valid {
+        return Err(PollError::InvalidRequest);
+    }
+
+    Ok(Some(avatar_url))
+}
+
+fn validate_email(email: &str) -> Result<Option<String>, PollError> {
+    if email.trim().is_empty() {
+        return Ok(None);
+    }
+
+    normalize_email(email)
+        .map(Some)
+        .ok_or(PollError::InvalidEmail)
+}
+
+async fn load_profile(app_state: &AppState, user_id: Uuid) -> Result<ProfileResponse, PollError> {
+    let profile = db::get_user_profile(&app_state.db, user_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::UserNotFound)?;
+
+    let votes_cast = db::count_votes_for_user(
+        &app_state.db,
+        user_id,
+        &voter_key(&app_state.config, user_id),
+    )
+    .await
+    .map_err(PollError::from)?;
+
+    Ok(ProfileResponse {
+        id: profile.id,
+        username: profile.username,
+        display_name: profile.display_name,
+        avatar_url: profile.avatar_url,
+        email: profile.email,
+        email_verified: profile.email_verified_at.is_some(),
+        email_notifications: profile.email_notifications,
+        created_at: profile.created_at.map(|t| t.and_utc()),
+        passkey_count: profile.passkey_count,
+        polls_created: profile.polls_created,
+        votes_cast,
+    })
+}
+
+pub async fn get_profile(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+) -> Result<impl IntoResponse, PollError> {
+    let profile = load_profile(&app_state, auth.0.sub).await?;
+
+    Ok((StatusCode::OK, Json(profile)))
+}
+
+pub async fn update_profile(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Json(payload): Json<UpdateProfileRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let display_name = payload
+        .display_name
+        .as_deref()
+        .map(validate_display_name)
+        .transpose()?;
+    let avatar_url = payload
+        .avatar_url
+        .as_deref()
+        .map(validate_avatar_url)
+        .transpose()?;
+    let email = payload.email.as_deref().map(validate_email).transpose()?;
+
+    let current = db::get_user_profile(&app_state.db, user_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::UserNotFound)?;
+
+    let new_email = email.filter(|email| *email != current.email);
+    if let Some(Some(_)) = &new_email {
+        check_verification_cooldown(&app_state, user_id).await?;
+    }
+
+    let updated = db::update_user_profile(
+        &app_state.db,
+        user_id,
+        display_name,
+        avatar_url,
+        payload.email_notifications,
+    )
+    .await
+    .map_err(PollError::from)?;
+
+    if !updated {
+        return Err(PollError::UserNotFound);
+    }
+
+    if let Some(new_email) = new_email {
+        db::set_user_email(&app_state.db, user_id, new_email.as_deref())
+            .await
+            .map_err(PollError::from)?;
+
+        if let Some(email) = &new_email {
+            send_verification_email(&app_state, user_id, &current.username, email).await?;
+        }
+    }
+
+    let profile = load_profile(&app_state, user_id).await?;
+
+    Ok((StatusCode::OK, Json(profile)))
+}
+
+pub async fn delete_account(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let deletion = db::delete_user_account(
+        &app_state.db,
+        user_id,
+        &voter_key(&app_state.config, user_id),
+    )
+    .await
+    .map_err(PollError::from)?
+    .ok_or(PollError::UserNotFound)?;
+
+    for poll_id in &deletion.deleted_polls {
+        let _ = sse_tx.send(SseEvent::PollDeleted(*poll_id));
+    }
+
+    let mut options_by_poll: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    for (poll_id, option_id) in &deletion.affected_options {
+        options_by_poll
+            .entry(*poll_id)
+            .or_default()
+            .push(*option_id);
+    }
+
+    for (poll_id, option_ids) in options_by_poll {
+        broadcast_vote_updates(&app_state, &sse_tx, poll_id, &option_ids).await?;
+    }
+
+    info!("User {} deleted their account", auth.0.username);
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "deleted_polls": deletion.deleted_polls.len(),
+        })),
+    ))
+}
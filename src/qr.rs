@@ -0,0 +1,141 @@
+use crate::access::verify_invite_token;
+use crate::db;
+use crate::error::PollError;
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Path, Query},
+    http::{
+        StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE},
+    },
+    response::IntoResponse,
+};
+use qrcode::{Color, EcLevel, QrCode};
+use serde::Deserialize;
+use tracing::error;
+use uuid::Uuid;
+
+const DEFAULT_QR_SIZE: u32 = 512;
+const MIN_QR_SIZE: u32 = 64;
+const MAX_QR_SIZE: u32 = 2048;
+// Scanners expect a light border four modules wide.
+const QUIET_ZONE_MODULES: usize = 4;
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrErrorCorrection {
+    L,
+    #[default]
+    M,
+    Q,
+    H,
+}
+
+impl From<QrErrorCorrection> for EcLevel {
+    fn from(level: QrErrorCorrection) -> Self {
+        match level {
+            QrErrorCorrection::L => EcLevel::L,
+            QrErrorCorrection::M => EcLevel::M,
+            QrErrorCorrection::Q => EcLevel::Q,
+            QrErrorCorrection::H => EcLevel::H,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QrParams {
+    pub size: Option<u32>,
+    #[serde(default)]
+    pub ecc: QrErrorCorrection,
+    pub token: Option<String>,
+}
+
+// Modules are drawn as whole pixel squares, so the image comes out at the
+// largest multiple of the code width that fits in `size`.
+fn render_png(code: &QrCode, size: u32) -> Result<Vec<u8>, png::EncodingError> {
+    let modules = code.width();
+    let colors = code.to_colors();
+    let span = modules + 2 * QUIET_ZONE_MODULES;
+    let scale = (size as usize / span).max(1);
+    let dimension = span * scale;
+
+    let mut pixels = vec![255u8; dimension * dimension];
+    for (index, color) in colors.iter().enumerate() {
+        if *color != Color::Dark {
+            continue;
+        }
+
+        let x = (index % modules + QUIET_ZONE_MODULES) * scale;
+        let y = (index / modules + QUIET_ZONE_MODULES) * scale;
+        for row in y..y + scale {
+            pixels[row * dimension + x..row * dimension + x + scale].fill(0);
+        }
+    }
+
+    let mut buffer = Vec::new();
+    let mut encoder = png::Encoder::new(&mut buffer, dimension as u32, dimension as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+
+    Ok(buffer)
+}
+
+// Meant to be used directly as an <img> source, so no bearer token is needed.
+// The image only encodes a link to a poll the caller already knows the id of.
+// Private polls need an invite token, which is carried into the link so
+// people scanning it can open the poll.
+pub async fn poll_qr_code(
+    Extension(app_state): Extension<AppState>,
+    Path(poll_id): Path<Uuid>,
+    Query(params): Query<QrParams>,
+) -> Result<impl IntoResponse, PollError> {
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    let mut link = format!(
+        "{}/polls/{}",
+        app_state.config.frontend_url.as_str().trim_end_matches('/'),
+        poll.id
+    );
+
+    if poll.is_private() {
+        let token = params
+            .token
+            .as_deref()
+            .filter(|token| verify_invite_token(token, poll.id, &app_state.config))
+            .ok_or(PollError::PollNotFound)?;
+        link = format!("{}?token={}", link, token);
+    }
+
+    let size = params
+        .size
+        .unwrap_or(DEFAULT_QR_SIZE)
+        .clamp(MIN_QR_SIZE, MAX_QR_SIZE);
+
+    let image = QrCode::with_error_correction_level(link.as_bytes(), params.ecc.into())
+        .map_err(|e| {
+            error!("Error encoding QR code for poll {}: {:?}", poll_id, e);
+            PollError::QrCodeError
+        })
+        .and_then(|code| {
+            render_png(&code, size).map_err(|e| {
+                error!("Error rendering QR code for poll {}: {:?}", poll_id, e);
+                PollError::QrCodeError
+            })
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        [
+            (CONTENT_TYPE, "image/png"),
+            (CACHE_CONTROL, "private, max-age=3600"),
+        ],
+        image,
+    ))
+}
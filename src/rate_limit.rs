@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Expired windows are swept once the map grows past this many keys.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+pub struct RateLimiter<K> {
+    limit: u32,
+    window: Duration,
+    buckets: Mutex<HashMap<K, (Instant, u32)>>,
+}
+
+impl<K: Hash + Eq> RateLimiter<K> {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn check(&self, key: K) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+        }
+
+        let (start, count) = buckets.entry(key).or_insert((now, 0));
+        if now.duration_since(*start) >= self.window {
+            *start = now;
+            *count = 0;
+        }
+
+        if *count >= self.limit {
+            return Err(self.window - now.duration_since(*start));
+        }
+
+        *count += 1;
+        Ok(())
+    }
+
+    // Like `check`, without counting this call: for limits on failures, which
+    // are only recorded once they happen.
+    pub fn peek(&self, key: &K) -> Result<(), Duration> {
+        let now = Instant::now();
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        match buckets.get(key) {
+            Some((start, count))
+                if now.duration_since(*start) < self.window && *count >= self.limit =>
+            {
+                Err(self.window - now.duration_since(*start))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    const WINDOW: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn requests_over_the_limit_wait_for_the_window() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+
+        assert!(limiter.check("alice").is_ok());
+        assert!(limiter.check("alice").is_ok());
+        let retry_after = limiter.check("alice").unwrap_err();
+        assert!(retry_after > Duration::from_secs(59), "{:?}", retry_after);
+
+        // Other keys have their own budget.
+        assert!(limiter.check("bob").is_ok());
+    }
+
+    #[test]
+    fn peeking_does_not_count() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+
+        assert!(limiter.peek(&"alice").is_ok());
+        assert!(limiter.peek(&"alice").is_ok());
+        assert!(limiter.check("alice").is_ok());
+        assert!(limiter.peek(&"alice").is_err());
+    }
+
+    #[test]
+    fn an_expired_window_starts_over() {
+        let limiter = RateLimiter::new(1, WINDOW);
+
+        assert!(limiter.check("alice").is_ok());
+        assert!(limiter.check("alice").is_err());
+
+        sleep(WINDOW);
+        assert!(limiter.check("alice").is_ok());
+        assert!(limiter.check("alice").is_err());
+    }
+
+    #[test]
+    fn expired_keys_are_pruned_past_the_threshold() {
+        let limiter = RateLimiter::new(1, WINDOW);
+        for key in 0..=PRUNE_THRESHOLD {
+            limiter.check(key).unwrap();
+        }
+        assert_eq!(limiter.buckets.lock().unwrap().len(), PRUNE_THRESHOLD + 1);
+
+        sleep(WINDOW);
+        limiter.check(PRUNE_THRESHOLD + 1).unwrap();
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn live_windows_survive_a_prune() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        for key in 0..=PRUNE_THRESHOLD {
+            limiter.check(key).unwrap();
+        }
+
+        limiter.check(PRUNE_THRESHOLD + 1).unwrap();
+        assert_eq!(limiter.buckets.lock().unwrap().len(), PRUNE_THRESHOLD + 2);
+        assert!(limiter.check(0).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::access::require_poll_access;
+use crate::auth::BearerAuth;
+use crate::db;
+use crate::error::{ErrorResponse, PollError};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Utc};
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VoteReceipt {
+    pub receipt: String,
+    pub issued_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReceiptVerification {
+    pub poll_id: Uuid,
+    pub issued_at: DateTime<Utc>,
+}
+
+// HMAC-SHA256 over the poll, option, voter and issue time. Anonymous votes use
+// the voter hash in place of the user id, so neither the receipt nor the row
+// ties the vote to an account.
+fn sign(
+    secret: &str,
+    poll_id: Uuid,
+    option_id: Option<Uuid>,
+    voter: &str,
+    issued_at: DateTime<Utc>,
+) -> Option<String> {
+    let key = PKey::hmac(secret.as_bytes()).ok()?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).ok()?;
+    let option = option_id.map_or_else(|| "-".to_string(), |id| id.to_string());
+    signer
+        .update(
+            format!(
+                "{}:{}:{}:{}",
+                poll_id,
+                option,
+                voter,
+                issued_at.timestamp_micros()
+            )
+            .as_bytes(),
+        )
+        .ok()?;
+
+    Some(URL_SAFE_NO_PAD.encode(signer.sign_to_vec().ok()?))
+}
+
+fn voter_key(voter: db::Voter<'_>) -> String {
+    match voter {
+        db::Voter::User(user_id) => user_id.to_string(),
+        db::Voter::Anonymous(voter_hash) => voter_hash.to_string(),
+    }
+}
+
+// Issuing a new receipt for the same vote row replaces the old one, so after
+// a vote change only the latest receipt verifies.
+pub async fn issue_receipt(
+    app_state: &AppState,
+    poll_id: Uuid,
+    option_id: Option<Uuid>,
+    voter: db::Voter<'_>,
+) -> Result<Option<VoteReceipt>, PollError> {
+    // Postgres keeps microseconds; truncate so the stored time signs the same.
+    let issued_at = DateTime::from_timestamp_micros(Utc::now().timestamp_micros())
+        .ok_or(PollError::ReceiptCreationError)?;
+
+    let receipt = sign(
+        &app_state.config.vote_receipt_secret,
+        poll_id,
+        option_id,
+        &voter_key(voter),
+        issued_at,
+    )
+    .ok_or_else(|| {
+        error!("Error signing vote receipt for poll {}", poll_id);
+        PollError::ReceiptCreationError
+    })?;
+
+    let stored = db::store_vote_receipt(
+        &app_state.db,
+        poll_id,
+        option_id,
+        voter,
+        &receipt,
+        issued_at,
+    )
+    .await
+    .map_err(PollError::from)?;
+
+    Ok(stored.then_some(VoteReceipt { receipt, issued_at }))
+}
+
+// The receipt only says that this exact ballot is still counted; it reveals
+// nothing about other votes, and nothing about this one beyond that.
+#[utoipa::path(
+    get,
+    path = "/polls/{poll_id}/receipts/{receipt}",
+    tag = "polls",
+    params(("poll_id" = Uuid, Path), ("receipt" = String, Path)),
+    responses(
+        (status = 200, description = "The ballot behind this receipt is recorded", body = ReceiptVerification),
+        (status = 404, description = "No counted ballot matches this receipt", body = ErrorResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+pub async fn verify_receipt(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path((poll_id, receipt)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse, PollError> {
+    let poll = db::get_poll(&app_state.db, poll_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::PollNotFound)?;
+
+    require_poll_access(&app_state, &poll, &auth, None).await?;
+
+    let vote = db::get_receipted_vote(&app_state.db, poll_id, &receipt)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::ReceiptNotFound)?;
+
+    let voter = match (vote.user_id, vote.voter_hash) {
+        (_, Some(voter_hash)) => voter_hash,
+        (Some(user_id), None) => user_id.to_string(),
+        (None, None) => return Err(PollError::ReceiptNotFound),
+    };
+
+    // A changed vote keeps the row but not the option the receipt was for.
+    let expected = sign(
+        &app_state.config.vote_receipt_secret,
+        poll_id,
+        vote.option_id,
+        &voter,
+        vote.issued_at,
+    )
+    .ok_or(PollError::ReceiptCreationError)?;
+
+    if expected.len() != receipt.len() || !memcmp::eq(expected.as_bytes(), receipt.as_bytes()) {
+        return Err(PollError::ReceiptNotFound);
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(ReceiptVerification {
+            poll_id,
+            issued_at: vote.issued_at,
+        }),
+    ))
+}
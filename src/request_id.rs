@@ -0,0 +1,55 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{Instrument, info_span};
+use uuid::Uuid;
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// The id of the request currently being handled, for error bodies.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+// An id supplied by a proxy or client is kept so logs on both sides line up,
+// as long as it's short and plain enough to echo back.
+fn incoming_id(request: &Request) -> Option<String> {
+    let id = request.headers().get(&X_REQUEST_ID)?.to_str().ok()?;
+
+    let valid = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    valid.then(|| id.to_string())
+}
+
+pub async fn propagate(mut request: Request, next: Next) -> Response {
+    let request_id = incoming_id(&request).unwrap_or_else(|| Uuid::new_v4().to_string());
+    let header = HeaderValue::from_str(&request_id).expect("request id is a valid header value");
+    request.headers_mut().insert(X_REQUEST_ID, header.clone());
+
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+
+    let mut response = REQUEST_ID
+        .scope(request_id, next.run(request).instrument(span))
+        .await;
+    response.headers_mut().insert(X_REQUEST_ID, header);
+
+    response
+}
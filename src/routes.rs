@@ -0,0 +1,404 @@
+use crate::access::{create_poll_invite, send_vote_links, unlock_poll, vote_with_link};
+use crate::admin::{
+    admin_overview, ban_user, cleanup_orphans, delete_any_poll, list_reports, list_users,
+    resolve_report, set_poll_creation_permission, verify_poll_counts,
+};
+use crate::api_keys::{self, create_api_key, delete_api_key, list_api_keys};
+use crate::auth::{
+    authenticate_user, check_username_available, delete_credential, finish_authentication,
+    finish_discoverable_authentication, finish_register, list_credential_details, list_credentials,
+    logout, refresh_session, register_user, start_authentication,
+    start_discoverable_authentication, start_register,
+};
+use crate::comments::{create_comment, delete_comment, list_comments};
+use crate::cors;
+use crate::csrf::{self, issue_csrf_token};
+use crate::email::{resend_verification_email, verify_email};
+use crate::exports::export_poll;
+use crate::oauth::{finish_oidc_login, start_oidc_login};
+use crate::openapi::{openapi_json, swagger_ui};
+use crate::polls::{
+    archive_poll, bulk_create_polls, close_poll, create_poll, delete_poll, edit_poll, get_my_polls,
+    get_my_votes, get_option_write_ins, get_poll, get_poll_breakdown, get_poll_definition,
+    get_poll_history, import_poll, list_polls, list_tags, pin_poll_in_tag, report_poll,
+    restart_poll, restore_poll, retract_vote, search_polls, tally_poll, trending_polls,
+    unpin_poll_in_tag, vote_on_poll,
+};
+use crate::profile::{delete_account, get_profile, update_profile};
+use crate::qr::poll_qr_code;
+use crate::receipts::verify_receipt;
+use crate::request_id;
+use crate::session::session_layer;
+use crate::sse::{SseSender, all_polls_sse, poll_updates_sse};
+use crate::startup::AppState;
+use crate::surveys::{create_survey, get_survey, submit_survey};
+use crate::webhooks::{create_webhook, delete_webhook, list_webhook_deliveries, list_webhooks};
+use axum::{
+    Json, Router,
+    extract::Extension,
+    http::{
+        StatusCode,
+        header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
+    },
+    middleware,
+    response::IntoResponse,
+    routing::{get, options},
+};
+use chrono::Utc;
+use serde_json::json;
+use std::time::Duration;
+use tower_http::cors::CorsLayer;
+use tower_http::timeout::TimeoutLayer;
+
+pub fn build_router(app_state: AppState, sse_tx: SseSender) -> Router {
+    Router::new()
+        .route(
+            "/register_start/:username",
+            options(|| async { (StatusCode::OK, "") }).post(start_register),
+        )
+        .route(
+            "/register_finish",
+            options(|| async { (StatusCode::OK, "") }).post(finish_register),
+        )
+        .route(
+            "/login_start/:username",
+            options(|| async { (StatusCode::OK, "") }).post(start_authentication),
+        )
+        .route(
+            "/login_finish",
+            options(|| async { (StatusCode::OK, "") }).post(finish_authentication),
+        )
+        .route(
+            "/login_start_discoverable",
+            options(|| async { (StatusCode::OK, "") }).post(start_discoverable_authentication),
+        )
+        .route(
+            "/login_finish_discoverable",
+            options(|| async { (StatusCode::OK, "") }).post(finish_discoverable_authentication),
+        )
+        .route(
+            "/register",
+            options(|| async { (StatusCode::OK, "") }).post(register_user),
+        )
+        .route(
+            "/login",
+            options(|| async { (StatusCode::OK, "") }).post(authenticate_user),
+        )
+        .route(
+            "/auth/refresh",
+            options(|| async { (StatusCode::OK, "") }).post(refresh_session),
+        )
+        .route(
+            "/auth/oidc/start",
+            options(|| async { (StatusCode::OK, "") }).post(start_oidc_login),
+        )
+        .route(
+            "/auth/oidc/finish",
+            options(|| async { (StatusCode::OK, "") }).post(finish_oidc_login),
+        )
+        .route(
+            "/auth/verify-email",
+            options(|| async { (StatusCode::OK, "") }).post(verify_email),
+        )
+        .route(
+            "/logout",
+            options(|| async { (StatusCode::OK, "") }).post(logout),
+        )
+        .route(
+            "/username/available/:username",
+            options(|| async { (StatusCode::OK, "") }).get(check_username_available),
+        )
+        .route(
+            "/credentials",
+            options(|| async { (StatusCode::OK, "") }).get(list_credentials),
+        )
+        .route(
+            "/credentials/:cred_id",
+            options(|| async { (StatusCode::OK, "") }).delete(delete_credential),
+        )
+        .route(
+            "/me",
+            options(|| async { (StatusCode::OK, "") })
+                .get(get_profile)
+                .patch(update_profile)
+                .delete(delete_account),
+        )
+        .route(
+            "/me/email/verification",
+            options(|| async { (StatusCode::OK, "") }).post(resend_verification_email),
+        )
+        .route(
+            "/me/polls",
+            options(|| async { (StatusCode::OK, "") }).get(get_my_polls),
+        )
+        .route(
+            "/me/votes",
+            options(|| async { (StatusCode::OK, "") }).get(get_my_votes),
+        )
+        .route(
+            "/me/api-keys",
+            options(|| async { (StatusCode::OK, "") })
+                .get(list_api_keys)
+                .post(create_api_key),
+        )
+        .route(
+            "/me/api-keys/:key_id",
+            options(|| async { (StatusCode::OK, "") }).delete(delete_api_key),
+        )
+        .route(
+            "/me/credentials/details",
+            options(|| async { (StatusCode::OK, "") }).get(list_credential_details),
+        )
+        .route(
+            "/polls",
+            options(|| async { (StatusCode::OK, "") })
+                .post(create_poll)
+                .get(list_polls),
+        )
+        .route(
+            "/polls/:poll_id",
+            options(|| async { (StatusCode::OK, "") })
+                .get(get_poll)
+                .patch(edit_poll)
+                .delete(delete_poll),
+        )
+        .route(
+            "/polls/:poll_id/receipts/:receipt",
+            options(|| async { (StatusCode::OK, "") }).get(verify_receipt),
+        )
+        .route(
+            "/polls/bulk",
+            options(|| async { (StatusCode::OK, "") }).post(bulk_create_polls),
+        )
+        .route(
+            "/search",
+            options(|| async { (StatusCode::OK, "") }).get(search_polls),
+        )
+        .route(
+            "/polls/trending",
+            options(|| async { (StatusCode::OK, "") }).get(trending_polls),
+        )
+        .route(
+            "/polls/:poll_id/definition",
+            options(|| async { (StatusCode::OK, "") }).get(get_poll_definition),
+        )
+        .route(
+            "/polls/import",
+            options(|| async { (StatusCode::OK, "") }).post(import_poll),
+        )
+        .route(
+            "/polls/:poll_id/invites",
+            options(|| async { (StatusCode::OK, "") }).post(create_poll_invite),
+        )
+        .route(
+            "/polls/:poll_id/qr.png",
+            options(|| async { (StatusCode::OK, "") }).get(poll_qr_code),
+        )
+        .route(
+            "/polls/:poll_id/access",
+            options(|| async { (StatusCode::OK, "") }).post(unlock_poll),
+        )
+        .route(
+            "/polls/:poll_id/invite",
+            options(|| async { (StatusCode::OK, "") }).post(send_vote_links),
+        )
+        .route(
+            "/polls/:poll_id/vote/magic",
+            options(|| async { (StatusCode::OK, "") }).post(vote_with_link),
+        )
+        .route(
+            "/webhooks",
+            options(|| async { (StatusCode::OK, "") })
+                .get(list_webhooks)
+                .post(create_webhook),
+        )
+        .route(
+            "/webhooks/:webhook_id",
+            options(|| async { (StatusCode::OK, "") }).delete(delete_webhook),
+        )
+        .route(
+            "/webhooks/:webhook_id/deliveries",
+            options(|| async { (StatusCode::OK, "") }).get(list_webhook_deliveries),
+        )
+        .route(
+            "/polls/:poll_id/vote",
+            options(|| async { (StatusCode::OK, "") })
+                .post(vote_on_poll)
+                .delete(retract_vote),
+        )
+        .route(
+            "/polls/:poll_id/close",
+            options(|| async { (StatusCode::OK, "") }).post(close_poll),
+        )
+        .route(
+            "/polls/:poll_id/restart",
+            options(|| async { (StatusCode::OK, "") }).post(restart_poll),
+        )
+        .route(
+            "/polls/:poll_id/archive",
+            options(|| async { (StatusCode::OK, "") })
+                .post(archive_poll)
+                .delete(restore_poll),
+        )
+        .route(
+            "/polls/:poll_id/tags/:tag/pin",
+            options(|| async { (StatusCode::OK, "") })
+                .post(pin_poll_in_tag)
+                .delete(unpin_poll_in_tag),
+        )
+        .route(
+            "/polls/:poll_id/breakdown",
+            options(|| async { (StatusCode::OK, "") }).get(get_poll_breakdown),
+        )
+        .route(
+            "/polls/:poll_id/history",
+            options(|| async { (StatusCode::OK, "") }).get(get_poll_history),
+        )
+        .route(
+            "/polls/:poll_id/tally",
+            options(|| async { (StatusCode::OK, "") }).post(tally_poll),
+        )
+        .route(
+            "/polls/:poll_id/options/:option_id/write-ins",
+            options(|| async { (StatusCode::OK, "") }).get(get_option_write_ins),
+        )
+        .route(
+            "/polls/:poll_id/comments",
+            options(|| async { (StatusCode::OK, "") })
+                .get(list_comments)
+                .post(create_comment),
+        )
+        .route(
+            "/comments/:comment_id",
+            options(|| async { (StatusCode::OK, "") }).delete(delete_comment),
+        )
+        .route(
+            "/polls/:poll_id/export",
+            options(|| async { (StatusCode::OK, "") }).get(export_poll),
+        )
+        .route(
+            "/polls/:poll_id/report",
+            options(|| async { (StatusCode::OK, "") }).post(report_poll),
+        )
+        .route(
+            "/polls/:poll_id/sse",
+            options(|| async { (StatusCode::OK, "") }).get(poll_updates_sse),
+        )
+        .route(
+            "/polls/sse",
+            options(|| async { (StatusCode::OK, "") }).get(all_polls_sse),
+        )
+        .route(
+            "/tags",
+            options(|| async { (StatusCode::OK, "") }).get(list_tags),
+        )
+        .route(
+            "/surveys",
+            options(|| async { (StatusCode::OK, "") }).post(create_survey),
+        )
+        .route(
+            "/surveys/:survey_id",
+            options(|| async { (StatusCode::OK, "") }).get(get_survey),
+        )
+        .route(
+            "/surveys/:survey_id/submit",
+            options(|| async { (StatusCode::OK, "") }).post(submit_survey),
+        )
+        .route(
+            "/admin/reports",
+            options(|| async { (StatusCode::OK, "") }).get(list_reports),
+        )
+        .route(
+            "/admin/reports/:report_id/resolve",
+            options(|| async { (StatusCode::OK, "") }).post(resolve_report),
+        )
+        .route(
+            "/admin/users",
+            options(|| async { (StatusCode::OK, "") }).get(list_users),
+        )
+        .route(
+            "/admin/users/:user_id/ban",
+            options(|| async { (StatusCode::OK, "") }).post(ban_user),
+        )
+        .route(
+            "/admin/polls/:poll_id",
+            options(|| async { (StatusCode::OK, "") }).delete(delete_any_poll),
+        )
+        .route(
+            "/admin/polls/:poll_id/verify",
+            options(|| async { (StatusCode::OK, "") }).get(verify_poll_counts),
+        )
+        .route(
+            "/admin/users/:user_id/poll-creation",
+            options(|| async { (StatusCode::OK, "") }).post(set_poll_creation_permission),
+        )
+        .route(
+            "/admin/overview",
+            options(|| async { (StatusCode::OK, "") }).get(admin_overview),
+        )
+        .route(
+            "/admin/maintenance/orphans",
+            options(|| async { (StatusCode::OK, "") }).post(cleanup_orphans),
+        )
+        .route(
+            "/csrf",
+            options(|| async { (StatusCode::OK, "") }).get(issue_csrf_token),
+        )
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(swagger_ui))
+        .route("/time", get(server_time))
+        .layer(
+            CorsLayer::new()
+                .allow_origin(cors::allow_origin(app_state.config.cors_origins.clone()))
+                .allow_credentials(true)
+                .allow_methods([
+                    axum::http::Method::GET,
+                    axum::http::Method::POST,
+                    axum::http::Method::PUT,
+                    axum::http::Method::DELETE,
+                    axum::http::Method::OPTIONS,
+                    axum::http::Method::PATCH,
+                    axum::http::Method::HEAD,
+                ])
+                .allow_headers([
+                    CONTENT_TYPE,
+                    ACCEPT,
+                    AUTHORIZATION,
+                    axum::http::header::ORIGIN,
+                    axum::http::header::COOKIE,
+                    csrf::X_CSRF_TOKEN,
+                    api_keys::X_API_KEY,
+                    axum::http::header::IF_NONE_MATCH,
+                ])
+                .expose_headers([
+                    axum::http::header::CONTENT_TYPE,
+                    AUTHORIZATION,
+                    axum::http::header::SET_COOKIE,
+                    request_id::X_REQUEST_ID,
+                    axum::http::header::ETAG,
+                ])
+                .max_age(Duration::from_secs(86400)),
+        )
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            Duration::from_hours(24 * 30),
+        ))
+        .layer(session_layer(&app_state.db, &app_state.config))
+        .layer(Extension(app_state))
+        .layer(Extension(sse_tx))
+        .layer(middleware::from_fn(request_id::propagate))
+}
+
+#[allow(dead_code)]
+async fn handler_404() -> impl IntoResponse {
+    (StatusCode::NOT_FOUND, "nothing to see here")
+}
+
+async fn server_time() -> impl IntoResponse {
+    let now = Utc::now();
+
+    Json(json!({
+        "now": now.to_rfc3339(),
+        "unix_ms": now.timestamp_millis(),
+    }))
+}
@@ -0,0 +1,143 @@
+use crate::auth::Claims;
+use crate::config::Config;
+use crate::db;
+use crate::db::connection::DbPool;
+use crate::error::WebauthnError;
+use axum::http::{HeaderMap, HeaderValue, header::COOKIE};
+use time::Duration;
+use tower_sessions::cookie::{Cookie, CookieBuilder, SameSite};
+use tower_sessions::{Expiry, Session, SessionManagerLayer};
+use tower_sessions_sqlx_store::PostgresStore;
+use tracing::error;
+use uuid::Uuid;
+
+pub const SESSION_COOKIE: &str = "session";
+const USER_ID_KEY: &str = "user_id";
+
+// Browser clients can sign in with a session cookie instead of keeping the
+// JWT pair around. The table comes from migrations, not the store's own
+// migrate().
+pub fn session_layer(db: &DbPool, config: &Config) -> SessionManagerLayer<PostgresStore> {
+    let store = PostgresStore::new(db.clone())
+        .with_schema_name("public")
+        .and_then(|store| store.with_table_name("sessions"))
+        .expect("Invalid session table name");
+
+    SessionManagerLayer::new(store)
+        .with_name(SESSION_COOKIE)
+        .with_http_only(true)
+        .with_secure(config.cookie_secure)
+        .with_same_site(SameSite::Lax)
+        .with_expiry(Expiry::OnInactivity(Duration::days(
+            config.session_ttl_days,
+        )))
+}
+
+// A fresh id on every sign-in, so a session id planted before login can't be
+// used afterwards.
+pub async fn start_session(session: &Session, user_id: Uuid) -> Result<(), WebauthnError> {
+    session.cycle_id().await.map_err(|e| {
+        error!("Error cycling session id: {:?}", e);
+        WebauthnError::Unknown
+    })?;
+
+    session.insert(USER_ID_KEY, user_id).await.map_err(|e| {
+        error!("Error starting session: {:?}", e);
+        WebauthnError::Unknown
+    })
+}
+
+pub async fn end_session(session: &Session) -> Result<(), WebauthnError> {
+    session.flush().await.map_err(|e| {
+        error!("Error ending session: {:?}", e);
+        WebauthnError::Unknown
+    })
+}
+
+// Role and ban status are read on every request, so unlike a JWT a session
+// picks up changes immediately. Session claims have a nil jti since there is
+// no token to revoke.
+pub async fn session_claims(session: &Session, db: &DbPool) -> Result<Claims, WebauthnError> {
+    let user_id = session
+        .get::<Uuid>(USER_ID_KEY)
+        .await
+        .map_err(|e| {
+            error!("Error loading session: {:?}", e);
+            WebauthnError::Unknown
+        })?
+        .ok_or(WebauthnError::Unauthorized)?;
+
+    let (username, role, banned) = db::get_session_user(db, user_id)
+        .await
+        .map_err(|e| {
+            error!("Error loading session user: {:?}", e);
+            WebauthnError::Unknown
+        })?
+        .ok_or(WebauthnError::Unauthorized)?;
+
+    if banned {
+        return Err(WebauthnError::UserBanned);
+    }
+
+    Ok(Claims {
+        sub: user_id,
+        exp: session.expiry_date().unix_timestamp() as usize,
+        iat: chrono::Utc::now().timestamp() as usize,
+        username,
+        role,
+        jti: Uuid::nil(),
+    })
+}
+
+pub const ACCESS_TOKEN_COOKIE: &str = "access_token";
+
+// Cookies read by the API on requests from the frontend, which may be on
+// another site. Cross-site cookies need SameSite=None, which browsers only
+// accept together with Secure.
+pub fn api_cookie(name: &'static str, value: String, config: &Config) -> CookieBuilder<'static> {
+    let same_site = if config.cookie_secure {
+        SameSite::None
+    } else {
+        SameSite::Lax
+    };
+
+    Cookie::build((name, value))
+        .path("/")
+        .secure(config.cookie_secure)
+        .same_site(same_site)
+}
+
+// Lets a browser client keep the access token out of script reach instead of
+// in localStorage.
+pub fn access_token_cookie(token: &str, config: &Config) -> Option<HeaderValue> {
+    let cookie = api_cookie(ACCESS_TOKEN_COOKIE, token.to_string(), config)
+        .http_only(true)
+        .max_age(Duration::seconds(config.access_token_ttl_secs))
+        .build();
+
+    HeaderValue::from_str(&cookie.to_string()).ok()
+}
+
+pub fn clear_access_token_cookie() -> HeaderValue {
+    let cookie = Cookie::build((ACCESS_TOKEN_COOKIE, ""))
+        .path("/")
+        .removal()
+        .build();
+
+    HeaderValue::from_str(&cookie.to_string()).expect("Removal cookie is a valid header")
+}
+
+pub fn cookie_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(Cookie::split_parse)
+        .filter_map(Result::ok)
+        .find(|cookie| cookie.name() == name && !cookie.value().is_empty())
+        .map(|cookie| cookie.value().to_string())
+}
+
+pub fn access_token_from_cookie(headers: &HeaderMap) -> Option<String> {
+    cookie_value(headers, ACCESS_TOKEN_COOKIE)
+}
@@ -1,188 +1,369 @@
 use crate::db;
-use crate::sse::models::{SseEvent, SseSender};
+use crate::db::models::{Poll, PollOption};
+use crate::error::{ErrorResponse, PollError};
+use crate::polls::normalize_tag;
+use crate::sse::models::{
+    SseEvent, SseParams, StreamWake, heartbeat_event, heartbeat_timer, last_event_id, resync_event,
+    shutdown_event,
+};
+use crate::sse::payload::to_sse_json;
+use crate::sse::sse_broadcaster::SseSender;
 use crate::startup::AppState;
 use axum::{
-    extract::Extension,
-    response::sse::{Event, KeepAlive, Sse},
+    extract::{Extension, Query},
+    http::HeaderMap,
+    response::sse::{Event, Sse},
 };
 use futures::stream::Stream;
+use serde::Deserialize;
 use serde_json::json;
-use std::{convert::Infallible, time::Duration};
+use std::collections::HashSet;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+use utoipa::IntoParams;
+use uuid::Uuid;
+
+// Lets a dashboard narrow the feed to one creator, one tag, or a few event
+// types, so a busy deployment doesn't push every change to every screen.
+// `vote_update` is accepted as another name for poll_updated.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FeedFilterParams {
+    pub creator_id: Option<Uuid>,
+    pub tag: Option<String>,
+    // Comma-separated event names.
+    pub events: Option<String>,
+}
+
+const FEED_EVENTS: &[&str] = &[
+    "poll_created",
+    "polls_created",
+    "poll_updated",
+    "poll_edited",
+    "poll_opened",
+    "poll_closed",
+    "poll_deleted",
+];
+
+// Close and delete events only carry the poll id, so the polls that matched
+// are remembered to know which of those to pass on.
+struct FeedFilter {
+    creator_id: Option<Uuid>,
+    tag: Option<String>,
+    events: Option<HashSet<&'static str>>,
+    matched: HashSet<Uuid>,
+}
+
+impl FeedFilter {
+    fn from_params(params: &FeedFilterParams) -> Result<Self, PollError> {
+        let tag = params
+            .tag
+            .as_deref()
+            .map(|tag| normalize_tag(tag).ok_or(PollError::InvalidRequest))
+            .transpose()?;
+
+        let events = params
+            .events
+            .as_deref()
+            .map(|events| {
+                events
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| {
+                        let name = if name == "vote_update" {
+                            "poll_updated"
+                        } else {
+                            name
+                        };
+                        FEED_EVENTS
+                            .iter()
+                            .copied()
+                            .find(|event| *event == name)
+                            .ok_or(PollError::InvalidRequest)
+                    })
+                    .collect::<Result<HashSet<_>, _>>()
+            })
+            .transpose()?;
+
+        Ok(FeedFilter {
+            creator_id: params.creator_id,
+            tag,
+            events,
+            matched: HashSet::new(),
+        })
+    }
+
+    fn by_poll(&self) -> bool {
+        self.creator_id.is_some() || self.tag.is_some()
+    }
+
+    fn wants(&self, event: &str) -> bool {
+        self.events
+            .as_ref()
+            .is_none_or(|events| events.contains(event))
+    }
+
+    fn matches(&mut self, poll: &Poll) -> bool {
+        let matches = poll.is_listed()
+            && self.creator_id.is_none_or(|id| poll.creator_id == id)
+            && self.tag.as_ref().is_none_or(|tag| poll.tags.contains(tag));
+
+        if matches && self.by_poll() {
+            self.matched.insert(poll.id);
+        }
+        matches
+    }
+
+    fn matched_before(&self, poll_id: Uuid) -> bool {
+        !self.by_poll() || self.matched.contains(&poll_id)
+    }
+}
+
+async fn load_polls(
+    app_state: &AppState,
+    filter: &mut FeedFilter,
+) -> Result<Vec<(Poll, Vec<PollOption>)>, sqlx::Error> {
+    let _permit = app_state.sse_read_limiter.acquire().await;
+
+    let db_filter = db::PollFilter {
+        creator_id: filter.creator_id,
+        tag: filter.tag.as_deref(),
+        ..Default::default()
+    };
+    let polls = db::get_polls_with_options(&app_state.db, &db_filter, None, 0).await?;
+
+    Ok(polls
+        .into_iter()
+        .filter(|(poll, _)| filter.matches(poll))
+        .collect())
+}
+
+async fn init_event(app_state: &AppState, filter: &mut FeedFilter) -> Event {
+    match load_polls(app_state, filter).await {
+        Ok(polls) => {
+            let polls_with_details: Vec<_> = polls
+                .iter()
+                .map(|(poll, options)| to_sse_json(poll, options, poll.results_public()))
+                .collect();
+
+            Event::default()
+                .event("init")
+                .data(json!({"polls": polls_with_details}).to_string())
+        }
+        Err(_) => Event::default()
+            .event("error")
+            .data(json!({"code": "DATABASE_ERROR", "error": "Failed to load polls"}).to_string()),
+    }
+}
 
+fn feed_event_name(event: &SseEvent) -> Option<&'static str> {
+    match event {
+        SseEvent::PollCreated(_) => Some("poll_created"),
+        SseEvent::PollsCreated(_) => Some("polls_created"),
+        SseEvent::VoteUpdate(_) => Some("poll_updated"),
+        SseEvent::PollEdited(_) => Some("poll_edited"),
+        SseEvent::PollOpened(_) => Some("poll_opened"),
+        SseEvent::PollClosed(_) => Some("poll_closed"),
+        SseEvent::PollDeleted(_) => Some("poll_deleted"),
+        // Discussion and write-ins are only shown on the poll's own page; the
+        // vote that follows a write-in brings the new option to the feed.
+        SseEvent::CommentAdded(_) | SseEvent::OptionAdded(_) | SseEvent::ViewerCount(..) => None,
+    }
+}
+
+// The feed is unauthenticated, so only listed polls are ever rendered into it,
+// and only with counts that are public. Filters are checked before anything is
+// loaded or serialized.
+async fn render_event(
+    app_state: &AppState,
+    event: SseEvent,
+    filter: &mut FeedFilter,
+) -> Option<Event> {
+    if !filter.wants(feed_event_name(&event)?) {
+        return None;
+    }
+
+    match event {
+        SseEvent::PollCreated(poll_created) => {
+            let permit = app_state.sse_read_limiter.acquire().await;
+            let poll_result = db::get_poll_with_options(&app_state.db, poll_created.poll_id).await;
+            drop(permit);
+            match poll_result {
+                Ok(Some((poll, options))) if filter.matches(&poll) => Some(
+                    Event::default().event("poll_created").data(
+                        json!({
+                            "poll": to_sse_json(&poll, &options, poll.results_public()),
+                            "poll_id": poll_created.poll_id,
+                            "title": poll_created.title,
+                        })
+                        .to_string(),
+                    ),
+                ),
+                _ => {
+                    // Poll not found or error
+                    None
+                }
+            }
+        }
+        SseEvent::PollsCreated(created) => {
+            let poll_ids: Vec<_> = created.iter().map(|poll| poll.poll_id).collect();
+            let permit = app_state.sse_read_limiter.acquire().await;
+            let polls_result = db::get_polls_with_options_by_ids(&app_state.db, &poll_ids).await;
+            drop(permit);
+
+            let polls: Vec<_> = polls_result
+                .ok()?
+                .iter()
+                .filter(|(poll, _)| filter.matches(poll))
+                .map(|(poll, options)| to_sse_json(poll, options, poll.results_public()))
+                .collect();
+
+            (!polls.is_empty()).then(|| {
+                Event::default()
+                    .event("polls_created")
+                    .data(json!({"polls": polls}).to_string())
+            })
+        }
+        SseEvent::VoteUpdate(update) if filter.matches(&update.snapshot.poll) => {
+            let snapshot = &update.snapshot;
+            let show_counts = snapshot.poll.results_public();
+            Some(
+                Event::default().event("poll_updated").data(
+                    json!({
+                        "poll": to_sse_json(&snapshot.poll, &snapshot.options, show_counts),
+                        "poll_id": update.poll_id,
+                        "updated_option_id": update.option_id,
+                        "new_vote_count": show_counts.then_some(update.new_vote_count),
+                    })
+                    .to_string(),
+                ),
+            )
+        }
+        SseEvent::PollEdited(snapshot) if filter.matches(&snapshot.poll) => Some(
+            Event::default().event("poll_edited").data(
+                json!({
+                    "poll": to_sse_json(
+                        &snapshot.poll,
+                        &snapshot.options,
+                        snapshot.poll.results_public(),
+                    ),
+                    "poll_id": snapshot.poll.id,
+                })
+                .to_string(),
+            ),
+        ),
+        SseEvent::PollOpened(poll_id) if filter.matched_before(poll_id) => Some(
+            Event::default()
+                .event("poll_opened")
+                .data(json!({"poll_id": poll_id}).to_string()),
+        ),
+        SseEvent::PollClosed(poll_id) if filter.matched_before(poll_id) => Some(
+            Event::default()
+                .event("poll_closed")
+                .data(json!({"poll_id": poll_id}).to_string()),
+        ),
+        SseEvent::PollDeleted(poll_id) if filter.matched_before(poll_id) => {
+            filter.matched.remove(&poll_id);
+            Some(
+                Event::default()
+                    .event("poll_deleted")
+                    .data(json!({"poll_id": poll_id}).to_string()),
+            )
+        }
+        _ => None,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/polls/sse",
+    tag = "sse",
+    params(SseParams, FeedFilterParams),
+    responses(
+        (status = 200, description = "Event stream of listed polls, optionally narrowed by creator_id, tag and events. Events: init, poll_created, polls_created, poll_updated, poll_edited, poll_opened, poll_closed, poll_deleted, heartbeat, resync, server_shutdown. resync is followed by a fresh init. Send Last-Event-ID to resume.", content_type = "text/event-stream", body = String),
+        (status = 400, description = "Unknown event name or invalid tag", body = ErrorResponse),
+    )
+)]
 pub async fn all_polls_sse(
     Extension(app_state): Extension<AppState>,
     Extension(sse_tx): Extension<SseSender>,
-) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Query(params): Query<SseParams>,
+    Query(filter_params): Query<FeedFilterParams>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, PollError> {
+    let mut filter = FeedFilter::from_params(&filter_params)?;
     let mut rx = sse_tx.subscribe();
+    let last_event_id = last_event_id(&headers);
+    let replay = last_event_id.and_then(|id| sse_tx.replay_since(id));
+    let mut shutdown = sse_tx.shutdown_receiver();
+    let heartbeat_interval = params.heartbeat_interval();
 
     let stream = async_stream::stream! {
-        {
-            let polls_result = db::get_all_polls(&app_state.db).await;
-            match polls_result {
-                Ok(polls) => {
-                    let mut polls_with_details = Vec::new();
-
-                    for poll in polls {
-                        let options_result = db::get_poll_options(&app_state.db, poll.id).await;
-                        match options_result {
-                            Ok(options) => {
-                                let total_votes = options.iter().map(|o| o.votes).sum::<i32>();
-                                polls_with_details.push(json!({
-                                    "id": poll.id,
-                                    "title": poll.title,
-                                    "description": poll.description,
-                                    "creator_id": poll.creator_id,
-                                    "created_at": poll.created_at,
-                                    "closed": poll.closed,
-                                    "options": options,
-                                    "total_votes": total_votes,
-                                }));
-                            }
-                            Err(_) => {
-                                polls_with_details.push(json!({
-                                    "id": poll.id,
-                                    "title": poll.title,
-                                    "description": poll.description,
-                                    "creator_id": poll.creator_id,
-                                    "created_at": poll.created_at,
-                                    "closed": poll.closed,
-                                    "options": [],
-                                    "total_votes": 0,
-                                }));
-                            }
-                        }
-                    }
+        let mut last_seen = 0;
 
-                    yield Ok(Event::default()
-                        .event("init")
-                        .data(json!({"polls": polls_with_details}).to_string()));
+        match replay {
+            Some(messages) => {
+                // No init to learn the matching polls from.
+                if filter.by_poll() {
+                    let _ = load_polls(&app_state, &mut filter).await;
                 }
-                Err(_) => {
-                    yield Ok(Event::default()
-                        .event("error")
-                        .data(json!({"error": "Failed to load polls"}).to_string()));
+
+                for message in messages {
+                    last_seen = message.id;
+                    if let Some(event) = render_event(&app_state, message.event, &mut filter).await {
+                        yield Ok(event.id(message.id.to_string()));
+                    }
                 }
             }
+            None => yield Ok(init_event(&app_state, &mut filter).await),
         }
 
+        let mut heartbeat = heartbeat_timer(heartbeat_interval);
+        let mut dropped = 0;
 
-        while let Ok(event) = rx.recv().await {
-            match event {
-                SseEvent::PollCreated(poll_created) => {
-                    let poll_result = db::get_poll(&app_state.db, poll_created.poll_id).await;
-                    match poll_result {
-                        Ok(Some(poll)) => {
-                            let options_result = db::get_poll_options(&app_state.db, poll_created.poll_id).await;
-                            match options_result {
-                                Ok(options) => {
-                                    let total_votes = options.iter().map(|o| o.votes).sum::<i32>();
-                                    yield Ok(Event::default()
-                                        .event("poll_created")
-                                        .data(json!({
-                                            "poll": {
-                                                "id": poll.id,
-                                                "title": poll.title,
-                                                "description": poll.description,
-                                                "creator_id": poll.creator_id,
-                                                "created_at": poll.created_at,
-                                                "closed": poll.closed,
-                                                "options": options,
-                                                "total_votes": total_votes,
-                                            },
-                                            "poll_id": poll_created.poll_id,
-                                            "title": poll_created.title,
-                                        }).to_string()));
-                                }
-                                Err(_) => {
-
-                                    yield Ok(Event::default()
-                                        .event("poll_created")
-                                        .data(json!({
-                                            "poll": {
-                                                "id": poll.id,
-                                                "title": poll.title,
-                                                "description": poll.description,
-                                                "creator_id": poll.creator_id,
-                                                "created_at": poll.created_at,
-                                                "closed": poll.closed,
-                                                "options": [],
-                                                "total_votes": 0,
-                                            },
-                                            "poll_id": poll_created.poll_id,
-                                            "title": poll_created.title,
-                                        }).to_string()));
-                                }
-                            }
-                        }
-                        _ => {
-                            // Poll not found or error
-                        }
-                    }
+        loop {
+            let wake = tokio::select! {
+                result = rx.recv() => StreamWake::Received(result),
+                _ = heartbeat.tick() => StreamWake::Heartbeat,
+                _ = shutdown.wait_for(|closed| *closed) => StreamWake::Shutdown,
+            };
+
+            let received = match wake {
+                StreamWake::Received(received) => received,
+                StreamWake::Heartbeat => {
+                    yield Ok(heartbeat_event(heartbeat_interval, dropped, rx.len()));
+                    continue;
                 }
-                SseEvent::VoteUpdate(update) => {
-
-                    match db::get_poll(&app_state.db, update.poll_id).await {
-                        Ok(Some(poll)) => {
-                            match db::get_poll_options(&app_state.db, update.poll_id).await {
-                                Ok(options) => {
-                                    let total_votes = options.iter().map(|o| o.votes).sum::<i32>();
-                                    yield Ok(Event::default()
-                                        .event("poll_updated")
-                                        .data(json!({
-                                            "poll": {
-                                                "id": poll.id,
-                                                "title": poll.title,
-                                                "description": poll.description,
-                                                "creator_id": poll.creator_id,
-                                                "created_at": poll.created_at,
-                                                "closed": poll.closed,
-                                                "options": options,
-                                                "total_votes": total_votes,
-                                            },
-                                            "poll_id": update.poll_id,
-                                            "updated_option_id": update.option_id,
-                                            "new_vote_count": update.new_vote_count,
-                                        }).to_string()));
-                                }
-                                Err(_) => {
-
-                                    yield Ok(Event::default()
-                                        .event("poll_updated")
-                                        .data(json!({
-                                            "poll": {
-                                                "id": poll.id,
-                                                "title": poll.title,
-                                                "description": poll.description,
-                                                "creator_id": poll.creator_id,
-                                                "created_at": poll.created_at,
-                                                "closed": poll.closed,
-                                                "options": [],
-                                                "total_votes": 0,
-                                            },
-                                            "poll_id": update.poll_id,
-                                            "updated_option_id": update.option_id,
-                                            "new_vote_count": update.new_vote_count,
-                                        }).to_string()));
-                                }
-                            }
-                        }
-                        _ => {
-
-                        }
-                    }
+                StreamWake::Shutdown => {
+                    yield Ok(shutdown_event());
+                    break;
                 }
-                SseEvent::PollClosed(poll_id) => {
-                    yield Ok(Event::default()
-                        .event("poll_closed")
-                        .data(json!({"poll_id": poll_id}).to_string()));
+            };
+
+            let message = match received {
+                Ok(message) => message,
+                Err(RecvError::Lagged(skipped)) => {
+                    dropped += skipped;
+                    warn!("All-polls SSE subscriber lagged by {} events, resyncing", skipped);
+                    yield Ok(resync_event(skipped));
+                    yield Ok(init_event(&app_state, &mut filter).await);
+                    continue;
                 }
+                Err(RecvError::Closed) => break,
+            };
+
+            // Already delivered from the replay buffer.
+            if message.id <= last_seen {
+                continue;
+            }
+
+            if let Some(event) = render_event(&app_state, message.event, &mut filter).await {
+                yield Ok(event.id(message.id.to_string()));
             }
         }
     };
 
-    Sse::new(stream).keep_alive(
-        KeepAlive::new()
-            .interval(Duration::from_secs(30))
-            .text("keep-alive"),
-    )
+    Ok(Sse::new(stream).keep_alive(params.keep_alive()))
 }
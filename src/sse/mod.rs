@@ -1,11 +1,16 @@
 pub mod models;
 pub use models::*;
 
+mod payload;
+
 mod sse_broadcaster;
 pub use sse_broadcaster::*;
 
-mod all_polls_sse;
-mod poll_updates_sse;
+mod relay;
+pub use relay::*;
+
+pub mod all_polls_sse;
+pub mod poll_updates_sse;
 
 pub use all_polls_sse::all_polls_sse;
 pub use poll_updates_sse::poll_updates_sse;
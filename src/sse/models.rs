@@ -1,17 +1,126 @@
+use crate::db::models::{Comment, Poll, PollOption};
+use crate::sse::SseMessage;
+use axum::http::HeaderMap;
+use axum::response::sse::{Event, KeepAlive};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+use utoipa::IntoParams;
 use uuid::Uuid;
 
+const DEFAULT_KEEPALIVE_SECS: u64 = 30;
+const MIN_KEEPALIVE_SECS: u64 = 5;
+const MAX_KEEPALIVE_SECS: u64 = 60;
+const DEFAULT_HEARTBEAT_SECS: u64 = 30;
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SseParams {
+    pub keepalive: Option<u64>,
+    // Seconds between heartbeat events, with the same bounds as keepalive.
+    pub heartbeat: Option<u64>,
+    pub token: Option<String>,
+    pub access_token: Option<String>,
+}
+
+impl SseParams {
+    pub fn keep_alive(&self) -> KeepAlive {
+        let secs = self
+            .keepalive
+            .unwrap_or(DEFAULT_KEEPALIVE_SECS)
+            .clamp(MIN_KEEPALIVE_SECS, MAX_KEEPALIVE_SECS);
+
+        KeepAlive::new()
+            .interval(Duration::from_secs(secs))
+            .text("keep-alive")
+    }
+
+    pub fn heartbeat_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.heartbeat
+                .unwrap_or(DEFAULT_HEARTBEAT_SECS)
+                .clamp(MIN_KEEPALIVE_SECS, MAX_KEEPALIVE_SECS),
+        )
+    }
+}
+
+// Keep-alive comments never reach client code, so this is what a frontend can
+// watch to spot a stalled stream. `dropped` counts events this subscriber
+// missed by falling behind since it connected; `pending` is its current
+// backlog.
+pub fn heartbeat_event(interval: Duration, dropped: u64, pending: usize) -> Event {
+    Event::default().event("heartbeat").data(
+        json!({
+            "server_time": Utc::now().to_rfc3339(),
+            "interval_secs": interval.as_secs(),
+            "dropped": dropped,
+            "pending": pending,
+        })
+        .to_string(),
+    )
+}
+
+// Sent when the subscriber fell behind and missed events. A fresh init follows
+// straight away, so clients only need to drop whatever state they were
+// patching and take the init as the new baseline.
+pub fn resync_event(skipped: u64) -> Event {
+    Event::default()
+        .event("resync")
+        .data(json!({ "skipped": skipped }).to_string())
+}
+
+pub enum StreamWake {
+    Received(Result<SseMessage, RecvError>),
+    Heartbeat,
+    Shutdown,
+}
+
+// The first heartbeat goes out one interval after connecting, not straight
+// away alongside init.
+pub fn heartbeat_timer(interval: Duration) -> Interval {
+    let mut timer = time::interval_at(Instant::now() + interval, interval);
+    timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    timer
+}
+
+pub fn shutdown_event() -> Event {
+    Event::default()
+        .event("server_shutdown")
+        .retry(Duration::from_secs(5))
+        .data(json!({"message": "Server is shutting down"}).to_string())
+}
+
+pub fn last_event_id(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+}
+
+// Built once by the handler that changed the counts, so subscribers can render
+// the update without going back to the database.
+#[derive(Debug)]
+pub struct PollSnapshot {
+    pub poll: Poll,
+    pub options: Vec<PollOption>,
+}
+
 #[derive(Debug, Clone)]
 pub struct PollUpdate {
     pub poll_id: Uuid,
     pub option_id: Uuid,
     pub new_vote_count: i64,
+    pub snapshot: Arc<PollSnapshot>,
 }
 
 #[derive(Debug, Clone)]
 pub struct PollCreated {
     pub poll_id: Uuid,
     pub title: String,
-    #[allow(dead_code)]
     pub creator_id: Uuid,
 }
 
@@ -19,7 +128,30 @@ pub struct PollCreated {
 pub enum SseEvent {
     VoteUpdate(PollUpdate),
     PollCreated(PollCreated),
+    PollsCreated(Arc<Vec<PollCreated>>),
+    PollOpened(Uuid),
     PollClosed(Uuid),
+    PollDeleted(Uuid),
+    PollEdited(Arc<PollSnapshot>),
+    CommentAdded(Arc<Comment>),
+    OptionAdded(Arc<PollOption>),
+    ViewerCount(Uuid, usize),
 }
 
-pub type SseSender = tokio::sync::broadcast::Sender<SseEvent>;
+impl SseEvent {
+    // Batched events span several polls and only go to the global feed.
+    pub fn poll_id(&self) -> Option<Uuid> {
+        match self {
+            SseEvent::VoteUpdate(update) => Some(update.poll_id),
+            SseEvent::PollCreated(created) => Some(created.poll_id),
+            SseEvent::PollsCreated(_) => None,
+            SseEvent::PollOpened(poll_id)
+            | SseEvent::PollClosed(poll_id)
+            | SseEvent::PollDeleted(poll_id) => Some(*poll_id),
+            SseEvent::PollEdited(snapshot) => Some(snapshot.poll.id),
+            SseEvent::CommentAdded(comment) => Some(comment.poll_id),
+            SseEvent::OptionAdded(option) => Some(option.poll_id),
+            SseEvent::ViewerCount(poll_id, _) => Some(*poll_id),
+        }
+    }
+}
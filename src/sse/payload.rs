@@ -0,0 +1,48 @@
+use crate::db::models::{Poll, PollOption};
+use serde_json::{Value, json};
+
+// Streams can't always tell who is watching, so callers decide whether the
+// counts go out; hidden counts are sent as null.
+pub fn options_json(options: &[PollOption], show_counts: bool) -> Value {
+    let mut value = json!(options);
+
+    if !show_counts && let Some(options) = value.as_array_mut() {
+        for option in options {
+            option["votes"] = Value::Null;
+        }
+    }
+
+    value
+}
+
+pub fn total_votes(options: &[PollOption], show_counts: bool) -> Value {
+    if show_counts {
+        json!(options.iter().map(|o| o.votes).sum::<i32>())
+    } else {
+        Value::Null
+    }
+}
+
+pub fn to_sse_json(poll: &Poll, options: &[PollOption], show_counts: bool) -> Value {
+    json!({
+        "id": poll.id,
+        "title": poll.title,
+        "description": poll.description,
+        "creator_id": poll.creator_id,
+        "created_at": poll.created_at.to_rfc3339(),
+        "closed": poll.is_closed(),
+        "expires_at": poll.expires_at.map(|t| t.to_rfc3339()),
+        "opens_at": poll.opens_at.map(|t| t.to_rfc3339()),
+        "upcoming": poll.is_upcoming(),
+        "tallied": poll.tallied,
+        "allow_multiple": poll.allow_multiple,
+        "vote_type": poll.vote_type,
+        "max_choices": poll.max_choices,
+        "anonymous": poll.anonymous,
+        "allow_vote_change": poll.allow_vote_change,
+        "ballot_public_key": poll.ballot_public_key,
+        "result_visibility": poll.result_visibility,
+        "options": options_json(options, show_counts),
+        "total_votes": total_votes(options, show_counts),
+    })
+}
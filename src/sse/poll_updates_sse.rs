@@ -1,87 +1,305 @@
+use crate::access::{has_standing_access, verify_invite_token};
+use crate::auth::BearerAuth;
 use crate::db;
-use crate::sse::models::{SseEvent, SseSender};
+use crate::db::models::Poll;
+use crate::polls::viewer_has_voted;
+use crate::sse::models::{
+    SseEvent, SseParams, StreamWake, heartbeat_event, heartbeat_timer, last_event_id, resync_event,
+    shutdown_event,
+};
+use crate::sse::payload::{options_json, to_sse_json, total_votes};
+use crate::sse::sse_broadcaster::SseSender;
 use crate::startup::AppState;
 use axum::{
-    extract::{Extension, Path},
-    response::sse::{Event, KeepAlive, Sse},
+    extract::{Extension, Path, Query},
+    http::HeaderMap,
+    response::sse::{Event, Sse},
 };
 use futures::stream::Stream;
 use serde_json::json;
-use std::{convert::Infallible, time::Duration};
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
 use uuid::Uuid;
 
+// Whether this subscriber gets vote counts for polls whose results are
+// hidden. Only known when the stream was opened with an access token.
+pub(crate) struct CountsViewer {
+    auth: Option<BearerAuth>,
+    voted: bool,
+}
+
+impl CountsViewer {
+    pub(crate) fn new(auth: Option<BearerAuth>) -> Self {
+        Self { auth, voted: false }
+    }
+
+    pub(crate) async fn show_counts(&mut self, app_state: &AppState, poll: &Poll) -> bool {
+        let Some(auth) = &self.auth else {
+            return poll.results_public();
+        };
+
+        // Until the viewer votes, every update on an after_vote poll checks
+        // again whether they have.
+        if !self.voted
+            && poll.result_visibility == "after_vote"
+            && !poll.results_visible_to(auth.0.sub, auth.is_admin(), false)
+        {
+            let _permit = app_state.sse_read_limiter.acquire().await;
+            self.voted = viewer_has_voted(app_state, poll, auth.0.sub)
+                .await
+                .unwrap_or(false);
+        }
+
+        poll.results_visible_to(auth.0.sub, auth.is_admin(), self.voted)
+    }
+}
+
+async fn init_event(app_state: &AppState, poll_id: Uuid, viewer: &mut CountsViewer) -> Event {
+    let poll_result = {
+        let _permit = app_state.sse_read_limiter.acquire().await;
+        db::get_poll_with_options(&app_state.db, poll_id).await
+    };
+
+    match poll_result {
+        Ok(Some((poll, _))) if poll.is_archived() => poll_not_found_event(),
+        Ok(Some((poll, options))) => {
+            let show_counts = viewer.show_counts(app_state, &poll).await;
+            Event::default().event("init").data(
+                json!({
+                    "total_votes": total_votes(&options, show_counts),
+                    "options": options_json(&options, show_counts),
+                    "poll": to_sse_json(&poll, &options, show_counts),
+                })
+                .to_string(),
+            )
+        }
+        Ok(None) => poll_not_found_event(),
+        Err(_) => Event::default()
+            .event("error")
+            .data(json!({"code": "DATABASE_ERROR", "error": "Database error"}).to_string()),
+    }
+}
+
+fn poll_not_found_event() -> Event {
+    Event::default()
+        .event("error")
+        .data(json!({"code": "POLL_NOT_FOUND", "error": "Poll not found"}).to_string())
+}
+
+// EventSource can't send an Authorization header, so gated polls take the
+// caller's access token as ?access_token= and honour the grants recorded for
+// that user. A private poll without an access code also accepts its invite.
+async fn stream_allowed(
+    app_state: &AppState,
+    poll_id: Uuid,
+    params: &SseParams,
+    viewer: Option<&BearerAuth>,
+) -> bool {
+    let poll = {
+        let _permit = app_state.sse_read_limiter.acquire().await;
+        db::get_poll(&app_state.db, poll_id).await
+    };
+
+    let Ok(Some(poll)) = poll else {
+        return true;
+    };
+
+    if !poll.is_private() && !poll.requires_access_code() {
+        return true;
+    }
+
+    if let Some(auth) = viewer
+        && has_standing_access(app_state, &poll, auth)
+            .await
+            .unwrap_or(false)
+    {
+        return true;
+    }
+
+    !poll.requires_access_code()
+        && params
+            .token
+            .as_deref()
+            .is_some_and(|token| verify_invite_token(token, poll_id, &app_state.config))
+}
+
+async fn render_event(
+    app_state: &AppState,
+    poll_id: Uuid,
+    event: &SseEvent,
+    viewer: &mut CountsViewer,
+) -> Option<Event> {
+    match event {
+        SseEvent::VoteUpdate(update) if update.poll_id == poll_id => {
+            let options = &update.snapshot.options;
+            let show_counts = viewer.show_counts(app_state, &update.snapshot.poll).await;
+            Some(
+                Event::default().event("vote_update").data(
+                    json!({
+                        "options": options_json(options, show_counts),
+                        "total_votes": total_votes(options, show_counts),
+                        "updated_option_id": update.option_id,
+                    })
+                    .to_string(),
+                ),
+            )
+        }
+        SseEvent::PollEdited(snapshot) if snapshot.poll.id == poll_id => {
+            let show_counts = viewer.show_counts(app_state, &snapshot.poll).await;
+            Some(
+                Event::default()
+                    .event("poll_edited")
+                    .data(to_sse_json(&snapshot.poll, &snapshot.options, show_counts).to_string()),
+            )
+        }
+        SseEvent::CommentAdded(comment) if comment.poll_id == poll_id => Some(
+            Event::default()
+                .event("comment_added")
+                .data(json!(comment.as_ref()).to_string()),
+        ),
+        SseEvent::ViewerCount(viewer_poll_id, count) if *viewer_poll_id == poll_id => Some(
+            Event::default()
+                .event("viewer_count")
+                .data(json!({"poll_id": poll_id, "viewers": count}).to_string()),
+        ),
+        SseEvent::OptionAdded(option) if option.poll_id == poll_id => Some(
+            Event::default().event("option_added").data(
+                json!({
+                    "poll_id": poll_id,
+                    "option": option.as_ref(),
+                })
+                .to_string(),
+            ),
+        ),
+        SseEvent::PollOpened(opened_poll_id) if *opened_poll_id == poll_id => Some(
+            Event::default()
+                .event("poll_opened")
+                .data(json!({"poll_id": poll_id}).to_string()),
+        ),
+        SseEvent::PollClosed(closed_poll_id) if *closed_poll_id == poll_id => Some(
+            Event::default()
+                .event("poll_closed")
+                .data(json!({"poll_id": poll_id}).to_string()),
+        ),
+        SseEvent::PollDeleted(deleted_poll_id) if *deleted_poll_id == poll_id => Some(
+            Event::default()
+                .event("poll_deleted")
+                .data(json!({"poll_id": poll_id}).to_string()),
+        ),
+        _ => None,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/polls/{poll_id}/sse",
+    tag = "sse",
+    params(("poll_id" = Uuid, Path), SseParams),
+    responses(
+        (status = 200, description = "Event stream for one poll. Events: init, vote_update, poll_edited, comment_added, option_added, viewer_count, poll_opened, poll_closed, poll_deleted, heartbeat, resync, error, server_shutdown. resync is followed by a fresh init. Private polls need access_token, plus token for an invite. Hidden results are sent as null counts unless access_token shows the caller may see them. Send Last-Event-ID to resume.", content_type = "text/event-stream", body = String),
+    )
+)]
 pub async fn poll_updates_sse(
     Extension(app_state): Extension<AppState>,
     Extension(sse_tx): Extension<SseSender>,
     Path(poll_id): Path<Uuid>,
+    Query(params): Query<SseParams>,
+    headers: HeaderMap,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let mut rx = sse_tx.subscribe();
+    let mut rx = sse_tx.subscribe_poll(poll_id);
+    let last_event_id = last_event_id(&headers);
+    let replay = last_event_id.and_then(|id| sse_tx.replay_since(id));
+    let mut shutdown = sse_tx.shutdown_receiver();
+    let heartbeat_interval = params.heartbeat_interval();
+    let viewer = match params.access_token.as_deref() {
+        Some(token) => BearerAuth::from_token(token, &app_state).await.ok(),
+        None => None,
+    };
+    let allowed = stream_allowed(&app_state, poll_id, &params, viewer.as_ref()).await;
+    let mut viewer = CountsViewer::new(viewer);
 
     let stream = async_stream::stream! {
-        match db::get_poll(&app_state.db, poll_id).await {
-            Ok(Some(poll)) => {
-                match db::get_poll_options(&app_state.db, poll_id).await {
-                    Ok(options) => {
-                        let total_votes = options.iter().map(|o| o.votes).sum::<i32>();
-                        yield Ok(Event::default()
-                            .event("init")
-                            .data(json!({
-                                "poll": poll,
-                                "options": options,
-                                "total_votes": total_votes,
-                            }).to_string()));
+        if !allowed {
+            yield Ok(poll_not_found_event());
+            return;
+        }
+
+        // Counted until the client disconnects and the stream is dropped.
+        let _watching = sse_tx.watch_poll(poll_id);
+
+        let mut last_seen = 0;
+
+        match replay {
+            Some(messages) => {
+                for message in messages {
+                    last_seen = message.id;
+                    if let Some(event) = render_event(&app_state, poll_id, &message.event, &mut viewer).await {
+                        yield Ok(event.id(message.id.to_string()));
                     }
-                    Err(_) => {
-                        yield Ok(Event::default()
-                            .event("error")
-                            .data(json!({"error": "Failed to load poll options"}).to_string()));
+                    if matches!(message.event, SseEvent::PollDeleted(id) if id == poll_id) {
+                        return;
                     }
                 }
             }
-            Ok(None) => {
-                yield Ok(Event::default()
-                    .event("error")
-                    .data(json!({"error": "Poll not found"}).to_string()));
-            }
-            Err(_) => {
-                yield Ok(Event::default()
-                    .event("error")
-                    .data(json!({"error": "Database error"}).to_string()));
-            }
+            None => yield Ok(init_event(&app_state, poll_id, &mut viewer).await),
         }
 
-        while let Ok(event) = rx.recv().await {
-            match event {
-                SseEvent::VoteUpdate(update) if update.poll_id == poll_id => {
-                    match db::get_poll_options(&app_state.db, poll_id).await {
-                        Ok(options) => {
-                            let total_votes = options.iter().map(|o| o.votes).sum::<i32>();
-                            yield Ok(Event::default()
-                                .event("vote_update")
-                                .data(json!({
-                                    "options": options,
-                                    "total_votes": total_votes,
-                                    "updated_option_id": update.option_id,
-                                }).to_string()));
-                        }
-                        Err(_) => {
-                            // Silently continue on error
-                        }
-                    }
+        let mut heartbeat = heartbeat_timer(heartbeat_interval);
+        let mut dropped = 0;
+
+        loop {
+            let wake = tokio::select! {
+                result = rx.recv() => StreamWake::Received(result),
+                _ = heartbeat.tick() => StreamWake::Heartbeat,
+                _ = shutdown.wait_for(|closed| *closed) => StreamWake::Shutdown,
+            };
+
+            let received = match wake {
+                StreamWake::Received(received) => received,
+                StreamWake::Heartbeat => {
+                    yield Ok(heartbeat_event(heartbeat_interval, dropped, rx.len()));
+                    continue;
+                }
+                StreamWake::Shutdown => {
+                    yield Ok(shutdown_event());
+                    break;
                 }
-                SseEvent::PollClosed(closed_poll_id) if closed_poll_id == poll_id => {
-                    yield Ok(Event::default()
-                        .event("poll_closed")
-                        .data(json!({"poll_id": poll_id}).to_string()));
+            };
+
+            let message = match received {
+                Ok(message) => message,
+                Err(RecvError::Lagged(skipped)) => {
+                    dropped += skipped;
+                    warn!("SSE subscriber for poll {} lagged by {} events, resyncing", poll_id, skipped);
+                    yield Ok(resync_event(skipped));
+                    yield Ok(init_event(&app_state, poll_id, &mut viewer).await);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            // Transient events aren't replayed, so they carry no event id.
+            if message.id == 0 {
+                if let Some(event) = render_event(&app_state, poll_id, &message.event, &mut viewer).await {
+                    yield Ok(event);
                 }
-                _ => {}
+                continue;
+            }
+
+            // Already delivered from the replay buffer.
+            if message.id <= last_seen {
+                continue;
+            }
+
+            if let Some(event) = render_event(&app_state, poll_id, &message.event, &mut viewer).await {
+                yield Ok(event.id(message.id.to_string()));
+            }
+            if matches!(message.event, SseEvent::PollDeleted(id) if id == poll_id) {
+                break;
             }
         }
     };
 
-    Sse::new(stream).keep_alive(
-        KeepAlive::new()
-            .interval(Duration::from_secs(30))
-            .text("keep-alive"),
-    )
+    Sse::new(stream).keep_alive(params.keep_alive())
 }
@@ -0,0 +1,321 @@
+use crate::db;
+use crate::db::connection::DbPool;
+use crate::sse::models::{PollCreated, PollSnapshot, PollUpdate, SseEvent};
+use crate::sse::sse_broadcaster::SseSender;
+use axum::async_trait;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+pub type RelayError = Box<dyn std::error::Error + Send + Sync>;
+
+// Carries events between instances, so a vote handled by one replica reaches
+// SSE clients connected to another. Every instance receives every message,
+// its own included.
+#[async_trait]
+pub trait Broadcaster: Send + Sync {
+    async fn publish(&self, payload: String) -> Result<(), RelayError>;
+    async fn listen(&self) -> Result<BoxStream<'static, String>, RelayError>;
+}
+
+const NOTIFY_CHANNEL: &str = "poll_events";
+
+// Uses the database every instance already shares, so no extra service is
+// needed. Notifications sent while the listener reconnects are lost; streams
+// pick the missed state up from their next init.
+pub struct PostgresBroadcaster {
+    pool: DbPool,
+}
+
+impl PostgresBroadcaster {
+    pub fn new(pool: DbPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Broadcaster for PostgresBroadcaster {
+    async fn publish(&self, payload: String) -> Result<(), RelayError> {
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(NOTIFY_CHANNEL)
+            .bind(payload)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn listen(&self) -> Result<BoxStream<'static, String>, RelayError> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(NOTIFY_CHANNEL).await?;
+
+        Ok(listener
+            .into_stream()
+            .filter_map(|notification| async move {
+                match notification {
+                    Ok(notification) => Some(notification.payload().to_string()),
+                    Err(e) => {
+                        warn!("SSE relay listener error: {}", e);
+                        None
+                    }
+                }
+            })
+            .boxed())
+    }
+}
+
+// Snapshots, comments and new options are looked up again by the receiving
+// instance rather than sent along: that keeps messages well under NOTIFY's
+// 8000 byte limit and costs one query per instance, not per subscriber. The
+// database change triggers send the same format.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum RelayedEvent {
+    VoteUpdate {
+        poll_id: Uuid,
+        option_id: Uuid,
+    },
+    PollCreated {
+        poll_id: Uuid,
+        title: String,
+        creator_id: Uuid,
+    },
+    PollsCreated {
+        poll_ids: Vec<Uuid>,
+    },
+    PollOpened {
+        poll_id: Uuid,
+    },
+    PollClosed {
+        poll_id: Uuid,
+    },
+    PollDeleted {
+        poll_id: Uuid,
+    },
+    PollEdited {
+        poll_id: Uuid,
+    },
+    CommentAdded {
+        comment_id: Uuid,
+    },
+    OptionAdded {
+        poll_id: Uuid,
+        option_id: Uuid,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    origin: Uuid,
+    event: RelayedEvent,
+}
+
+// Viewer counts are per instance and stay local.
+fn relayed_event(event: &SseEvent) -> Option<RelayedEvent> {
+    Some(match event {
+        SseEvent::VoteUpdate(update) => RelayedEvent::VoteUpdate {
+            poll_id: update.poll_id,
+            option_id: update.option_id,
+        },
+        SseEvent::PollCreated(created) => RelayedEvent::PollCreated {
+            poll_id: created.poll_id,
+            title: created.title.clone(),
+            creator_id: created.creator_id,
+        },
+        SseEvent::PollsCreated(created) => RelayedEvent::PollsCreated {
+            poll_ids: created.iter().map(|poll| poll.poll_id).collect(),
+        },
+        SseEvent::PollOpened(poll_id) => RelayedEvent::PollOpened { poll_id: *poll_id },
+        SseEvent::PollClosed(poll_id) => RelayedEvent::PollClosed { poll_id: *poll_id },
+        SseEvent::PollDeleted(poll_id) => RelayedEvent::PollDeleted { poll_id: *poll_id },
+        SseEvent::PollEdited(snapshot) => RelayedEvent::PollEdited {
+            poll_id: snapshot.poll.id,
+        },
+        SseEvent::CommentAdded(comment) => RelayedEvent::CommentAdded {
+            comment_id: comment.id,
+        },
+        SseEvent::OptionAdded(option) => RelayedEvent::OptionAdded {
+            poll_id: option.poll_id,
+            option_id: option.id,
+        },
+        SseEvent::ViewerCount(..) => return None,
+    })
+}
+
+// None when what the event was about has since been deleted.
+async fn rebuild_event(db: &DbPool, event: RelayedEvent) -> Result<Option<SseEvent>, sqlx::Error> {
+    let event = match event {
+        RelayedEvent::VoteUpdate { poll_id, option_id } => {
+            let Some((poll, options)) = db::get_poll_with_options(db, poll_id).await? else {
+                return Ok(None);
+            };
+            let Some(option) = options.iter().find(|option| option.id == option_id) else {
+                return Ok(None);
+            };
+
+            SseEvent::VoteUpdate(PollUpdate {
+                poll_id,
+                option_id,
+                new_vote_count: option.votes as i64,
+                snapshot: Arc::new(PollSnapshot { poll, options }),
+            })
+        }
+        RelayedEvent::PollCreated {
+            poll_id,
+            title,
+            creator_id,
+        } => SseEvent::PollCreated(PollCreated {
+            poll_id,
+            title,
+            creator_id,
+        }),
+        RelayedEvent::PollsCreated { poll_ids } => {
+            let polls = db::get_polls_with_options_by_ids(db, &poll_ids).await?;
+            SseEvent::PollsCreated(Arc::new(
+                polls
+                    .into_iter()
+                    .map(|(poll, _)| PollCreated {
+                        poll_id: poll.id,
+                        title: poll.title,
+                        creator_id: poll.creator_id,
+                    })
+                    .collect(),
+            ))
+        }
+        RelayedEvent::PollOpened { poll_id } => SseEvent::PollOpened(poll_id),
+        RelayedEvent::PollClosed { poll_id } => SseEvent::PollClosed(poll_id),
+        RelayedEvent::PollDeleted { poll_id } => SseEvent::PollDeleted(poll_id),
+        RelayedEvent::PollEdited { poll_id } => {
+            let Some((poll, options)) = db::get_poll_with_options(db, poll_id).await? else {
+                return Ok(None);
+            };
+            SseEvent::PollEdited(Arc::new(PollSnapshot { poll, options }))
+        }
+        RelayedEvent::CommentAdded { comment_id } => match db::get_comment(db, comment_id).await? {
+            Some(comment) => SseEvent::CommentAdded(Arc::new(comment)),
+            None => return Ok(None),
+        },
+        RelayedEvent::OptionAdded { poll_id, option_id } => {
+            let options = db::get_poll_options(db, poll_id).await?;
+            match options.into_iter().find(|option| option.id == option_id) {
+                Some(option) => SseEvent::OptionAdded(Arc::new(option)),
+                None => return Ok(None),
+            }
+        }
+    };
+
+    Ok(Some(event))
+}
+
+async fn deliver_remote(db: &DbPool, sse_tx: &SseSender, event: RelayedEvent) {
+    match rebuild_event(db, event).await {
+        Ok(Some(event)) => {
+            sse_tx.send_remote(event);
+        }
+        Ok(None) => {}
+        Err(e) => error!("Failed to load relayed SSE event: {}", e),
+    }
+}
+
+// Publishes this instance's events through the broadcaster and delivers other
+// instances' events to local subscribers. Fails if the broadcaster can't be
+// reached or a relay is already running.
+pub async fn start_relay(
+    sse_tx: &SseSender,
+    broadcaster: Arc<dyn Broadcaster>,
+    db: DbPool,
+) -> Result<(), RelayError> {
+    let mut incoming = broadcaster.listen().await?;
+
+    let (outbox, mut outgoing) = mpsc::unbounded_channel();
+    if !sse_tx.set_relay(outbox) {
+        return Err("an SSE relay is already running".into());
+    }
+
+    let origin = Uuid::new_v4();
+
+    tokio::spawn(async move {
+        while let Some(event) = outgoing.recv().await {
+            let Some(event) = relayed_event(&event) else {
+                continue;
+            };
+
+            let payload = match serde_json::to_string(&Envelope { origin, event }) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    error!("Failed to encode relayed SSE event: {}", e);
+                    continue;
+                }
+            };
+
+            if let Err(e) = broadcaster.publish(payload).await {
+                error!("Failed to relay SSE event: {}", e);
+            }
+        }
+    });
+
+    let sse_tx = sse_tx.clone();
+    tokio::spawn(async move {
+        while let Some(payload) = incoming.next().await {
+            let envelope: Envelope = match serde_json::from_str(&payload) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    warn!("Ignoring malformed relayed SSE event: {}", e);
+                    continue;
+                }
+            };
+
+            if envelope.origin != origin {
+                deliver_remote(&db, &sse_tx, envelope.event).await;
+            }
+        }
+
+        warn!("SSE relay listener stopped");
+    });
+
+    info!("SSE relay started");
+    Ok(())
+}
+
+const CHANGES_CHANNEL: &str = "poll_changes";
+const CHANGES_RETRY: Duration = Duration::from_secs(5);
+
+// Streams writes made outside the API, announced by the triggers from
+// migration 0017. Every instance listens, so these reach all subscribers
+// without the relay; like relayed events they skip webhooks and notifications.
+pub fn spawn_change_listener(db: DbPool, sse_tx: SseSender) {
+    tokio::spawn(async move {
+        let mut listener = loop {
+            match PgListener::connect_with(&db).await {
+                Ok(mut listener) => match listener.listen(CHANGES_CHANNEL).await {
+                    Ok(()) => break listener,
+                    Err(e) => error!("Failed to listen for database changes: {}", e),
+                },
+                Err(e) => error!("Failed to connect the database change listener: {}", e),
+            }
+            tokio::time::sleep(CHANGES_RETRY).await;
+        };
+
+        // recv reconnects by itself; notifications sent meanwhile are lost.
+        loop {
+            let notification = match listener.recv().await {
+                Ok(notification) => notification,
+                Err(e) => {
+                    warn!("Database change listener error: {}", e);
+                    tokio::time::sleep(CHANGES_RETRY).await;
+                    continue;
+                }
+            };
+
+            match serde_json::from_str(notification.payload()) {
+                Ok(event) => deliver_remote(&db, &sse_tx, event).await,
+                Err(e) => warn!("Ignoring malformed database change: {}", e),
+            }
+        }
+    });
+}
@@ -1,7 +1,262 @@
+use crate::cache::ReadCache;
+use crate::config::Config;
 use crate::sse::models::SseEvent;
-use tokio::sync::broadcast;
+use chrono::Utc;
+use dashmap::DashMap;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch};
+use uuid::Uuid;
 
-pub fn create_sse_broadcaster() -> broadcast::Sender<SseEvent> {
-    let (tx, _rx) = broadcast::channel(100);
-    tx
+// Viewers coming and going in a burst (a link shared in chat, a page reload)
+// produce one viewer_count event rather than one per connection.
+const VIEWER_COUNT_DEBOUNCE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone)]
+pub struct SseMessage {
+    pub id: u64,
+    pub event: SseEvent,
+    // Relayed from another instance, which already ran its own side effects
+    // (webhooks, notifications) for it.
+    pub remote: bool,
+}
+
+struct ReplayBuffer {
+    last_id: u64,
+    capacity: usize,
+    messages: VecDeque<SseMessage>,
+}
+
+// The global channel feeds the all-polls stream, which renders vote counts as
+// well as lifecycle changes. Each poll also gets its own channel, created when
+// the first subscriber arrives, so a busy poll only wakes its own viewers.
+//
+// Ids are assigned and buffered under the same lock that broadcasts them, so a
+// handler that subscribes before reading the buffer sees every event at least
+// once and can drop duplicates by id.
+#[derive(Clone)]
+pub struct SseSender {
+    global: broadcast::Sender<SseMessage>,
+    polls: Arc<DashMap<Uuid, broadcast::Sender<SseMessage>>>,
+    poll_channel_capacity: usize,
+    replay: Arc<Mutex<ReplayBuffer>>,
+    shutdown: Arc<watch::Sender<bool>>,
+    viewers: Arc<DashMap<Uuid, AtomicUsize>>,
+    viewer_updates_pending: Arc<DashMap<Uuid, ()>>,
+    relay: Arc<OnceLock<mpsc::UnboundedSender<SseEvent>>>,
+    read_cache: Arc<ReadCache>,
+}
+
+// Held by a poll's event stream for as long as the client is connected.
+pub struct PollViewer {
+    sender: SseSender,
+    poll_id: Uuid,
+}
+
+impl Drop for PollViewer {
+    fn drop(&mut self) {
+        if let Some(count) = self.sender.viewers.get(&self.poll_id) {
+            count.fetch_sub(1, Ordering::Relaxed);
+        }
+        self.sender
+            .viewers
+            .remove_if(&self.poll_id, |_, count| count.load(Ordering::Relaxed) == 0);
+        self.sender.schedule_viewer_count(self.poll_id);
+    }
+}
+
+impl SseSender {
+    pub fn send(&self, event: SseEvent) -> u64 {
+        if let Some(relay) = self.relay.get() {
+            let _ = relay.send(event.clone());
+        }
+
+        self.deliver(event, false)
+    }
+
+    // Events from other instances reach local subscribers only.
+    pub(crate) fn send_remote(&self, event: SseEvent) -> u64 {
+        self.deliver(event, true)
+    }
+
+    // Set once, when a relay is started; events sent from then on are also
+    // handed to it for other instances.
+    pub(crate) fn set_relay(&self, outbox: mpsc::UnboundedSender<SseEvent>) -> bool {
+        self.relay.set(outbox).is_ok()
+    }
+
+    fn deliver(&self, event: SseEvent, remote: bool) -> u64 {
+        // Before anyone is told, so a client refetching on the event can't be
+        // handed the copy it replaces.
+        self.read_cache.invalidate_for(&event);
+
+        let mut replay = self.replay.lock().unwrap();
+        replay.last_id += 1;
+
+        let poll_id = event.poll_id();
+        let deleted = matches!(event, SseEvent::PollDeleted(_));
+        let message = SseMessage {
+            id: replay.last_id,
+            event,
+            remote,
+        };
+
+        if replay.messages.len() == replay.capacity {
+            replay.messages.pop_front();
+        }
+        replay.messages.push_back(message.clone());
+
+        let _ = self.global.send(message.clone());
+
+        if let Some(poll_id) = poll_id {
+            let delivered = self
+                .polls
+                .get(&poll_id)
+                .is_some_and(|tx| tx.send(message).is_ok());
+
+            if deleted || !delivered {
+                self.polls
+                    .remove_if(&poll_id, |_, tx| deleted || tx.receiver_count() == 0);
+            }
+        }
+
+        replay.last_id
+    }
+
+    pub fn read_cache(&self) -> Arc<ReadCache> {
+        self.read_cache.clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SseMessage> {
+        self.global.subscribe()
+    }
+
+    pub fn subscribe_poll(&self, poll_id: Uuid) -> broadcast::Receiver<SseMessage> {
+        self.polls
+            .entry(poll_id)
+            .or_insert_with(|| broadcast::channel(self.poll_channel_capacity).0)
+            .subscribe()
+    }
+
+    // Viewer counts skip the replay buffer and the global feed: they're only
+    // interesting live, and would otherwise crowd out events worth replaying.
+    // Such messages have id 0.
+    fn send_transient(&self, poll_id: Uuid, event: SseEvent) {
+        if let Some(tx) = self.polls.get(&poll_id) {
+            let _ = tx.send(SseMessage {
+                id: 0,
+                event,
+                remote: false,
+            });
+        }
+    }
+
+    pub fn watch_poll(&self, poll_id: Uuid) -> PollViewer {
+        self.viewers
+            .entry(poll_id)
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+        self.schedule_viewer_count(poll_id);
+
+        PollViewer {
+            sender: self.clone(),
+            poll_id,
+        }
+    }
+
+    pub fn viewer_count(&self, poll_id: Uuid) -> usize {
+        self.viewers
+            .get(&poll_id)
+            .map_or(0, |count| count.load(Ordering::Relaxed))
+    }
+
+    fn schedule_viewer_count(&self, poll_id: Uuid) {
+        if self.viewer_updates_pending.insert(poll_id, ()).is_some() {
+            return;
+        }
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.viewer_updates_pending.remove(&poll_id);
+            return;
+        };
+
+        let sender = self.clone();
+        runtime.spawn(async move {
+            tokio::time::sleep(VIEWER_COUNT_DEBOUNCE).await;
+            // Cleared before reading, so a change from here on schedules
+            // another update instead of being lost.
+            sender.viewer_updates_pending.remove(&poll_id);
+            let count = sender.viewer_count(poll_id);
+            sender.send_transient(poll_id, SseEvent::ViewerCount(poll_id, count));
+        });
+    }
+
+    // Tells every open stream to send its final event and end, so graceful
+    // shutdown isn't held up by long-lived SSE connections.
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn shutdown_receiver(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.global.receiver_count()
+            + self
+                .polls
+                .iter()
+                .map(|tx| tx.receiver_count())
+                .sum::<usize>()
+    }
+
+    // Returns None when the client is too far behind (or reconnecting after a
+    // restart) and needs a full init instead.
+    pub fn replay_since(&self, last_event_id: u64) -> Option<Vec<SseMessage>> {
+        let replay = self.replay.lock().unwrap();
+
+        let oldest_id = replay
+            .messages
+            .front()
+            .map_or(replay.last_id + 1, |message| message.id);
+
+        if last_event_id > replay.last_id || last_event_id + 1 < oldest_id {
+            return None;
+        }
+
+        Some(
+            replay
+                .messages
+                .iter()
+                .filter(|message| message.id > last_event_id)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+pub fn create_sse_broadcaster(config: &Config) -> SseSender {
+    let (global, _rx) = broadcast::channel(config.sse_channel_capacity);
+
+    // Seeding from the clock keeps ids increasing across restarts, so ids
+    // handed out by a previous process fall outside the replay window.
+    let replay = ReplayBuffer {
+        last_id: Utc::now().timestamp_micros() as u64,
+        capacity: config.sse_replay_buffer_size,
+        messages: VecDeque::with_capacity(config.sse_replay_buffer_size),
+    };
+
+    SseSender {
+        global,
+        polls: Arc::new(DashMap::new()),
+        poll_channel_capacity: config.sse_poll_channel_capacity,
+        replay: Arc::new(Mutex::new(replay)),
+        shutdown: Arc::new(watch::channel(false).0),
+        viewers: Arc::new(DashMap::new()),
+        viewer_updates_pending: Arc::new(DashMap::new()),
+        relay: Arc::new(OnceLock::new()),
+        read_cache: Arc::new(ReadCache::new(config.read_cache_ttl)),
+    }
 }
@@ -1,30 +1,49 @@
+use crate::cache::ReadCache;
+use crate::ceremony::CeremonyStore;
+use crate::config::Config;
+use crate::db;
 use crate::db::connection::DbPool;
-use std::{env, sync::Arc};
+use crate::mailer::{Mailer, build_mailer};
+use crate::notifications;
+use crate::oauth::OidcClient;
+use crate::rate_limit::RateLimiter;
+use crate::sse::{self, SseEvent, SseSender};
+use crate::webhooks::{self, DELIVERY_RETENTION_DAYS};
+use std::{net::IpAddr, sync::Arc, time::Instant};
+use tokio::sync::{Mutex, Semaphore};
 use tokio::time::{Duration, interval};
 use tracing::{error, info};
 use webauthn_rs::prelude::*;
 
+const POLL_SCHEDULE_INTERVAL: Duration = Duration::from_secs(5);
+
 #[derive(Clone)]
 pub struct AppState {
     pub webauthn: Arc<Webauthn>,
+    pub config: Arc<Config>,
     pub db: DbPool,
-    pub jwt_secret: String,
+    pub sse_read_limiter: Arc<Semaphore>,
+    pub username_check_limiter: Arc<RateLimiter<IpAddr>>,
+    pub poll_create_limiter: Arc<RateLimiter<Uuid>>,
+    pub vote_limiter: Arc<RateLimiter<Uuid>>,
+    pub access_code_limiter: Arc<RateLimiter<Uuid>>,
+    pub access_code_failure_limiter: Arc<RateLimiter<Uuid>>,
+    pub overview_cache: Arc<Mutex<Option<(Instant, serde_json::Value)>>>,
+    pub read_cache: Arc<ReadCache>,
+    pub ceremonies: Arc<CeremonyStore>,
+    pub mailer: Arc<dyn Mailer>,
+    pub oidc: Option<Arc<OidcClient>>,
 }
 
 impl AppState {
-    pub async fn new(db: DbPool, jwt_secret: String) -> Self {
-        let frontend_url =
-            env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
-
-        let rp_origin = Url::parse(&frontend_url).expect("Invalid FRONTEND_URL format");
+    pub async fn new(config: Arc<Config>, db: DbPool, sse_tx: SseSender) -> Self {
+        let rp_origin = config.frontend_url.clone();
 
         let rp_id = rp_origin
             .host_str()
             .expect("Could not extract host from FRONTEND_URL")
             .to_string();
 
-        let rp_id = rp_id.split(':').next().unwrap().to_string();
-
         info!("WebAuthn configured with:");
         info!("  RP ID: {}", rp_id);
         info!("  RP Origin: {}", rp_origin);
@@ -32,8 +51,76 @@ impl AppState {
         let builder =
             WebauthnBuilder::new(&rp_id, &rp_origin).expect("Invalid WebAuthn configuration");
 
+        let sse_read_limiter = Arc::new(Semaphore::new(config.sse_db_concurrency));
+
+        let username_check_limiter = Arc::new(RateLimiter::new(
+            config.username_checks_per_minute,
+            Duration::from_secs(60),
+        ));
+
+        let poll_create_limiter = Arc::new(RateLimiter::new(
+            config.poll_creates_per_minute,
+            Duration::from_secs(60),
+        ));
+
+        let vote_limiter = Arc::new(RateLimiter::new(
+            config.votes_per_minute,
+            Duration::from_secs(60),
+        ));
+
+        let access_code_limiter = Arc::new(RateLimiter::new(
+            config.access_code_attempts_per_minute,
+            Duration::from_secs(60),
+        ));
+
+        // Keyed by poll, so spreading guesses over accounts doesn't help.
+        let access_code_failure_limiter = Arc::new(RateLimiter::new(
+            config.access_code_failures_per_poll_per_hour,
+            Duration::from_secs(60 * 60),
+        ));
+
         let builder = builder.rp_name("Polling App");
         let webauthn = Arc::new(builder.build().expect("Invalid configuration"));
+        let mailer = build_mailer(&config).expect("Invalid mail configuration");
+        let read_cache = sse_tx.read_cache();
+        let oidc = config
+            .oidc
+            .clone()
+            .map(|oidc| Arc::new(OidcClient::new(oidc)));
+
+        webhooks::spawn_webhook_workers(db.clone(), sse_tx.clone(), config.clone());
+        if config.sse_database_changes {
+            sse::spawn_change_listener(db.clone(), sse_tx.clone());
+        }
+        notifications::spawn_notification_worker(
+            db.clone(),
+            sse_tx.clone(),
+            config.clone(),
+            mailer.clone(),
+        );
+
+        // Scheduled polls take votes from opens_at on; this only announces
+        // them, so a short interval keeps the announcement close to the time.
+        let scheduler_db = db.clone();
+        let scheduler_tx = sse_tx.clone();
+        tokio::spawn(async move {
+            let mut interval = interval(POLL_SCHEDULE_INTERVAL);
+            loop {
+                interval.tick().await;
+                match db::open_scheduled_polls(&scheduler_db).await {
+                    Ok(opened) => {
+                        for poll_id in opened {
+                            info!("Scheduled poll {} opened", poll_id);
+                            let _ = scheduler_tx.send(SseEvent::PollOpened(poll_id));
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to open scheduled polls: {}", e);
+                    }
+                }
+            }
+        });
+
         let db_clone = db.clone();
         tokio::spawn(async move {
             let mut interval = interval(Duration::from_secs(60));
@@ -47,13 +134,58 @@ impl AppState {
                         error!("Database connection health check failed: {}", e);
                     }
                 }
+
+                match db::close_expired_polls(&db_clone).await {
+                    Ok(closed) => {
+                        for poll_id in closed {
+                            info!("Poll {} expired and was closed", poll_id);
+                            let _ = sse_tx.send(SseEvent::PollClosed(poll_id));
+                        }
+                    }
+                    Err(e) => {
+                        error!("Failed to close expired polls: {}", e);
+                    }
+                }
+
+                if let Err(e) = db::purge_expired_revoked_tokens(&db_clone).await {
+                    error!("Failed to purge expired revoked tokens: {}", e);
+                }
+
+                if let Err(e) = db::purge_expired_sessions(&db_clone).await {
+                    error!("Failed to purge expired sessions: {}", e);
+                }
+
+                if let Err(e) = db::purge_expired_email_verification_tokens(&db_clone).await {
+                    error!("Failed to purge expired verification tokens: {}", e);
+                }
+
+                if let Err(e) = db::purge_expired_vote_links(&db_clone).await {
+                    error!("Failed to purge expired vote links: {}", e);
+                }
+
+                if let Err(e) =
+                    db::purge_finished_webhook_deliveries(&db_clone, DELIVERY_RETENTION_DAYS).await
+                {
+                    error!("Failed to purge webhook deliveries: {}", e);
+                }
             }
         });
 
         AppState {
             webauthn,
+            config,
             db,
-            jwt_secret,
+            sse_read_limiter,
+            username_check_limiter,
+            poll_create_limiter,
+            vote_limiter,
+            access_code_limiter,
+            access_code_failure_limiter,
+            overview_cache: Arc::new(Mutex::new(None)),
+            read_cache,
+            ceremonies: Arc::new(CeremonyStore::default()),
+            mailer,
+            oidc,
         }
     }
 }
@@ -0,0 +1,258 @@
+use crate::auth::BearerAuth;
+use crate::db;
+use crate::error::PollError;
+use crate::polls::{
+    CreatePollRequest, CreatePollResponse, PollOptionResponse, PollResponse, PollVisibility,
+    ResultVisibility, broadcast_vote_updates, build_poll_response, require_poll_creation,
+    validate_create_poll_request,
+};
+use crate::sse::{PollCreated, SseEvent, SseSender};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json, Path},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+const MAX_SURVEY_QUESTIONS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSurveyRequest {
+    pub title: String,
+    pub questions: Vec<SurveyQuestionRequest>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SurveyQuestionRequest {
+    pub title: String,
+    pub description: Option<String>,
+    pub options: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateSurveyResponse {
+    pub survey_id: Uuid,
+    pub title: String,
+    pub questions: Vec<CreatePollResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SurveyResponse {
+    pub id: Uuid,
+    pub title: String,
+    pub creator_id: Uuid,
+    pub created_at: String,
+    pub closed: bool,
+    pub questions: Vec<PollResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmitSurveyRequest {
+    pub answers: Vec<SurveyAnswer>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SurveyAnswer {
+    pub poll_id: Uuid,
+    pub option_id: Uuid,
+}
+
+pub async fn create_survey(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Json(payload): Json<CreateSurveyRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    if payload.title.is_empty()
+        || payload.questions.is_empty()
+        || payload.questions.len() > MAX_SURVEY_QUESTIONS
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    let mut questions: Vec<CreatePollRequest> = payload
+        .questions
+        .into_iter()
+        .map(|q| CreatePollRequest {
+            title: q.title,
+            description: q.description,
+            options: q.options,
+            external_id: None,
+            ballot_public_key: None,
+            write_in_options: Vec::new(),
+            expires_at: None,
+            opens_at: None,
+            allow_multiple: false,
+            vote_type: None,
+            anonymous: false,
+            allow_vote_change: false,
+            tags: Vec::new(),
+            visibility: PollVisibility::Public,
+            access_code: None,
+            result_visibility: ResultVisibility::Always,
+            allow_write_in: false,
+        })
+        .collect();
+
+    for question in &mut questions {
+        validate_create_poll_request(question, app_state.config.max_poll_options)?;
+    }
+
+    require_poll_creation(&app_state, user_id).await?;
+
+    let new_questions: Vec<db::NewSurveyQuestion> = questions
+        .iter()
+        .map(|q| db::NewSurveyQuestion {
+            title: &q.title,
+            description: q.description.as_deref(),
+            options: &q.options,
+        })
+        .collect();
+
+    let (survey_id, created) =
+        db::create_survey(&app_state.db, user_id, &payload.title, &new_questions)
+            .await
+            .map_err(PollError::from)?;
+
+    let mut question_responses = Vec::with_capacity(questions.len());
+    for (question, created) in questions.into_iter().zip(created) {
+        let _ = sse_tx.send(SseEvent::PollCreated(PollCreated {
+            poll_id: created.poll_id,
+            title: question.title.clone(),
+            creator_id: user_id,
+        }));
+
+        question_responses.push(CreatePollResponse {
+            poll_id: created.poll_id,
+            title: question.title,
+            description: question.description,
+            options: created
+                .option_ids
+                .into_iter()
+                .zip(question.options)
+                .map(|(id, text)| PollOptionResponse { id, text })
+                .collect(),
+        });
+    }
+
+    let response = CreateSurveyResponse {
+        survey_id,
+        title: payload.title,
+        questions: question_responses,
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+pub async fn get_survey(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(survey_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    let survey = db::get_survey(&app_state.db, survey_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::SurveyNotFound)?;
+
+    let polls = db::get_survey_polls(&app_state.db, survey_id)
+        .await
+        .map_err(PollError::from)?;
+
+    let now = Utc::now();
+    let mut questions = Vec::with_capacity(polls.len());
+    for poll in polls {
+        questions.push(build_poll_response(&app_state, poll, &auth, now).await?);
+    }
+
+    let response = SurveyResponse {
+        id: survey.id,
+        title: survey.title,
+        creator_id: survey.creator_id,
+        created_at: survey.created_at.to_rfc3339(),
+        closed: survey.closed,
+        questions,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+pub async fn submit_survey(
+    Extension(app_state): Extension<AppState>,
+    Extension(sse_tx): Extension<SseSender>,
+    auth: BearerAuth,
+    Path(survey_id): Path<Uuid>,
+    Json(payload): Json<SubmitSurveyRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+
+    let survey = db::get_survey(&app_state.db, survey_id)
+        .await
+        .map_err(PollError::from)?
+        .ok_or(PollError::SurveyNotFound)?;
+
+    if survey.closed {
+        return Err(PollError::PollClosed);
+    }
+
+    let polls = db::get_survey_polls(&app_state.db, survey_id)
+        .await
+        .map_err(PollError::from)?;
+
+    let answered: HashSet<Uuid> = payload.answers.iter().map(|a| a.poll_id).collect();
+    if answered.len() != payload.answers.len()
+        || answered.len() != polls.len()
+        || polls.iter().any(|p| !answered.contains(&p.id))
+    {
+        return Err(PollError::InvalidRequest);
+    }
+
+    for answer in &payload.answers {
+        let poll = polls
+            .iter()
+            .find(|p| p.id == answer.poll_id)
+            .ok_or(PollError::PollNotFound)?;
+
+        if poll.is_closed() {
+            return Err(PollError::PollClosed);
+        }
+
+        let options = db::get_poll_options(&app_state.db, poll.id)
+            .await
+            .map_err(PollError::from)?;
+
+        if !options.iter().any(|opt| opt.id == answer.option_id) {
+            return Err(PollError::OptionNotFound);
+        }
+    }
+
+    let answers: Vec<(Uuid, Uuid)> = payload
+        .answers
+        .iter()
+        .map(|a| (a.poll_id, a.option_id))
+        .collect();
+
+    match db::cast_survey_votes(&app_state.db, user_id, &answers).await {
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => return Err(PollError::AlreadyVoted),
+        Err(e) => return Err(PollError::from(e)),
+    }
+
+    for (poll_id, option_id) in answers {
+        broadcast_vote_updates(&app_state, &sse_tx, poll_id, &[option_id]).await?;
+    }
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "success": true,
+            "message": "Survey submitted successfully"
+        })),
+    ))
+}
@@ -0,0 +1,138 @@
+use crate::polls::CreatePollRequest;
+use serde::Serialize;
+use std::collections::HashMap;
+
+const MAX_TITLE_LENGTH: usize = 200;
+const MAX_DESCRIPTION_LENGTH: usize = 2000;
+const MAX_OPTION_LENGTH: usize = 200;
+const MIN_OPTIONS: usize = 2;
+
+// One entry per bad field so a form can point at the input that needs fixing.
+// `field` uses `options[2]` style paths for list items.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+// Leading and trailing whitespace is never meaningful in these fields; a blank
+// description is the same as none.
+pub fn trim_poll_fields(payload: &mut CreatePollRequest) {
+    payload.title = payload.title.trim().to_string();
+    payload.description = payload
+        .description
+        .as_deref()
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(str::to_string);
+
+    for option in &mut payload.options {
+        *option = option.trim().to_string();
+    }
+}
+
+pub fn check_title(title: &str, errors: &mut Vec<FieldError>) {
+    if title.is_empty() {
+        errors.push(FieldError::new("title", "must not be empty"));
+    } else if title.chars().count() > MAX_TITLE_LENGTH {
+        errors.push(FieldError::new(
+            "title",
+            format!("must be at most {} characters", MAX_TITLE_LENGTH),
+        ));
+    }
+}
+
+pub fn check_description(description: Option<&str>, errors: &mut Vec<FieldError>) {
+    if description.is_some_and(|d| d.chars().count() > MAX_DESCRIPTION_LENGTH) {
+        errors.push(FieldError::new(
+            "description",
+            format!("must be at most {} characters", MAX_DESCRIPTION_LENGTH),
+        ));
+    }
+}
+
+// Options are compared the way write-ins are matched: case-insensitively with
+// runs of whitespace collapsed. `existing` are options already on the poll.
+pub fn check_options(
+    field: &str,
+    options: &[String],
+    existing: &[&str],
+    errors: &mut Vec<FieldError>,
+) {
+    let mut seen: HashMap<String, Option<usize>> = existing
+        .iter()
+        .map(|text| (option_key(text), None))
+        .collect();
+
+    for (index, option) in options.iter().enumerate() {
+        let path = format!("{}[{}]", field, index);
+
+        if option.is_empty() {
+            errors.push(FieldError::new(path, "must not be empty"));
+            continue;
+        }
+
+        if option.chars().count() > MAX_OPTION_LENGTH {
+            errors.push(FieldError::new(
+                path,
+                format!("must be at most {} characters", MAX_OPTION_LENGTH),
+            ));
+            continue;
+        }
+
+        match seen.get(&option_key(option)) {
+            Some(Some(first)) => errors.push(FieldError::new(
+                path,
+                format!("duplicates {}[{}]", field, first),
+            )),
+            Some(None) => errors.push(FieldError::new(path, "duplicates an existing option")),
+            None => {
+                seen.insert(option_key(option), Some(index));
+            }
+        }
+    }
+}
+
+pub fn check_option_count(
+    field: &str,
+    count: usize,
+    max_options: usize,
+    errors: &mut Vec<FieldError>,
+) {
+    if !(MIN_OPTIONS..=max_options).contains(&count) {
+        errors.push(FieldError::new(
+            field,
+            format!(
+                "must have between {} and {} options",
+                MIN_OPTIONS, max_options
+            ),
+        ));
+    }
+}
+
+pub fn check_poll_fields(payload: &CreatePollRequest, max_options: usize) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+
+    check_title(&payload.title, &mut errors);
+    check_description(payload.description.as_deref(), &mut errors);
+    check_option_count("options", payload.options.len(), max_options, &mut errors);
+    check_options("options", &payload.options, &[], &mut errors);
+
+    errors
+}
+
+fn option_key(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
@@ -0,0 +1,562 @@
+use crate::auth::BearerAuth;
+use crate::config::Config;
+use crate::db;
+use crate::db::connection::DbPool;
+use crate::db::models::{PendingWebhookDelivery, Webhook};
+use crate::error::PollError;
+use crate::sse::{PollCreated, SseEvent, SseSender};
+use crate::startup::AppState;
+use axum::{
+    extract::{Extension, Json, Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{Duration as ChronoDuration, Utc};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use openssl::sign::Signer;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::lookup_host;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{Duration, interval};
+use tracing::{error, warn};
+use uuid::Uuid;
+use webauthn_rs::prelude::Url;
+
+pub const WEBHOOK_EVENTS: [&str; 4] = ["poll_created", "poll_opened", "vote_cast", "poll_closed"];
+
+const MAX_WEBHOOKS_PER_USER: i64 = 10;
+const MAX_WEBHOOK_URL_LENGTH: usize = 2048;
+const DEFAULT_DELIVERIES_PAGE_SIZE: u32 = 50;
+const MAX_DELIVERIES_PAGE_SIZE: u32 = 200;
+
+const DELIVERY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const DELIVERY_BATCH_SIZE: i64 = 20;
+const RETRY_BASE_SECS: i64 = 30;
+const MAX_RETRY_DELAY_SECS: i64 = 6 * 60 * 60;
+const MAX_ERROR_LENGTH: usize = 500;
+pub const DELIVERY_RETENTION_DAYS: i32 = 7;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub events: Vec<String>,
+    pub poll_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListDeliveriesParams {
+    pub limit: Option<u32>,
+}
+
+// Loopback, private, link-local, shared (CGNAT) and other non-public ranges,
+// including IPv4 addresses smuggled inside IPv6 ones.
+fn is_internal_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (b & 0xc0) == 64)
+                || (a == 192 && b == 0 && c == 0)
+                || (a == 198 && (b & 0xfe) == 18)
+        }
+        IpAddr::V6(ip) => {
+            let [a, b, c, d, e, f, _, _] = ip.segments();
+            let [.., w, x, y, z] = ip.octets();
+            // IPv4-mapped, IPv4-compatible and NAT64 addresses reach IPv4 hosts.
+            let embeds_v4 = matches!(
+                (a, b, c, d, e, f),
+                (0, 0, 0, 0, 0, 0xffff) | (0, 0, 0, 0, 0, 0) | (0x64, 0xff9b, 0, 0, 0, 0)
+            );
+
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+                || ip.is_multicast()
+                || (embeds_v4 && is_internal_address(IpAddr::V4(Ipv4Addr::new(w, x, y, z))))
+        }
+    }
+}
+
+// Resolves webhook hosts for the delivery client and refuses any name with an
+// internal address among its records. The client connects to the addresses
+// checked here rather than resolving again, so a rebinding DNS server can't
+// swap them between the check and the request.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0)).await?.collect();
+
+            if addrs.is_empty() || addrs.iter().any(|addr| is_internal_address(addr.ip())) {
+                return Err(format!("{} resolves to an internal address", name.as_str()).into());
+            }
+
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// Webhooks are fetched server-side, so unless explicitly allowed for local
+// development, targets must be HTTPS and must not name a loopback or private
+// address directly. Names are checked again as they resolve, on every
+// delivery.
+fn validate_webhook_url(url: &str, allow_insecure: bool) -> Result<(), PollError> {
+    let parsed = Url::parse(url)
+        .ok()
+        .filter(|_| url.len() <= MAX_WEBHOOK_URL_LENGTH)
+        .filter(|parsed| matches!(parsed.scheme(), "http" | "https"))
+        .ok_or(PollError::InvalidRequest)?;
+
+    let host = parsed.host_str().ok_or(PollError::InvalidRequest)?;
+
+    if allow_insecure {
+        return Ok(());
+    }
+
+    let internal_host = match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => is_internal_address(ip),
+        Err(_) => host == "localhost" || host.ends_with(".localhost"),
+    };
+
+    if parsed.scheme() != "https" || internal_host {
+        return Err(PollError::InvalidRequest);
+    }
+
+    Ok(())
+}
+
+fn normalize_events(events: &[String]) -> Result<Vec<String>, PollError> {
+    let mut normalized: Vec<String> = Vec::with_capacity(events.len());
+
+    for event in events {
+        if !WEBHOOK_EVENTS.contains(&event.as_str()) {
+            return Err(PollError::InvalidRequest);
+        }
+        if !normalized.contains(event) {
+            normalized.push(event.clone());
+        }
+    }
+
+    if normalized.is_empty() {
+        return Err(PollError::InvalidRequest);
+    }
+
+    Ok(normalized)
+}
+
+fn new_webhook_secret() -> Result<String, PollError> {
+    let mut bytes = [0u8; 32];
+    rand_bytes(&mut bytes).map_err(|e| {
+        error!("Error generating webhook secret: {:?}", e);
+        PollError::WebhookSecretError
+    })?;
+    Ok(format!("whsec_{}", URL_SAFE_NO_PAD.encode(bytes)))
+}
+
+async fn owned_webhook(
+    app_state: &AppState,
+    webhook_id: Uuid,
+    user_id: Uuid,
+) -> Result<Webhook, PollError> {
+    db::get_webhook(&app_state.db, webhook_id)
+        .await
+        .map_err(PollError::from)?
+        .filter(|webhook| webhook.owner_id == user_id)
+        .ok_or(PollError::WebhookNotFound)
+}
+
+pub async fn create_webhook(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Json(payload): Json<CreateWebhookRequest>,
+) -> Result<impl IntoResponse, PollError> {
+    let user_id = auth.0.sub;
+    let url = payload.url.trim();
+
+    validate_webhook_url(url, app_state.config.webhook_allow_insecure_targets)?;
+    let events = normalize_events(&payload.events)?;
+
+    if let Some(poll_id) = payload.poll_id {
+        let poll = db::get_poll(&app_state.db, poll_id)
+            .await
+            .map_err(PollError::from)?
+            .ok_or(PollError::PollNotFound)?;
+
+        if poll.creator_id != user_id {
+            return Err(PollError::Unauthorized);
+        }
+    }
+
+    let existing = db::count_user_webhooks(&app_state.db, user_id)
+        .await
+        .map_err(PollError::from)?;
+
+    if existing >= MAX_WEBHOOKS_PER_USER {
+        return Err(PollError::WebhookLimitReached);
+    }
+
+    let secret = new_webhook_secret()?;
+    let webhook = db::create_webhook(
+        &app_state.db,
+        user_id,
+        payload.poll_id,
+        url,
+        &secret,
+        &events,
+    )
+    .await
+    .map_err(PollError::from)?;
+
+    // The secret is only ever returned here; receivers need it to check
+    // signatures.
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateWebhookResponse { webhook, secret }),
+    ))
+}
+
+pub async fn list_webhooks(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+) -> Result<impl IntoResponse, PollError> {
+    let webhooks = db::list_user_webhooks(&app_state.db, auth.0.sub)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((StatusCode::OK, Json(webhooks)))
+}
+
+pub async fn delete_webhook(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(webhook_id): Path<Uuid>,
+) -> Result<impl IntoResponse, PollError> {
+    let deleted = db::delete_webhook(&app_state.db, webhook_id, auth.0.sub)
+        .await
+        .map_err(PollError::from)?;
+
+    if !deleted {
+        return Err(PollError::WebhookNotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_webhook_deliveries(
+    Extension(app_state): Extension<AppState>,
+    auth: BearerAuth,
+    Path(webhook_id): Path<Uuid>,
+    Query(params): Query<ListDeliveriesParams>,
+) -> Result<impl IntoResponse, PollError> {
+    owned_webhook(&app_state, webhook_id, auth.0.sub).await?;
+
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_DELIVERIES_PAGE_SIZE)
+        .clamp(1, MAX_DELIVERIES_PAGE_SIZE) as i64;
+
+    let deliveries = db::list_webhook_deliveries(&app_state.db, webhook_id, limit)
+        .await
+        .map_err(PollError::from)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "webhook_id": webhook_id,
+            "deliveries": deliveries,
+        })),
+    ))
+}
+
+fn poll_created_event(created: &PollCreated) -> (Uuid, &'static str, Value) {
+    (
+        created.poll_id,
+        "poll_created",
+        json!({
+            "poll_id": created.poll_id,
+            "title": created.title,
+            "creator_id": created.creator_id,
+        }),
+    )
+}
+
+fn webhook_events(event: &SseEvent) -> Vec<(Uuid, &'static str, Value)> {
+    match event {
+        SseEvent::PollCreated(created) => vec![poll_created_event(created)],
+        SseEvent::PollsCreated(created) => created.iter().map(poll_created_event).collect(),
+        SseEvent::VoteUpdate(update) => {
+            let options = &update.snapshot.options;
+            vec![(
+                update.poll_id,
+                "vote_cast",
+                json!({
+                    "poll_id": update.poll_id,
+                    "option_id": update.option_id,
+                    "new_vote_count": update.new_vote_count,
+                    "total_votes": options.iter().map(|o| o.votes as i64).sum::<i64>(),
+                    "options": options
+                        .iter()
+                        .map(|o| json!({"id": o.id, "text": o.option_text, "votes": o.votes}))
+                        .collect::<Vec<_>>(),
+                }),
+            )]
+        }
+        SseEvent::PollOpened(poll_id) => {
+            vec![(*poll_id, "poll_opened", json!({ "poll_id": poll_id }))]
+        }
+        SseEvent::PollClosed(poll_id) => {
+            vec![(*poll_id, "poll_closed", json!({ "poll_id": poll_id }))]
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Receivers verify `X-Webhook-Signature: t=<unix>,v1=<hex>` by computing
+// HMAC-SHA256 over "<unix>.<body>" with the webhook secret; the timestamp lets
+// them reject replays.
+fn sign_payload(secret: &str, timestamp: i64, body: &str) -> Option<String> {
+    let key = PKey::hmac(secret.as_bytes()).ok()?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key).ok()?;
+    signer
+        .update(format!("{}.{}", timestamp, body).as_bytes())
+        .ok()?;
+    let mac = signer.sign_to_vec().ok()?;
+
+    Some(format!(
+        "t={},v1={}",
+        timestamp,
+        mac.iter().map(|b| format!("{:02x}", b)).collect::<String>()
+    ))
+}
+
+fn retry_delay(attempts: i32) -> ChronoDuration {
+    let exponent = attempts.clamp(1, 20) as u32 - 1;
+    ChronoDuration::seconds((RETRY_BASE_SECS << exponent).min(MAX_RETRY_DELAY_SECS))
+}
+
+async fn deliver(
+    db: &DbPool,
+    client: &reqwest::Client,
+    config: &Config,
+    delivery: PendingWebhookDelivery,
+) {
+    let body = delivery.payload.to_string();
+    let timestamp = Utc::now().timestamp();
+
+    // The target was checked when the webhook was created; check it again in
+    // case the rules have tightened since.
+    let allowed = validate_webhook_url(&delivery.url, config.webhook_allow_insecure_targets);
+
+    let result = match sign_payload(&delivery.secret, timestamp, &body) {
+        _ if allowed.is_err() => Err("Target address is not allowed".to_string()),
+        Some(signature) => client
+            .post(&delivery.url)
+            .header("content-type", "application/json")
+            .header("x-webhook-event", &delivery.event_type)
+            .header("x-webhook-delivery", delivery.id.to_string())
+            .header("x-webhook-signature", signature)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string()),
+        None => Err("Failed to sign payload".to_string()),
+    };
+
+    let (status_code, failure) = match result {
+        Ok(response) if response.status().is_success() => {
+            let status = response.status().as_u16() as i32;
+            if let Err(e) = db::mark_webhook_delivered(db, delivery.id, status).await {
+                error!("Failed to record webhook delivery {}: {}", delivery.id, e);
+            }
+            return;
+        }
+        Ok(response) => (
+            Some(response.status().as_u16() as i32),
+            format!("Receiver responded with {}", response.status()),
+        ),
+        Err(e) => (None, e),
+    };
+
+    let retry_at = (delivery.attempts < config.webhook_max_attempts)
+        .then(|| Utc::now() + retry_delay(delivery.attempts));
+    let failure: String = failure.chars().take(MAX_ERROR_LENGTH).collect();
+
+    if let Err(e) =
+        db::mark_webhook_attempt_failed(db, delivery.id, status_code, &failure, retry_at).await
+    {
+        error!("Failed to record webhook failure {}: {}", delivery.id, e);
+    }
+}
+
+// One task turns broadcast events into queued deliveries; another sends whatever
+// is due. Deliveries live in the database, so retries survive restarts.
+pub fn spawn_webhook_workers(db: DbPool, sse_tx: SseSender, config: Arc<Config>) {
+    let enqueue_db = db.clone();
+    let mut rx = sse_tx.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let message = match rx.recv().await {
+                Ok(message) => message,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Webhook dispatcher lagged, {} events were not queued",
+                        skipped
+                    );
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+
+            // The instance that raised the event queues its deliveries.
+            if message.remote {
+                continue;
+            }
+
+            for (poll_id, event_type, data) in webhook_events(&message.event) {
+                let payload = json!({
+                    "event": event_type,
+                    "created_at": Utc::now().to_rfc3339(),
+                    "data": data,
+                });
+
+                if let Err(e) =
+                    db::enqueue_webhook_deliveries(&enqueue_db, poll_id, event_type, &payload).await
+                {
+                    error!(
+                        "Failed to queue {} webhooks for poll {}: {}",
+                        event_type, poll_id, e
+                    );
+                }
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut builder = reqwest::Client::builder()
+            .timeout(config.webhook_timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy();
+        if !config.webhook_allow_insecure_targets {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+
+        let client = match builder.build() {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to build webhook HTTP client: {}", e);
+                return;
+            }
+        };
+
+        // Long enough that a delivery still in flight isn't claimed twice.
+        let lease_secs = config.webhook_timeout.as_secs_f64() * 2.0 + 30.0;
+        let mut ticker = interval(DELIVERY_POLL_INTERVAL);
+
+        loop {
+            ticker.tick().await;
+
+            match db::claim_due_webhook_deliveries(&db, DELIVERY_BATCH_SIZE, lease_secs).await {
+                Ok(deliveries) => {
+                    futures::future::join_all(
+                        deliveries
+                            .into_iter()
+                            .map(|delivery| deliver(&db, &client, &config, delivery)),
+                    )
+                    .await;
+                }
+                Err(e) => error!("Failed to claim webhook deliveries: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_refused() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "100.127.255.254",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fc00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "::10.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(is_internal_address(ip.parse().unwrap()), "{}", ip);
+        }
+
+        for ip in [
+            "93.184.216.34",
+            "100.128.0.1",
+            "2606:4700::1111",
+            "::ffff:8.8.8.8",
+        ] {
+            assert!(!is_internal_address(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn webhook_urls_must_be_public_https() {
+        assert!(validate_webhook_url("https://hooks.example.com/poll", false).is_ok());
+
+        for url in [
+            "http://hooks.example.com/poll",
+            "https://localhost/poll",
+            "https://127.0.0.1/poll",
+            "https://[::ffff:7f00:1]/poll",
+            "https://100.100.100.100/poll",
+            "ftp://hooks.example.com/poll",
+        ] {
+            assert!(validate_webhook_url(url, false).is_err(), "{}", url);
+        }
+
+        assert!(validate_webhook_url("http://localhost:8000/poll", true).is_ok());
+    }
+
+    #[tokio::test]
+    async fn names_resolving_to_internal_addresses_are_refused() {
+        let resolved = PublicResolver
+            .resolve("localhost".parse::<Name>().unwrap())
+            .await;
+        assert!(resolved.is_err());
+    }
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use common::TestApp;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+async fn api_keys_act_within_their_scopes() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let created = app
+        .post("/me/api-keys")
+        .signed_in_as(&bob)
+        .json(&json!({ "name": "voting bot", "scopes": ["read", "vote"] }))
+        .send()
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    let key = created.body["key"].as_str().unwrap().to_string();
+    let key_id = created.body["id"].as_str().unwrap().to_string();
+    assert!(key.starts_with(created.body["prefix"].as_str().unwrap()));
+
+    let listed = app.get("/me/api-keys").signed_in_as(&bob).send().await;
+    assert_eq!(listed.body[0]["name"], "voting bot");
+    assert!(listed.body[0].get("key").is_none());
+
+    let poll = app
+        .get(&format!("/polls/{}", poll_id))
+        .header("X-Api-Key", &key)
+        .send()
+        .await;
+    assert_eq!(poll.status, StatusCode::OK, "{}", poll.body);
+    assert_eq!(poll.body["current_user_id"], bob.id.to_string());
+
+    let vote = app
+        .post(&format!("/polls/{}/vote", poll_id))
+        .header("X-Api-Key", &key)
+        .json(&json!({ "option_id": options[0] }))
+        .send()
+        .await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+
+    let create = app
+        .post("/polls")
+        .header("X-Api-Key", &key)
+        .json(&json!({ "title": "From a bot", "options": ["A", "B"] }))
+        .send()
+        .await;
+    assert_eq!(create.status, StatusCode::FORBIDDEN);
+    assert_eq!(create.code(), "INSUFFICIENT_SCOPE");
+
+    let mint = app
+        .post("/me/api-keys")
+        .header("X-Api-Key", &key)
+        .json(&json!({ "name": "another", "scopes": ["manage"] }))
+        .send()
+        .await;
+    assert_eq!(mint.status, StatusCode::FORBIDDEN);
+
+    let deleted = app
+        .delete(&format!("/me/api-keys/{}", key_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(deleted.status, StatusCode::NO_CONTENT);
+
+    let revoked = app
+        .get(&format!("/polls/{}", poll_id))
+        .header("X-Api-Key", &key)
+        .send()
+        .await;
+    assert_eq!(revoked.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(revoked.code(), "INVALID_TOKEN");
+}
+
+#[tokio::test]
+async fn api_keys_cannot_reach_account_routes() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let created = app
+        .post("/me/api-keys")
+        .signed_in_as(&alice)
+        .json(&json!({ "name": "admin bot", "scopes": ["manage"] }))
+        .send()
+        .await;
+    let key = created.body["key"].as_str().unwrap().to_string();
+
+    let create = app
+        .post("/polls")
+        .header("X-Api-Key", &key)
+        .json(&json!({ "title": "From a bot", "options": ["A", "B"] }))
+        .send()
+        .await;
+    assert_eq!(create.status, StatusCode::CREATED, "{}", create.body);
+
+    let account_routes = [
+        app.get("/me"),
+        app.patch("/me").json(&json!({ "username": "mallory" })),
+        app.delete("/me"),
+        app.get("/credentials"),
+        app.delete("/credentials/AAAA"),
+        app.get("/me/credentials/details"),
+        app.get("/me/api-keys"),
+        app.post("/me/api-keys")
+            .json(&json!({ "name": "another", "scopes": ["manage"] })),
+        app.get("/webhooks"),
+        app.get("/admin/overview"),
+    ];
+    for request in account_routes {
+        let response = request.header("X-Api-Key", &key).send().await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN, "{}", response.body);
+    }
+
+    // Adding a passkey counts as anonymous with a key, so the name is taken.
+    let passkey = app
+        .post("/register_start/alice")
+        .header("X-Api-Key", &key)
+        .send()
+        .await;
+    assert_eq!(passkey.status, StatusCode::CONFLICT, "{}", passkey.body);
+
+    let profile = app.get("/me").signed_in_as(&alice).send().await;
+    assert_eq!(profile.body["username"], "alice");
+}
@@ -0,0 +1,330 @@
+mod common;
+
+use common::{TestApp, TestResponse, TestUser};
+use jsonwebtoken::{EncodingKey, Header, encode};
+use reqwest::StatusCode;
+use rust_backend::auth::Claims;
+use serde_json::json;
+use uuid::Uuid;
+
+// The `session=...` pair from a sign-in response.
+fn session_cookie(response: &TestResponse) -> String {
+    response
+        .headers
+        .get_all("set-cookie")
+        .iter()
+        .map(|value| value.to_str().unwrap())
+        .find(|value| value.starts_with("session="))
+        .expect("sign-in sets a session cookie")
+        .split(';')
+        .next()
+        .unwrap()
+        .to_string()
+}
+
+#[tokio::test]
+async fn access_token_cookie_is_accepted_without_a_header() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let response = app
+        .get("/me")
+        .header(
+            "cookie",
+            &format!("theme=dark; access_token={}", alice.token),
+        )
+        .send()
+        .await;
+
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["username"], "alice");
+}
+
+#[tokio::test]
+async fn cookie_authenticated_writes_need_a_csrf_token() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let cookie = format!("access_token={}", alice.token);
+
+    let response = app.post("/logout").header("cookie", &cookie).send().await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    assert_eq!(response.code(), "CSRF_TOKEN_INVALID");
+
+    let response = app
+        .post("/logout")
+        .header("cookie", &format!("{}; csrf_token=abc", cookie))
+        .header("x-csrf-token", "abd")
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn logout_revokes_a_cookie_token_and_clears_the_cookie() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let csrf = app.get("/csrf").send().await;
+    let csrf_token = csrf.body["csrf_token"].as_str().unwrap();
+    let cookie = format!("access_token={}; csrf_token={}", alice.token, csrf_token);
+
+    let logout = app
+        .post("/logout")
+        .header("cookie", &cookie)
+        .header("x-csrf-token", csrf_token)
+        .send()
+        .await;
+    assert_eq!(logout.status, StatusCode::NO_CONTENT, "{}", logout.body);
+    let set_cookie = logout.headers["set-cookie"].to_str().unwrap();
+    assert!(set_cookie.starts_with("access_token=;"), "{}", set_cookie);
+
+    let response = app.get("/me").header("cookie", &cookie).send().await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "TOKEN_REVOKED");
+}
+
+#[tokio::test]
+async fn a_deleted_users_token_and_session_are_rejected() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let (alice, mut passkey) = app.register_with_passkey("alice").await;
+
+    let login = app
+        .login_with_passkey("alice", &mut passkey, json!({}))
+        .await;
+    assert_eq!(login.status, StatusCode::OK, "{}", login.body);
+    let session = session_cookie(&login);
+    let token = TestUser {
+        id: alice.id,
+        username: alice.username.clone(),
+        token: login.body["access_token"].as_str().unwrap().to_string(),
+    };
+
+    let profile = app.get("/me").header("cookie", &session).send().await;
+    assert_eq!(profile.status, StatusCode::OK, "{}", profile.body);
+
+    let deleted = app.delete("/me").signed_in_as(&alice).send().await;
+    assert_eq!(deleted.status, StatusCode::OK, "{}", deleted.body);
+
+    for user in [&alice, &token] {
+        let response = app.get("/me").signed_in_as(user).send().await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(response.code(), "TOKEN_REVOKED");
+    }
+
+    let response = app.get("/me").header("cookie", &session).send().await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn a_ban_stops_outstanding_access_tokens() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let (admin, mut passkey) = app.register_with_passkey("admin").await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE id = $1")
+        .bind(admin.id)
+        .execute(&app.db)
+        .await
+        .unwrap();
+    // Signing in again picks up the role.
+    let login = app
+        .login_with_passkey("admin", &mut passkey, json!({}))
+        .await;
+    let admin = TestUser {
+        token: login.body["access_token"].as_str().unwrap().to_string(),
+        ..admin
+    };
+    let bob = app.register("bob").await;
+
+    let ban = |banned: bool| {
+        app.post(&format!("/admin/users/{}/ban", bob.id))
+            .signed_in_as(&admin)
+            .json(&json!({ "banned": banned }))
+            .send()
+    };
+    let banned = ban(true).await;
+    assert_eq!(banned.status, StatusCode::OK, "{}", banned.body);
+
+    let response = app.get("/me").signed_in_as(&bob).send().await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "TOKEN_REVOKED");
+
+    // Lifting the ban lets the same token back in.
+    let lifted = ban(false).await;
+    assert_eq!(lifted.status, StatusCode::OK, "{}", lifted.body);
+    let response = app.get("/me").signed_in_as(&bob).send().await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+}
+
+#[tokio::test]
+async fn a_passkey_login_signs_the_browser_in_with_a_session() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let (_, mut passkey) = app.register_with_passkey("alice").await;
+
+    let login = app
+        .login_with_passkey("alice", &mut passkey, json!({}))
+        .await;
+    assert_eq!(login.status, StatusCode::OK, "{}", login.body);
+    let session = session_cookie(&login);
+
+    let profile = app.get("/me").header("cookie", &session).send().await;
+    assert_eq!(profile.status, StatusCode::OK, "{}", profile.body);
+    assert_eq!(profile.body["username"], "alice");
+
+    // Writes made with the session need the CSRF token like any cookie.
+    let poll = json!({ "title": "Session poll", "options": ["Yes", "No"] });
+    let refused = app
+        .post("/polls")
+        .header("cookie", &session)
+        .json(&poll)
+        .send()
+        .await;
+    assert_eq!(refused.status, StatusCode::FORBIDDEN);
+    assert_eq!(refused.code(), "CSRF_TOKEN_INVALID");
+
+    let csrf = app.get("/csrf").send().await;
+    let csrf_token = csrf.body["csrf_token"].as_str().unwrap();
+    let cookie = format!("{}; csrf_token={}", session, csrf_token);
+    let created = app
+        .post("/polls")
+        .header("cookie", &cookie)
+        .header("x-csrf-token", csrf_token)
+        .json(&poll)
+        .send()
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+
+    let logout = app
+        .post("/logout")
+        .header("cookie", &cookie)
+        .header("x-csrf-token", csrf_token)
+        .send()
+        .await;
+    assert_eq!(logout.status, StatusCode::NO_CONTENT, "{}", logout.body);
+
+    let response = app.get("/me").header("cookie", &session).send().await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let forged = app
+        .get("/me")
+        .header("cookie", "session=not-a-session-id")
+        .send()
+        .await;
+    assert_eq!(forged.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn a_token_only_acts_for_its_own_user() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+    let key = app
+        .post("/me/api-keys")
+        .signed_in_as(&alice)
+        .json(&json!({ "name": "bot", "scopes": ["read"] }))
+        .send()
+        .await;
+
+    let attempts = [
+        app.patch(&format!("/polls/{}", poll_id))
+            .json(&json!({ "title": "Taken over" })),
+        app.post(&format!("/polls/{}/close", poll_id)),
+        app.post(&format!("/polls/{}/restart", poll_id)),
+        app.delete(&format!("/polls/{}", poll_id)),
+    ];
+    for attempt in attempts {
+        let response = attempt.signed_in_as(&bob).send().await;
+        assert_eq!(
+            response.status,
+            StatusCode::UNAUTHORIZED,
+            "{}",
+            response.body
+        );
+    }
+
+    let response = app
+        .delete(&format!(
+            "/me/api-keys/{}",
+            key.body["id"].as_str().unwrap()
+        ))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND, "{}", response.body);
+
+    let poll = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(poll.body["title"], "Integration test poll");
+    assert_eq!(poll.body["closed"], false);
+    let keys = app.get("/me/api-keys").signed_in_as(&alice).send().await;
+    assert_eq!(keys.body.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn expired_forged_and_revoked_tokens_are_rejected() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let now = chrono::Utc::now().timestamp() as usize;
+    let claims = Claims {
+        sub: alice.id,
+        exp: now - 3600,
+        iat: now - 7200,
+        username: alice.username.clone(),
+        role: "user".to_string(),
+        jti: Uuid::new_v4(),
+    };
+    let sign = |claims: &Claims, secret: &str| {
+        let token = encode(
+            &Header::default(),
+            claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap();
+        TestUser {
+            id: alice.id,
+            username: alice.username.clone(),
+            token,
+        }
+    };
+
+    let expired = sign(&claims, &app.app_state.config.jwt_secret);
+    let response = app.get("/me").signed_in_as(&expired).send().await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "INVALID_TOKEN");
+
+    let forged = sign(
+        &Claims {
+            exp: now + 3600,
+            ..claims
+        },
+        "not-the-server-secret",
+    );
+    let response = app.get("/me").signed_in_as(&forged).send().await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "INVALID_TOKEN");
+
+    let logout = app.post("/logout").signed_in_as(&alice).send().await;
+    assert_eq!(logout.status, StatusCode::NO_CONTENT, "{}", logout.body);
+    let response = app.get("/me").signed_in_as(&alice).send().await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.code(), "TOKEN_REVOKED");
+}
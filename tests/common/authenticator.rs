@@ -0,0 +1,136 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::nid::Nid;
+use openssl::pkey::Private;
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use serde_cbor_2::Value as Cbor;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+// Matches the frontend_url the test config hands to webauthn-rs.
+const RP_ID: &str = "localhost";
+const ORIGIN: &str = "http://localhost:3000";
+
+// User present and user verified; registrations add attested credential data.
+const FLAGS_UP_UV: u8 = 0x01 | 0x04;
+const FLAG_AT: u8 = 0x40;
+
+// A platform authenticator in software: one P-256 key using "none"
+// attestation, so tests can run real registration and login ceremonies.
+pub struct SoftPasskey {
+    key: EcKey<Private>,
+    credential_id: Vec<u8>,
+    sign_count: u32,
+}
+
+impl SoftPasskey {
+    pub fn new() -> Self {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let mut credential_id = vec![0u8; 16];
+        rand_bytes(&mut credential_id).unwrap();
+
+        SoftPasskey {
+            key: EcKey::generate(&group).unwrap(),
+            credential_id,
+            sign_count: 0,
+        }
+    }
+
+    pub fn credential_id(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.credential_id)
+    }
+
+    // Answers the `public_key` of a /register_start response.
+    pub fn register(&self, options: &Value) -> Value {
+        let client_data = client_data("webauthn.create", options);
+
+        let mut auth_data = authenticator_data(FLAGS_UP_UV | FLAG_AT, self.sign_count);
+        auth_data.extend_from_slice(&[0u8; 16]);
+        auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&self.credential_id);
+        auth_data.extend_from_slice(&self.cose_key());
+
+        let attestation_object = serde_cbor_2::to_vec(&Cbor::Map(BTreeMap::from([
+            (Cbor::Text("fmt".into()), Cbor::Text("none".into())),
+            (Cbor::Text("attStmt".into()), Cbor::Map(BTreeMap::new())),
+            (Cbor::Text("authData".into()), Cbor::Bytes(auth_data)),
+        ])))
+        .unwrap();
+
+        json!({
+            "id": self.credential_id(),
+            "rawId": self.credential_id(),
+            "type": "public-key",
+            "response": {
+                "attestationObject": URL_SAFE_NO_PAD.encode(attestation_object),
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+            },
+            "extensions": {},
+        })
+    }
+
+    // Answers the `public_key` of a /login_start response.
+    pub fn authenticate(&mut self, options: &Value) -> Value {
+        self.sign_count += 1;
+        let client_data = client_data("webauthn.get", options);
+        let auth_data = authenticator_data(FLAGS_UP_UV, self.sign_count);
+
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&sha256(&client_data));
+        let signature = EcdsaSig::sign(&sha256(&signed), &self.key)
+            .unwrap()
+            .to_der()
+            .unwrap();
+
+        json!({
+            "id": self.credential_id(),
+            "rawId": self.credential_id(),
+            "type": "public-key",
+            "response": {
+                "authenticatorData": URL_SAFE_NO_PAD.encode(auth_data),
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                "signature": URL_SAFE_NO_PAD.encode(signature),
+            },
+            "extensions": {},
+        })
+    }
+
+    fn cose_key(&self) -> Vec<u8> {
+        let mut x = BigNum::new().unwrap();
+        let mut y = BigNum::new().unwrap();
+        let mut ctx = BigNumContext::new().unwrap();
+        self.key
+            .public_key()
+            .affine_coordinates(self.key.group(), &mut x, &mut y, &mut ctx)
+            .unwrap();
+
+        serde_cbor_2::to_vec(&Cbor::Map(BTreeMap::from([
+            (Cbor::Integer(1), Cbor::Integer(2)),
+            (Cbor::Integer(3), Cbor::Integer(-7)),
+            (Cbor::Integer(-1), Cbor::Integer(1)),
+            (Cbor::Integer(-2), Cbor::Bytes(x.to_vec_padded(32).unwrap())),
+            (Cbor::Integer(-3), Cbor::Bytes(y.to_vec_padded(32).unwrap())),
+        ])))
+        .unwrap()
+    }
+}
+
+fn client_data(kind: &str, options: &Value) -> Vec<u8> {
+    serde_json::to_vec(&json!({
+        "type": kind,
+        "challenge": options["publicKey"]["challenge"],
+        "origin": ORIGIN,
+        "crossOrigin": false,
+    }))
+    .unwrap()
+}
+
+fn authenticator_data(flags: u8, sign_count: u32) -> Vec<u8> {
+    let mut data = sha256(RP_ID.as_bytes()).to_vec();
+    data.push(flags);
+    data.extend_from_slice(&sign_count.to_be_bytes());
+    data
+}
@@ -0,0 +1,483 @@
+// Each test file only uses part of the harness.
+#![allow(dead_code)]
+
+pub mod authenticator;
+
+use authenticator::SoftPasskey;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, RequestBuilder, StatusCode, Url};
+use rust_backend::config::{AttestationPolicy, Config, MailBackend, SseRelay};
+use rust_backend::cors;
+use rust_backend::db::{self, connection::DbPool};
+use rust_backend::routes::build_router;
+use rust_backend::sse::{SseSender, create_sse_broadcaster};
+use rust_backend::startup::AppState;
+use serde_json::{Value, json};
+use sqlx::{Connection, Executor, PgConnection};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+// Longer than the shortest heartbeat interval a stream can be asked for.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Tests never touch DATABASE_URL, which may point at a shared database. They
+// need TEST_DATABASE_URL pointing at a server where the role can create
+// databases, and are skipped when it isn't set.
+fn test_database_url() -> Option<String> {
+    std::env::var("TEST_DATABASE_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+}
+
+fn test_config(database_url: String) -> Config {
+    Config {
+        port: 0,
+        grpc_port: 0,
+        database_url,
+        jwt_secret: "integration-test-secret".to_string(),
+        vote_receipt_secret: "integration-test-receipts".to_string(),
+        voter_hash_secret: "integration-test-voters".to_string(),
+        frontend_url: Url::parse("http://localhost:3000").unwrap(),
+        cors_origins: cors::parse_origin_rules(cors::DEFAULT_ALLOWED_ORIGINS),
+        db_max_connections: 5,
+        db_statement_timeout: Duration::from_secs(10),
+        access_token_ttl_secs: 15 * 60,
+        refresh_token_ttl_days: 30,
+        session_ttl_days: 7,
+        cookie_secure: false,
+        dev_login_enabled: false,
+        attestation_policy: AttestationPolicy::default(),
+        sse_channel_capacity: 100,
+        sse_poll_channel_capacity: 32,
+        sse_replay_buffer_size: 500,
+        sse_db_concurrency: 4,
+        sse_relay: SseRelay::Local,
+        sse_database_changes: true,
+        read_cache_ttl: Duration::from_secs(5),
+        max_poll_options: 20,
+        max_write_in_options: 20,
+        username_checks_per_minute: 1000,
+        poll_creates_per_minute: 1000,
+        votes_per_minute: 1000,
+        access_code_attempts_per_minute: 1000,
+        access_code_failures_per_poll_per_hour: 1000,
+        trending_window_hours: 24,
+        trending_half_life_hours: 6.0,
+        webhook_allow_insecure_targets: false,
+        webhook_max_attempts: 8,
+        webhook_timeout: Duration::from_secs(10),
+        mail_backend: MailBackend::Log,
+        mail_from: "Polling App <noreply@localhost>".to_string(),
+        email_verification_ttl_hours: 24,
+        vote_milestones: vec![10, 100, 1000],
+        oidc: None,
+    }
+}
+
+// A database created for one test and dropped when the test finishes.
+struct TestDatabase {
+    server_url: String,
+    name: String,
+}
+
+impl TestDatabase {
+    async fn create(server_url: &str) -> (Self, String) {
+        let name = format!("poll_test_{}", Uuid::new_v4().simple());
+
+        let mut conn = PgConnection::connect(server_url)
+            .await
+            .expect("Failed to connect to TEST_DATABASE_URL");
+        conn.execute(format!(r#"CREATE DATABASE "{}""#, name).as_str())
+            .await
+            .expect("Failed to create test database");
+        conn.close().await.ok();
+
+        let mut url = Url::parse(server_url).expect("TEST_DATABASE_URL is not a valid URL");
+        url.set_path(&name);
+
+        let database = TestDatabase {
+            server_url: server_url.to_string(),
+            name,
+        };
+        (database, url.to_string())
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        let server_url = self.server_url.clone();
+        let name = self.name.clone();
+
+        // The test's runtime is shutting down, so the drop runs on its own.
+        let _ = std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to build runtime");
+            runtime.block_on(async {
+                if let Ok(mut conn) = PgConnection::connect(&server_url).await {
+                    let _ = conn
+                        .execute(
+                            format!(r#"DROP DATABASE IF EXISTS "{}" WITH (FORCE)"#, name).as_str(),
+                        )
+                        .await;
+                }
+            });
+        })
+        .join();
+    }
+}
+
+pub struct TestApp {
+    pub address: String,
+    pub db: DbPool,
+    pub sse_tx: SseSender,
+    pub app_state: AppState,
+    client: reqwest::Client,
+    _database: TestDatabase,
+}
+
+pub struct TestUser {
+    pub id: Uuid,
+    pub username: String,
+    pub token: String,
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+impl TestResponse {
+    pub fn code(&self) -> &str {
+        self.body["code"].as_str().unwrap_or_default()
+    }
+}
+
+pub struct TestRequest {
+    builder: RequestBuilder,
+}
+
+impl TestRequest {
+    pub fn signed_in_as(self, user: &TestUser) -> Self {
+        TestRequest {
+            builder: self.builder.bearer_auth(&user.token),
+        }
+    }
+
+    pub fn header(self, name: &str, value: &str) -> Self {
+        TestRequest {
+            builder: self.builder.header(name, value),
+        }
+    }
+
+    pub fn json(self, body: &Value) -> Self {
+        TestRequest {
+            builder: self.builder.json(body),
+        }
+    }
+
+    pub async fn send(self) -> TestResponse {
+        let response = self.builder.send().await.expect("Request failed");
+        let status = response.status();
+        let headers = response.headers().clone();
+        let text = response.text().await.expect("Failed to read body");
+        let body = if text.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_str(&text).unwrap_or(Value::String(text))
+        };
+
+        TestResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    pub async fn stream(self) -> EventStream {
+        let response = self.builder.send().await.expect("Request failed");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        EventStream {
+            response,
+            buffer: String::new(),
+        }
+    }
+}
+
+pub struct SseEvent {
+    pub event: String,
+    pub data: Value,
+}
+
+// Reads a text/event-stream response one event at a time.
+pub struct EventStream {
+    response: reqwest::Response,
+    buffer: String,
+}
+
+impl EventStream {
+    pub async fn next_event(&mut self) -> SseEvent {
+        loop {
+            while let Some(end) = self.buffer.find("\n\n") {
+                let raw: String = self.buffer.drain(..end + 2).collect();
+                if let Some(event) = parse_event(&raw) {
+                    return event;
+                }
+            }
+
+            let chunk = tokio::time::timeout(EVENT_TIMEOUT, self.response.chunk())
+                .await
+                .expect("Timed out waiting for an event")
+                .expect("Event stream failed")
+                .expect("Event stream ended");
+            self.buffer.push_str(&String::from_utf8_lossy(&chunk));
+        }
+    }
+
+    // Skips events of other types until one named `event` arrives.
+    pub async fn expect_event(&mut self, event: &str) -> Value {
+        loop {
+            let next = self.next_event().await;
+            if next.event == event {
+                return next.data;
+            }
+        }
+    }
+}
+
+// Keep-alive comments have no event name and are dropped.
+fn parse_event(raw: &str) -> Option<SseEvent> {
+    let mut event = None;
+    let mut data = String::new();
+
+    for line in raw.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            event = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push_str(value.trim_start());
+        }
+    }
+
+    Some(SseEvent {
+        event: event?,
+        data: serde_json::from_str(&data).unwrap_or(Value::Null),
+    })
+}
+
+impl TestApp {
+    // Starts the full router on a random port against a fresh database.
+    pub async fn spawn() -> Option<TestApp> {
+        Self::spawn_with(|_| {}).await
+    }
+
+    // Like `spawn`, with the test's own changes to the configuration.
+    pub async fn spawn_with(configure: impl FnOnce(&mut Config)) -> Option<TestApp> {
+        let Some(server_url) = test_database_url() else {
+            eprintln!("TEST_DATABASE_URL is not set, skipping");
+            return None;
+        };
+
+        let (database, database_url) = TestDatabase::create(&server_url).await;
+        let mut config = test_config(database_url);
+        configure(&mut config);
+        let config = Arc::new(config);
+
+        let db = db::init_db(&config)
+            .await
+            .expect("Failed to initialize test database");
+        let sse_tx = create_sse_broadcaster(&config);
+        let app_state = AppState::new(config, db.clone(), sse_tx.clone()).await;
+        let app = build_router(app_state.clone(), sse_tx.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind test listener");
+        let address = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .expect("Test server failed");
+        });
+
+        Some(TestApp {
+            address,
+            db,
+            sse_tx,
+            app_state,
+            client: reqwest::Client::new(),
+            _database: database,
+        })
+    }
+
+    pub fn request(&self, method: Method, path: &str) -> TestRequest {
+        TestRequest {
+            builder: self
+                .client
+                .request(method, format!("{}{}", self.address, path)),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> TestRequest {
+        self.request(Method::GET, path)
+    }
+
+    pub fn post(&self, path: &str) -> TestRequest {
+        self.request(Method::POST, path)
+    }
+
+    pub fn patch(&self, path: &str) -> TestRequest {
+        self.request(Method::PATCH, path)
+    }
+
+    pub fn delete(&self, path: &str) -> TestRequest {
+        self.request(Method::DELETE, path)
+    }
+
+    pub async fn register(&self, username: &str) -> TestUser {
+        let response = self
+            .post("/register")
+            .json(&json!({ "username": username }))
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+        TestUser {
+            id: response.body["user_id"].as_str().unwrap().parse().unwrap(),
+            username: username.to_string(),
+            token: response.body["access_token"].as_str().unwrap().to_string(),
+        }
+    }
+
+    // Runs /register_start and /register_finish with `passkey`. `signed_in` is
+    // the account's own token when adding a passkey to an existing account.
+    pub async fn register_passkey(
+        &self,
+        username: &str,
+        passkey: &SoftPasskey,
+        signed_in: Option<&TestUser>,
+    ) -> TestResponse {
+        let mut start = self.post(&format!("/register_start/{}", username));
+        if let Some(user) = signed_in {
+            start = start.signed_in_as(user);
+        }
+        let started = start.send().await;
+        if started.status != StatusCode::OK {
+            return started;
+        }
+
+        let mut finish = self.post("/register_finish").json(&json!({
+            "state_id": started.body["state_id"],
+            "credential": passkey.register(&started.body["public_key"]),
+        }));
+        if let Some(user) = signed_in {
+            finish = finish.signed_in_as(user);
+        }
+        finish.send().await
+    }
+
+    pub async fn register_with_passkey(&self, username: &str) -> (TestUser, SoftPasskey) {
+        let passkey = SoftPasskey::new();
+        let response = self.register_passkey(username, &passkey, None).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+        let user = TestUser {
+            id: response.body["user_id"].as_str().unwrap().parse().unwrap(),
+            username: username.to_string(),
+            token: response.body["access_token"].as_str().unwrap().to_string(),
+        };
+        (user, passkey)
+    }
+
+    // Runs /login_start and /login_finish; `extra` is merged into the finish
+    // request, e.g. to ask for a cookie.
+    pub async fn login_with_passkey(
+        &self,
+        username: &str,
+        passkey: &mut SoftPasskey,
+        extra: Value,
+    ) -> TestResponse {
+        let started = self
+            .post(&format!("/login_start/{}", username))
+            .send()
+            .await;
+        assert_eq!(started.status, StatusCode::OK, "{}", started.body);
+
+        let mut body = json!({
+            "state_id": started.body["state_id"],
+            "credential": passkey.authenticate(&started.body["public_key"]),
+        });
+        if let (Some(body), Some(extra)) = (body.as_object_mut(), extra.as_object()) {
+            body.extend(extra.clone());
+        }
+        self.post("/login_finish").json(&body).send().await
+    }
+
+    // Creates a single-choice poll and returns its id and option ids in order.
+    pub async fn create_poll(&self, user: &TestUser, options: &[&str]) -> (Uuid, Vec<Uuid>) {
+        let response = self
+            .post("/polls")
+            .signed_in_as(user)
+            .json(&json!({
+                "title": "Integration test poll",
+                "options": options,
+            }))
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+        let poll_id = response.body["poll_id"].as_str().unwrap().parse().unwrap();
+        let option_ids = response.body["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|option| option["id"].as_str().unwrap().parse().unwrap())
+            .collect();
+
+        (poll_id, option_ids)
+    }
+
+    pub async fn vote(&self, user: &TestUser, poll_id: Uuid, option_id: Uuid) -> TestResponse {
+        self.post(&format!("/polls/{}/vote", poll_id))
+            .signed_in_as(user)
+            .json(&json!({ "option_id": option_id }))
+            .send()
+            .await
+    }
+
+    // Vote counts in the order of `option_ids`; the API lists options by text.
+    pub async fn vote_counts(
+        &self,
+        user: &TestUser,
+        poll_id: Uuid,
+        option_ids: &[Uuid],
+    ) -> Vec<i64> {
+        let response = self
+            .get(&format!("/polls/{}", poll_id))
+            .signed_in_as(user)
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+        let options = response.body["options"].as_array().unwrap();
+        option_ids
+            .iter()
+            .map(|id| {
+                options
+                    .iter()
+                    .find(|option| option["id"] == id.to_string())
+                    .and_then(|option| option["votes"].as_i64())
+                    .unwrap()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use common::TestApp;
+use common::authenticator::SoftPasskey;
+use reqwest::StatusCode;
+use serde_json::json;
+use std::collections::HashMap;
+
+#[tokio::test]
+async fn the_last_passkey_cannot_be_deleted() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let (alice, first) = app.register_with_passkey("alice").await;
+
+    let refused = app
+        .delete(&format!("/credentials/{}", first.credential_id()))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(refused.status, StatusCode::BAD_REQUEST, "{}", refused.body);
+    assert_eq!(refused.code(), "LAST_CREDENTIAL");
+
+    let mut second = SoftPasskey::new();
+    let added = app.register_passkey("alice", &second, Some(&alice)).await;
+    assert_eq!(added.status, StatusCode::OK, "{}", added.body);
+
+    let deleted = app
+        .delete(&format!("/credentials/{}", first.credential_id()))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(deleted.status, StatusCode::NO_CONTENT, "{}", deleted.body);
+
+    let credentials = app
+        .get("/me/credentials/details")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    let credentials = credentials.body.as_array().unwrap();
+    assert_eq!(credentials.len(), 1);
+    assert_eq!(credentials[0]["cred_id"], second.credential_id());
+
+    let refused = app
+        .delete(&format!("/credentials/{}", second.credential_id()))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(refused.code(), "LAST_CREDENTIAL");
+
+    let login = app
+        .login_with_passkey("alice", &mut second, json!({}))
+        .await;
+    assert_eq!(login.status, StatusCode::OK, "{}", login.body);
+}
+
+#[tokio::test]
+async fn only_the_owner_can_delete_a_passkey() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let (alice, first) = app.register_with_passkey("alice").await;
+    let second = SoftPasskey::new();
+    app.register_passkey("alice", &second, Some(&alice)).await;
+    let bob = app.register("bob").await;
+
+    let response = app
+        .delete(&format!("/credentials/{}", first.credential_id()))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND, "{}", response.body);
+    assert_eq!(response.code(), "CREDENTIAL_NOT_FOUND");
+}
+
+#[tokio::test]
+async fn signing_in_records_use_of_that_passkey_only() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let (alice, mut first) = app.register_with_passkey("alice").await;
+    let second = SoftPasskey::new();
+    app.register_passkey("alice", &second, Some(&alice)).await;
+
+    let last_used = || async {
+        let credentials = app
+            .get("/me/credentials/details")
+            .signed_in_as(&alice)
+            .send()
+            .await;
+        credentials
+            .body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|credential| {
+                (
+                    credential["cred_id"].as_str().unwrap().to_string(),
+                    credential["last_used_at"].clone(),
+                )
+            })
+            .collect::<HashMap<_, _>>()
+    };
+    let before = last_used().await;
+
+    let login = app.login_with_passkey("alice", &mut first, json!({})).await;
+    assert_eq!(login.status, StatusCode::OK, "{}", login.body);
+
+    let after = last_used().await;
+    assert_ne!(
+        after[&first.credential_id()],
+        before[&first.credential_id()]
+    );
+    assert!(after[&first.credential_id()].is_string());
+    assert_eq!(
+        after[&second.credential_id()],
+        before[&second.credential_id()]
+    );
+}
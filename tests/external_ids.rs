@@ -0,0 +1,77 @@
+mod common;
+
+use common::{TestApp, TestResponse, TestUser};
+use reqwest::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+async fn create_with_external_id(
+    app: &TestApp,
+    user: &TestUser,
+    external_id: &str,
+) -> TestResponse {
+    app.post("/polls")
+        .signed_in_as(user)
+        .json(&json!({
+            "title": "Lunch?",
+            "options": ["Yes", "No"],
+            "external_id": external_id,
+        }))
+        .send()
+        .await
+}
+
+#[tokio::test]
+async fn external_ids_are_scoped_to_their_creator() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    let created = create_with_external_id(&app, &alice, "order-1").await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    let retried = create_with_external_id(&app, &alice, "order-1").await;
+    assert_eq!(retried.status, StatusCode::OK, "{}", retried.body);
+    assert_eq!(retried.body["poll_id"], created.body["poll_id"]);
+
+    // Bob's order-1 is his own poll, and says nothing about Alice's.
+    let bobs = create_with_external_id(&app, &bob, "order-1").await;
+    assert_eq!(bobs.status, StatusCode::CREATED, "{}", bobs.body);
+    assert_ne!(bobs.body["poll_id"], created.body["poll_id"]);
+
+    let bulk = app
+        .post("/polls/bulk")
+        .signed_in_as(&bob)
+        .json(&json!([
+            { "title": "Lunch?", "options": ["Yes", "No"], "external_id": "order-1" },
+            { "title": "Dinner?", "options": ["Yes", "No"], "external_id": "order-2" },
+        ]))
+        .send()
+        .await;
+    assert_eq!(bulk.status, StatusCode::CREATED, "{}", bulk.body);
+    assert_eq!(bulk.body["existing"], 1);
+    assert_eq!(bulk.body["results"][0]["poll_id"], bobs.body["poll_id"]);
+}
+
+#[tokio::test]
+async fn retries_find_polls_created_under_unscoped_ids() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let legacy_id = Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO polls (id, creator_id, title, external_id) VALUES ($1, $2, 'Lunch?', 'order-1')",
+    )
+    .bind(legacy_id)
+    .bind(alice.id)
+    .execute(&app.db)
+    .await
+    .unwrap();
+
+    let retried = create_with_external_id(&app, &alice, "order-1").await;
+    assert_eq!(retried.status, StatusCode::OK, "{}", retried.body);
+    assert_eq!(retried.body["poll_id"], legacy_id.to_string());
+}
@@ -0,0 +1,160 @@
+#![cfg(feature = "grpc")]
+
+mod common;
+
+use common::{TestApp, TestUser};
+use rust_backend::grpc::proto::poll_event::Event;
+use rust_backend::grpc::proto::poll_service_client::PollServiceClient;
+use rust_backend::grpc::proto::{
+    CastVoteRequest, CreatePollRequest, GetPollRequest, WatchPollRequest,
+};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+
+async fn connect(app: &TestApp) -> PollServiceClient<Channel> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Failed to bind gRPC listener");
+    let address = format!("http://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(rust_backend::grpc::serve(
+        listener,
+        app.app_state.clone(),
+        app.sse_tx.clone(),
+    ));
+
+    PollServiceClient::connect(address)
+        .await
+        .expect("Failed to connect to the gRPC server")
+}
+
+fn signed_in<T>(user: &TestUser, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request.metadata_mut().insert(
+        "authorization",
+        format!("Bearer {}", user.token).parse().unwrap(),
+    );
+    request
+}
+
+#[tokio::test]
+async fn grpc_clients_create_vote_and_watch() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let mut client = connect(&app).await;
+
+    let created = client
+        .create_poll(signed_in(
+            &alice,
+            CreatePollRequest {
+                title: "Over gRPC".to_string(),
+                options: vec!["Yes".to_string(), "No".to_string()],
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(created.created);
+    let poll_id = created.poll_id;
+    let yes = created.options[0].id.clone();
+    let no = created.options[1].id.clone();
+
+    let mut events = client
+        .watch_poll(signed_in(
+            &alice,
+            WatchPollRequest {
+                poll_id: poll_id.clone(),
+                token: None,
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+
+    let first = events.message().await.unwrap().unwrap();
+    let Some(Event::Snapshot(snapshot)) = first.event else {
+        panic!("Expected a snapshot first, got {:?}", first.event);
+    };
+    assert_eq!(snapshot.title, "Over gRPC");
+    assert_eq!(snapshot.total_votes, Some(0));
+
+    let vote = client
+        .cast_vote(signed_in(
+            &bob,
+            CastVoteRequest {
+                poll_id: poll_id.clone(),
+                option_id: Some(yes.clone()),
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(vote.receipt.is_some());
+
+    let update = loop {
+        let next = tokio::time::timeout(Duration::from_secs(10), events.message())
+            .await
+            .expect("Timed out waiting for a vote update")
+            .unwrap()
+            .unwrap();
+        if let Some(Event::VoteUpdate(update)) = next.event {
+            break update;
+        }
+    };
+    assert_eq!(update.updated_option_id, yes);
+    assert_eq!(update.total_votes, Some(1));
+
+    let second_vote = client
+        .cast_vote(signed_in(
+            &bob,
+            CastVoteRequest {
+                poll_id: poll_id.clone(),
+                option_id: Some(no),
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap_err();
+    assert_eq!(second_vote.code(), Code::AlreadyExists);
+    assert_eq!(
+        second_vote.metadata().get("x-error-code").unwrap(),
+        "ALREADY_VOTED"
+    );
+
+    let poll = client
+        .get_poll(signed_in(
+            &bob,
+            GetPollRequest {
+                poll_id,
+                token: None,
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(poll.user_voted);
+}
+
+#[tokio::test]
+async fn grpc_calls_need_an_access_token() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let mut client = connect(&app).await;
+
+    let error = client
+        .get_poll(GetPollRequest {
+            poll_id: uuid::Uuid::new_v4().to_string(),
+            token: None,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(error.code(), Code::Unauthenticated);
+}
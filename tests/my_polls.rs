@@ -0,0 +1,105 @@
+mod common;
+
+use common::TestApp;
+use reqwest::StatusCode;
+
+#[tokio::test]
+async fn creators_see_their_polls_with_stats() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let carol = app.register("carol").await;
+
+    let (quiet_poll, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+    let (busy_poll, options) = app.create_poll(&alice, &["Tea", "Coffee"]).await;
+    app.create_poll(&bob, &["Up", "Down"]).await;
+
+    app.vote(&bob, busy_poll, options[1]).await;
+    app.vote(&carol, busy_poll, options[1]).await;
+
+    let response = app
+        .get("/me/polls?sort=total_votes")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["total"], 2);
+
+    let polls = response.body["polls"].as_array().unwrap();
+    assert_eq!(polls[0]["id"], busy_poll.to_string());
+    assert_eq!(polls[0]["status"], "open");
+    assert_eq!(polls[0]["total_votes"], 2);
+    assert_eq!(polls[0]["voter_count"], 2);
+    assert_eq!(polls[0]["option_count"], 2);
+    assert_eq!(polls[0]["leading_option"], "Coffee");
+    assert!(polls[0]["last_vote_at"].is_string());
+
+    assert_eq!(polls[1]["id"], quiet_poll.to_string());
+    assert_eq!(polls[1]["total_votes"], 0);
+    assert!(polls[1]["leading_option"].is_null());
+}
+
+#[tokio::test]
+async fn deleted_polls_are_archived_and_can_be_restored() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+    app.vote(&bob, poll_id, options[0]).await;
+
+    let deleted = app
+        .delete(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(deleted.status, StatusCode::OK, "{}", deleted.body);
+
+    let listed = app.get("/polls").signed_in_as(&bob).send().await;
+    assert_eq!(listed.body["total"], 0);
+    let hidden = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(hidden.status, StatusCode::NOT_FOUND);
+
+    let mine = app.get("/me/polls").signed_in_as(&alice).send().await;
+    assert_eq!(mine.body["total"], 0);
+    let archived = app
+        .get("/me/polls?include_archived=true")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(archived.body["total"], 1);
+    assert!(archived.body["polls"][0]["archived_at"].is_string());
+    assert_eq!(archived.body["polls"][0]["total_votes"], 1);
+
+    let forbidden = app
+        .delete(&format!("/polls/{}/archive", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(forbidden.status, StatusCode::UNAUTHORIZED);
+
+    let restored = app
+        .delete(&format!("/polls/{}/archive", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(restored.status, StatusCode::OK, "{}", restored.body);
+    assert_eq!(app.vote_counts(&bob, poll_id, &options).await, vec![1, 0]);
+
+    let archived = app
+        .post(&format!("/polls/{}/archive", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(archived.status, StatusCode::OK, "{}", archived.body);
+    let vote = app.vote(&alice, poll_id, options[1]).await;
+    assert_eq!(vote.code(), "POLL_CLOSED");
+}
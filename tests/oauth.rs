@@ -0,0 +1,245 @@
+mod common;
+
+use axum::{Form, Json, Router, routing::get, routing::post};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use common::{TestApp, TestResponse, TestUser};
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use openssl::rsa::Rsa;
+use reqwest::{StatusCode, Url};
+use rust_backend::config::OidcConfig;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+const CLIENT_ID: &str = "polling-app";
+
+// A provider that signs in whoever the code names: codes are `subject|nonce`.
+async fn spawn_provider() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+    let rsa = Rsa::generate(2048).unwrap();
+    let jwks = json!({
+        "keys": [{
+            "kty": "RSA",
+            "kid": "test-key",
+            "use": "sig",
+            "alg": "RS256",
+            "n": URL_SAFE_NO_PAD.encode(rsa.n().to_vec()),
+            "e": URL_SAFE_NO_PAD.encode(rsa.e().to_vec()),
+        }]
+    });
+    let signing_key =
+        Arc::new(EncodingKey::from_rsa_pem(&rsa.private_key_to_pem().unwrap()).unwrap());
+
+    let discovery = json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/authorize", issuer),
+        "token_endpoint": format!("{}/token", issuer),
+        "jwks_uri": format!("{}/jwks", issuer),
+    });
+
+    let token_issuer = issuer.clone();
+    let app = Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(move || async move { Json(discovery) }),
+        )
+        .route("/jwks", get(move || async move { Json(jwks) }))
+        .route(
+            "/token",
+            post(
+                move |Form(form): Form<HashMap<String, String>>| async move {
+                    let (subject, nonce) = form["code"].split_once('|').unwrap();
+                    let now = chrono::Utc::now().timestamp();
+                    let claims = json!({
+                        "iss": token_issuer,
+                        "aud": CLIENT_ID,
+                        "sub": subject,
+                        "iat": now,
+                        "exp": now + 300,
+                        "nonce": nonce,
+                        "email": format!("{}@example.com", subject),
+                        "preferred_username": subject,
+                    });
+                    let mut header = Header::new(Algorithm::RS256);
+                    header.kid = Some("test-key".to_string());
+
+                    Json(json!({
+                        "access_token": "unused",
+                        "token_type": "Bearer",
+                        "id_token": encode(&header, &claims, &signing_key).unwrap(),
+                    }))
+                },
+            ),
+        );
+
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    issuer
+}
+
+// A started login: its state and nonce, as the provider would see them, and
+// the cookie that binds it to the browser that started it.
+struct Login {
+    state: String,
+    nonce: String,
+    cookie: String,
+}
+
+async fn start_login(app: &TestApp, user: Option<&TestUser>) -> Login {
+    let mut request = app.post("/auth/oidc/start");
+    if let Some(user) = user {
+        request = request.signed_in_as(user);
+    }
+    let started = request.send().await;
+    assert_eq!(started.status, StatusCode::OK, "{}", started.body);
+
+    let set_cookie = started.headers["set-cookie"].to_str().unwrap();
+    assert!(set_cookie.contains("HttpOnly"), "{}", set_cookie);
+    let cookie = set_cookie.split(';').next().unwrap().to_string();
+    assert!(cookie.starts_with("oidc_login="), "{}", cookie);
+
+    let url = Url::parse(started.body["authorization_url"].as_str().unwrap()).unwrap();
+    let params: HashMap<_, _> = url.query_pairs().into_owned().collect();
+    assert_eq!(params["client_id"], CLIENT_ID);
+    assert_eq!(params["code_challenge_method"], "S256");
+
+    Login {
+        state: params["state"].clone(),
+        nonce: params["nonce"].clone(),
+        cookie,
+    }
+}
+
+async fn finish_login(app: &TestApp, login: &Login, code: &str) -> TestResponse {
+    app.post("/auth/oidc/finish")
+        .header("cookie", &login.cookie)
+        .json(&json!({ "state": login.state, "code": code }))
+        .send()
+        .await
+}
+
+fn oidc_config(issuer: &str) -> OidcConfig {
+    OidcConfig {
+        issuer: Url::parse(issuer).unwrap(),
+        client_id: CLIENT_ID.to_string(),
+        client_secret: "client-secret".to_string(),
+        redirect_url: Url::parse("http://localhost:3000/oauth/callback").unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn oidc_logins_create_and_link_accounts() {
+    let issuer = spawn_provider().await;
+    let Some(app) = TestApp::spawn_with(|config| config.oidc = Some(oidc_config(&issuer))).await
+    else {
+        return;
+    };
+
+    let login = start_login(&app, None).await;
+    let first = finish_login(&app, &login, &format!("kiosk|{}", login.nonce)).await;
+    assert_eq!(first.status, StatusCode::OK, "{}", first.body);
+    assert_eq!(first.body["username"], "kiosk");
+
+    let kiosk = TestUser {
+        id: first.body["user_id"].as_str().unwrap().parse().unwrap(),
+        username: "kiosk".to_string(),
+        token: first.body["access_token"].as_str().unwrap().to_string(),
+    };
+    let profile = app.get("/me").signed_in_as(&kiosk).send().await;
+    assert_eq!(profile.status, StatusCode::OK, "{}", profile.body);
+
+    // The same identity signs in to the same account, and a state is only
+    // good once.
+    let login = start_login(&app, None).await;
+    let again = finish_login(&app, &login, &format!("kiosk|{}", login.nonce)).await;
+    assert_eq!(again.body["user_id"], kiosk.id.to_string());
+    let replayed = finish_login(&app, &login, &format!("kiosk|{}", login.nonce)).await;
+    assert_eq!(replayed.status, StatusCode::BAD_REQUEST);
+
+    let login = start_login(&app, None).await;
+    let wrong_nonce = finish_login(&app, &login, "kiosk|not-the-nonce").await;
+    assert_eq!(wrong_nonce.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(wrong_nonce.code(), "OIDC_TOKEN_INVALID");
+
+    // Started while signed in, a login links the identity to that account.
+    let alice = app.register("alice").await;
+    let login = start_login(&app, Some(&alice)).await;
+    let linked = finish_login(&app, &login, &format!("alice-google|{}", login.nonce)).await;
+    assert_eq!(linked.status, StatusCode::OK, "{}", linked.body);
+    assert_eq!(linked.body["user_id"], alice.id.to_string());
+
+    let login = start_login(&app, Some(&alice)).await;
+    let taken = finish_login(&app, &login, &format!("kiosk|{}", login.nonce)).await;
+    assert_eq!(taken.status, StatusCode::CONFLICT);
+    assert_eq!(taken.code(), "IDENTITY_IN_USE");
+}
+
+#[tokio::test]
+async fn oidc_is_unavailable_unless_configured() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let started = app.post("/auth/oidc/start").send().await;
+    assert_eq!(started.status, StatusCode::NOT_FOUND);
+    assert_eq!(started.code(), "OIDC_NOT_CONFIGURED");
+}
+
+#[tokio::test]
+async fn oidc_logins_are_bound_to_the_starting_client() {
+    let issuer = spawn_provider().await;
+    let Some(app) = TestApp::spawn_with(|config| config.oidc = Some(oidc_config(&issuer))).await
+    else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    // A victim's state and code are useless without the victim's cookie.
+    let login = start_login(&app, Some(&alice)).await;
+    let elsewhere = app
+        .post("/auth/oidc/finish")
+        .json(&json!({
+            "state": login.state,
+            "code": format!("mallory|{}", login.nonce),
+        }))
+        .send()
+        .await;
+    assert_eq!(elsewhere.status, StatusCode::BAD_REQUEST);
+    assert_eq!(elsewhere.code(), "CORRUPT_SESSION");
+
+    let other = start_login(&app, None).await;
+    let wrong_cookie = app
+        .post("/auth/oidc/finish")
+        .header("cookie", &other.cookie)
+        .json(&json!({
+            "state": login.state,
+            "code": format!("mallory|{}", login.nonce),
+        }))
+        .send()
+        .await;
+    assert_eq!(wrong_cookie.status, StatusCode::BAD_REQUEST);
+
+    // API keys can't start a link, and a bad token isn't treated as signed out.
+    let created = app
+        .post("/me/api-keys")
+        .signed_in_as(&alice)
+        .json(&json!({ "name": "bot", "scopes": ["manage"] }))
+        .send()
+        .await;
+    let with_key = app
+        .post("/auth/oidc/start")
+        .header("X-Api-Key", created.body["key"].as_str().unwrap())
+        .send()
+        .await;
+    assert_eq!(with_key.status, StatusCode::FORBIDDEN);
+    assert_eq!(with_key.code(), "INSUFFICIENT_SCOPE");
+
+    let bad_token = app
+        .post("/auth/oidc/start")
+        .header("authorization", "Bearer not-a-token")
+        .send()
+        .await;
+    assert_eq!(bad_token.status, StatusCode::UNAUTHORIZED);
+}
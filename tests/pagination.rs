@@ -0,0 +1,196 @@
+mod common;
+
+use common::{TestApp, TestUser};
+use reqwest::StatusCode;
+use rust_backend::db;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+// Walks GET /polls one page at a time and returns every id in order.
+async fn list_all(app: &TestApp, user: &TestUser, limit: usize) -> Vec<Uuid> {
+    let mut ids = Vec::new();
+
+    loop {
+        let response = app
+            .get(&format!("/polls?limit={}&offset={}", limit, ids.len()))
+            .signed_in_as(user)
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+        let polls = response.body["polls"].as_array().unwrap();
+        ids.extend(
+            polls
+                .iter()
+                .map(|poll| poll["id"].as_str().unwrap().parse::<Uuid>().unwrap()),
+        );
+        if polls.len() < limit {
+            return ids;
+        }
+    }
+}
+
+#[tokio::test]
+async fn polls_with_the_same_created_at_paginate_without_gaps() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let mut created = HashSet::new();
+    for _ in 0..7 {
+        let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+        created.insert(poll_id);
+    }
+    sqlx::query("UPDATE polls SET created_at = '2024-01-01T00:00:00Z'")
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+    let listed = list_all(&app, &alice, 2).await;
+    let unique: HashSet<Uuid> = listed.iter().copied().collect();
+
+    assert_eq!(listed.len(), created.len(), "{:?}", listed);
+    assert_eq!(unique, created);
+
+    // Ties fall back to id, newest first.
+    let mut expected = listed.clone();
+    expected.sort_by(|a, b| b.cmp(a));
+    assert_eq!(listed, expected);
+}
+
+#[tokio::test]
+async fn page_size_is_capped_and_offset_skips_polls() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    sqlx::query(
+        "INSERT INTO polls (id, creator_id, title, created_at)
+         SELECT gen_random_uuid(), $1, 'Poll ' || n, NOW() - n * INTERVAL '1 minute'
+         FROM generate_series(1, 105) AS n",
+    )
+    .bind(alice.id)
+    .execute(&app.db)
+    .await
+    .unwrap();
+
+    let capped = app
+        .get("/polls?limit=500")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(capped.status, StatusCode::OK, "{}", capped.body);
+    assert_eq!(capped.body["polls"].as_array().unwrap().len(), 100);
+    assert_eq!(capped.body["limit"], 100);
+    assert_eq!(capped.body["total"], 105);
+
+    let default = app.get("/polls").signed_in_as(&alice).send().await;
+    assert_eq!(default.body["polls"].as_array().unwrap().len(), 20);
+
+    let page = app
+        .get("/polls?limit=3&offset=101")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    let titles: Vec<&str> = page.body["polls"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|poll| poll["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["Poll 102", "Poll 103", "Poll 104"]);
+    assert_eq!(page.body["offset"], 101);
+
+    let past_the_end = app
+        .get("/polls?offset=200")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert!(past_the_end.body["polls"].as_array().unwrap().is_empty());
+    assert_eq!(past_the_end.body["total"], 105);
+}
+
+#[tokio::test]
+async fn polls_can_be_filtered_by_closed() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (open_poll, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+    let (closed_poll, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+    app.post(&format!("/polls/{}/close", closed_poll))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+
+    for (closed, expected) in [("true", closed_poll), ("false", open_poll)] {
+        let response = app
+            .get(&format!("/polls?closed={}", closed))
+            .signed_in_as(&alice)
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(response.body["total"], 1);
+        assert_eq!(response.body["polls"][0]["id"], expected.to_string());
+    }
+
+    let all = list_all(&app, &alice, 10).await;
+    assert_eq!(all.len(), 2);
+}
+
+#[tokio::test]
+async fn polls_are_listed_with_exactly_their_own_options() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let (several, _) = app.create_poll(&alice, &["C", "A", "B"]).await;
+    let (single, option_ids) = app.create_poll(&alice, &["Only", "Dropped"]).await;
+    sqlx::query("DELETE FROM poll_options WHERE id = $1")
+        .bind(option_ids[1])
+        .execute(&app.db)
+        .await
+        .unwrap();
+    let (empty, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+    sqlx::query("DELETE FROM poll_options WHERE poll_id = $1")
+        .bind(empty)
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+    let polls = db::get_polls_with_options(&app.db, &db::PollFilter::default(), None, 0)
+        .await
+        .unwrap();
+    let listed: Vec<(Uuid, Vec<&str>)> = polls
+        .iter()
+        .map(|(poll, options)| {
+            assert!(options.iter().all(|option| option.poll_id == poll.id));
+            (
+                poll.id,
+                options
+                    .iter()
+                    .map(|option| option.option_text.as_str())
+                    .collect(),
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        listed,
+        vec![
+            (empty, vec![]),
+            (single, vec!["Only"]),
+            (several, vec!["A", "B", "C"]),
+        ]
+    );
+
+    // A page boundary counts polls, not option rows.
+    let page = db::get_polls_with_options(&app.db, &db::PollFilter::default(), Some(2), 1)
+        .await
+        .unwrap();
+    let ids: Vec<Uuid> = page.iter().map(|(poll, _)| poll.id).collect();
+    assert_eq!(ids, [single, several]);
+    assert_eq!(page[1].1.len(), 3);
+}
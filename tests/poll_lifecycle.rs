@@ -0,0 +1,412 @@
+mod common;
+
+use common::TestApp;
+use reqwest::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn closed_polls_reject_votes() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let close = app
+        .post(&format!("/polls/{}/close", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(close.status, StatusCode::OK, "{}", close.body);
+
+    let poll = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(poll.body["closed"], true);
+
+    let vote = app.vote(&bob, poll_id, options[0]).await;
+    assert_eq!(vote.status, StatusCode::BAD_REQUEST);
+    assert_eq!(vote.code(), "POLL_CLOSED");
+}
+
+#[tokio::test]
+async fn only_the_creator_can_close_or_restart() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let close = app
+        .post(&format!("/polls/{}/close", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(close.status, StatusCode::UNAUTHORIZED);
+
+    let restart = app
+        .post(&format!("/polls/{}/restart", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(restart.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn restart_reopens_and_keeps_votes() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let carol = app.register("carol").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    app.vote(&bob, poll_id, options[0]).await;
+    app.post(&format!("/polls/{}/close", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+
+    let restart = app
+        .post(&format!("/polls/{}/restart", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(restart.status, StatusCode::OK, "{}", restart.body);
+
+    let vote = app.vote(&carol, poll_id, options[1]).await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, vec![1, 1]);
+}
+
+#[tokio::test]
+async fn restart_with_reset_clears_votes() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    app.vote(&bob, poll_id, options[0]).await;
+    app.post(&format!("/polls/{}/close", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+
+    let restart = app
+        .post(&format!("/polls/{}/restart?reset_votes=true", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(restart.status, StatusCode::OK, "{}", restart.body);
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, vec![0, 0]);
+
+    // With the old votes gone, the same user can vote again.
+    let vote = app.vote(&bob, poll_id, options[1]).await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, vec![0, 1]);
+}
+
+#[tokio::test]
+async fn scheduled_polls_open_at_opens_at() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    let opens_at = chrono::Utc::now() + chrono::Duration::seconds(2);
+    let created = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "title": "Scheduled poll",
+            "options": ["Yes", "No"],
+            "opens_at": opens_at.to_rfc3339(),
+        }))
+        .send()
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    let poll_id: Uuid = created.body["poll_id"].as_str().unwrap().parse().unwrap();
+    let option_id: Uuid = created.body["options"][0]["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let mut events = app.get(&format!("/polls/{}/sse", poll_id)).stream().await;
+    events.expect_event("init").await;
+
+    let poll = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(poll.body["upcoming"], true);
+
+    let early = app.vote(&bob, poll_id, option_id).await;
+    assert_eq!(early.status, StatusCode::BAD_REQUEST);
+    assert_eq!(early.code(), "POLL_NOT_OPEN");
+
+    let opened = events.expect_event("poll_opened").await;
+    assert_eq!(opened["poll_id"], poll_id.to_string());
+
+    let vote = app.vote(&bob, poll_id, option_id).await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+}
+
+#[tokio::test]
+async fn only_the_creator_can_delete_and_deleting_archives() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, option_ids) = app.create_poll(&alice, &["Yes", "No"]).await;
+    app.vote(&bob, poll_id, option_ids[1]).await;
+    app.vote(&alice, poll_id, option_ids[0]).await;
+
+    let mut events = app.get(&format!("/polls/{}/sse", poll_id)).stream().await;
+    events.expect_event("init").await;
+
+    let refused = app
+        .delete(&format!("/polls/{}", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(refused.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(refused.code(), "UNAUTHORIZED");
+
+    let deleted = app
+        .delete(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(deleted.status, StatusCode::OK, "{}", deleted.body);
+    let announced = events.expect_event("poll_deleted").await;
+    assert_eq!(announced["poll_id"], poll_id.to_string());
+
+    let listed = app.get("/polls").signed_in_as(&alice).send().await;
+    assert_eq!(listed.body["total"], 0);
+
+    // The poll is archived, not removed: its options and votes remain.
+    let archived: bool =
+        sqlx::query_scalar("SELECT archived_at IS NOT NULL FROM polls WHERE id = $1")
+            .bind(poll_id)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+    assert!(archived);
+
+    // Votes on an archived poll can't be taken back: it's hidden from
+    // voters, and closed to the creator who can still see it.
+    let hidden = app
+        .delete(&format!("/polls/{}/vote", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(hidden.status, StatusCode::NOT_FOUND);
+    let retracted = app
+        .delete(&format!("/polls/{}/vote", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(
+        retracted.status,
+        StatusCode::BAD_REQUEST,
+        "{}",
+        retracted.body
+    );
+    assert_eq!(retracted.code(), "POLL_CLOSED");
+
+    let votes: Vec<i32> = sqlx::query_scalar(
+        "SELECT votes FROM poll_options WHERE poll_id = $1 ORDER BY option_text",
+    )
+    .bind(poll_id)
+    .fetch_all(&app.db)
+    .await
+    .unwrap();
+    assert_eq!(votes, [1, 1]);
+    let ballots: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM votes WHERE poll_id = $1")
+        .bind(poll_id)
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(ballots, 2);
+}
+
+#[tokio::test]
+async fn archived_polls_cannot_be_restarted_or_edited() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+    app.post(&format!("/polls/{}/close", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    app.delete(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+
+    let mut events = app.get("/polls/sse").stream().await;
+    events.expect_event("init").await;
+
+    let restart = app
+        .post(&format!("/polls/{}/restart", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(restart.status, StatusCode::NOT_FOUND, "{}", restart.body);
+    let edit = app
+        .patch(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .json(&json!({ "title": "Renamed" }))
+        .send()
+        .await;
+    assert_eq!(edit.status, StatusCode::NOT_FOUND, "{}", edit.body);
+
+    let (closed, title): (bool, String) =
+        sqlx::query_as("SELECT closed, title FROM polls WHERE id = $1")
+            .bind(poll_id)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+    assert!(closed);
+    assert_ne!(title, "Renamed");
+
+    // Nothing reached the public feed, and restoring reopens the poll rather
+    // than announcing it as new.
+    let restored = app
+        .delete(&format!("/polls/{}/archive", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(restored.status, StatusCode::OK, "{}", restored.body);
+    let next = events.next_event().await;
+    assert_eq!(next.event, "poll_opened");
+    assert_eq!(next.data["poll_id"], poll_id.to_string());
+}
+
+#[tokio::test]
+async fn polls_close_when_they_expire_before_the_next_sweep() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    let expires_at = chrono::Utc::now() + chrono::Duration::seconds(1);
+    let created = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "title": "Expiring poll",
+            "options": ["Yes", "No"],
+            "expires_at": expires_at.to_rfc3339(),
+        }))
+        .send()
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    let poll_id: Uuid = created.body["poll_id"].as_str().unwrap().parse().unwrap();
+    let option_id: Uuid = created.body["options"][0]["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let vote = app.vote(&alice, poll_id, option_id).await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+
+    // The sweep runs once a minute; the poll is closed as soon as it expires.
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    let closed: bool = sqlx::query_scalar("SELECT closed FROM polls WHERE id = $1")
+        .bind(poll_id)
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert!(!closed);
+
+    let late = app.vote(&bob, poll_id, option_id).await;
+    assert_eq!(late.status, StatusCode::BAD_REQUEST);
+    assert_eq!(late.code(), "POLL_CLOSED");
+
+    let poll = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(poll.body["closed"], true, "{}", poll.body);
+
+    // The next sweep records it.
+    let swept = rust_backend::db::close_expired_polls(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(swept, vec![poll_id]);
+}
+
+#[tokio::test]
+async fn access_codes_unlock_polls() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    let created = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "title": "Members only",
+            "options": ["Yes", "No"],
+            "access_code": "open sesame",
+        }))
+        .send()
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    let poll_id = created.body["poll_id"].as_str().unwrap();
+    let option_id = created.body["options"][0]["id"].as_str().unwrap();
+
+    let locked = app
+        .post(&format!("/polls/{}/vote", poll_id))
+        .signed_in_as(&bob)
+        .json(&json!({ "option_id": option_id }))
+        .send()
+        .await;
+    assert_eq!(locked.code(), "ACCESS_CODE_REQUIRED");
+
+    let wrong = app
+        .post(&format!("/polls/{}/access", poll_id))
+        .signed_in_as(&bob)
+        .json(&json!({ "access_code": "open barley" }))
+        .send()
+        .await;
+    assert_eq!(wrong.status, StatusCode::FORBIDDEN);
+    assert_eq!(wrong.code(), "INVALID_ACCESS_CODE");
+
+    let unlocked = app
+        .post(&format!("/polls/{}/access", poll_id))
+        .signed_in_as(&bob)
+        .json(&json!({ "access_code": "open sesame" }))
+        .send()
+        .await;
+    assert_eq!(unlocked.status, StatusCode::NO_CONTENT, "{}", unlocked.body);
+
+    let vote = app
+        .post(&format!("/polls/{}/vote", poll_id))
+        .signed_in_as(&bob)
+        .json(&json!({ "option_id": option_id }))
+        .send()
+        .await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+}
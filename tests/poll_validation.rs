@@ -0,0 +1,135 @@
+mod common;
+
+use common::TestApp;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+async fn invalid_fields_are_reported_individually() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let response = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "title": "x".repeat(500),
+            "options": ["Yes", "  ", " yes "],
+        }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "VALIDATION_FAILED");
+
+    let fields: Vec<(&str, &str)> = response.body["details"]["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| (f["field"].as_str().unwrap(), f["message"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        fields,
+        vec![
+            ("title", "must be at most 200 characters"),
+            ("options[1]", "must not be empty"),
+            ("options[2]", "duplicates options[0]"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn poll_text_is_trimmed_before_saving() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let response = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "title": "  Lunch?  ",
+            "description": "   ",
+            "options": [" Pizza", "Soup "],
+        }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    assert_eq!(response.body["title"], "Lunch?");
+    assert!(response.body["description"].is_null());
+    assert_eq!(response.body["options"][0]["text"], "Pizza");
+}
+
+#[tokio::test]
+async fn edits_cannot_add_an_existing_option() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let response = app
+        .patch(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .json(&json!({ "add_options": ["NO"] }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        response.body["details"]["fields"][0]["message"],
+        "duplicates an existing option"
+    );
+}
+
+#[tokio::test]
+async fn too_many_options_insert_nothing() {
+    let Some(app) = TestApp::spawn_with(|config| config.max_poll_options = 3).await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let response = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "title": "Too many",
+            "options": ["A", "B", "C", "D"],
+        }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.body["details"]["fields"][0]["field"], "options");
+    assert_eq!(
+        response.body["details"]["fields"][0]["message"],
+        "must have between 2 and 3 options"
+    );
+
+    let polls: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM polls")
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    let options: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM poll_options")
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert_eq!((polls, options), (0, 0));
+
+    // Edits count the options the poll already has.
+    let (poll_id, _) = app.create_poll(&alice, &["A", "B", "C"]).await;
+    let response = app
+        .patch(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .json(&json!({ "add_options": ["D"] }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let options: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM poll_options WHERE poll_id = $1")
+        .bind(poll_id)
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(options, 3);
+}
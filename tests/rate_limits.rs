@@ -0,0 +1,130 @@
+mod common;
+
+use common::{TestApp, TestResponse, TestUser};
+use reqwest::StatusCode;
+use serde_json::json;
+
+fn assert_rate_limited(response: &TestResponse) {
+    assert_eq!(
+        response.status,
+        StatusCode::TOO_MANY_REQUESTS,
+        "{}",
+        response.body
+    );
+    assert_eq!(response.code(), "RATE_LIMITED");
+
+    let retry_after: u64 = response.headers["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=60).contains(&retry_after), "{}", retry_after);
+}
+
+#[tokio::test]
+async fn poll_creation_is_rate_limited() {
+    let Some(app) = TestApp::spawn_with(|config| config.poll_creates_per_minute = 2).await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    app.create_poll(&alice, &["Yes", "No"]).await;
+    app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let limited = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({ "title": "One too many", "options": ["Yes", "No"] }))
+        .send()
+        .await;
+    assert_rate_limited(&limited);
+
+    // The limit is per user.
+    app.create_poll(&bob, &["Yes", "No"]).await;
+}
+
+#[tokio::test]
+async fn voting_is_rate_limited() {
+    let Some(app) = TestApp::spawn_with(|config| config.votes_per_minute = 1).await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (first_poll, first_options) = app.create_poll(&alice, &["Yes", "No"]).await;
+    let (second_poll, second_options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let vote = app.vote(&bob, first_poll, first_options[0]).await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+
+    let limited = app.vote(&bob, second_poll, second_options[0]).await;
+    assert_rate_limited(&limited);
+    assert_eq!(
+        app.vote_counts(&alice, second_poll, &second_options).await,
+        [0, 0]
+    );
+}
+
+async fn unlock(app: &TestApp, user: &TestUser, poll_id: &str, code: &str) -> TestResponse {
+    app.post(&format!("/polls/{}/access", poll_id))
+        .signed_in_as(user)
+        .json(&json!({ "access_code": code }))
+        .send()
+        .await
+}
+
+#[tokio::test]
+async fn access_code_guesses_are_limited_per_poll() {
+    let Some(app) =
+        TestApp::spawn_with(|config| config.access_code_failures_per_poll_per_hour = 2).await
+    else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let created = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "title": "Members only",
+            "options": ["Yes", "No"],
+            "access_code": "1234",
+        }))
+        .send()
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    let poll_id = created.body["poll_id"].as_str().unwrap();
+    let (other_poll, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    // Each guess comes from a fresh account, so only the poll's count stops
+    // them.
+    for guess in ["0000", "0001"] {
+        let guesser = app.register(&format!("guesser{}", guess)).await;
+        let wrong = unlock(&app, &guesser, poll_id, guess).await;
+        assert_eq!(wrong.code(), "INVALID_ACCESS_CODE");
+    }
+
+    let guesser = app.register("guesser1234").await;
+    let limited = unlock(&app, &guesser, poll_id, "1234").await;
+    assert_eq!(
+        limited.status,
+        StatusCode::TOO_MANY_REQUESTS,
+        "{}",
+        limited.body
+    );
+    assert_eq!(limited.code(), "RATE_LIMITED");
+    let retry_after: u64 = limited.headers["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 60, "{}", retry_after);
+
+    // Other polls keep their own count.
+    let elsewhere = unlock(&app, &guesser, &other_poll.to_string(), "1234").await;
+    assert_eq!(
+        elsewhere.status,
+        StatusCode::NO_CONTENT,
+        "{}",
+        elsewhere.body
+    );
+}
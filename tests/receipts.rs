@@ -0,0 +1,79 @@
+mod common;
+
+use common::TestApp;
+use reqwest::StatusCode;
+use serde_json::json;
+
+#[tokio::test]
+async fn a_vote_receipt_verifies_until_the_vote_changes() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    let created = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "title": "Changeable",
+            "options": ["Yes", "No"],
+            "allow_vote_change": true,
+        }))
+        .send()
+        .await;
+    let poll_id = created.body["poll_id"].as_str().unwrap().parse().unwrap();
+    let yes = created.body["options"][0]["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let no = created.body["options"][1]["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let vote = app.vote(&bob, poll_id, yes).await;
+    let receipt = vote.body["receipt"]["receipt"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let verified = app
+        .get(&format!("/polls/{}/receipts/{}", poll_id, receipt))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(verified.status, StatusCode::OK, "{}", verified.body);
+    assert_eq!(
+        verified.body["issued_at"],
+        vote.body["receipt"]["issued_at"]
+    );
+
+    let forged = app
+        .get(&format!("/polls/{}/receipts/{}x", poll_id, receipt))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(forged.status, StatusCode::NOT_FOUND);
+    assert_eq!(forged.code(), "RECEIPT_NOT_FOUND");
+
+    let changed = app.vote(&bob, poll_id, no).await;
+    assert_eq!(changed.status, StatusCode::OK, "{}", changed.body);
+    let new_receipt = changed.body["receipt"]["receipt"].as_str().unwrap();
+
+    let stale = app
+        .get(&format!("/polls/{}/receipts/{}", poll_id, receipt))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(stale.status, StatusCode::NOT_FOUND);
+
+    let current = app
+        .get(&format!("/polls/{}/receipts/{}", poll_id, new_receipt))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(current.status, StatusCode::OK, "{}", current.body);
+}
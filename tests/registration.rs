@@ -0,0 +1,320 @@
+mod common;
+
+use common::authenticator::SoftPasskey;
+use common::{TestApp, TestUser};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+// Well-formed enough to reach webauthn-rs, which then rejects it.
+fn bogus_credential() -> Value {
+    json!({
+        "id": "AAAA",
+        "rawId": "AAAA",
+        "response": {
+            "attestationObject": "AAAA",
+            "clientDataJSON": "AAAA",
+        },
+        "type": "public-key",
+        "extensions": {},
+    })
+}
+
+#[tokio::test]
+async fn start_register_returns_creation_options_and_state() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let response = app.post("/register_start/alice").send().await;
+
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["username"], "alice");
+    assert!(response.body["state_id"].is_string());
+    assert!(response.body["user_id"].is_string());
+    assert!(response.body["public_key"]["publicKey"]["challenge"].is_string());
+    assert_eq!(
+        response.body["public_key"]["publicKey"]["user"]["name"],
+        "alice"
+    );
+}
+
+#[tokio::test]
+async fn finish_register_consumes_the_ceremony() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let start = app.post("/register_start/alice").send().await;
+    let finish = json!({
+        "state_id": start.body["state_id"],
+        "credential": bogus_credential(),
+    });
+
+    let first = app.post("/register_finish").json(&finish).send().await;
+    assert_eq!(first.status, StatusCode::BAD_REQUEST);
+    assert_eq!(first.body["status"], "error");
+
+    let second = app.post("/register_finish").json(&finish).send().await;
+    assert_eq!(second.status, StatusCode::BAD_REQUEST);
+    assert_eq!(second.code(), "CORRUPT_SESSION");
+}
+
+#[tokio::test]
+async fn finish_register_rejects_a_different_username() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let start = app.post("/register_start/alice").send().await;
+
+    let response = app
+        .post("/register_finish")
+        .json(&json!({
+            "state_id": start.body["state_id"],
+            "username": "mallory",
+            "credential": bogus_credential(),
+        }))
+        .send()
+        .await;
+
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "CORRUPT_SESSION");
+}
+
+#[tokio::test]
+async fn finish_register_rejects_an_unknown_state() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let response = app
+        .post("/register_finish")
+        .json(&json!({
+            "state_id": "00000000-0000-0000-0000-000000000000",
+            "credential": bogus_credential(),
+        }))
+        .send()
+        .await;
+
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "CORRUPT_SESSION");
+}
+
+#[tokio::test]
+async fn registration_state_is_not_usable_for_login() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let start = app.post("/register_start/alice").send().await;
+
+    let response = app
+        .post("/login_finish")
+        .json(&json!({
+            "state_id": start.body["state_id"],
+            "credential": {
+                "id": "AAAA",
+                "rawId": "AAAA",
+                "response": {
+                    "authenticatorData": "AAAA",
+                    "clientDataJSON": "AAAA",
+                    "signature": "AAAA",
+                },
+                "type": "public-key",
+                "extensions": {},
+            },
+        }))
+        .send()
+        .await;
+
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.code(), "CORRUPT_SESSION");
+}
+
+#[tokio::test]
+async fn registering_creates_the_user_with_a_working_passkey() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let (alice, mut passkey) = app.register_with_passkey("alice").await;
+
+    let credentials = app
+        .get("/me/credentials/details")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(credentials.status, StatusCode::OK, "{}", credentials.body);
+    assert_eq!(credentials.body.as_array().unwrap().len(), 1);
+
+    let login = app
+        .login_with_passkey("alice", &mut passkey, json!({}))
+        .await;
+    assert_eq!(login.status, StatusCode::OK, "{}", login.body);
+    assert_eq!(login.body["user_id"], alice.id.to_string());
+}
+
+#[tokio::test]
+async fn a_failed_passkey_insert_leaves_no_user_behind() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    sqlx::query(
+        "CREATE FUNCTION reject_passkeys() RETURNS trigger AS $$
+         BEGIN RAISE EXCEPTION 'passkeys are read-only'; END;
+         $$ LANGUAGE plpgsql",
+    )
+    .execute(&app.db)
+    .await
+    .unwrap();
+    sqlx::query(
+        "CREATE TRIGGER reject_passkeys BEFORE INSERT ON passkeys
+         FOR EACH ROW EXECUTE FUNCTION reject_passkeys()",
+    )
+    .execute(&app.db)
+    .await
+    .unwrap();
+
+    let response = app
+        .register_passkey("alice", &SoftPasskey::new(), None)
+        .await;
+    assert_eq!(response.status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE username = 'alice'")
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(users, 0);
+}
+
+#[tokio::test]
+async fn registering_an_existing_username_fails() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let (alice, _) = app.register_with_passkey("alice").await;
+
+    let response = app
+        .register_passkey("alice", &SoftPasskey::new(), None)
+        .await;
+    assert_eq!(response.status, StatusCode::CONFLICT, "{}", response.body);
+    assert_eq!(response.code(), "USER_EXISTS");
+
+    let credentials = app
+        .get("/me/credentials/details")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(credentials.body.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn only_the_account_itself_can_add_a_passkey() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let (alice, _) = app.register_with_passkey("alice").await;
+    let bob = app.register("bob").await;
+
+    let hijack = app
+        .register_passkey("alice", &SoftPasskey::new(), Some(&bob))
+        .await;
+    assert_eq!(hijack.status, StatusCode::CONFLICT, "{}", hijack.body);
+
+    let mut second = SoftPasskey::new();
+    let added = app.register_passkey("alice", &second, Some(&alice)).await;
+    assert_eq!(added.status, StatusCode::OK, "{}", added.body);
+    assert_eq!(added.body["user_id"], alice.id.to_string());
+
+    let credentials = app
+        .get("/me/credentials/details")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(credentials.body.as_array().unwrap().len(), 2);
+
+    let login = app
+        .login_with_passkey("alice", &mut second, json!({}))
+        .await;
+    assert_eq!(login.status, StatusCode::OK, "{}", login.body);
+}
+
+#[tokio::test]
+async fn start_register_refuses_someone_elses_username() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    let anonymous = app.post("/register_start/alice").send().await;
+    assert_eq!(anonymous.status, StatusCode::CONFLICT, "{}", anonymous.body);
+    assert_eq!(anonymous.code(), "USER_EXISTS");
+    assert!(anonymous.body.get("user_id").is_none());
+    assert!(anonymous.body.get("access_token").is_none());
+
+    let other = app
+        .post("/register_start/alice")
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(other.status, StatusCode::CONFLICT, "{}", other.body);
+
+    let owner = app
+        .post("/register_start/alice")
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(owner.status, StatusCode::OK, "{}", owner.body);
+    assert_eq!(owner.body["user_id"], alice.id.to_string());
+}
+
+#[tokio::test]
+async fn adding_a_passkey_keeps_the_accounts_role_and_ban() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let (admin, _) = app.register_with_passkey("admin").await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE id = $1")
+        .bind(admin.id)
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+    let added = app
+        .register_passkey("admin", &SoftPasskey::new(), Some(&admin))
+        .await;
+    assert_eq!(added.status, StatusCode::OK, "{}", added.body);
+    let admin = TestUser {
+        token: added.body["access_token"].as_str().unwrap().to_string(),
+        ..admin
+    };
+    let users = app.get("/admin/users").signed_in_as(&admin).send().await;
+    assert_eq!(users.status, StatusCode::OK, "{}", users.body);
+
+    let (banned, _) = app.register_with_passkey("banned").await;
+    sqlx::query("UPDATE users SET banned = TRUE WHERE id = $1")
+        .bind(banned.id)
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+    let refused = app
+        .register_passkey("banned", &SoftPasskey::new(), Some(&banned))
+        .await;
+    // A banned user's token no longer signs them in, so this asks for a name
+    // that's already taken.
+    assert_eq!(refused.status, StatusCode::CONFLICT, "{}", refused.body);
+    assert_eq!(refused.code(), "USER_EXISTS");
+    let passkeys: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM passkeys WHERE user_id = $1")
+        .bind(banned.id)
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(passkeys, 1);
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use common::{TestApp, TestUser};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+async fn create_hidden_poll(app: &TestApp, user: &TestUser, visibility: &str) -> (Uuid, Uuid) {
+    let response = app
+        .post("/polls")
+        .signed_in_as(user)
+        .json(&json!({
+            "title": "Hidden results",
+            "options": ["Yes", "No"],
+            "result_visibility": visibility,
+        }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    (
+        response.body["poll_id"].as_str().unwrap().parse().unwrap(),
+        response.body["options"][0]["id"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap(),
+    )
+}
+
+async fn poll_as(app: &TestApp, user: &TestUser, poll_id: Uuid) -> Value {
+    let response = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(user)
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    response.body
+}
+
+#[tokio::test]
+async fn after_vote_results_show_once_the_caller_votes() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, option_id) = create_hidden_poll(&app, &alice, "after_vote").await;
+
+    let before = poll_as(&app, &bob, poll_id).await;
+    assert_eq!(before["results_visible"], false);
+    assert!(before["options"][0]["votes"].is_null());
+
+    let vote = app.vote(&bob, poll_id, option_id).await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+
+    let after = poll_as(&app, &bob, poll_id).await;
+    assert_eq!(after["results_visible"], true);
+    assert!(after["options"][0]["votes"].is_i64());
+}
+
+#[tokio::test]
+async fn after_close_results_stay_hidden_from_voters_until_close() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, option_id) = create_hidden_poll(&app, &alice, "after_close").await;
+
+    app.vote(&bob, poll_id, option_id).await;
+
+    let open = poll_as(&app, &bob, poll_id).await;
+    assert_eq!(open["results_visible"], false);
+
+    let history = app
+        .get(&format!("/polls/{}/history", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(history.status, StatusCode::FORBIDDEN);
+    assert_eq!(history.code(), "RESULTS_HIDDEN");
+
+    let creator_view = poll_as(&app, &alice, poll_id).await;
+    assert_eq!(creator_view["results_visible"], true);
+
+    app.post(&format!("/polls/{}/close", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+
+    let closed = poll_as(&app, &bob, poll_id).await;
+    assert_eq!(closed["results_visible"], true);
+}
+
+#[tokio::test]
+async fn anonymous_streams_get_no_hidden_counts() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, option_id) = create_hidden_poll(&app, &alice, "after_vote").await;
+
+    let mut events = app.get(&format!("/polls/{}/sse", poll_id)).stream().await;
+    let init = events.expect_event("init").await;
+    assert!(init["total_votes"].is_null());
+
+    app.vote(&bob, poll_id, option_id).await;
+
+    let update = events.expect_event("vote_update").await;
+    assert!(update["total_votes"].is_null());
+    assert!(update["options"][0]["votes"].is_null());
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{TestApp, TestUser};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+
+async fn create(app: &TestApp, user: &TestUser, body: Value) {
+    let response = app
+        .post("/polls")
+        .signed_in_as(user)
+        .json(&body)
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+}
+
+#[tokio::test]
+async fn search_ranks_title_matches_above_option_matches() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    create(
+        &app,
+        &alice,
+        json!({ "title": "Weekend plans", "options": ["Hiking", "Pizza night"] }),
+    )
+    .await;
+    create(
+        &app,
+        &alice,
+        json!({
+            "title": "Best pizza <topping>",
+            "description": "Settle this once and for all",
+            "options": ["Mushroom", "Pineapple"],
+        }),
+    )
+    .await;
+    create(
+        &app,
+        &alice,
+        json!({ "title": "Lunch", "options": ["Soup", "Salad"] }),
+    )
+    .await;
+
+    let response = app.get("/search?q=pizza").signed_in_as(&alice).send().await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    assert_eq!(response.body["total"], 2);
+
+    let results = response.body["results"].as_array().unwrap();
+    assert_eq!(
+        results[0]["title"],
+        "Best <mark>pizza</mark> &lt;topping&gt;"
+    );
+    assert_eq!(results[1]["title"], "Weekend plans");
+    assert_eq!(
+        results[1]["matched_options"],
+        json!(["<mark>Pizza</mark> night"])
+    );
+}
+
+#[tokio::test]
+async fn search_leaves_out_private_polls() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    create(
+        &app,
+        &alice,
+        json!({ "title": "Secret pizza vote", "options": ["Yes", "No"], "visibility": "private" }),
+    )
+    .await;
+
+    let hidden = app.get("/search?q=pizza").signed_in_as(&bob).send().await;
+    assert_eq!(hidden.body["total"], 0);
+
+    let own = app.get("/search?q=pizza").signed_in_as(&alice).send().await;
+    assert_eq!(own.body["total"], 1);
+}
@@ -0,0 +1,278 @@
+mod common;
+
+use common::TestApp;
+use reqwest::StatusCode;
+use rust_backend::sse::{PostgresBroadcaster, SseEvent, create_sse_broadcaster, start_relay};
+use sqlx::{Connection, PgConnection};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn poll_stream_sends_init_then_vote_updates() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let mut events = app.get(&format!("/polls/{}/sse", poll_id)).stream().await;
+
+    let init = events.next_event().await;
+    assert_eq!(init.event, "init");
+
+    let vote = app.vote(&bob, poll_id, options[1]).await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+
+    let update = events.expect_event("vote_update").await;
+    assert_eq!(update["updated_option_id"], options[1].to_string());
+    assert_eq!(update["total_votes"], 1);
+}
+
+#[tokio::test]
+async fn poll_stream_reports_close() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let mut events = app.get(&format!("/polls/{}/sse", poll_id)).stream().await;
+    events.expect_event("init").await;
+
+    app.post(&format!("/polls/{}/close", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+
+    let closed = events.expect_event("poll_closed").await;
+    assert_eq!(closed["poll_id"], poll_id.to_string());
+}
+
+#[tokio::test]
+async fn feed_announces_new_polls() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let mut events = app.get("/polls/sse").stream().await;
+    events.expect_event("init").await;
+
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let created = events.expect_event("poll_created").await;
+    assert_eq!(created["poll_id"], poll_id.to_string());
+    assert_eq!(created["poll"]["options"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn streams_send_heartbeats() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let mut events = app.get("/polls/sse?heartbeat=5").stream().await;
+    events.expect_event("init").await;
+
+    let heartbeat = events.expect_event("heartbeat").await;
+    assert!(heartbeat["server_time"].is_string());
+    assert_eq!(heartbeat["interval_secs"], 5);
+    assert_eq!(heartbeat["dropped"], 0);
+}
+
+#[tokio::test]
+async fn feed_can_be_filtered_by_creator_and_event() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    let mut events = app
+        .get(&format!(
+            "/polls/sse?creator_id={}&events=poll_created,poll_closed",
+            bob.id
+        ))
+        .stream()
+        .await;
+    events.expect_event("init").await;
+
+    let (alice_poll, alice_options) = app.create_poll(&alice, &["Yes", "No"]).await;
+    let (bob_poll, _) = app.create_poll(&bob, &["Yes", "No"]).await;
+    app.vote(&bob, alice_poll, alice_options[0]).await;
+    app.post(&format!("/polls/{}/close", bob_poll))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+
+    let created = events.next_event().await;
+    assert_eq!(created.event, "poll_created");
+    assert_eq!(created.data["poll_id"], bob_poll.to_string());
+
+    let closed = events.next_event().await;
+    assert_eq!(closed.event, "poll_closed");
+    assert_eq!(closed.data["poll_id"], bob_poll.to_string());
+}
+
+#[tokio::test]
+async fn feed_rejects_unknown_event_names() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+
+    let response = app
+        .get("/polls/sse?events=poll_created,nonsense")
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn poll_stream_counts_viewers() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let mut events = app.get(&format!("/polls/{}/sse", poll_id)).stream().await;
+    let viewers = events.expect_event("viewer_count").await;
+    assert_eq!(viewers["viewers"], 1);
+
+    let poll = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(poll.body["viewer_count"], 1);
+}
+
+#[tokio::test]
+async fn relay_delivers_votes_to_other_instances() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    // A second instance sharing the database, with its own broadcaster.
+    let other = create_sse_broadcaster(&app.app_state.config);
+    for sse_tx in [&app.sse_tx, &other] {
+        start_relay(
+            sse_tx,
+            Arc::new(PostgresBroadcaster::new(app.db.clone())),
+            app.db.clone(),
+        )
+        .await
+        .unwrap();
+    }
+
+    let mut local = app.sse_tx.subscribe();
+    let mut remote = other.subscribe_poll(poll_id);
+    app.vote(&alice, poll_id, options[0]).await;
+
+    let message = tokio::time::timeout(Duration::from_secs(10), remote.recv())
+        .await
+        .expect("Timed out waiting for the relayed vote")
+        .unwrap();
+    assert!(message.remote);
+    let SseEvent::VoteUpdate(update) = message.event else {
+        panic!("Expected a vote update, got {:?}", message.event);
+    };
+    assert_eq!(update.option_id, options[0]);
+    assert_eq!(update.new_vote_count, 1);
+
+    // The voting instance doesn't get its own event back.
+    let first = local.recv().await.unwrap();
+    assert!(!first.remote);
+    assert!(local.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn streams_pick_up_writes_made_outside_the_api() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let mut events = app.sse_tx.subscribe();
+    let mut stream = app.get(&format!("/polls/{}/sse", poll_id)).stream().await;
+    stream.expect_event("init").await;
+
+    app.vote(&alice, poll_id, options[0]).await;
+
+    // A connection of its own, as a script or admin console would use.
+    let mut conn = PgConnection::connect(&app.app_state.config.database_url)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE polls SET closed = TRUE WHERE id = $1")
+        .bind(poll_id)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let closed = stream.expect_event("poll_closed").await;
+    assert_eq!(closed["poll_id"], poll_id.to_string());
+
+    // The API's own vote was broadcast once, by its handler.
+    let vote = events.recv().await.unwrap();
+    assert!(matches!(vote.event, SseEvent::VoteUpdate(_)) && !vote.remote);
+    let close = events.recv().await.unwrap();
+    assert!(matches!(close.event, SseEvent::PollClosed(id) if id == poll_id) && close.remote);
+}
+
+// Sends without yielding, so the streams can't keep up and fall behind.
+fn flood(app: &TestApp, poll_id: Uuid, events: usize) {
+    for viewers in 0..events {
+        app.sse_tx.send(SseEvent::ViewerCount(poll_id, viewers));
+    }
+}
+
+#[tokio::test]
+async fn lagging_poll_streams_resync_and_stay_open() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let mut events = app.get(&format!("/polls/{}/sse", poll_id)).stream().await;
+    events.expect_event("init").await;
+
+    flood(&app, poll_id, 150);
+
+    let resync = events.expect_event("resync").await;
+    assert!(resync["skipped"].as_u64().unwrap() > 0, "{}", resync);
+    let init = events.next_event().await;
+    assert_eq!(init.event, "init");
+    assert_eq!(init.data["poll"]["id"], poll_id.to_string());
+
+    app.vote(&alice, poll_id, options[0]).await;
+    let update = events.expect_event("vote_update").await;
+    assert_eq!(update["total_votes"], 1);
+}
+
+#[tokio::test]
+async fn lagging_feeds_resync_and_stay_open() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    let mut events = app.get("/polls/sse").stream().await;
+    events.expect_event("init").await;
+
+    flood(&app, Uuid::new_v4(), 150);
+
+    events.expect_event("resync").await;
+    let init = events.next_event().await;
+    assert_eq!(init.event, "init");
+    assert!(init.data["polls"].is_array(), "{}", init.data);
+
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+    let created = events.expect_event("poll_created").await;
+    assert_eq!(created["poll_id"], poll_id.to_string());
+}
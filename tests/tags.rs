@@ -0,0 +1,127 @@
+mod common;
+
+use common::{TestApp, TestUser};
+use reqwest::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+async fn create_tagged_poll(app: &TestApp, user: &TestUser, title: &str, tags: &[&str]) -> Uuid {
+    let response = app
+        .post("/polls")
+        .signed_in_as(user)
+        .json(&json!({
+            "title": title,
+            "options": ["Yes", "No"],
+            "tags": tags,
+        }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+    response.body["poll_id"].as_str().unwrap().parse().unwrap()
+}
+
+async fn listed_titles(app: &TestApp, user: &TestUser, query: &str) -> Vec<String> {
+    let response = app
+        .get(&format!("/polls{}", query))
+        .signed_in_as(user)
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    response.body["polls"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|poll| poll["title"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn creators_pin_polls_to_the_top_of_a_tag() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+
+    let oldest = create_tagged_poll(&app, &alice, "Oldest", &["food", "drink"]).await;
+    create_tagged_poll(&app, &bob, "Middle", &["food"]).await;
+    create_tagged_poll(&app, &bob, "Newest", &["food", "drink"]).await;
+
+    let pin = app
+        .post(&format!("/polls/{}/tags/Food/pin", oldest))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(pin.status, StatusCode::OK, "{}", pin.body);
+
+    assert_eq!(
+        listed_titles(&app, &bob, "?tag=food").await,
+        ["Oldest", "Newest", "Middle"]
+    );
+    // The pin is for that tag alone.
+    assert_eq!(
+        listed_titles(&app, &bob, "?tag=drink").await,
+        ["Newest", "Oldest"]
+    );
+    assert_eq!(
+        listed_titles(&app, &bob, "").await,
+        ["Newest", "Middle", "Oldest"]
+    );
+    assert_eq!(
+        listed_titles(&app, &bob, "?tag=food&limit=1&offset=1").await,
+        ["Newest"]
+    );
+
+    let unpin = app
+        .delete(&format!("/polls/{}/tags/food/pin", oldest))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(unpin.status, StatusCode::OK, "{}", unpin.body);
+    assert_eq!(
+        listed_titles(&app, &bob, "?tag=food").await,
+        ["Newest", "Middle", "Oldest"]
+    );
+}
+
+#[tokio::test]
+async fn only_the_creator_can_pin_and_only_in_the_polls_tags() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let poll_id = create_tagged_poll(&app, &alice, "Lunch", &["food"]).await;
+    create_tagged_poll(&app, &bob, "Games", &["games"]).await;
+
+    let not_creator = app
+        .post(&format!("/polls/{}/tags/food/pin", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(not_creator.status, StatusCode::UNAUTHORIZED);
+
+    for tag in ["games", "unused"] {
+        let response = app
+            .post(&format!("/polls/{}/tags/{}/pin", poll_id, tag))
+            .signed_in_as(&alice)
+            .send()
+            .await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.code(), "TAG_NOT_ON_POLL");
+    }
+
+    let invalid = app
+        .post(&format!("/polls/{}/tags/no%20spaces/pin", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(invalid.code(), "INVALID_REQUEST");
+
+    let missing = app
+        .post(&format!("/polls/{}/tags/food/pin", Uuid::new_v4()))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    assert_eq!(missing.status, StatusCode::NOT_FOUND);
+}
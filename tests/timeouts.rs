@@ -0,0 +1,38 @@
+mod common;
+
+use common::TestApp;
+use reqwest::StatusCode;
+use std::time::Duration;
+
+#[tokio::test]
+async fn slow_queries_are_cancelled_with_a_timeout_error() {
+    let Some(app) =
+        TestApp::spawn_with(|config| config.db_statement_timeout = Duration::from_millis(300))
+            .await
+    else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, option_ids) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    // Holding a lock on the polls makes every query that reads them wait.
+    let mut lock = app.db.begin().await.unwrap();
+    sqlx::query("LOCK TABLE polls IN ACCESS EXCLUSIVE MODE")
+        .execute(&mut *lock)
+        .await
+        .unwrap();
+
+    let vote = app.vote(&alice, poll_id, option_ids[0]).await;
+    assert_eq!(
+        vote.status,
+        StatusCode::SERVICE_UNAVAILABLE,
+        "{}",
+        vote.body
+    );
+    assert_eq!(vote.code(), "DATABASE_TIMEOUT");
+
+    lock.rollback().await.unwrap();
+
+    let vote = app.vote(&alice, poll_id, option_ids[0]).await;
+    assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+}
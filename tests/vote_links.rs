@@ -0,0 +1,186 @@
+mod common;
+
+use common::{TestApp, TestResponse, TestUser};
+use jsonwebtoken::{EncodingKey, Header, encode};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+// Mail only goes to the log in tests, so links are signed here from the rows
+// the invite recorded, as the server signed them.
+async fn vote_link(app: &TestApp, poll_id: Uuid, email: &str) -> (Uuid, String) {
+    let (id, expires_at): (Uuid, chrono::DateTime<chrono::Utc>) = sqlx::query_as(
+        "SELECT id, expires_at FROM poll_vote_links WHERE poll_id = $1 AND email = $2",
+    )
+    .bind(poll_id)
+    .bind(email)
+    .fetch_one(&app.db)
+    .await
+    .unwrap();
+
+    (id, sign(app, id, poll_id, email, expires_at.timestamp()))
+}
+
+fn sign(app: &TestApp, id: Uuid, poll_id: Uuid, email: &str, exp: i64) -> String {
+    let claims = json!({
+        "jti": id,
+        "poll_id": poll_id,
+        "email": email,
+        "aud": "poll-vote-link",
+        "exp": exp,
+        "iat": chrono::Utc::now().timestamp(),
+    });
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(app.app_state.config.jwt_secret.as_bytes()),
+    )
+    .unwrap()
+}
+
+async fn magic_vote(app: &TestApp, poll_id: Uuid, token: &str, option_id: Uuid) -> TestResponse {
+    app.post(&format!("/polls/{}/vote/magic", poll_id))
+        .json(&json!({ "token": token, "option_id": option_id }))
+        .send()
+        .await
+}
+
+async fn invite(app: &TestApp, user: &TestUser, poll_id: Uuid, emails: Value) -> TestResponse {
+    app.post(&format!("/polls/{}/invite", poll_id))
+        .signed_in_as(user)
+        .json(&json!({ "emails": emails }))
+        .send()
+        .await
+}
+
+#[tokio::test]
+async fn only_the_creator_can_send_vote_links() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let by_bob = invite(&app, &bob, poll_id, json!(["carol@example.com"])).await;
+    assert_eq!(by_bob.status, StatusCode::UNAUTHORIZED, "{}", by_bob.body);
+
+    let invalid = invite(&app, &alice, poll_id, json!(["not an email"])).await;
+    assert_eq!(invalid.status, StatusCode::BAD_REQUEST);
+
+    let sent = invite(
+        &app,
+        &alice,
+        poll_id,
+        json!(["Carol@Example.com", "carol@example.com", "dave@example.com"]),
+    )
+    .await;
+    assert_eq!(sent.status, StatusCode::ACCEPTED, "{}", sent.body);
+    assert_eq!(sent.body["sent"], 2);
+}
+
+#[tokio::test]
+async fn a_vote_link_votes_once_as_its_address() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+    invite(&app, &alice, poll_id, json!(["carol@example.com"])).await;
+
+    let (_, token) = vote_link(&app, poll_id, "carol@example.com").await;
+    let voted = magic_vote(&app, poll_id, &token, options[0]).await;
+    assert_eq!(voted.status, StatusCode::OK, "{}", voted.body);
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, [1, 0]);
+
+    // The vote belongs to an account for the address, now verified.
+    let (username, verified): (String, bool) = sqlx::query_as(
+        "SELECT username, email_verified_at IS NOT NULL FROM users WHERE email = $1",
+    )
+    .bind("carol@example.com")
+    .fetch_one(&app.db)
+    .await
+    .unwrap();
+    assert_eq!(username, "carol");
+    assert!(verified);
+
+    let reused = magic_vote(&app, poll_id, &token, options[1]).await;
+    assert_eq!(reused.status, StatusCode::BAD_REQUEST);
+    assert_eq!(reused.code(), "INVALID_VOTE_LINK");
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, [1, 0]);
+}
+
+#[tokio::test]
+async fn a_failed_vote_leaves_the_link_usable_and_grants_nothing() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let created = app
+        .post("/polls")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "title": "Invite only",
+            "options": ["Yes", "No"],
+            "visibility": "private",
+        }))
+        .send()
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    let poll_id: Uuid = created.body["poll_id"].as_str().unwrap().parse().unwrap();
+    let option_id: Uuid = created.body["options"][0]["id"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    invite(&app, &alice, poll_id, json!(["carol@example.com"])).await;
+    let (_, token) = vote_link(&app, poll_id, "carol@example.com").await;
+
+    let granted = || async {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM poll_access WHERE poll_id = $1")
+            .bind(poll_id)
+            .fetch_one(&app.db)
+            .await
+            .unwrap()
+    };
+
+    let unknown_option = magic_vote(&app, poll_id, &token, Uuid::new_v4()).await;
+    assert!(
+        unknown_option.status.is_client_error(),
+        "{}",
+        unknown_option.body
+    );
+    assert_eq!(granted().await, 0);
+
+    let voted = magic_vote(&app, poll_id, &token, option_id).await;
+    assert_eq!(voted.status, StatusCode::OK, "{}", voted.body);
+    assert_eq!(granted().await, 1);
+}
+
+#[tokio::test]
+async fn vote_links_are_bound_to_their_poll_and_address() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+    let (other_poll_id, other_options) = app.create_poll(&alice, &["Up", "Down"]).await;
+    invite(&app, &alice, poll_id, json!(["carol@example.com"])).await;
+    let (id, token) = vote_link(&app, poll_id, "carol@example.com").await;
+
+    let elsewhere = magic_vote(&app, other_poll_id, &token, other_options[0]).await;
+    assert_eq!(elsewhere.code(), "INVALID_VOTE_LINK");
+
+    let exp = (chrono::Utc::now() + chrono::Duration::days(1)).timestamp();
+    let other_address = sign(&app, id, poll_id, "mallory@example.com", exp);
+    let tampered = magic_vote(&app, poll_id, &other_address, options[0]).await;
+    assert_eq!(tampered.code(), "INVALID_VOTE_LINK");
+
+    let expired = sign(&app, id, poll_id, "carol@example.com", 1);
+    let too_late = magic_vote(&app, poll_id, &expired, options[0]).await;
+    assert_eq!(too_late.code(), "INVALID_VOTE_LINK");
+
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, [0, 0]);
+    let voted = magic_vote(&app, poll_id, &token, options[0]).await;
+    assert_eq!(voted.status, StatusCode::OK, "{}", voted.body);
+}
@@ -0,0 +1,369 @@
+mod common;
+
+use common::{TestApp, TestUser};
+use futures::future::join_all;
+use openssl::sha::sha256;
+use reqwest::StatusCode;
+use rust_backend::polls::rekey_legacy_voter_hashes;
+use serde_json::json;
+use uuid::Uuid;
+
+#[tokio::test]
+async fn a_user_can_only_vote_once() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let first = app.vote(&bob, poll_id, options[0]).await;
+    assert_eq!(first.status, StatusCode::OK, "{}", first.body);
+
+    let second = app.vote(&bob, poll_id, options[1]).await;
+    assert_eq!(second.status, StatusCode::CONFLICT);
+    assert_eq!(second.code(), "ALREADY_VOTED");
+
+    assert_eq!(app.vote_counts(&bob, poll_id, &options).await, vec![1, 0]);
+}
+
+#[tokio::test]
+async fn concurrent_votes_from_one_user_count_once() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let responses = join_all((0..8).map(|i| app.vote(&bob, poll_id, options[i % 2]))).await;
+
+    // Repeating the recorded choice is accepted as a no-op; the other option
+    // is refused. Either way only one vote is ever cast.
+    let recorded = responses
+        .iter()
+        .filter(|r| r.body["message"] == "Vote recorded successfully")
+        .count();
+    assert_eq!(recorded, 1);
+    assert!(
+        responses
+            .iter()
+            .all(|r| r.status == StatusCode::OK || r.code() == "ALREADY_VOTED")
+    );
+
+    let counts = app.vote_counts(&bob, poll_id, &options).await;
+    assert_eq!(counts.iter().sum::<i64>(), 1);
+}
+
+async fn create_multiple_choice_poll(app: &TestApp, user: &TestUser) -> (Uuid, Vec<Uuid>) {
+    let response = app
+        .post("/polls")
+        .signed_in_as(user)
+        .json(&json!({
+            "title": "Pick any",
+            "options": ["A", "B", "C"],
+            "allow_multiple": true,
+        }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    let poll_id = response.body["poll_id"].as_str().unwrap().parse().unwrap();
+    let option_ids = response.body["options"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|option| option["id"].as_str().unwrap().parse().unwrap())
+        .collect();
+    (poll_id, option_ids)
+}
+
+#[tokio::test]
+async fn multiple_choice_polls_take_one_vote_per_option() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = create_multiple_choice_poll(&app, &alice).await;
+
+    let poll = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&bob)
+        .send()
+        .await;
+    assert_eq!(poll.body["allow_multiple"], true, "{}", poll.body);
+
+    for option_id in &options[..2] {
+        let vote = app.vote(&bob, poll_id, *option_id).await;
+        assert_eq!(vote.status, StatusCode::OK, "{}", vote.body);
+    }
+
+    let again = app.vote(&bob, poll_id, options[0]).await;
+    assert_eq!(again.status, StatusCode::CONFLICT);
+    assert_eq!(again.code(), "ALREADY_VOTED");
+
+    assert_eq!(
+        app.vote_counts(&bob, poll_id, &options).await,
+        vec![1, 1, 0]
+    );
+}
+
+#[tokio::test]
+async fn concurrent_multiple_choice_votes_count_each_option_once() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = create_multiple_choice_poll(&app, &alice).await;
+
+    let responses = join_all((0..9).map(|i| app.vote(&bob, poll_id, options[i % 3]))).await;
+    assert!(
+        responses
+            .iter()
+            .all(|r| r.status == StatusCode::OK || r.code() == "ALREADY_VOTED")
+    );
+
+    assert_eq!(
+        app.vote_counts(&bob, poll_id, &options).await,
+        vec![1, 1, 1]
+    );
+}
+
+#[tokio::test]
+async fn votes_from_different_users_are_all_counted() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    for (i, name) in ["bob", "carol", "dave"].iter().enumerate() {
+        let voter = app.register(name).await;
+        let response = app.vote(&voter, poll_id, options[i % 2]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, vec![2, 1]);
+}
+
+#[tokio::test]
+async fn voting_requires_authentication() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let response = app
+        .post(&format!("/polls/{}/vote", poll_id))
+        .json(&serde_json::json!({ "option_id": options[0] }))
+        .send()
+        .await;
+
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn cached_polls_pick_up_new_votes() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, vec![0, 0]);
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, vec![0, 0]);
+    let listed = app.get("/polls").signed_in_as(&alice).send().await;
+    assert_eq!(listed.body["total"], 1);
+
+    let response = app.vote(&bob, poll_id, options[0]).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, vec![1, 0]);
+    let listed = app.get("/polls").signed_in_as(&alice).send().await;
+    let yes = listed.body["polls"][0]["options"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|option| option["id"] == options[0].to_string())
+        .unwrap();
+    assert_eq!(yes["votes"], 1);
+
+    let stats = app.app_state.read_cache.stats();
+    assert_eq!(stats["polls"]["hits"], 1);
+    assert_eq!(stats["polls"]["misses"], 2);
+    assert_eq!(stats["listings"]["misses"], 2);
+}
+
+#[tokio::test]
+async fn unchanged_polls_are_answered_with_304() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    for path in [format!("/polls/{}", poll_id), "/polls".to_string()] {
+        let first = app.get(&path).signed_in_as(&alice).send().await;
+        assert_eq!(first.status, StatusCode::OK, "{}", first.body);
+        let etag = first.headers["etag"].to_str().unwrap().to_string();
+
+        let unchanged = app
+            .get(&path)
+            .signed_in_as(&alice)
+            .header("If-None-Match", &etag)
+            .send()
+            .await;
+        assert_eq!(unchanged.status, StatusCode::NOT_MODIFIED, "{}", path);
+        assert_eq!(unchanged.headers["etag"], etag.as_str());
+        assert!(unchanged.body.is_null());
+
+        // Results are per viewer, so another user's copy differs.
+        let other_viewer = app
+            .get(&path)
+            .signed_in_as(&bob)
+            .header("If-None-Match", &etag)
+            .send()
+            .await;
+        assert_eq!(other_viewer.status, StatusCode::OK, "{}", path);
+    }
+
+    let etag = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await
+        .headers["etag"]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    app.vote(&bob, poll_id, options[0]).await;
+
+    let changed = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .header("If-None-Match", &etag)
+        .send()
+        .await;
+    assert_eq!(changed.status, StatusCode::OK);
+    assert_ne!(changed.headers["etag"], etag.as_str());
+}
+
+// Creates an anonymous single-choice poll and returns its id and option ids.
+async fn create_anonymous_poll(app: &TestApp, user: &TestUser) -> (Uuid, Vec<Uuid>) {
+    let response = app
+        .post("/polls")
+        .signed_in_as(user)
+        .json(&json!({
+            "title": "Anonymous poll",
+            "options": ["Yes", "No"],
+            "anonymous": true,
+        }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    let poll_id = response.body["poll_id"].as_str().unwrap().parse().unwrap();
+    let option_ids = response.body["options"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|option| option["id"].as_str().unwrap().parse().unwrap())
+        .collect();
+    (poll_id, option_ids)
+}
+
+fn sha256_hex(input: &str) -> String {
+    sha256(input.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[tokio::test]
+async fn anonymous_ballots_are_keyed_by_a_server_secret() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = create_anonymous_poll(&app, &alice).await;
+
+    let response = app.vote(&bob, poll_id, options[0]).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+
+    let (user_id, voter_hash): (Option<Uuid>, String) =
+        sqlx::query_as("SELECT user_id, voter_hash FROM votes WHERE poll_id = $1")
+            .bind(poll_id)
+            .fetch_one(&app.db)
+            .await
+            .unwrap();
+    assert_eq!(user_id, None);
+    assert_ne!(voter_hash, sha256_hex(&format!("{}:{}", poll_id, bob.id)));
+    assert_ne!(voter_hash, sha256_hex(&format!("{}:{}", bob.id, poll_id)));
+    let salts: i64 = sqlx::query_scalar("SELECT COUNT(voter_salt) FROM polls")
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(salts, 0);
+
+    // The ballot is still bob's: he can't vote twice, sees it in his history,
+    // and deleting his account takes it back out of the open poll.
+    let again = app.vote(&bob, poll_id, options[1]).await;
+    assert_eq!(again.status, StatusCode::CONFLICT, "{}", again.body);
+
+    let history = app.get("/me/votes").signed_in_as(&bob).send().await;
+    assert_eq!(history.body["total"], 1);
+
+    let deleted = app.delete("/me").signed_in_as(&bob).send().await;
+    assert!(deleted.status.is_success(), "{}", deleted.body);
+    assert_eq!(app.vote_counts(&alice, poll_id, &options).await, [0, 0]);
+}
+
+#[tokio::test]
+async fn legacy_salted_ballots_are_rekeyed() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, options) = create_anonymous_poll(&app, &alice).await;
+
+    // As an anonymous ballot was stored before voter keys.
+    let salt = "legacy-salt";
+    sqlx::query("UPDATE polls SET voter_salt = $1 WHERE id = $2")
+        .bind(salt)
+        .bind(poll_id)
+        .execute(&app.db)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO votes (id, poll_id, option_id, voter_hash) VALUES ($1, $2, $3, $4)")
+        .bind(Uuid::new_v4())
+        .bind(poll_id)
+        .bind(options[0])
+        .bind(sha256_hex(&format!("{}:{}", salt, bob.id)))
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+    let rekeyed = rekey_legacy_voter_hashes(&app.db, &app.app_state.config)
+        .await
+        .unwrap();
+    assert_eq!(rekeyed, 1);
+
+    let again = app.vote(&bob, poll_id, options[1]).await;
+    assert_eq!(again.status, StatusCode::CONFLICT, "{}", again.body);
+    let history = app.get("/me/votes").signed_in_as(&bob).send().await;
+    assert_eq!(history.body["total"], 1);
+
+    let salts: i64 = sqlx::query_scalar("SELECT COUNT(voter_salt) FROM polls")
+        .fetch_one(&app.db)
+        .await
+        .unwrap();
+    assert_eq!(salts, 0);
+}
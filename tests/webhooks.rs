@@ -0,0 +1,84 @@
+mod common;
+
+use axum::{Router, routing::post};
+use common::TestApp;
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+// Deliveries are claimed every few seconds.
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[tokio::test]
+async fn deliveries_to_internal_addresses_are_refused() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+
+    // A receiver on loopback that counts what reaches it.
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let receiver = Router::new().route(
+        "/hook",
+        post(move || async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            StatusCode::OK
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+    let created = app
+        .post("/webhooks")
+        .signed_in_as(&alice)
+        .json(&json!({
+            "url": "https://hooks.example.com/hook",
+            "events": ["poll_created"],
+        }))
+        .send()
+        .await;
+    assert_eq!(created.status, StatusCode::CREATED, "{}", created.body);
+    let webhook_id = created.body["id"].as_str().unwrap().to_string();
+
+    // As if the stored target now pointed inside the network, through an
+    // IPv4-mapped IPv6 literal.
+    sqlx::query("UPDATE webhooks SET url = $1")
+        .bind(format!("https://[::ffff:127.0.0.1]:{}/hook", port))
+        .execute(&app.db)
+        .await
+        .unwrap();
+
+    app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let delivery = tokio::time::timeout(DELIVERY_TIMEOUT, async {
+        loop {
+            let deliveries = app
+                .get(&format!("/webhooks/{}/deliveries", webhook_id))
+                .signed_in_as(&alice)
+                .send()
+                .await;
+            if let Some(delivery) = deliveries.body["deliveries"]
+                .as_array()
+                .and_then(|d| d.first())
+                && delivery["attempts"].as_i64() > Some(0)
+            {
+                return delivery.clone();
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    })
+    .await
+    .expect("Delivery was never attempted");
+
+    assert_ne!(delivery["status"], "delivered");
+    assert_eq!(
+        delivery["last_error"],
+        Value::from("Target address is not allowed")
+    );
+    assert_eq!(hits.load(Ordering::SeqCst), 0);
+}
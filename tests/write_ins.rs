@@ -0,0 +1,86 @@
+mod common;
+
+use common::{TestApp, TestUser};
+use reqwest::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+async fn create_write_in_poll(app: &TestApp, user: &TestUser) -> Uuid {
+    let response = app
+        .post("/polls")
+        .signed_in_as(user)
+        .json(&json!({
+            "title": "Favourite fruit",
+            "options": ["Apple", "Pear"],
+            "allow_write_in": true,
+        }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+
+    response.body["poll_id"].as_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn write_ins_add_one_option_per_normalized_text() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let carol = app.register("carol").await;
+    let poll_id = create_write_in_poll(&app, &alice).await;
+
+    let mut events = app.get(&format!("/polls/{}/sse", poll_id)).stream().await;
+    events.expect_event("init").await;
+
+    let first = app
+        .post(&format!("/polls/{}/vote", poll_id))
+        .signed_in_as(&bob)
+        .json(&json!({ "write_in": "Blood  orange" }))
+        .send()
+        .await;
+    assert_eq!(first.status, StatusCode::OK, "{}", first.body);
+
+    let added = events.expect_event("option_added").await;
+    assert_eq!(added["option"]["option_text"], "Blood orange");
+
+    let second = app
+        .post(&format!("/polls/{}/vote", poll_id))
+        .signed_in_as(&carol)
+        .json(&json!({ "write_in": " blood ORANGE " }))
+        .send()
+        .await;
+    assert_eq!(second.status, StatusCode::OK, "{}", second.body);
+
+    let poll = app
+        .get(&format!("/polls/{}", poll_id))
+        .signed_in_as(&alice)
+        .send()
+        .await;
+    let options = poll.body["options"].as_array().unwrap();
+    assert_eq!(options.len(), 3);
+    let written_in = options
+        .iter()
+        .find(|option| option["text"] == "Blood orange")
+        .unwrap();
+    assert_eq!(written_in["votes"], 2);
+}
+
+#[tokio::test]
+async fn write_ins_are_refused_unless_the_poll_allows_them() {
+    let Some(app) = TestApp::spawn().await else {
+        return;
+    };
+    let alice = app.register("alice").await;
+    let bob = app.register("bob").await;
+    let (poll_id, _) = app.create_poll(&alice, &["Yes", "No"]).await;
+
+    let response = app
+        .post(&format!("/polls/{}/vote", poll_id))
+        .signed_in_as(&bob)
+        .json(&json!({ "write_in": "Maybe" }))
+        .send()
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+}